@@ -1,6 +1,9 @@
 import { imageElementMap } from './canvas_store'
 import { texCreate } from './tex_manager'
 
+// the browsers do not tell why an image failed to load
+const imageErrorMap = {}
+
 export const imageLoadUrl = function(imgId, url, cbPtr) {
   const imgElem = document.createElement('img')
   imgElem.onload = function() {
@@ -8,13 +11,45 @@ export const imageLoadUrl = function(imgId, url, cbPtr) {
     __glayoutAsm__._callback(cbPtr, 0, 0, 0, 0)
   }
   imgElem.onerror = imgElem.onabort = function(){
+    imageErrorMap[imgId] = 'Failed to load ' + imgElem.src
     __glayoutAsm__._callback(cbPtr, -1, 0, 0, 0)
   }
   imgElem.src = __glayoutAsm__.UTF8ToString(url)
 }
 
+export const imageLoadBytes = function(imgId, buf, len, cbPtr) {
+  const bytes = __glayoutAsm__.HEAPU8.slice(buf, buf + len)
+  // the bytes are treated as SVG if starting with a tag
+  let i = 0
+  while (i < bytes.length && (bytes[i] === 0x20 || bytes[i] === 0x09 || bytes[i] === 0x0a || bytes[i] === 0x0d || bytes[i] === 0xef || bytes[i] === 0xbb || bytes[i] === 0xbf)) i++
+  const isVector = bytes[i] === 0x3c
+  const url = URL.createObjectURL(new Blob([bytes], isVector ? { type: 'image/svg+xml' } : {}))
+  const imgElem = document.createElement('img')
+  imgElem.onload = function() {
+    URL.revokeObjectURL(url)
+    imgElem.glayoutIsVector = isVector
+    imageElementMap[imgId] = imgElem
+    __glayoutAsm__._callback(cbPtr, 0, 0, 0, 0)
+  }
+  imgElem.onerror = imgElem.onabort = function(){
+    URL.revokeObjectURL(url)
+    imageErrorMap[imgId] = isVector ? 'Failed to decode the SVG image' : 'Failed to decode the image'
+    __glayoutAsm__._callback(cbPtr, -1, 0, 0, 0)
+  }
+  imgElem.src = url
+}
+
 export const imageUnload = function(imgId) {
   delete imageElementMap[imgId]
+  delete imageErrorMap[imgId]
+}
+
+export const imageGetError = function(imgId, buf, len) {
+  const message = imageErrorMap[imgId]
+  if (!message) return 0
+  const bytes = new TextEncoder().encode(message)
+  __glayoutAsm__.HEAPU8.set(bytes.subarray(0, len), buf)
+  return bytes.length
 }
 
 export const imageGetNaturalWidth = function(imgId) {
@@ -25,6 +60,25 @@ export const imageGetNaturalHeight = function(imgId) {
   return imageElementMap[imgId].naturalHeight
 }
 
+export const imageIsVector = function(imgId) {
+  const imgElem = imageElementMap[imgId]
+  return imgElem.glayoutIsVector || /\.svg([?#]|$)/i.test(imgElem.src) ? 1 : 0
+}
+
 export const texFromImage = function(canvasIndex, texId, imgId) {
   texCreate(canvasIndex, imageElementMap[imgId], texId)
 }
+
+export const texFromImageWithSize = function(canvasIndex, texId, imgId, width, height) {
+  const imgElem = imageElementMap[imgId]
+  if (!imageIsVector(imgId)) {
+    texCreate(canvasIndex, imgElem, texId)
+    return
+  }
+  // the browser renders the vector image at the size drawn
+  const canvas = document.createElement('canvas')
+  canvas.width = Math.max(width, 1)
+  canvas.height = Math.max(height, 1)
+  canvas.getContext('2d').drawImage(imgElem, 0, 0, canvas.width, canvas.height)
+  texCreate(canvasIndex, canvas, texId)
+}
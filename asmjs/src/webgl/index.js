@@ -13,16 +13,22 @@ export {
 } from './text'
 export {
   imageLoadUrl,
+  imageLoadBytes,
   imageUnload,
+  imageGetError,
   imageGetNaturalWidth,
   imageGetNaturalHeight,
+  imageIsVector,
   texFromImage,
+  texFromImageWithSize,
 } from './image'
 export {
   texGetSize,
   texGetCount,
   texGetMaxDraws,
+  texGetPixels,
   texCreateEmpty,
+  texCreateFromPixels,
   texCopy,
   texBindRenderingTarget,
   texUnbindRenderingTarget,
@@ -31,6 +37,12 @@ export {
   texSetActiveTexture,
   texDrawEnd,
   texSetDrawState,
+  texSetDrawShape,
+  texSetDrawBlur,
+  texPushClipRect,
+  texPushClipQuad,
+  texPushClipShape,
+  texPopClip,
 } from './tex_manager'
 
 const IS_TOUCH_DEVICE = typeof window !== 'undefined' && typeof document !== 'undefined' && ('ontouchstart' in window || (navigator.msMaxTouchPoints > 0))
@@ -48,7 +60,8 @@ const initCanvas = function(canvas, gl) {
 
 export const bindCanvas = function(canvasIndex) {
   const elem = document.querySelector('canvas[glayout="' + canvasIndex + '"]')
-  const canvasOption = {premultipliedAlpha: true}
+  // the content is preserved between frames, so that only the changed area needs to be repainted
+  const canvasOption = {premultipliedAlpha: true, preserveDrawingBuffer: true, stencil: true}
   const ctx = elem.getContext('webgl', canvasOption) || elem.getContext('experimental-webgl', canvasOption)
   initCanvas(elem, ctx)
   canvases[canvasIndex] = {
@@ -169,6 +182,18 @@ export const bindTouchEvents = function(canvasIndex, cbPtr) {
   }
 }
 
+// the delta is in px, positive for scrolling down and right
+const WHEEL_LINE_HEIGHT = 40
+export const bindWheelEvents = function(canvasIndex, cbPtr) {
+  const {canvas} = canvases[canvasIndex]
+  canvas.addEventListener('wheel', function(e) {
+    e.preventDefault()
+    const rect = canvas.getBoundingClientRect()
+    const ratio = e.deltaMode === 1 ? WHEEL_LINE_HEIGHT : (e.deltaMode === 2 ? rect.height : 1)
+    __glayoutAsm__._callback(cbPtr, e.clientX - rect.left, e.clientY - rect.top, e.deltaX * ratio, e.deltaY * ratio)
+  })
+}
+
 const convertKeyEvent = function(e) {
   return (e.shiftKey ? 8 : 0) +
     (e.ctrlKey ? 4 : 0) +
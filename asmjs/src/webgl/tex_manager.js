@@ -57,12 +57,12 @@ export const createTexManager = function(ctx) {
 
   // the texture position buffer
   const texPosGLBuf = ctx.createBuffer()
-  const texPosBuf = new Float32Array(8 * GL_DRAW_RECT_MAX)
+  const texPosBuf = new Float32Array(12 * GL_DRAW_RECT_MAX)
   ctx.bindBuffer(ctx.ARRAY_BUFFER, texPosGLBuf)
   ctx.bufferData(ctx.ARRAY_BUFFER, texPosBuf, ctx.STREAM_DRAW)
   const aTexPos = ctx.getAttribLocation(imgShaderProgram, 'aTexPos')
   ctx.enableVertexAttribArray(aTexPos)
-  ctx.vertexAttribPointer(aTexPos, 2, ctx.FLOAT, false, 0, 0)
+  ctx.vertexAttribPointer(aTexPos, 3, ctx.FLOAT, false, 0, 0)
 
   // the draw position buffer
   const drawPosGLBuf = ctx.createBuffer()
@@ -99,12 +99,28 @@ export const createTexManager = function(ctx) {
   ctx.texImage2D(ctx.TEXTURE_2D, 0, ctx.RGBA, 256, 256, 0, ctx.RGBA, ctx.UNSIGNED_BYTE, null)
   ctx.bindTexture(ctx.TEXTURE_2D, null)
 
+  // the stencil buffer for clipping in the temp framebuffer, resized with the rendering targets
+  const tempStencil = ctx.createRenderbuffer()
+  ctx.bindRenderbuffer(ctx.RENDERBUFFER, tempStencil)
+  ctx.renderbufferStorage(ctx.RENDERBUFFER, ctx.STENCIL_INDEX8, 256, 256)
+  ctx.bindRenderbuffer(ctx.RENDERBUFFER, null)
+  ctx.bindFramebuffer(ctx.FRAMEBUFFER, tempFramebuffer)
+  ctx.framebufferRenderbuffer(ctx.FRAMEBUFFER, ctx.STENCIL_ATTACHMENT, ctx.RENDERBUFFER, tempStencil)
+  ctx.bindFramebuffer(ctx.FRAMEBUFFER, null)
+
   // get other vars
   const uAreaSize = ctx.getUniformLocation(imgShaderProgram, 'uAreaSize')
   const uColor = ctx.getUniformLocation(imgShaderProgram, 'uColor')
   const uAlpha = ctx.getUniformLocation(imgShaderProgram, 'uAlpha')
+  const uShapeSize = ctx.getUniformLocation(imgShaderProgram, 'uShapeSize')
+  const uShapeRadii = ctx.getUniformLocation(imgShaderProgram, 'uShapeRadii')
+  const uShapeBorder = ctx.getUniformLocation(imgShaderProgram, 'uShapeBorder')
+  const uShapeScale = ctx.getUniformLocation(imgShaderProgram, 'uShapeScale')
+  const uBlur = ctx.getUniformLocation(imgShaderProgram, 'uBlur')
   ctx.uniform4f(uColor, 0, 0, 0, 1)
   ctx.uniform1f(uAlpha, 1)
+  ctx.uniform1f(uShapeScale, 1)
+  ctx.uniform3f(uBlur, 0, 0, 0)
 
   // bind default tex
   for (let i = 0; i < TEXTURE_MAX; i++) {
@@ -121,6 +137,12 @@ export const createTexManager = function(ctx) {
     uAreaSize,
     uColor,
     uAlpha,
+    uShapeSize,
+    uShapeRadii,
+    uShapeBorder,
+    uShapeScale,
+    uBlur,
+    shape: {size: [0, 0], radii: [0, 0, 0, 0], border: [0, 0, 0, 0], pixelScale: 1},
     texSize,
     texCount,
     imgShaderProgram,
@@ -132,7 +154,12 @@ export const createTexManager = function(ctx) {
     texIndexBuf,
     tempFramebuffer,
     tempTex,
+    tempStencil,
+    tempStencilSize: [256, 256],
     texBindedRenderingTargetStack: [],
+    clipStack: [],
+    // the clips of the outer rendering targets, which do not apply to the bound one
+    savedClipStacks: [],
   }
   return texManager
 }
@@ -143,6 +170,82 @@ export const setTexDrawSize = function(ctx, texManager, w, h, pixelRatio) {
   texManager.pixelRatio = pixelRatio
   ctx.viewport(0, 0, w * pixelRatio, h * pixelRatio)
   ctx.uniform3f(texManager.uAreaSize, w, h, 1)
+  restampClip(ctx, texManager)
+}
+
+const clipStencilLevel = function(texManager) {
+  return texManager.clipStack.filter((c) => c.quad).length
+}
+
+// update the scissor and stencil test according to the clip stack and the current rendering target
+const applyClip = function(ctx, texManager) {
+  const {clipStack, texBindedRenderingTargetStack} = texManager
+  const level = clipStencilLevel(texManager)
+  if (level) {
+    ctx.enable(ctx.STENCIL_TEST)
+    ctx.stencilFunc(ctx.EQUAL, level, 0xff)
+    ctx.stencilOp(ctx.KEEP, ctx.KEEP, ctx.KEEP)
+  } else {
+    ctx.disable(ctx.STENCIL_TEST)
+  }
+  if (!clipStack.length) {
+    ctx.disable(ctx.SCISSOR_TEST)
+    return
+  }
+  const [left, top, right, bottom] = clipStack[clipStack.length - 1].rect
+  const scale = texBindedRenderingTargetStack.length ? 1 : texManager.pixelRatio
+  const l = Math.floor(left * scale)
+  const t = Math.floor(top * scale)
+  const r = Math.max(Math.ceil(right * scale), l)
+  const b = Math.max(Math.ceil(bottom * scale), t)
+  // textures are drawn upside down, see uAreaSize
+  const y = texBindedRenderingTargetStack.length ? t : Math.round(texManager.height * texManager.pixelRatio) - b
+  ctx.enable(ctx.SCISSOR_TEST)
+  ctx.scissor(l, y, r - l, b - t)
+}
+
+const applyShape = function(ctx, texManager, shape) {
+  ctx.uniform2f(texManager.uShapeSize, ...shape.size)
+  ctx.uniform4f(texManager.uShapeRadii, ...shape.radii)
+  ctx.uniform4f(texManager.uShapeBorder, ...shape.border)
+  ctx.uniform1f(texManager.uShapeScale, shape.pixelScale)
+}
+
+// increase the stencil value in the quad (or the shape stretched to the quad) where it equals level - 1
+const stampClipQuad = function(ctx, texManager, quad, shape, level) {
+  ctx.disable(ctx.SCISSOR_TEST)
+  ctx.enable(ctx.STENCIL_TEST)
+  ctx.stencilFunc(ctx.EQUAL, level - 1, 0xff)
+  ctx.stencilOp(ctx.KEEP, ctx.KEEP, ctx.INCR)
+  ctx.colorMask(false, false, false, false)
+  if (shape) {
+    applyShape(ctx, texManager, shape)
+    setDrawQuad(texManager, 0, -4, 0, 0, shape.size[0], shape.size[1], quad)
+    drawRects(ctx, texManager, 1)
+    applyShape(ctx, texManager, texManager.shape)
+  } else {
+    setDrawQuad(texManager, 0, -2, 0, 0, 1, 1, quad)
+    drawRects(ctx, texManager, 1)
+  }
+  ctx.colorMask(true, true, true, true)
+}
+
+const clearStencil = function(ctx) {
+  ctx.disable(ctx.SCISSOR_TEST)
+  ctx.clearStencil(0)
+  ctx.clear(ctx.STENCIL_BUFFER_BIT)
+}
+
+// rebuild the stencil buffer of the current rendering target from the clip stack
+const restampClip = function(ctx, texManager) {
+  if (clipStencilLevel(texManager)) {
+    clearStencil(ctx)
+    let level = 0
+    texManager.clipStack.forEach((c) => {
+      if (c.quad) stampClipQuad(ctx, texManager, c.quad, c.shape, ++level)
+    })
+  }
+  applyClip(ctx, texManager)
 }
 
 export const texGetSize = function(canvasIndex) {
@@ -170,6 +273,21 @@ export const texCreate = function(canvasIndex, img, texId) {
   ctx.bindTexture(ctx.TEXTURE_2D, null)
 }
 
+export const texCreateFromPixels = function(canvasIndex, texId, width, height, buf) {
+  const {ctx, texManager, texMap} = canvases[canvasIndex]
+  const tex = texId < 0 ? texManager.tempTex : (texMap[texId] = ctx.createTexture())
+  ctx.bindTexture(ctx.TEXTURE_2D, tex)
+  ctx.texParameteri(ctx.TEXTURE_2D, ctx.TEXTURE_MIN_FILTER, ctx.LINEAR)
+  ctx.texParameteri(ctx.TEXTURE_2D, ctx.TEXTURE_WRAP_S, ctx.CLAMP_TO_EDGE)
+  ctx.texParameteri(ctx.TEXTURE_2D, ctx.TEXTURE_WRAP_T, ctx.CLAMP_TO_EDGE)
+  const pixels = __glayoutAsm__.HEAPU8.slice(buf, buf + width * height * 4)
+  // the pixels are premultiplied already
+  ctx.pixelStorei(ctx.UNPACK_PREMULTIPLY_ALPHA_WEBGL, false)
+  ctx.texImage2D(ctx.TEXTURE_2D, 0, ctx.RGBA, width, height, 0, ctx.RGBA, ctx.UNSIGNED_BYTE, pixels)
+  ctx.pixelStorei(ctx.UNPACK_PREMULTIPLY_ALPHA_WEBGL, true)
+  ctx.bindTexture(ctx.TEXTURE_2D, null)
+}
+
 export const texRewrite = function(canvasIndex, img, texId, left, top) {
   const {ctx, texManager, texMap} = canvases[canvasIndex]
   const tex = texId < 0 ? texManager.tempTex : texMap[texId]
@@ -195,11 +313,20 @@ const texSetRenderingTarget = function(ctx, texManager, tex, width, height) {
   } else {
     ctx.bindFramebuffer(ctx.FRAMEBUFFER, texManager.tempFramebuffer)
     ctx.framebufferTexture2D(ctx.FRAMEBUFFER, ctx.COLOR_ATTACHMENT0, ctx.TEXTURE_2D, tex, 0)
+    const [stencilW, stencilH] = texManager.tempStencilSize
+    if ((stencilW !== width || stencilH !== height) && width > 0 && height > 0) {
+      texManager.tempStencilSize = [width, height]
+      ctx.bindRenderbuffer(ctx.RENDERBUFFER, texManager.tempStencil)
+      ctx.renderbufferStorage(ctx.RENDERBUFFER, ctx.STENCIL_INDEX8, width, height)
+      ctx.bindRenderbuffer(ctx.RENDERBUFFER, null)
+    }
     ctx.useProgram(texManager.imgShaderProgram)
     ctx.viewport(0, 0, width, height)
     ctx.uniform3f(texManager.uAreaSize, width, height, -1)
     ctx.clearColor(0.0, 0.0, 0.0, 0.0)
   }
+  // the stencil buffer is shared by the texture targets, so it is rebuilt when switching
+  restampClip(ctx, texManager)
 }
 
 export const texBindRenderingTarget = function(canvasIndex, texId, width, height) {
@@ -209,12 +336,15 @@ export const texBindRenderingTarget = function(canvasIndex, texId, width, height
   ctx.texImage2D(ctx.TEXTURE_2D, 0, ctx.RGBA, width, height, 0, ctx.RGBA, ctx.UNSIGNED_BYTE, null)
   ctx.bindTexture(ctx.TEXTURE_2D, null)
   texManager.texBindedRenderingTargetStack.push([tex, width, height])
+  texManager.savedClipStacks.push(texManager.clipStack)
+  texManager.clipStack = []
   texSetRenderingTarget(ctx, texManager, tex, width, height)
 }
 
 export const texUnbindRenderingTarget = function(canvasIndex) {
   const {ctx, texManager} = canvases[canvasIndex]
   texManager.texBindedRenderingTargetStack.pop()
+  texManager.clipStack = texManager.savedClipStacks.pop()
   if (texManager.texBindedRenderingTargetStack.length) {
     const [tex, width, height] = texManager.texBindedRenderingTargetStack[texManager.texBindedRenderingTargetStack.length - 1]
     texSetRenderingTarget(ctx, texManager, tex, width, height)
@@ -243,33 +373,56 @@ export const texDelete = function(canvasIndex, texId) {
   texMap[texId] = null
 }
 
-export const texDraw = function(canvasIndex, drawIndex, texShaderIndex, normalizedTexX, normalizedTexY, normalizedTexW, normalizedTexH, x, y, w, h) {
+export const texDraw = function(canvasIndex, drawIndex, texShaderIndex, normalizedTexX, normalizedTexY, normalizedTexW, normalizedTexH, x1, y1, x2, y2, x3, y3, x4, y4) {
   // texShaderIndex === vTexIndex | (!useColor << 8)
   // a.k.a. unless rgb of tex should be replaced by uColor (e.g. text), texShaderIndex should be added by 256
   const {texManager} = canvases[canvasIndex]
+  setDrawQuad(texManager, drawIndex, texShaderIndex, normalizedTexX, normalizedTexY, normalizedTexW, normalizedTexH, [x1, y1, x2, y2, x3, y3, x4, y4])
+}
+
+// the weights of the corners for the perspective-correct mapping of a rect to the quad
+const quadProjectiveWeights = function(quad) {
+  // the intersection of the diagonals
+  const d1x = quad[4] - quad[0]
+  const d1y = quad[5] - quad[1]
+  const d2x = quad[6] - quad[2]
+  const d2y = quad[7] - quad[3]
+  const det = d1x * d2y - d1y * d2x
+  if (det === 0) return [1, 1, 1, 1]
+  const ox = quad[2] - quad[0]
+  const oy = quad[3] - quad[1]
+  const s = (ox * d2y - oy * d2x) / det
+  const t = (ox * d1y - oy * d1x) / det
+  if (s <= 0 || s >= 1 || t <= 0 || t >= 1) return [1, 1, 1, 1]
+  // the ratio of the diagonal to the distance from the intersection to the opposite corner
+  return [1 / (1 - s), 1 / (1 - t), 1 / s, 1 / t]
+}
+
+// the quad is [x1, y1, ... x4, y4] for left-top, left-bottom, right-bottom and right-top
+const setDrawQuad = function(texManager, drawIndex, texShaderIndex, normalizedTexX, normalizedTexY, normalizedTexW, normalizedTexH, quad) {
   const {
     texPosBuf,
     drawPosBuf,
     texIndexBuf,
   } = texManager
+  const drawIndex12 = drawIndex * 12
   const drawIndex8 = drawIndex << 3
   const drawIndex4 = drawIndex << 2
-  texPosBuf[drawIndex8 + 0] = normalizedTexX
-  texPosBuf[drawIndex8 + 1] = normalizedTexY
-  texPosBuf[drawIndex8 + 2] = normalizedTexX
-  texPosBuf[drawIndex8 + 3] = normalizedTexY + normalizedTexH
-  texPosBuf[drawIndex8 + 4] = normalizedTexX + normalizedTexW
-  texPosBuf[drawIndex8 + 5] = normalizedTexY + normalizedTexH
-  texPosBuf[drawIndex8 + 6] = normalizedTexX + normalizedTexW
-  texPosBuf[drawIndex8 + 7] = normalizedTexY
-  drawPosBuf[drawIndex8 + 0] = x
-  drawPosBuf[drawIndex8 + 1] = y
-  drawPosBuf[drawIndex8 + 2] = x
-  drawPosBuf[drawIndex8 + 3] = y + h
-  drawPosBuf[drawIndex8 + 4] = x + w
-  drawPosBuf[drawIndex8 + 5] = y + h
-  drawPosBuf[drawIndex8 + 6] = x + w
-  drawPosBuf[drawIndex8 + 7] = y
+  const texPos = [
+    normalizedTexX, normalizedTexY,
+    normalizedTexX, normalizedTexY + normalizedTexH,
+    normalizedTexX + normalizedTexW, normalizedTexY + normalizedTexH,
+    normalizedTexX + normalizedTexW, normalizedTexY,
+  ]
+  const q = quadProjectiveWeights(quad)
+  for (let i = 0; i < 4; i++) {
+    texPosBuf[drawIndex12 + i * 3] = texPos[i * 2] * q[i]
+    texPosBuf[drawIndex12 + i * 3 + 1] = texPos[i * 2 + 1] * q[i]
+    texPosBuf[drawIndex12 + i * 3 + 2] = q[i]
+  }
+  for (let i = 0; i < 8; i++) {
+    drawPosBuf[drawIndex8 + i] = quad[i]
+  }
   texIndexBuf[drawIndex4 + 0] = texShaderIndex
   texIndexBuf[drawIndex4 + 1] = texShaderIndex
   texIndexBuf[drawIndex4 + 2] = texShaderIndex
@@ -284,6 +437,10 @@ export const texSetActiveTexture = function(canvasIndex, texShaderIndex, texId)
 
 export const texDrawEnd = function(canvasIndex, drawCount) {
   const {ctx, texManager} = canvases[canvasIndex]
+  drawRects(ctx, texManager, drawCount)
+}
+
+const drawRects = function(ctx, texManager, drawCount) {
   const {
     texPosGLBuf,
     texPosBuf,
@@ -311,9 +468,75 @@ export const texSetDrawState = function(canvasIndex, colorR, colorG, colorB, col
   ctx.uniform1f(texManager.uAlpha, alpha)
 }
 
-// const texGetPixels = function(canvasIndex, left, top, width, height) {
-//   const {ctx} = canvases[canvasIndex]
-//   const ret = new window.Uint8Array(width * height * 4)
-//   ctx.readPixels(left, top, width, height, ctx.RGBA, ctx.UNSIGNED_BYTE, ret)
-//   return ret
-// }
+export const texSetDrawShape = function(canvasIndex, width, height, radiusLT, radiusRT, radiusRB, radiusLB, borderTop, borderRight, borderBottom, borderLeft, pixelScale) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  texManager.shape = {
+    size: [width, height],
+    radii: [radiusLT, radiusRT, radiusRB, radiusLB],
+    border: [borderTop, borderRight, borderBottom, borderLeft],
+    pixelScale,
+  }
+  applyShape(ctx, texManager, texManager.shape)
+}
+
+export const texSetDrawBlur = function(canvasIndex, stepX, stepY, sigma) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  ctx.uniform3f(texManager.uBlur, stepX, stepY, sigma)
+}
+
+const pushClip = function(texManager, l, t, r, b, quad, shape) {
+  const {clipStack} = texManager
+  if (clipStack.length) {
+    const [cl, ct, cr, cb] = clipStack[clipStack.length - 1].rect
+    l = Math.max(l, cl)
+    t = Math.max(t, ct)
+    r = Math.min(r, cr)
+    b = Math.min(b, cb)
+  }
+  clipStack.push({rect: [l, t, Math.max(r, l), Math.max(b, t)], quad, shape})
+}
+
+export const texPushClipRect = function(canvasIndex, left, top, width, height) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  pushClip(texManager, Math.min(left, left + width), Math.min(top, top + height), Math.max(left, left + width), Math.max(top, top + height), null, null)
+  applyClip(ctx, texManager)
+}
+
+const pushClipQuad = function(ctx, texManager, quad, shape) {
+  const xs = [quad[0], quad[2], quad[4], quad[6]]
+  const ys = [quad[1], quad[3], quad[5], quad[7]]
+  pushClip(texManager, Math.min(...xs), Math.min(...ys), Math.max(...xs), Math.max(...ys), quad, shape)
+  const level = clipStencilLevel(texManager)
+  if (level === 1) clearStencil(ctx)
+  stampClipQuad(ctx, texManager, quad, shape, level)
+  applyClip(ctx, texManager)
+}
+
+export const texPushClipQuad = function(canvasIndex, x1, y1, x2, y2, x3, y3, x4, y4) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  pushClipQuad(ctx, texManager, [x1, y1, x2, y2, x3, y3, x4, y4], null)
+}
+
+export const texPushClipShape = function(canvasIndex, x1, y1, x2, y2, x3, y3, x4, y4) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  pushClipQuad(ctx, texManager, [x1, y1, x2, y2, x3, y3, x4, y4], texManager.shape)
+}
+
+export const texPopClip = function(canvasIndex) {
+  const {ctx, texManager} = canvases[canvasIndex]
+  const clip = texManager.clipStack.pop()
+  if (clip && clip.quad) restampClip(ctx, texManager)
+  else applyClip(ctx, texManager)
+}
+
+export const texGetPixels = function(canvasIndex, left, top, width, height, buf) {
+  const {canvas, ctx} = canvases[canvasIndex]
+  const ret = new window.Uint8Array(width * height * 4)
+  ctx.bindFramebuffer(ctx.FRAMEBUFFER, null)
+  ctx.readPixels(left, canvas.height - top - height, width, height, ctx.RGBA, ctx.UNSIGNED_BYTE, ret)
+  // rows from top to bottom
+  const rowSize = width * 4
+  for (let y = 0; y < height; y++) {
+    __glayoutAsm__.HEAPU8.set(ret.subarray((height - y - 1) * rowSize, (height - y) * rowSize), buf + y * rowSize)
+  }
+}
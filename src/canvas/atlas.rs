@@ -0,0 +1,237 @@
+use std::rc::{Rc, Weak};
+use std::cell::Cell;
+
+pub(crate) const ATLAS_SIZE: i32 = 1024;
+pub(crate) const ATLAS_IMAGE_SIZE_MAX: i32 = 256;
+pub(crate) const ATLAS_PADDING: i32 = 1;
+const ATLAS_PAGES_MAX: usize = 4;
+const SHELF_HEIGHT_STEP: i32 = 4;
+
+/// An image packed into a page of the atlas.
+/// The position is changed when the atlas is repacked, so it should be read every time drawing.
+pub struct AtlasImage {
+    tex_id: Cell<i32>,
+    rect: Cell<(i32, i32, i32, i32)>,
+}
+
+impl AtlasImage {
+    #[inline]
+    pub fn tex_id(&self) -> i32 {
+        self.tex_id.get()
+    }
+    /// The left, top, width and height in the page, in pixels and without the padding.
+    #[inline]
+    pub fn rect(&self) -> (i32, i32, i32, i32) {
+        self.rect.get()
+    }
+    /// The normalized position in the page texture.
+    #[inline]
+    pub fn tex_pos(&self) -> (f64, f64, f64, f64) {
+        let (left, top, width, height) = self.rect.get();
+        let size = ATLAS_SIZE as f64;
+        (left as f64 / size, top as f64 / size, width as f64 / size, height as f64 / size)
+    }
+    #[inline]
+    fn padded_area(&self) -> i32 {
+        let (_, _, width, height) = self.rect.get();
+        (width + ATLAS_PADDING * 2) * (height + ATLAS_PADDING * 2)
+    }
+}
+
+struct Shelf {
+    top: i32,
+    height: i32,
+    left: i32,
+}
+
+struct AtlasPage {
+    tex_id: i32,
+    shelves: Vec<Shelf>,
+    bottom: i32,
+    used_area: i32,
+    images: Vec<Weak<AtlasImage>>,
+}
+
+impl AtlasPage {
+    fn new(tex_id: i32) -> Self {
+        Self {
+            tex_id,
+            shelves: vec![],
+            bottom: 0,
+            used_area: 0,
+            images: vec![],
+        }
+    }
+    // find the left-top of a free slot, preferring the lowest shelf which is not too high for it
+    fn alloc(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let mut best: Option<usize> = None;
+        for (i, shelf) in self.shelves.iter().enumerate() {
+            if shelf.height < height || shelf.height > height * 2 || shelf.left + width > ATLAS_SIZE { continue }
+            best = match best {
+                Some(b) if self.shelves[b].height <= shelf.height => Some(b),
+                _ => Some(i),
+            };
+        }
+        let index = match best {
+            Some(x) => x,
+            None => {
+                if width > ATLAS_SIZE || self.bottom + height > ATLAS_SIZE { return None }
+                let shelf_height = ((height + SHELF_HEIGHT_STEP - 1) / SHELF_HEIGHT_STEP * SHELF_HEIGHT_STEP).min(ATLAS_SIZE - self.bottom);
+                self.shelves.push(Shelf { top: self.bottom, height: shelf_height, left: 0 });
+                self.bottom += shelf_height;
+                self.shelves.len() - 1
+            },
+        };
+        let shelf = &mut self.shelves[index];
+        let ret = (shelf.left, shelf.top);
+        shelf.left += width;
+        self.used_area += width * height;
+        Some(ret)
+    }
+    fn live_area(&self) -> i32 {
+        self.images.iter().filter_map(|x| x.upgrade()).map(|x| x.padded_area()).sum()
+    }
+}
+
+/// An image moved to another position by repacking.
+pub(crate) struct AtlasMove {
+    pub image: Rc<AtlasImage>,
+    pub page: usize,
+    pub left: i32,
+    pub top: i32,
+}
+
+/// The shelf packing of small images into shared textures (pages) of `ATLAS_SIZE`.
+/// The pages are kept while any image in them is alive, i.e. the `AtlasImage` is not dropped.
+/// The space of dropped images is not reused until the pages are repacked.
+pub(crate) struct ImageAtlas {
+    pages: Vec<AtlasPage>,
+}
+
+impl ImageAtlas {
+    pub fn new() -> Self {
+        Self {
+            pages: vec![],
+        }
+    }
+    #[inline]
+    pub fn fits(width: i32, height: i32) -> bool {
+        width > 0 && height > 0 && width <= ATLAS_IMAGE_SIZE_MAX && height <= ATLAS_IMAGE_SIZE_MAX
+    }
+    #[inline]
+    pub fn can_add_page(&self) -> bool {
+        self.pages.len() < ATLAS_PAGES_MAX
+    }
+    #[inline]
+    pub fn add_page(&mut self, tex_id: i32) {
+        self.pages.push(AtlasPage::new(tex_id));
+    }
+    #[inline]
+    pub fn page_tex_ids(&self) -> Vec<i32> {
+        self.pages.iter().map(|x| x.tex_id).collect()
+    }
+    #[inline]
+    pub fn set_page_tex_id(&mut self, page: usize, tex_id: i32) {
+        self.pages[page].tex_id = tex_id;
+    }
+    /// Remove the pages without any alive image, and return their tex ids.
+    pub fn release_unused_pages(&mut self) -> Vec<i32> {
+        let mut ret = vec![];
+        self.pages.retain(|page| {
+            if page.images.iter().any(|x| x.upgrade().is_some()) {
+                return true;
+            }
+            ret.push(page.tex_id);
+            false
+        });
+        ret
+    }
+    fn alloc_slot(&mut self, width: i32, height: i32) -> Option<(usize, i32, i32)> {
+        let (width, height) = (width + ATLAS_PADDING * 2, height + ATLAS_PADDING * 2);
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            match page.alloc(width, height) {
+                None => { },
+                Some((left, top)) => return Some((i, left + ATLAS_PADDING, top + ATLAS_PADDING)),
+            }
+        }
+        None
+    }
+    /// Allocate an image in the current pages, or return `None` if there is no space.
+    pub fn alloc(&mut self, width: i32, height: i32) -> Option<Rc<AtlasImage>> {
+        let (page, left, top) = self.alloc_slot(width, height)?;
+        let image = Rc::new(AtlasImage {
+            tex_id: Cell::new(self.pages[page].tex_id),
+            rect: Cell::new((left, top, width, height)),
+        });
+        self.pages[page].images.push(Rc::downgrade(&image));
+        Some(image)
+    }
+    /// Whether the dropped images waste enough space to be worth repacking.
+    pub fn should_repack(&self) -> bool {
+        let wasted: i32 = self.pages.iter().map(|x| x.used_area - x.live_area()).sum();
+        wasted >= ATLAS_SIZE * ATLAS_SIZE / 4
+    }
+    /// Pack the alive images again into new pages, higher images first.
+    /// The new pages have tex id -1, which should be set before the moves are applied.
+    pub fn repacked(&self) -> (ImageAtlas, Vec<AtlasMove>) {
+        let mut images: Vec<Rc<AtlasImage>> = self.pages.iter().flat_map(|x| x.images.iter().filter_map(|x| x.upgrade())).collect();
+        images.sort_by(|a, b| b.rect().3.cmp(&a.rect().3).then(b.rect().2.cmp(&a.rect().2)));
+        let mut atlas = ImageAtlas::new();
+        let mut moves = vec![];
+        for image in images {
+            let (_, _, width, height) = image.rect();
+            let (page, left, top) = match atlas.alloc_slot(width, height) {
+                Some(x) => x,
+                None => {
+                    atlas.add_page(-1);
+                    atlas.alloc_slot(width, height).unwrap()
+                },
+            };
+            atlas.pages[page].images.push(Rc::downgrade(&image));
+            moves.push(AtlasMove { image, page, left, top });
+        }
+        (atlas, moves)
+    }
+    /// Update the image to the new position in `self`.
+    pub fn apply_move(&self, m: &AtlasMove) {
+        let (_, _, width, height) = m.image.rect();
+        m.image.tex_id.set(self.pages[m.page].tex_id);
+        m.image.rect.set((m.left, m.top, width, height));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ImageAtlas, ATLAS_SIZE};
+
+    #[test]
+    fn alloc_and_repack() {
+        let mut atlas = ImageAtlas::new();
+        assert!(atlas.alloc(10, 10).is_none());
+        atlas.add_page(7);
+        let a = atlas.alloc(30, 20).unwrap();
+        let b = atlas.alloc(16, 16).unwrap();
+        let c = atlas.alloc(40, 40).unwrap();
+        assert_eq!(a.tex_id(), 7);
+        assert_eq!(a.rect(), (1, 1, 30, 20));
+        assert_eq!(b.rect(), (33, 1, 16, 16));
+        assert_eq!(c.rect(), (1, 25, 40, 40));
+        assert!(atlas.alloc(ATLAS_SIZE, 1).is_none());
+        drop(a);
+        let (mut repacked, moves) = atlas.repacked();
+        assert_eq!(moves.len(), 2);
+        repacked.set_page_tex_id(0, 8);
+        for m in moves.iter() {
+            repacked.apply_move(m);
+        }
+        assert_eq!(c.rect(), (1, 1, 40, 40));
+        assert_eq!(b.tex_id(), 8);
+        assert_eq!(b.rect(), (1, 45, 16, 16));
+        drop(moves);
+        assert_eq!(atlas.release_unused_pages(), vec![]);
+        drop(b);
+        drop(c);
+        assert_eq!(atlas.release_unused_pages(), vec![7]);
+        assert_eq!(repacked.release_unused_pages(), vec![8]);
+    }
+}
@@ -0,0 +1,186 @@
+#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+use std::path::Path;
+#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+use std::io;
+#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+use image;
+
+/// The pixel size of a captured frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CaptureSize {
+    /// One pixel for each logical pixel (the device pixels are averaged).
+    Logical,
+    /// One pixel for each device pixel.
+    DevicePixel,
+}
+
+/// A captured frame in non-premultiplied RGBA, rows from top to bottom.
+#[derive(Clone, Debug)]
+pub struct FrameCapture {
+    width: i32,
+    height: i32,
+    data: Vec<u8>,
+}
+
+fn resize_by_area(width: i32, height: i32, src: Vec<u8>, dest_width: i32, dest_height: i32) -> Vec<u8> {
+    if width == dest_width && height == dest_height {
+        return src;
+    }
+    let mut data = vec![0; (dest_width * dest_height * 4) as usize];
+    let scale_x = width as f64 / dest_width as f64;
+    let scale_y = height as f64 / dest_height as f64;
+    for y in 0..dest_height {
+        let sy_start = (y as f64 * scale_y).floor() as i32;
+        let sy_end = (((y + 1) as f64 * scale_y).ceil() as i32).max(sy_start + 1).min(height);
+        for x in 0..dest_width {
+            let sx_start = (x as f64 * scale_x).floor() as i32;
+            let sx_end = (((x + 1) as f64 * scale_x).ceil() as i32).max(sx_start + 1).min(width);
+            let mut sum = [0u32; 4];
+            let mut count = 0;
+            for sy in sy_start..sy_end {
+                for sx in sx_start..sx_end {
+                    let p = ((sx + sy * width) * 4) as usize;
+                    for i in 0..4 {
+                        sum[i] += src[p + i] as u32;
+                    }
+                    count += 1;
+                }
+            }
+            if count == 0 { continue }
+            let p = ((x + y * dest_width) * 4) as usize;
+            for i in 0..4 {
+                data[p + i] = ((sum[i] + count / 2) / count) as u8;
+            }
+        }
+    }
+    data
+}
+
+impl FrameCapture {
+    /// Build from premultiplied device pixels, scaled to `width` x `height`.
+    pub(super) fn from_premultiplied(src_width: i32, src_height: i32, src: Vec<u8>, width: i32, height: i32) -> Self {
+        let mut data = resize_by_area(src_width, src_height, src, width, height);
+        for p in data.chunks_mut(4) {
+            let a = p[3] as u32;
+            if a > 0 && a < 255 {
+                for i in 0..3 {
+                    let c = (p[i] as u32 * 255 + a / 2) / a;
+                    p[i] = if c > 255 { 255 } else { c as u8 };
+                }
+            }
+        }
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    #[inline]
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+    #[inline]
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+    #[inline]
+    pub fn pixel(&self, x: i32, y: i32) -> (u8, u8, u8, u8) {
+        let p = ((x + y * self.width) * 4) as usize;
+        (self.data[p], self.data[p + 1], self.data[p + 2], self.data[p + 3])
+    }
+
+    /// Encode the frame as a PNG file content.
+    #[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+    pub fn to_png(&self) -> io::Result<Vec<u8>> {
+        let mut ret = vec![];
+        image::png::PNGEncoder::new(&mut ret).encode(&self.data, self.width as u32, self.height as u32, image::RGBA(8))?;
+        Ok(ret)
+    }
+
+    /// Write the frame to a PNG file.
+    #[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        image::save_buffer(path, &self.data, self.width as u32, self.height as u32, image::RGBA(8))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image;
+    use super::FrameCapture;
+
+    #[test]
+    fn from_premultiplied() {
+        // 2x1 device pixels: opaque red and transparent, averaged to one pixel
+        let src = vec![255, 0, 0, 255, 0, 0, 0, 0];
+        let c = FrameCapture::from_premultiplied(2, 1, src.clone(), 2, 1);
+        assert_eq!(c.pixel(0, 0), (255, 0, 0, 255));
+        assert_eq!(c.pixel(1, 0), (0, 0, 0, 0));
+        let c = FrameCapture::from_premultiplied(2, 1, src, 1, 1);
+        assert_eq!(c.pixel(0, 0), (255, 0, 0, 128));
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let c = FrameCapture::from_premultiplied(2, 2, vec![255, 0, 0, 255, 0, 64, 0, 128, 0, 0, 0, 0, 10, 20, 30, 255], 2, 2);
+        let png = c.to_png().unwrap();
+        let decoded = image::load_from_memory(&png).unwrap().to_rgba();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        assert_eq!(decoded.into_raw(), c.data());
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn capture_frame() {
+        use super::CaptureSize;
+        use super::super::Canvas;
+        use super::super::element::{Element, Empty};
+        use super::super::element::style::DisplayType;
+        use super::super::super::lib_interfaces::run_canvas_test;
+        run_canvas_test(|| {
+            let mut canvas = Canvas::new(101);
+            {
+                let ctx = canvas.context();
+                let mut ctx = ctx.borrow_mut();
+                ctx.set_canvas_size(10, 10, 2.);
+                ctx.set_clear_color(0., 0., 0., 0.);
+                {
+                    let cfg = ctx.canvas_config();
+                    let root = ctx.root();
+                    let mut root = root.borrow_mut();
+                    let child = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                    {
+                        let child = child.deref_mut_with(&mut root);
+                        child.style_mut().display(DisplayType::Block);
+                        child.style_mut().width(2.5);
+                        child.style_mut().height(10.);
+                        child.style_mut().background_color((1., 0., 0., 1.));
+                    }
+                    root.append(child);
+                }
+                let c = ctx.capture_frame(CaptureSize::DevicePixel);
+                assert_eq!((c.width(), c.height()), (20, 20));
+                assert_eq!(c.pixel(4, 19), (255, 0, 0, 255));
+                assert_eq!(c.pixel(5, 0), (0, 0, 0, 0));
+                // the device pixel at the edge is averaged with a transparent one
+                let c = ctx.capture_frame(CaptureSize::Logical);
+                assert_eq!((c.width(), c.height()), (10, 10));
+                assert_eq!(c.pixel(1, 9), (255, 0, 0, 255));
+                assert_eq!(c.pixel(2, 0), (255, 0, 0, 128));
+                assert_eq!(c.pixel(3, 0), (0, 0, 0, 0));
+                let png = c.to_png().unwrap();
+                assert_eq!(image::load_from_memory(&png).unwrap().to_rgba().into_raw(), c.data());
+            }
+            canvas.destroy();
+        });
+    }
+}
@@ -1,18 +1,20 @@
 use std::cmp;
+use std::mem;
 use std::rc::Rc;
 use std::cell::{RefCell, Cell};
 use std::str::Chars;
-use std::ffi::CString;
 use super::super::utils::PretendSend;
+use super::super::lib_interfaces::Backend;
 use super::resource::ResourceManager;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 const MAX_TEX_SIZE: i32 = 4096;
 const MIN_FONT_SIZE: i32 = 1;
+const TEX_BYTES: usize = (MAX_TEX_SIZE * MAX_TEX_SIZE * 4) as usize;
+const DEFAULT_TEX_BUDGET: usize = TEX_BYTES * 2;
 
 lazy_static! {
     static ref FONT_FAMILY_ID_INC: PretendSend<Cell<i32>> = PretendSend::new(Cell::new(0));
-    static ref FONT_FAMILY_MAP: PretendSend<RefCell<HashMap<String, i32>>> = PretendSend::new(RefCell::new(HashMap::new()));
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -106,22 +108,38 @@ fn default_line_height(font_size: i32) -> f64 {
     (font_size as f64 * 1.5).ceil()
 }
 
+// the max count of characters drawn to the texture at once
+#[inline]
+fn tex_batch_max(font_size: i32) -> usize {
+    // at least one, since a character of a large font size may not fit in a row of the texture
+    cmp::max(1, (MAX_TEX_SIZE / (font_size * 2)) * (MAX_TEX_SIZE / default_line_height(font_size) as i32)) as usize
+}
+
 pub struct CharacterManager {
+    backend: Rc<Backend>,
     canvas_index: i32,
+    font_family_map: HashMap<String, i32>,
     char_tex_id_map: HashMap<CharacterKey, Rc<Character>>,
     tex_allocator: CharacterTexAllocator,
+    tex_budget: usize,
+    collected_tex_count: usize,
 }
 
-fn draw_to_tex(canvas_index: i32, tex_allocator: &mut CharacterTexAllocator, characters: &mut Vec<Rc<Character>>, font_size: i32) {
+fn draw_to_tex(backend: &Rc<Backend>, canvas_index: i32, tex_allocator: &mut CharacterTexAllocator, characters: &[Rc<Character>], font_size: i32) {
     let mut left: f64 = 0.;
     let mut top: f64 = 0.;
     let mut total_width: f64 = 0.;
     let line_height = default_line_height(font_size);
     let mut string_to_draw = String::new();
     characters.iter().for_each(|character| {
-        let mut s = String::new();
-        s.push(character.unicode);
-        let width = lib!(text_get_width(CString::new(s).unwrap().into_raw())); // FIXME should be able to batch
+        // the characters drawn before keep the width, which may be used in layout
+        let width = if character.tex_id() == -1 {
+            let mut s = String::new();
+            s.push(character.unicode);
+            backend.text_get_width(&s) // FIXME should be able to batch
+        } else {
+            character.natural_width.get()
+        };
         if left + width >= MAX_TEX_SIZE as f64 {
             total_width = if total_width > left { total_width } else { left };
             left = 0.;
@@ -136,7 +154,7 @@ fn draw_to_tex(canvas_index: i32, tex_allocator: &mut CharacterTexAllocator, cha
     total_width = total_width.ceil();
     let total_height = if left > 0. { top + line_height } else { top };
     let (tex_id, left, top) = tex_allocator.alloc_tex_pos(total_width as i32, total_height as i32);
-    lib!(text_to_tex(canvas_index, tex_id, left, top, CString::new(string_to_draw).unwrap().into_raw(), total_width as i32, total_height as i32, line_height as i32));
+    backend.text_to_tex(canvas_index, tex_id, left, top, &string_to_draw, total_width as i32, total_height as i32, line_height as i32);
     characters.iter().for_each(|character| {
         character.normalize_size(left as f64, top as f64, MAX_TEX_SIZE as f64, MAX_TEX_SIZE as f64);
         character.set_tex_id(tex_id);
@@ -144,19 +162,39 @@ fn draw_to_tex(canvas_index: i32, tex_allocator: &mut CharacterTexAllocator, cha
 }
 
 impl CharacterManager {
-    pub fn new(canvas_index: i32, resource_manager: Rc<RefCell<ResourceManager>>) -> Self {
+    pub fn new(backend: Rc<Backend>, canvas_index: i32, resource_manager: Rc<RefCell<ResourceManager>>) -> Self {
         Self {
+            backend: backend.clone(),
             canvas_index,
+            font_family_map: HashMap::new(),
             char_tex_id_map: HashMap::new(),
-            tex_allocator: CharacterTexAllocator::new(canvas_index, resource_manager),
+            tex_allocator: CharacterTexAllocator::new(backend, canvas_index, resource_manager),
+            tex_budget: DEFAULT_TEX_BUDGET,
+            collected_tex_count: 0,
         }
     }
 
+    /// Set the max bytes of the textures of the characters.
+    /// The characters not used by any text are released when exceeded, see `gabbage_collect`.
+    #[inline]
+    pub fn set_tex_budget(&mut self, bytes: usize) {
+        self.tex_budget = bytes;
+    }
+    #[inline]
+    pub fn tex_bytes(&self) -> usize {
+        self.tex_allocator.tex_count() * TEX_BYTES
+    }
+
+    fn set_font(&self, font_family_id: i32, font_size: i32, font_style: FontStyle) {
+        let line_height = default_line_height(font_size);
+        self.backend.text_set_font(font_size, line_height as i32, font_family_id, font_style == FontStyle::Italic || font_style == FontStyle::BoldItalic, font_style == FontStyle::Bold || font_style == FontStyle::BoldItalic);
+    }
+
     pub fn alloc_chars(&mut self, font_family_id: i32, font_size: i32, font_style: FontStyle, chars: Chars) -> Box<[(Rc<Character>, f32, f32)]> {
         let font_size = cmp::max(font_size, MIN_FONT_SIZE);
         let line_height = default_line_height(font_size);
-        let tex_batch_max = (MAX_TEX_SIZE / (font_size * 2)) * (MAX_TEX_SIZE / line_height as i32);
-        lib!(text_set_font(font_size, line_height as i32, font_family_id, (font_style == FontStyle::Italic || font_style == FontStyle::BoldItalic) as i32, (font_style == FontStyle::Bold || font_style == FontStyle::BoldItalic) as i32));
+        let tex_batch_max = tex_batch_max(font_size);
+        self.set_font(font_family_id, font_size, font_style);
         let mut characters_to_draw: Vec<Rc<Character>> = vec!();
         let mut characters_to_draw_count = 0;
         let characters = chars.map(|c| {
@@ -179,7 +217,7 @@ impl CharacterManager {
                         characters_to_draw.push(character.clone());
                         characters_to_draw_count += 1;
                         if characters_to_draw_count == tex_batch_max {
-                            draw_to_tex(self.canvas_index, &mut self.tex_allocator, &mut characters_to_draw, font_size);
+                            draw_to_tex(&self.backend, self.canvas_index, &mut self.tex_allocator, &characters_to_draw, font_size);
                             characters_to_draw.truncate(0);
                             characters_to_draw_count = 0;
                         }
@@ -194,13 +232,48 @@ impl CharacterManager {
             (character, 0., 0.)
         }).collect::<Vec<(Rc<Character>, f32, f32)>>().into_boxed_slice();
         if characters_to_draw.len() > 0 {
-            draw_to_tex(self.canvas_index, &mut self.tex_allocator, &mut characters_to_draw, font_size);
+            draw_to_tex(&self.backend, self.canvas_index, &mut self.tex_allocator, &characters_to_draw, font_size);
         }
         characters
     }
 
-    fn _gabbage_collect() {
-        // FIXME gabbage collect chars when needed
+    /// Release the characters if the textures exceed the budget, should be called after drawing a frame.
+    /// The characters not used by any text are removed, and then the textures without any character are deleted.
+    /// If it still exceeds, the characters are drawn again to new textures to reuse the space of the removed ones.
+    /// It is skipped if the textures are not increased since the last collection, which still exceeds the budget.
+    pub fn gabbage_collect(&mut self) {
+        if self.tex_bytes() <= self.tex_budget || self.tex_allocator.tex_count() <= self.collected_tex_count {
+            return;
+        }
+        let count = self.char_tex_id_map.len();
+        self.char_tex_id_map.retain(|_, c| Rc::strong_count(c) > 1);
+        let used: HashSet<i32> = self.char_tex_id_map.values().map(|c| c.tex_id()).collect();
+        self.tex_allocator.release_unused(&used);
+        debug!("Characters released: {} of {}, {} textures left", count - self.char_tex_id_map.len(), count, self.tex_allocator.tex_count());
+        if self.tex_bytes() > self.tex_budget {
+            self.compact();
+            if self.tex_bytes() > self.tex_budget {
+                warn!("The textures of the characters in use ({} bytes) exceed the budget ({} bytes).", self.tex_bytes(), self.tex_budget);
+            }
+        }
+        self.collected_tex_count = self.tex_allocator.tex_count();
+    }
+    // draw the characters again to new textures, and delete the old ones
+    fn compact(&mut self) {
+        let mut groups: HashMap<(i32, i32, FontStyle), Vec<Rc<Character>>> = HashMap::new();
+        for (key, c) in self.char_tex_id_map.iter() {
+            groups.entry((key.font_family_id, key.font_size, key.font_style)).or_insert(vec![]).push(c.clone());
+        }
+        let tex_allocator = self.tex_allocator.renew();
+        let mut old_tex_allocator = mem::replace(&mut self.tex_allocator, tex_allocator);
+        for ((font_family_id, font_size, font_style), characters) in groups {
+            self.set_font(font_family_id, font_size, font_style);
+            for chunk in characters.chunks(tex_batch_max(font_size)) {
+                draw_to_tex(&self.backend, self.canvas_index, &mut self.tex_allocator, chunk, font_size);
+            }
+        }
+        old_tex_allocator.release_unused(&HashSet::new());
+        debug!("Characters compacted: {} textures", self.tex_allocator.tex_count());
     }
 
     fn alloc_font_family_id() -> i32 {
@@ -209,28 +282,36 @@ impl CharacterManager {
         ret
     }
     pub fn font_family_id(&mut self, name: &str) -> i32 {
-        // NOTE font-family is never released
-        let mut font_family_map = FONT_FAMILY_MAP.borrow_mut();
+        // NOTE font-family is released only when the canvas is dropped
         let mut need_insert = false;
-        let font_family_id = match font_family_map.get(name) {
+        let font_family_id = match self.font_family_map.get(name) {
             Some(x) => {
                 *x
             },
             None => {
                 let font_family_id = Self::alloc_font_family_id();
-                lib!(text_bind_font_family(font_family_id, CString::new(name).unwrap().into_raw()));
+                self.backend.text_bind_font_family(font_family_id, name);
                 need_insert = true;
                 font_family_id
             }
         };
         if need_insert {
-            font_family_map.insert(name.to_string(), font_family_id);
+            self.font_family_map.insert(name.to_string(), font_family_id);
         }
         font_family_id
     }
 }
 
+impl Drop for CharacterManager {
+    fn drop(&mut self) {
+        for font_family_id in self.font_family_map.values() {
+            self.backend.text_unbind_font_family(*font_family_id);
+        }
+    }
+}
+
 struct CharacterTexAllocator {
+    backend: Rc<Backend>,
     canvas_index: i32,
     resource_manager: Rc<RefCell<ResourceManager>>,
     filled_height: i32,
@@ -240,8 +321,9 @@ struct CharacterTexAllocator {
 }
 
 impl CharacterTexAllocator {
-    fn new(canvas_index: i32, rm: Rc<RefCell<ResourceManager>>) -> Self {
+    fn new(backend: Rc<Backend>, canvas_index: i32, rm: Rc<RefCell<ResourceManager>>) -> Self {
         Self {
+            backend,
             canvas_index,
             resource_manager: rm,
             filled_height: MAX_TEX_SIZE,
@@ -251,12 +333,41 @@ impl CharacterTexAllocator {
         }
     }
 
+    // an empty allocator for the same canvas
+    fn renew(&self) -> Self {
+        Self::new(self.backend.clone(), self.canvas_index, self.resource_manager.clone())
+    }
+
+    #[inline]
+    fn tex_count(&self) -> usize {
+        self.tex_ids.len()
+    }
+
+    // delete the textures not in `used`, and the next allocation uses a new texture if the current one is deleted
+    fn release_unused(&mut self, used: &HashSet<i32>) {
+        let current = self.tex_ids.last().cloned();
+        let tex_ids = mem::replace(&mut self.tex_ids, vec![]);
+        for tex_id in tex_ids {
+            if used.contains(&tex_id) {
+                self.tex_ids.push(tex_id);
+            } else {
+                self.backend.tex_delete(self.canvas_index, tex_id);
+                self.resource_manager.borrow_mut().free_tex_id(tex_id);
+            }
+        }
+        if self.tex_ids.last().cloned() != current {
+            self.filled_height = MAX_TEX_SIZE;
+            self.half_filled_height = MAX_TEX_SIZE;
+            self.half_filled_left = 0;
+        }
+    }
+
     fn alloc_tex_pos(&mut self, width: i32, height: i32) -> (i32, i32, i32) {
         let mut use_half_filled = self.half_filled_left + width <= MAX_TEX_SIZE;
         let top = if use_half_filled { self.half_filled_height } else { self.filled_height };
         if top + height > MAX_TEX_SIZE {
             let new_tex_id = self.resource_manager.borrow_mut().alloc_tex_id();
-            lib!(tex_create_empty(self.canvas_index, new_tex_id, MAX_TEX_SIZE, MAX_TEX_SIZE));
+            self.backend.tex_create_empty(self.canvas_index, new_tex_id, MAX_TEX_SIZE, MAX_TEX_SIZE);
             self.tex_ids.push(new_tex_id);
             self.filled_height = 0;
             self.half_filled_height = 0;
@@ -282,3 +393,70 @@ impl CharacterTexAllocator {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use super::{CharacterManager, Character, FontStyle, TEX_BYTES};
+    use super::super::resource::ResourceManager;
+    use super::super::super::lib_interfaces::{RecordingBackend, Call, run_canvas_test};
+
+    // the characters should be in a texture not deleted, and drawn to it since `since` calls
+    fn assert_valid(backend: &RecordingBackend, since: usize, characters: &[(Rc<Character>, f32, f32)]) {
+        let calls = backend.calls();
+        let mut textures = HashSet::new();
+        for call in calls.iter() {
+            match call {
+                Call::TexCreateEmpty { tex_id, .. } => { textures.insert(*tex_id); },
+                Call::TexDelete { tex_id } => { textures.remove(tex_id); },
+                _ => { },
+            }
+        }
+        for (c, _, _) in characters.iter() {
+            assert!(textures.contains(&c.tex_id()), "{:?} is in a deleted texture", c);
+            let drawn = calls[since..].iter().any(|x| match x {
+                Call::TextToTex { tex_id, text, .. } => *tex_id == c.tex_id() && text.contains(c.unicode_char()),
+                _ => false,
+            });
+            assert!(drawn, "{:?} is not drawn", c);
+            let (left, top, width, height, _, _) = c.position();
+            assert!(left >= 0. && top >= 0. && width > 0. && height > 0. && left + width <= 1. && top + height <= 1., "{:?} is out of the texture", c);
+        }
+    }
+
+    #[test]
+    fn collect_and_compact() {
+        run_canvas_test(|| {
+            let backend = Rc::new(RecordingBackend::new());
+            let rm = Rc::new(RefCell::new(ResourceManager::new(backend.clone(), 0, 8192)));
+            let mut cm = CharacterManager::new(backend.clone(), 0, rm);
+            cm.set_tex_budget(TEX_BYTES);
+            // 4 characters are drawn at once in the font size 1000, and 4 batches fill a texture
+            let texts: Vec<String> = (0..12).map(|i| (0..4).map(|j| ::std::char::from_u32(0x4e00 + i * 4 + j).unwrap()).collect()).collect();
+            let mut kept = vec![];
+            let mut dropped = vec![];
+            for (i, text) in texts.iter().enumerate() {
+                let characters = cm.alloc_chars(0, 1000, FontStyle::Normal, text.chars());
+                if i % 3 == 0 { kept.push(characters) } else { dropped.push(characters) }
+            }
+            // too large to fit in a texture, which is drawn one by one
+            kept.push(cm.alloc_chars(0, 2100, FontStyle::Bold, "ab".chars()));
+            assert!(cm.tex_bytes() > TEX_BYTES * 3);
+            drop(dropped);
+            let since = backend.calls().len();
+            cm.gabbage_collect();
+            for characters in kept.iter() {
+                assert_valid(&backend, since, characters);
+            }
+            // the released characters are drawn again when used
+            let since = backend.calls().len();
+            let again = cm.alloc_chars(0, 1000, FontStyle::Normal, texts[1].chars());
+            assert_valid(&backend, since, &again);
+            for characters in kept.iter() {
+                assert_valid(&backend, 0, characters);
+            }
+        });
+    }
+}
@@ -3,37 +3,48 @@ use std::cell::{Cell, RefCell};
 use super::character::CharacterManager;
 use super::resource::ResourceManager;
 use super::element::style::{StyleSheetGroup, StyleSheet, ElementClass};
-use super::element::{Element, Size};
+use super::element::{Element, Size, Bounds};
 use rc_forest::{ForestNode, ForestNodeWeak};
+use super::super::lib_interfaces::Backend;
+use super::image_source::ImageResolver;
 
 pub struct CanvasConfig {
     pub index: i32,
     pub tex_size: i32,
     pub tex_count: i32,
     pub tex_max_draws: i32,
-    pub device_pixel_ratio: f64,
+    pub device_pixel_ratio: Cell<f64>,
     pub canvas_size: Cell<Size>,
+    backend: Rc<Backend>,
+    image_resolver: RefCell<Option<Rc<ImageResolver>>>,
     root_node: RefCell<Option<ForestNodeWeak<Element>>>,
     clear_color: Cell<(f32, f32, f32, f32)>,
+    repaint_requested: Cell<bool>,
+    damaged_bounds: Cell<Option<Bounds>>,
     resource_manager: Rc<RefCell<ResourceManager>>,
     character_manager: Rc<RefCell<CharacterManager>>,
     style_sheet_group: RefCell<StyleSheetGroup>,
 }
 
 impl CanvasConfig {
-    pub fn new(index: i32, tex_size: i32, tex_count: i32, tex_max_draws: i32, device_pixel_ratio: f64) -> Self {
-        let resource_manager = Rc::new(RefCell::new(ResourceManager::new(index, tex_max_draws)));
+    pub fn new(backend: Rc<Backend>, index: i32, tex_size: i32, tex_count: i32, tex_max_draws: i32, device_pixel_ratio: f64) -> Self {
+        let resource_manager = Rc::new(RefCell::new(ResourceManager::new(backend.clone(), index, tex_max_draws)));
+        let character_manager = Rc::new(RefCell::new(CharacterManager::new(backend.clone(), index, resource_manager.clone())));
         CanvasConfig {
             index,
             tex_size,
             tex_count,
             tex_max_draws,
-            device_pixel_ratio,
+            device_pixel_ratio: Cell::new(device_pixel_ratio),
             canvas_size: Cell::new(Size::new(1280., 720.)),
+            backend,
+            image_resolver: RefCell::new(None),
             root_node: RefCell::new(None),
             clear_color: Cell::new((1., 1., 1., 0.)),
-            resource_manager: resource_manager.clone(),
-            character_manager: Rc::new(RefCell::new(CharacterManager::new(index, resource_manager))),
+            repaint_requested: Cell::new(false),
+            damaged_bounds: Cell::new(None),
+            resource_manager,
+            character_manager,
             style_sheet_group: RefCell::new(StyleSheetGroup::new()),
         }
     }
@@ -54,6 +65,49 @@ impl CanvasConfig {
         self.clear_color.get()
     }
 
+    /// Request a frame even if the layout is not dirty.
+    #[inline]
+    pub(crate) fn request_repaint(&self) {
+        self.repaint_requested.set(true);
+    }
+    #[inline]
+    pub(crate) fn take_repaint_requested(&self) -> bool {
+        self.repaint_requested.replace(false)
+    }
+    /// Mark an area (in canvas coordinates) to be repainted in the next frame.
+    pub(crate) fn add_damage(&self, bounds: Option<Bounds>) {
+        match bounds {
+            None => { },
+            Some(b) => {
+                let damaged = match self.damaged_bounds.get() {
+                    None => b,
+                    Some(mut x) => {
+                        x.union(&b);
+                        x
+                    }
+                };
+                self.damaged_bounds.set(Some(damaged));
+                self.request_repaint();
+            }
+        }
+    }
+    #[inline]
+    pub(crate) fn take_damage(&self) -> Option<Bounds> {
+        self.damaged_bounds.replace(None)
+    }
+
+    #[inline]
+    pub fn backend(&self) -> Rc<Backend> {
+        self.backend.clone()
+    }
+    #[inline]
+    pub fn set_image_resolver(&self, resolver: Option<Rc<ImageResolver>>) {
+        *self.image_resolver.borrow_mut() = resolver;
+    }
+    #[inline]
+    pub fn image_resolver(&self) -> Option<Rc<ImageResolver>> {
+        self.image_resolver.borrow().clone()
+    }
     #[inline]
     pub fn character_manager(&self) -> Rc<RefCell<CharacterManager>> {
         self.character_manager.clone()
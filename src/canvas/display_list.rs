@@ -0,0 +1,343 @@
+use std::fmt;
+use std::str::FromStr;
+use super::super::lib_interfaces::Backend;
+
+/// A drawing command sent from the resource manager to the backend.
+/// The colors are the values passed to the backend, i.e. premultiplied.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    Clear { color: (f32, f32, f32, f32) },
+    SetDrawState { color: (f32, f32, f32, f32), alpha: f32 },
+    SetDrawShape { size: (f32, f32), radii: (f32, f32, f32, f32), border: (f32, f32, f32, f32), pixel_scale: f32 },
+    SetDrawBlur { step: (f32, f32), sigma: f32 },
+    Draw { draw_index: i32, tex_shader_index: i32, tex_pos: (f32, f32, f32, f32), quad: [(f32, f32); 4] },
+    SetActiveTexture { tex_shader_index: i32, tex_id: i32 },
+    DrawEnd { draw_count: i32 },
+    BindRenderingTarget { tex_id: i32, width: i32, height: i32 },
+    UnbindRenderingTarget,
+    CopyTex { dest_tex_id: i32, dest: (i32, i32), src: (i32, i32), size: (i32, i32) },
+    PushClipRect { rect: (f32, f32, f32, f32) },
+    PushClipQuad { points: [(f32, f32); 4] },
+    PushClipShape { points: [(f32, f32); 4] },
+    PopClip,
+}
+
+impl DrawCommand {
+    /// Send the command to a backend.
+    pub(crate) fn replay(&self, backend: &Backend, canvas_index: i32) {
+        match self {
+            DrawCommand::Clear { color } => {
+                backend.set_clear_color(canvas_index, color.0, color.1, color.2, color.3);
+                backend.clear(canvas_index);
+            },
+            DrawCommand::SetDrawState { color, alpha } => {
+                backend.tex_set_draw_state(canvas_index, color.0, color.1, color.2, color.3, *alpha);
+            },
+            DrawCommand::SetDrawShape { size, radii: r, border: b, pixel_scale } => {
+                backend.tex_set_draw_shape(canvas_index, size.0, size.1, r.0, r.1, r.2, r.3, b.0, b.1, b.2, b.3, *pixel_scale);
+            },
+            DrawCommand::SetDrawBlur { step, sigma } => {
+                backend.tex_set_draw_blur(canvas_index, step.0, step.1, *sigma);
+            },
+            DrawCommand::Draw { draw_index, tex_shader_index, tex_pos, quad: q } => {
+                backend.tex_draw(canvas_index, *draw_index, *tex_shader_index, tex_pos.0, tex_pos.1, tex_pos.2, tex_pos.3, q[0].0, q[0].1, q[1].0, q[1].1, q[2].0, q[2].1, q[3].0, q[3].1);
+            },
+            DrawCommand::SetActiveTexture { tex_shader_index, tex_id } => {
+                backend.tex_set_active_texture(canvas_index, *tex_shader_index, *tex_id);
+            },
+            DrawCommand::DrawEnd { draw_count } => {
+                backend.tex_draw_end(canvas_index, *draw_count);
+            },
+            DrawCommand::BindRenderingTarget { tex_id, width, height } => {
+                backend.tex_bind_rendering_target(canvas_index, *tex_id, *width, *height);
+            },
+            DrawCommand::UnbindRenderingTarget => {
+                backend.tex_unbind_rendering_target(canvas_index);
+            },
+            DrawCommand::CopyTex { dest_tex_id, dest, src, size } => {
+                backend.tex_copy(canvas_index, *dest_tex_id, dest.0, dest.1, src.0, src.1, size.0, size.1);
+            },
+            DrawCommand::PushClipRect { rect } => {
+                backend.tex_push_clip_rect(canvas_index, rect.0, rect.1, rect.2, rect.3);
+            },
+            DrawCommand::PushClipQuad { points: p } => {
+                backend.tex_push_clip_quad(canvas_index, p[0].0, p[0].1, p[1].0, p[1].1, p[2].0, p[2].1, p[3].0, p[3].1);
+            },
+            DrawCommand::PushClipShape { points: p } => {
+                backend.tex_push_clip_shape(canvas_index, p[0].0, p[0].1, p[1].0, p[1].1, p[2].0, p[2].1, p[3].0, p[3].1);
+            },
+            DrawCommand::PopClip => {
+                backend.tex_pop_clip(canvas_index);
+            },
+        }
+    }
+}
+
+impl fmt::Display for DrawCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DrawCommand::Clear { color } => {
+                write!(f, "clear {} {} {} {}", color.0, color.1, color.2, color.3)
+            },
+            DrawCommand::SetDrawState { color, alpha } => {
+                write!(f, "draw_state {} {} {} {} {}", color.0, color.1, color.2, color.3, alpha)
+            },
+            DrawCommand::SetDrawShape { size, radii: r, border: b, pixel_scale } => {
+                write!(f, "draw_shape {} {} {} {} {} {} {} {} {} {} {}", size.0, size.1, r.0, r.1, r.2, r.3, b.0, b.1, b.2, b.3, pixel_scale)
+            },
+            DrawCommand::SetDrawBlur { step, sigma } => {
+                write!(f, "draw_blur {} {} {}", step.0, step.1, sigma)
+            },
+            DrawCommand::Draw { draw_index, tex_shader_index, tex_pos, quad: q } => {
+                write!(f, "draw {} {} {} {} {} {} {} {} {} {} {} {} {} {}", draw_index, tex_shader_index, tex_pos.0, tex_pos.1, tex_pos.2, tex_pos.3, q[0].0, q[0].1, q[1].0, q[1].1, q[2].0, q[2].1, q[3].0, q[3].1)
+            },
+            DrawCommand::SetActiveTexture { tex_shader_index, tex_id } => {
+                write!(f, "active_texture {} {}", tex_shader_index, tex_id)
+            },
+            DrawCommand::DrawEnd { draw_count } => {
+                write!(f, "draw_end {}", draw_count)
+            },
+            DrawCommand::BindRenderingTarget { tex_id, width, height } => {
+                write!(f, "bind_rendering_target {} {} {}", tex_id, width, height)
+            },
+            DrawCommand::UnbindRenderingTarget => {
+                write!(f, "unbind_rendering_target")
+            },
+            DrawCommand::CopyTex { dest_tex_id, dest, src, size } => {
+                write!(f, "copy_tex {} {} {} {} {} {} {}", dest_tex_id, dest.0, dest.1, src.0, src.1, size.0, size.1)
+            },
+            DrawCommand::PushClipRect { rect } => {
+                write!(f, "push_clip_rect {} {} {} {}", rect.0, rect.1, rect.2, rect.3)
+            },
+            DrawCommand::PushClipQuad { points: p } => {
+                write!(f, "push_clip_quad {} {} {} {} {} {} {} {}", p[0].0, p[0].1, p[1].0, p[1].1, p[2].0, p[2].1, p[3].0, p[3].1)
+            },
+            DrawCommand::PushClipShape { points: p } => {
+                write!(f, "push_clip_shape {} {} {} {} {} {} {} {}", p[0].0, p[0].1, p[1].0, p[1].1, p[2].0, p[2].1, p[3].0, p[3].1)
+            },
+            DrawCommand::PopClip => {
+                write!(f, "pop_clip")
+            },
+        }
+    }
+}
+
+fn parse_args<T: FromStr>(args: &[&str], count: usize) -> Result<Vec<T>, String> {
+    if args.len() != count {
+        return Err(format!("expected {} arguments but got {}", count, args.len()));
+    }
+    args.iter().map(|x| {
+        x.parse::<T>().map_err(|_| format!("illegal argument {:?}", x))
+    }).collect()
+}
+
+impl FromStr for DrawCommand {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if words.len() == 0 {
+            return Err(String::from("empty command"));
+        }
+        let args = &words[1..];
+        let ret = match words[0] {
+            "clear" => {
+                let a: Vec<f32> = parse_args(args, 4)?;
+                DrawCommand::Clear { color: (a[0], a[1], a[2], a[3]) }
+            },
+            "draw_state" => {
+                let a: Vec<f32> = parse_args(args, 5)?;
+                DrawCommand::SetDrawState { color: (a[0], a[1], a[2], a[3]), alpha: a[4] }
+            },
+            "draw_shape" => {
+                let a: Vec<f32> = parse_args(args, 11)?;
+                DrawCommand::SetDrawShape { size: (a[0], a[1]), radii: (a[2], a[3], a[4], a[5]), border: (a[6], a[7], a[8], a[9]), pixel_scale: a[10] }
+            },
+            "draw_blur" => {
+                let a: Vec<f32> = parse_args(args, 3)?;
+                DrawCommand::SetDrawBlur { step: (a[0], a[1]), sigma: a[2] }
+            },
+            "draw" => {
+                if args.len() != 14 {
+                    return Err(format!("expected 14 arguments but got {}", args.len()));
+                }
+                let i: Vec<i32> = parse_args(&args[..2], 2)?;
+                let a: Vec<f32> = parse_args(&args[2..], 12)?;
+                DrawCommand::Draw { draw_index: i[0], tex_shader_index: i[1], tex_pos: (a[0], a[1], a[2], a[3]), quad: [(a[4], a[5]), (a[6], a[7]), (a[8], a[9]), (a[10], a[11])] }
+            },
+            "active_texture" => {
+                let a: Vec<i32> = parse_args(args, 2)?;
+                DrawCommand::SetActiveTexture { tex_shader_index: a[0], tex_id: a[1] }
+            },
+            "draw_end" => {
+                let a: Vec<i32> = parse_args(args, 1)?;
+                DrawCommand::DrawEnd { draw_count: a[0] }
+            },
+            "bind_rendering_target" => {
+                let a: Vec<i32> = parse_args(args, 3)?;
+                DrawCommand::BindRenderingTarget { tex_id: a[0], width: a[1], height: a[2] }
+            },
+            "unbind_rendering_target" => {
+                parse_args::<i32>(args, 0)?;
+                DrawCommand::UnbindRenderingTarget
+            },
+            "copy_tex" => {
+                let a: Vec<i32> = parse_args(args, 7)?;
+                DrawCommand::CopyTex { dest_tex_id: a[0], dest: (a[1], a[2]), src: (a[3], a[4]), size: (a[5], a[6]) }
+            },
+            "push_clip_rect" => {
+                let a: Vec<f32> = parse_args(args, 4)?;
+                DrawCommand::PushClipRect { rect: (a[0], a[1], a[2], a[3]) }
+            },
+            "push_clip_quad" => {
+                let a: Vec<f32> = parse_args(args, 8)?;
+                DrawCommand::PushClipQuad { points: [(a[0], a[1]), (a[2], a[3]), (a[4], a[5]), (a[6], a[7])] }
+            },
+            "push_clip_shape" => {
+                let a: Vec<f32> = parse_args(args, 8)?;
+                DrawCommand::PushClipShape { points: [(a[0], a[1]), (a[2], a[3]), (a[4], a[5]), (a[6], a[7])] }
+            },
+            "pop_clip" => {
+                parse_args::<i32>(args, 0)?;
+                DrawCommand::PopClip
+            },
+            x => {
+                return Err(format!("unknown command {:?}", x));
+            }
+        };
+        Ok(ret)
+    }
+}
+
+/// The drawing commands of a frame.
+/// It can be serialized as text (one command each line) with `to_string` and parsed back with `parse`, e.g. to inspect or diff frames.
+/// Textures are referenced by ids and their pixels are not recorded,
+/// so it can only be replayed to the canvas which recorded it while the textures are still alive.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DisplayList {
+    commands: Vec<DrawCommand>,
+}
+
+impl DisplayList {
+    pub fn new() -> Self {
+        Self {
+            commands: vec![],
+        }
+    }
+    #[inline]
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    #[inline]
+    pub fn push(&mut self, cmd: DrawCommand) {
+        self.commands.push(cmd);
+    }
+    /// Send all commands to a backend in order.
+    pub(crate) fn replay(&self, backend: &Backend, canvas_index: i32) {
+        for cmd in self.commands.iter() {
+            cmd.replay(backend, canvas_index);
+        }
+    }
+}
+
+impl fmt::Display for DisplayList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for cmd in self.commands.iter() {
+            writeln!(f, "{}", cmd)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DisplayList {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut ret = Self::new();
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.len() == 0 || line.starts_with('#') { continue }
+            let cmd = line.parse().map_err(|e| format!("line {}: {}", i + 1, e))?;
+            ret.push(cmd);
+        }
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DisplayList, DrawCommand};
+    use super::super::super::lib_interfaces::{Call, TestCanvas, run_canvas_test};
+    use super::super::element::{Element, Empty};
+    use super::super::element::style::DisplayType;
+
+    #[test]
+    fn serialize_and_parse() {
+        let mut dl = DisplayList::new();
+        dl.push(DrawCommand::PushClipRect { rect: (0.5, 1., 20., 30.) });
+        dl.push(DrawCommand::Clear { color: (1., 1., 1., 0.) });
+        dl.push(DrawCommand::SetDrawState { color: (0.1, 0.2, 0.3, 0.5), alpha: 1. });
+        dl.push(DrawCommand::BindRenderingTarget { tex_id: 3, width: 800, height: 600 });
+        dl.push(DrawCommand::Draw { draw_index: 0, tex_shader_index: 256, tex_pos: (0., 0., 1., 1.), quad: [(10.5, 20.25), (10.5, 1e-7), (100. / 3., 1e-7), (100. / 3., 20.25)] });
+        dl.push(DrawCommand::SetActiveTexture { tex_shader_index: 0, tex_id: 3 });
+        dl.push(DrawCommand::DrawEnd { draw_count: 1 });
+        dl.push(DrawCommand::CopyTex { dest_tex_id: 4, dest: (10, 20), src: (0, 0), size: (32, 16) });
+        dl.push(DrawCommand::UnbindRenderingTarget);
+        dl.push(DrawCommand::PushClipQuad { points: [(1., 0.), (0., 1.), (1., 2.), (2., 1.)] });
+        dl.push(DrawCommand::SetDrawShape { size: (40., 20.), radii: (4., 4., 0., 2.5), border: (1., 0., 1., 0.), pixel_scale: 2. });
+        dl.push(DrawCommand::PushClipShape { points: [(0., 0.), (0., 20.), (40., 20.), (40., 0.)] });
+        dl.push(DrawCommand::SetDrawBlur { step: (0.00125, 0.), sigma: 4.5 });
+        dl.push(DrawCommand::PopClip);
+        dl.push(DrawCommand::PopClip);
+        dl.push(DrawCommand::PopClip);
+        let text = dl.to_string();
+        assert_eq!(text.lines().count(), 16);
+        assert_eq!(text.parse::<DisplayList>().unwrap(), dl);
+        assert!("draw 0 1 2".parse::<DisplayList>().is_err());
+        assert!("clear 1 1 1 0\nunknown".parse::<DisplayList>().unwrap_err().starts_with("line 2:"));
+    }
+
+    #[test]
+    fn replay_recorded() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let ctx = t.canvas.context();
+            let mut ctx = ctx.borrow_mut();
+            {
+                let cfg = ctx.canvas_config();
+                let root = ctx.root();
+                let mut root = root.borrow_mut();
+                let child = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                {
+                    let child = child.deref_mut_with(&mut root);
+                    child.style_mut().display(DisplayType::Block);
+                    child.style_mut().width(10.);
+                    child.style_mut().height(10.);
+                    child.style_mut().background_color((1., 0., 0., 1.));
+                    child.style_mut().border_radius(2.);
+                    child.style_mut().opacity(0.5);
+                }
+                root.append(child);
+            }
+            t.backend.clear_calls();
+            let dl = ctx.record_frame();
+            let recorded = t.backend.calls();
+            t.backend.clear_calls();
+            ctx.replay_frame(&dl);
+            let replayed = t.backend.calls();
+            // the textures are not recorded, and the draw state is sent again after replaying
+            let drawn: Vec<Call> = recorded.into_iter().filter(|x| match x {
+                Call::TexCreateEmpty { .. } | Call::TexCreateFromPixels { .. } | Call::TexDelete { .. } => false,
+                _ => true,
+            }).collect();
+            assert!(drawn.len() > 0);
+            assert_eq!(&replayed[..drawn.len()], &drawn[..]);
+            assert!(replayed[drawn.len()..].iter().all(|x| match x {
+                Call::TexSetDrawState { .. } | Call::TexSetDrawShape { .. } | Call::TexSetDrawBlur { .. } => true,
+                _ => false,
+            }));
+        });
+    }
+}
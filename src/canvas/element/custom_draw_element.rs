@@ -0,0 +1,644 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::f64::consts::SQRT_2;
+use super::super::CanvasConfig;
+use super::super::resource::DrawState;
+use super::super::character::{Character, FontStyle};
+use super::super::path::{PathSegment, PathStyle, parse_path_data, path_bounds, circle_arc_to_cubics};
+use super::{Element, ElementStyle, InlineAllocator, Transform, Position, Bounds, Size, Point, ImageLoader, FillRule, LineJoin, LineCap};
+use rc_forest::{ForestNode, ForestNodeWeak};
+
+const DEFAULT_WIDTH: f64 = 300.;
+const DEFAULT_HEIGHT: f64 = 150.;
+const DEFAULT_DPR: f64 = 2.;
+const PATH_TEX_MAX_SIZE: i32 = 2048;
+
+// element drawn by a callback
+
+pub struct CustomDraw {
+    element: *mut Element,
+    canvas_config: Rc<CanvasConfig>,
+    draw_fn: Option<Rc<RefCell<Box<FnMut(&mut CustomDrawContext)>>>>,
+    inline_pos: Position,
+    characters: Vec<Rc<Character>>,
+}
+
+impl CustomDraw {
+    pub fn new(cfg: &Rc<CanvasConfig>) -> Self {
+        CustomDraw {
+            element: 0 as *mut Element,
+            canvas_config: cfg.clone(),
+            draw_fn: None,
+            inline_pos: Position::new(0., 0., 0., 0.),
+            characters: vec![],
+        }
+    }
+    #[inline]
+    fn element<'a>(&'a self) -> &'a Element {
+        unsafe { &*self.element }
+    }
+    #[inline]
+    fn element_mut<'a>(&'a mut self) -> &'a mut Element {
+        unsafe { &mut *self.element }
+    }
+    #[inline]
+    fn node<'a>(&'a self) -> &'a ForestNode<Element> {
+        self.element().node()
+    }
+    #[inline]
+    fn node_mut<'a>(&'a mut self) -> &'a mut ForestNode<Element> {
+        self.element_mut().node_mut()
+    }
+
+    /// Set the callback which draws the element content.
+    /// It is called whenever the element is repainted, with the coordinates of the element content box.
+    pub fn set_draw_fn<F: FnMut(&mut CustomDrawContext) + 'static>(&mut self, f: F) {
+        self.draw_fn = Some(Rc::new(RefCell::new(Box::new(f))));
+        self.request_redraw();
+    }
+    /// Call the draw callback again in the next frame.
+    /// Only the area of this element is repainted, and the layout is not changed.
+    pub fn request_redraw(&self) {
+        if self.element.is_null() { return }
+        self.element().mark_paint_dirty();
+    }
+}
+
+impl super::ElementContent for CustomDraw {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "CustomDraw"
+    }
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        true
+    }
+    fn clone(&self) -> Box<super::ElementContent> {
+        Box::new(CustomDraw {
+            element: 0 as *mut Element,
+            canvas_config: self.canvas_config.clone(),
+            draw_fn: self.draw_fn.clone(),
+            inline_pos: Position::new(0., 0., 0., 0.),
+            characters: vec![],
+        })
+    }
+    #[inline]
+    fn associate_element(&mut self, element: *mut Element) {
+        self.element = element;
+    }
+    fn suggest_size(&mut self, suggested_size: Size, inline_allocator: &mut InlineAllocator, style: &ElementStyle) -> Size {
+        let base_requested_top = inline_allocator.get_current_height();
+        let width = if style.get_width().is_finite() { style.get_width() } else { DEFAULT_WIDTH };
+        let height = if style.get_height().is_finite() { style.get_height() } else { DEFAULT_HEIGHT };
+        let baseline_top = height / 2.; // FIXME vertical-align middle
+        inline_allocator.start_node(self.node_mut(), height, baseline_top);
+        let (left, line_baseline_top) = inline_allocator.add_width(self.node_mut(), width, true).into();
+        self.inline_pos = Position::new(left, line_baseline_top - baseline_top - base_requested_top, width, height);
+        Size::new(suggested_size.width(), height - base_requested_top)
+    }
+    #[inline]
+    fn adjust_baseline_offset(&mut self, add_offset: f64) {
+        self.inline_pos.move_size(Size::new(0., add_offset));
+    }
+    #[inline]
+    fn adjust_text_align_offset(&mut self, add_offset: f64) {
+        self.inline_pos.move_size(Size::new(add_offset, 0.));
+    }
+    fn draw(&mut self, _viewport: Position, transform: &Transform) {
+        let draw_fn = match self.draw_fn {
+            None => return,
+            Some(ref x) => x.clone(),
+        };
+        if self.inline_pos.width() <= 0. || self.inline_pos.height() <= 0. {
+            return;
+        }
+        let base_transform = transform.clone().mul_clone(Transform::new().offset(Size::new(self.inline_pos.left(), self.inline_pos.top())));
+        let rm = self.canvas_config.resource_manager();
+        let base_alpha = {
+            let mut rm = rm.borrow_mut();
+            let size = Position::new(0., 0., self.inline_pos.width(), self.inline_pos.height());
+            if base_transform.is_axis_aligned() {
+                let clip = base_transform.apply_to_position(&size);
+                rm.push_clip_rect(clip.left(), clip.top(), clip.width(), clip.height());
+            } else {
+                rm.push_clip_quad(base_transform.apply_to_quad(&size));
+            }
+            rm.draw_state().alpha
+        };
+        let mut ctx = CustomDrawContext {
+            canvas_config: self.canvas_config.clone(),
+            node_weak: self.node().rc().downgrade(),
+            base_transform,
+            base_alpha,
+            size: (self.inline_pos.width(), self.inline_pos.height()),
+            state: DrawingState {
+                transform: Transform::new(),
+                fill_color: (0., 0., 0., 1.),
+                stroke_color: (0., 0., 0., 1.),
+                line_width: 1.,
+                line_join: LineJoin::Miter,
+                line_cap: LineCap::Butt,
+                miter_limit: 10.,
+                global_alpha: 1.,
+                font_size: 16.,
+                font_family: String::from("sans-serif"),
+            },
+            saved_states: vec![],
+            path: vec![],
+            path_start: None,
+            path_current: None,
+            characters: vec![],
+        };
+        (&mut *draw_fn.borrow_mut())(&mut ctx);
+        // keep the glyphs until the next draw
+        self.characters = ctx.characters;
+        let mut rm = rm.borrow_mut();
+        rm.set_draw_state(DrawState::new().alpha(base_alpha));
+        rm.pop_clip();
+    }
+    #[inline]
+    fn drawing_bounds(&self) -> Bounds {
+        self.inline_pos.into()
+    }
+    fn is_under_point(&self, point: Point, transform: Transform) -> bool {
+        match transform.unproject(point) {
+            None => false,
+            Some(x) => x.in_position(&self.inline_pos),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct DrawingState {
+    transform: Transform,
+    fill_color: (f32, f32, f32, f32),
+    stroke_color: (f32, f32, f32, f32),
+    line_width: f64,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    miter_limit: f64,
+    global_alpha: f32,
+    font_size: f32,
+    font_family: String,
+}
+
+/// The drawing context of `CustomDraw`, which is similar to the HTML canvas 2D context.
+/// The coordinates are relative to the element content box, and the drawing is clipped by it.
+/// All drawing is recorded into the same batch as the other elements.
+pub struct CustomDrawContext {
+    canvas_config: Rc<CanvasConfig>,
+    node_weak: ForestNodeWeak<Element>,
+    base_transform: Transform,
+    base_alpha: f32,
+    size: (f64, f64),
+    state: DrawingState,
+    saved_states: Vec<DrawingState>,
+    path: Vec<PathSegment>,
+    path_start: Option<(f64, f64)>,
+    path_current: Option<(f64, f64)>,
+    characters: Vec<Rc<Character>>,
+}
+
+impl CustomDrawContext {
+    /// The size of the element content box.
+    #[inline]
+    pub fn size(&self) -> Size {
+        Size::new(self.size.0, self.size.1)
+    }
+
+    /// Push the transform, the colors and the other drawing options to the stack.
+    pub fn save(&mut self) {
+        self.saved_states.push(self.state.clone());
+    }
+    /// Pop the drawing options from the stack.
+    pub fn restore(&mut self) {
+        match self.saved_states.pop() {
+            None => { },
+            Some(x) => self.state = x,
+        }
+    }
+    pub fn translate(&mut self, x: f64, y: f64) {
+        self.state.transform.translate(Size::new(x, y));
+    }
+    pub fn scale(&mut self, x: f64, y: f64) {
+        self.state.transform.scale(x, y);
+    }
+    /// Rotate clockwise by `angle` in radians.
+    pub fn rotate(&mut self, angle: f64) {
+        self.state.transform.rotate(angle);
+    }
+    /// Multiply the current transform by the matrix, the same as the canvas `transform`.
+    pub fn transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.state.transform.matrix(a, b, c, d, e, f);
+    }
+    pub fn set_transform(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        self.state.transform.reset().matrix(a, b, c, d, e, f);
+    }
+    pub fn reset_transform(&mut self) {
+        self.state.transform.reset();
+    }
+    pub fn set_fill_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.state.fill_color = color;
+    }
+    pub fn set_stroke_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.state.stroke_color = color;
+    }
+    pub fn set_line_width(&mut self, width: f64) {
+        self.state.line_width = width;
+    }
+    pub fn set_line_join(&mut self, line_join: LineJoin) {
+        self.state.line_join = line_join;
+    }
+    pub fn set_line_cap(&mut self, line_cap: LineCap) {
+        self.state.line_cap = line_cap;
+    }
+    pub fn set_miter_limit(&mut self, miter_limit: f64) {
+        self.state.miter_limit = miter_limit;
+    }
+    /// Set the opacity applied to all following drawing.
+    pub fn set_global_alpha(&mut self, alpha: f32) {
+        self.state.global_alpha = alpha;
+    }
+    pub fn set_font_size(&mut self, font_size: f32) {
+        self.state.font_size = font_size;
+    }
+    pub fn set_font_family<T>(&mut self, font_family: T) where String: From<T> {
+        self.state.font_family = String::from(font_family);
+    }
+
+    #[inline]
+    fn full_transform(&self) -> Transform {
+        self.base_transform.clone().mul_clone(&self.state.transform)
+    }
+    fn set_draw_state(&self, color: Option<(f32, f32, f32, f32)>) {
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        let mut ds = DrawState::new();
+        ds.alpha(self.base_alpha * self.state.global_alpha);
+        match color {
+            None => { },
+            Some(x) => { ds.color(x); },
+        }
+        rm.set_draw_state(&mut ds);
+    }
+
+    /// Fill a rect with the fill color.
+    pub fn fill_rect(&mut self, left: f64, top: f64, width: f64, height: f64) {
+        self.set_draw_state(Some(self.state.fill_color));
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().request_draw(
+            -2, true,
+            0., 0., 1., 1.,
+            self.full_transform().apply_to_quad(&Position::new(left, top, width, height))
+        );
+    }
+    /// Stroke a rect with the stroke color and the line options.
+    /// The current path is not changed.
+    pub fn stroke_rect(&mut self, left: f64, top: f64, width: f64, height: f64) {
+        let path = self.take_path();
+        self.rect(left, top, width, height);
+        self.stroke();
+        self.restore_path(path);
+    }
+    /// Draw a loaded image stretched to the rect.
+    /// If the image is still loading, the element is redrawn when it is loaded.
+    pub fn draw_image(&mut self, loader: &Rc<RefCell<ImageLoader>>, left: f64, top: f64, width: f64, height: f64) {
+        let (tex_id, tex_pos) = {
+            let mut loader = loader.borrow_mut();
+            if loader.is_loading() {
+                loader.unbind_tree_node(&self.node_weak);
+                loader.bind_tree_node(self.node_weak.clone());
+            }
+            (loader.tex_id(), loader.tex_pos())
+        };
+        if tex_id == -1 { return }
+        self.set_draw_state(None);
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().request_draw(
+            tex_id, false,
+            tex_pos.0, tex_pos.1, tex_pos.2, tex_pos.3,
+            self.full_transform().apply_to_quad(&Position::new(left, top, width, height))
+        );
+    }
+    fn alloc_chars(&mut self, text: &str) -> (Box<[(Rc<Character>, f32, f32)]>, f64) {
+        let dpr = self.canvas_config.device_pixel_ratio.get();
+        let dpr = if dpr == 1. { DEFAULT_DPR } else { dpr };
+        let tex_font_size = (self.state.font_size * dpr as f32 * self.base_transform.get_area_scale() as f32).ceil() as i32;
+        let cm = self.canvas_config.character_manager();
+        let mut manager = cm.borrow_mut();
+        let font_family_id = manager.font_family_id(&self.state.font_family);
+        let chars = manager.alloc_chars(font_family_id, tex_font_size, FontStyle::Normal, text.chars());
+        (chars, self.state.font_size as f64 / tex_font_size.max(1) as f64)
+    }
+    /// Draw a single line of text with the fill color, from the left-top corner.
+    pub fn draw_text(&mut self, text: &str, left: f64, top: f64) {
+        let (chars, size_ratio) = self.alloc_chars(text);
+        self.set_draw_state(Some(self.state.fill_color));
+        let transform = self.full_transform();
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        let mut x = left;
+        for (character, _, _) in chars.iter() {
+            if character.tex_id() == -1 { continue }
+            let char_pos = character.position();
+            let width = char_pos.4 * size_ratio;
+            let height = char_pos.5 * size_ratio;
+            rm.request_draw(
+                character.tex_id(), true,
+                char_pos.0, char_pos.1, char_pos.2, char_pos.3,
+                transform.apply_to_quad(&Position::new(x, top, width, height))
+            );
+            x += width;
+        }
+        self.characters.extend(chars.iter().map(|x| x.0.clone()));
+    }
+    /// The width of a single line of text with the current font.
+    pub fn measure_text(&mut self, text: &str) -> f64 {
+        let (chars, size_ratio) = self.alloc_chars(text);
+        chars.iter().filter(|x| x.0.tex_id() != -1).map(|x| x.0.position().4 * size_ratio).sum()
+    }
+
+    // the path is stored with the current transform applied
+    #[inline]
+    fn transform_point(&self, x: f64, y: f64) -> (f64, f64) {
+        let p = self.state.transform.apply_to_point(Point::new(x, y));
+        (p.left(), p.top())
+    }
+    fn take_path(&mut self) -> (Vec<PathSegment>, Option<(f64, f64)>, Option<(f64, f64)>) {
+        let path = ::std::mem::replace(&mut self.path, vec![]);
+        (path, self.path_start.take(), self.path_current.take())
+    }
+    fn restore_path(&mut self, (path, start, current): (Vec<PathSegment>, Option<(f64, f64)>, Option<(f64, f64)>)) {
+        self.path = path;
+        self.path_start = start;
+        self.path_current = current;
+    }
+    fn ensure_current(&mut self, x: f64, y: f64) -> (f64, f64) {
+        match self.path_current {
+            Some(p) => p,
+            None => {
+                let p = self.transform_point(x, y);
+                self.path.push(PathSegment::MoveTo(p.0, p.1));
+                self.path_start = Some(p);
+                self.path_current = Some(p);
+                p
+            }
+        }
+    }
+    /// Clear the current path.
+    pub fn begin_path(&mut self) {
+        self.take_path();
+    }
+    pub fn move_to(&mut self, x: f64, y: f64) {
+        let p = self.transform_point(x, y);
+        self.path.push(PathSegment::MoveTo(p.0, p.1));
+        self.path_start = Some(p);
+        self.path_current = Some(p);
+    }
+    pub fn line_to(&mut self, x: f64, y: f64) {
+        self.ensure_current(x, y);
+        let p = self.transform_point(x, y);
+        self.path.push(PathSegment::LineTo(p.0, p.1));
+        self.path_current = Some(p);
+    }
+    pub fn quadratic_curve_to(&mut self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        let from = self.ensure_current(cpx, cpy);
+        let c = self.transform_point(cpx, cpy);
+        let p = self.transform_point(x, y);
+        self.path.push(PathSegment::CubicTo(
+            from.0 + (c.0 - from.0) * 2. / 3., from.1 + (c.1 - from.1) * 2. / 3.,
+            p.0 + (c.0 - p.0) * 2. / 3., p.1 + (c.1 - p.1) * 2. / 3.,
+            p.0, p.1,
+        ));
+        self.path_current = Some(p);
+    }
+    pub fn bezier_curve_to(&mut self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        self.ensure_current(cp1x, cp1y);
+        let c1 = self.transform_point(cp1x, cp1y);
+        let c2 = self.transform_point(cp2x, cp2y);
+        let p = self.transform_point(x, y);
+        self.path.push(PathSegment::CubicTo(c1.0, c1.1, c2.0, c2.1, p.0, p.1));
+        self.path_current = Some(p);
+    }
+    /// Add a circular arc from the `start` angle to the `end` angle in radians, the same as the canvas `arc`.
+    /// A line is added from the current point to the start of the arc.
+    pub fn arc(&mut self, x: f64, y: f64, radius: f64, start: f64, end: f64, anticlockwise: bool) {
+        let (sin, cos) = start.sin_cos();
+        let (sx, sy) = (x + radius * cos, y + radius * sin);
+        match self.path_current {
+            None => self.move_to(sx, sy),
+            Some(_) => self.line_to(sx, sy),
+        }
+        let mut segments = vec![];
+        circle_arc_to_cubics(&mut segments, (x, y), radius, start, end, anticlockwise);
+        for seg in segments {
+            match seg {
+                PathSegment::CubicTo(x1, y1, x2, y2, x, y) => self.bezier_curve_to(x1, y1, x2, y2, x, y),
+                _ => { },
+            }
+        }
+    }
+    /// Add a closed rect sub path.
+    pub fn rect(&mut self, left: f64, top: f64, width: f64, height: f64) {
+        self.move_to(left, top);
+        self.line_to(left + width, top);
+        self.line_to(left + width, top + height);
+        self.line_to(left, top + height);
+        self.close_path();
+    }
+    pub fn close_path(&mut self) {
+        if self.path_current.is_none() { return }
+        self.path.push(PathSegment::Close);
+        self.path_current = self.path_start;
+    }
+    /// Add the SVG path data, e.g. `"M 0 0 L 10 10 Z"`, with the current transform.
+    pub fn add_path_data(&mut self, data: &str) {
+        let (segments, err) = parse_path_data(data);
+        match err {
+            None => { },
+            Some(pos) => warn!("Invalid path data at {}: {:?}", pos, data),
+        }
+        for seg in segments {
+            match seg {
+                PathSegment::MoveTo(x, y) => self.move_to(x, y),
+                PathSegment::LineTo(x, y) => self.line_to(x, y),
+                PathSegment::CubicTo(x1, y1, x2, y2, x, y) => self.bezier_curve_to(x1, y1, x2, y2, x, y),
+                PathSegment::Close => self.close_path(),
+            }
+        }
+    }
+    /// Fill the current path with the fill color.
+    pub fn fill(&mut self, fill_rule: FillRule) {
+        let style = PathStyle {
+            fill_color: self.state.fill_color,
+            fill_rule,
+            stroke_color: (0., 0., 0., 0.),
+            stroke_width: 0.,
+            line_join: self.state.line_join,
+            line_cap: self.state.line_cap,
+            miter_limit: self.state.miter_limit,
+        };
+        self.draw_path(style);
+    }
+    /// Stroke the current path with the stroke color and the line options.
+    pub fn stroke(&mut self) {
+        let style = PathStyle {
+            fill_color: (0., 0., 0., 0.),
+            fill_rule: FillRule::NonZero,
+            stroke_color: self.state.stroke_color,
+            stroke_width: self.state.line_width * self.state.transform.get_area_scale(),
+            line_join: self.state.line_join,
+            line_cap: self.state.line_cap,
+            miter_limit: self.state.miter_limit,
+        };
+        self.draw_path(style);
+    }
+    fn draw_path(&mut self, style: PathStyle) {
+        let b = match path_bounds(&self.path) {
+            None => return,
+            Some(x) => x,
+        };
+        // rasterize the part of the path inside the element
+        let extend = if style.stroke_color.3 > 0. {
+            let join = if style.line_join == LineJoin::Miter { style.miter_limit.max(SQRT_2) } else { SQRT_2 };
+            style.stroke_width / 2. * join
+        } else {
+            0.
+        };
+        let left = (b.0 - extend).max(0.);
+        let top = (b.1 - extend).max(0.);
+        let right = (b.2 + extend).min(self.size.0);
+        let bottom = (b.3 + extend).min(self.size.1);
+        if right <= left || bottom <= top { return }
+        let view_box = (left, top, right - left, bottom - top);
+        let scale = self.canvas_config.device_pixel_ratio.get() * self.base_transform.get_area_scale();
+        let tex_size = |x: f64| ((x * scale).ceil() as i32).max(1).min(PATH_TEX_MAX_SIZE);
+        let tex_size = (tex_size(view_box.2), tex_size(view_box.3));
+        self.set_draw_state(None);
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        let tex_id = rm.path_texture(&self.path, &style, view_box, tex_size);
+        rm.request_draw(
+            tex_id, false,
+            0., 0., 1., 1.,
+            self.base_transform.apply_to_quad(&Position::new(view_box.0, view_box.1, view_box.2, view_box.3))
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use std::time::Instant;
+    use rc_forest::ForestNodeRc;
+    use super::{CustomDraw, CustomDrawContext};
+    use super::super::{Element, Empty, Bounds};
+    use super::super::style::DisplayType;
+    use super::super::super::super::lib_interfaces::{Call, TestCanvas, run_canvas_test};
+    use super::super::super::super::frame;
+
+    // a 100x20 block with the background, followed by a 40x30 custom draw element in a block
+    fn custom_draw<F: FnMut(&mut CustomDrawContext) + 'static>(t: &TestCanvas, f: F) -> ForestNodeRc<Element> {
+        let ctx = t.canvas.context();
+        let mut ctx = ctx.borrow_mut();
+        let cfg = ctx.canvas_config();
+        let root = ctx.root();
+        let mut root = root.borrow_mut();
+        let block = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        {
+            let b = block.deref_mut_with(&mut root);
+            b.style_mut().display(DisplayType::Block);
+            b.style_mut().width(100.);
+            b.style_mut().height(20.);
+            b.style_mut().background_color((1., 0., 0., 1.));
+        }
+        root.append(block);
+        let wrapper = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        wrapper.deref_mut_with(&mut root).style_mut().display(DisplayType::Block);
+        let custom = root.create_another(Element::new(&cfg, Box::new(CustomDraw::new(&cfg))));
+        {
+            let c = custom.deref_mut_with(&mut root);
+            c.style_mut().width(40.);
+            c.style_mut().height(30.);
+            c.content_mut().downcast_mut::<CustomDraw>().unwrap().set_draw_fn(f);
+        }
+        wrapper.deref_mut_with(&mut root).append(custom.clone());
+        root.append(wrapper);
+        custom
+    }
+
+    // the bounds of the quads drawn, with the alpha of the draw state
+    fn drawn_quads(calls: &[Call]) -> Vec<(Bounds, f32)> {
+        let mut alpha = 1.;
+        calls.iter().filter_map(|x| match x {
+            Call::TexSetDrawState { alpha: a, .. } => {
+                alpha = *a;
+                None
+            },
+            Call::TexDraw { quad, .. } => Some((Bounds::new(quad[0].0 as f64, quad[0].1 as f64, quad[2].0 as f64, quad[2].1 as f64), alpha)),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn clip_and_states() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(200, 100);
+            custom_draw(&t, |ctx| {
+                ctx.fill_rect(0., 0., 10., 10.);
+                ctx.save();
+                ctx.translate(10., 5.);
+                ctx.scale(2., 2.);
+                ctx.set_global_alpha(0.5);
+                ctx.fill_rect(0., 0., 5., 5.);
+                ctx.restore();
+                ctx.fill_rect(1., 1., 2., 2.);
+            });
+            frame::generate(Instant::now());
+            let calls = t.backend.calls();
+            let push = calls.iter().position(|x| *x == Call::TexPushClipRect { rect: (0., 20., 40., 30.) }).unwrap();
+            let pop = push + calls[push..].iter().position(|x| *x == Call::TexPopClip).unwrap();
+            assert_eq!(t.backend.count_calls(|x| match x { Call::TexPushClipRect { .. } | Call::TexPushClipQuad { .. } => true, _ => false }), 1);
+            assert_eq!(t.backend.count_calls(|x| *x == Call::TexPopClip), 1);
+            // the transform and the alpha are restored for the last rect
+            assert_eq!(drawn_quads(&calls[push..pop]), vec![
+                (Bounds::new(0., 20., 10., 30.), 1.),
+                (Bounds::new(10., 25., 20., 35.), 0.5),
+                (Bounds::new(1., 21., 3., 23.), 1.),
+            ]);
+        });
+    }
+
+    #[test]
+    fn request_redraw() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(200, 100);
+            let count = Rc::new(Cell::new(0));
+            let custom = {
+                let count = count.clone();
+                custom_draw(&t, move |ctx| {
+                    count.set(count.get() + 1);
+                    ctx.fill_rect(0., 0., 10., 10.);
+                })
+            };
+            // the resizing repaints the first two frames
+            frame::generate(Instant::now());
+            frame::generate(Instant::now());
+            frame::generate(Instant::now());
+            assert_eq!(count.get(), 2);
+            custom.borrow_mut().content_mut().downcast_mut::<CustomDraw>().unwrap().request_redraw();
+            t.backend.clear_calls();
+            frame::generate(Instant::now());
+            assert_eq!(count.get(), 3);
+            // only the element is repainted, with the damage clip and its own clip
+            let calls = t.backend.calls();
+            assert_eq!(calls.iter().filter(|x| match x { Call::TexPushClipRect { .. } => true, _ => false }).collect::<Vec<_>>(), vec![
+                &Call::TexPushClipRect { rect: (0., 20., 40., 30.) },
+                &Call::TexPushClipRect { rect: (0., 20., 40., 30.) },
+            ]);
+            assert_eq!(t.backend.count_calls(|x| *x == Call::TexPopClip), 2);
+            assert_eq!(drawn_quads(&calls), vec![(Bounds::new(0., 20., 10., 30.), 1.)]);
+        });
+    }
+}
@@ -1,6 +1,6 @@
 use std::rc::Rc;
 use super::super::CanvasConfig;
-use super::{Transform, Point, Bounds};
+use super::{Transform, Position, Point, Bounds};
 
 #[derive(Debug)]
 pub struct Empty {}
@@ -24,7 +24,7 @@ impl super::ElementContent for Empty {
         Box::new(Empty {})
     }
     #[inline]
-    fn draw(&mut self, _transform: &Transform) {
+    fn draw(&mut self, _viewport: Position, _transform: &Transform) {
         // do nothing
     }
     fn drawing_bounds(&self) -> Bounds {
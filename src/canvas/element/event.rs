@@ -36,6 +36,25 @@ impl<'a> Event<'a> {
     }
 }
 
+/// The detail of `scroll` events.
+pub struct ScrollEventDetail {
+    pub scroll_left: f64,
+    pub scroll_top: f64,
+}
+
+/// The detail of `load` events of images.
+pub struct ImageLoadEventDetail {
+    pub natural_width: i32,
+    pub natural_height: i32,
+}
+
+/// The detail of `error` events of images.
+pub struct ImageErrorEventDetail {
+    /// The error code from the backend, e.g. `IMAGE_LOAD_ERROR_READ`.
+    pub code: i32,
+    pub reason: String,
+}
+
 pub struct EventReceiver {
     listeners: HashMap<String, Vec<EventCallback>>
 }
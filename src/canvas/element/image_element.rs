@@ -1,9 +1,12 @@
-use std::ffi::CString;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::any::Any;
 use super::super::CanvasConfig;
 use super::super::resource::ResourceManager;
-use super::{Element, ElementStyle, InlineAllocator, Transform, Position, Bounds, Size, Point};
+use super::super::atlas::AtlasImage;
+use super::super::image_source::{ImageSource, is_data_uri, decode_data_uri};
+use super::{Element, ElementStyle, InlineAllocator, Transform, Position, Bounds, Size, Point, ImageLoadEventDetail, ImageErrorEventDetail};
+use super::super::super::lib_interfaces::{IMAGE_LOAD_ERROR_READ, IMAGE_LOAD_ERROR_DECODE};
 use rc_forest::{ForestNode, ForestNodeWeak};
 
 const IMAGE_SIZE_WARN: i32 = 4096;
@@ -18,6 +21,8 @@ pub struct Image {
     loader: Option<Rc<RefCell<ImageLoader>>>,
     inline_pos: Position,
     natural_size: (i32, i32),
+    vector_tex_id: i32,
+    vector_tex_size: (i32, i32),
 }
 
 impl Image {
@@ -30,6 +35,8 @@ impl Image {
             loader: None,
             inline_pos: Position::new(0., 0., 0., 0.),
             natural_size: (0, 0),
+            vector_tex_id: -1,
+            vector_tex_size: (0, 0),
         }
     }
     #[inline]
@@ -53,16 +60,19 @@ impl Image {
         // NOTE this method should be called if manually updated loader
         self.tex_id = -1;
         self.natural_size = (0, 0);
+        self.delete_vector_tex();
         self.element_mut().mark_layout_dirty();
+        self.element().mark_paint_dirty();
     }
     fn update_from_loader(&mut self) {
         {
             let loader = self.loader.as_ref().unwrap().borrow();
-            self.tex_id = loader.tex_id;
+            self.tex_id = loader.tex_id();
             let size = loader.size();
             self.natural_size = size;
         }
         self.element_mut().mark_layout_dirty();
+        self.element().mark_paint_dirty();
     }
     pub fn set_loader(&mut self, loader: Rc<RefCell<ImageLoader>>) {
         self.need_update_from_loader();
@@ -84,6 +94,10 @@ impl Image {
         }
         if loader_loaded {
             self.update_from_loader();
+            // the listeners are usually added after binding, so the event is dispatched later
+            let node = self.node().rc().downgrade();
+            let loader = self.loader.clone().unwrap();
+            self.canvas_config.backend().timeout(0, lib_callback!(ImageLoaderEventCallback(node, loader)));
         }
     }
     pub fn load<T: Into<Vec<u8>>>(&mut self, url: T) {
@@ -91,6 +105,32 @@ impl Image {
         self.set_loader(Rc::new(RefCell::new(ImageLoader::new_with_canvas_config(cc))));
         ImageLoader::load(self.loader.as_mut().unwrap().clone(), url);
     }
+    pub fn load_from_bytes<T: AsRef<[u8]>>(&mut self, bytes: T) {
+        let cc = self.canvas_config.clone();
+        self.set_loader(Rc::new(RefCell::new(ImageLoader::new_with_canvas_config(cc))));
+        ImageLoader::load_from_bytes(self.loader.as_mut().unwrap().clone(), bytes);
+    }
+    // the vector image is rendered with the device pixels of the element, and again if the size changed
+    fn update_vector_tex(&mut self, transform: &Transform) {
+        let scale = self.canvas_config.device_pixel_ratio.get() * transform.get_area_scale();
+        let tex_size = |x: f64| ((x * scale).ceil() as i32).max(1).min(IMAGE_SIZE_WARN);
+        let tex_size = (tex_size(self.inline_pos.width()), tex_size(self.inline_pos.height()));
+        if self.vector_tex_id != -1 && self.vector_tex_size == tex_size {
+            return;
+        }
+        self.delete_vector_tex();
+        let rm = self.canvas_config.resource_manager();
+        self.vector_tex_id = rm.borrow_mut().alloc_tex_id();
+        self.vector_tex_size = tex_size;
+        self.loader.as_ref().unwrap().borrow().tex_from_image_with_size(self.vector_tex_id, tex_size);
+    }
+    fn delete_vector_tex(&mut self) {
+        if self.vector_tex_id == -1 { return }
+        self.canvas_config.backend().tex_delete(self.canvas_config.index, self.vector_tex_id);
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().free_tex_id(self.vector_tex_id);
+        self.vector_tex_id = -1;
+    }
     fn remove_from_loader(&mut self) {
         match &self.node_weak {
             Some(x) => {
@@ -109,7 +149,8 @@ impl Image {
 impl Drop for Image {
     fn drop(&mut self) {
         // NOTE when dropping, self.element is not usable, so keeping an weak ref in advance
-        self.remove_from_loader()
+        self.remove_from_loader();
+        self.delete_vector_tex();
     }
 }
 
@@ -132,6 +173,8 @@ impl super::ElementContent for Image {
             loader: None,
             inline_pos: Position::new(0., 0., 0., 0.),
             natural_size: self.natural_size,
+            vector_tex_id: -1,
+            vector_tex_size: (0, 0),
         });
         match self.loader.clone() {
             None => {},
@@ -186,15 +229,24 @@ impl super::ElementContent for Image {
     fn adjust_text_align_offset(&mut self, add_offset: f64) {
         self.inline_pos.move_size(Size::new(add_offset, 0.));
     }
-    fn draw(&mut self, transform: &Transform) {
+    fn draw(&mut self, _viewport: Position, transform: &Transform) {
         if self.tex_id == -1 {
             return;
         }
+        let is_vector = self.loader.as_ref().unwrap().borrow().is_vector();
+        // the texture of the loader may be moved into the image atlas after loaded
+        let (tex_id, tex_pos) = if is_vector {
+            self.update_vector_tex(transform);
+            (self.vector_tex_id, (0., 0., 1., 1.))
+        } else {
+            let loader = self.loader.as_ref().unwrap().borrow();
+            (loader.tex_id(), loader.tex_pos())
+        };
         let rm = self.canvas_config.resource_manager();
         rm.borrow_mut().request_draw(
-            self.tex_id, false,
-            0., 0., 1., 1.,
-            transform.apply_to_position(&self.inline_pos).into()
+            tex_id, false,
+            tex_pos.0, tex_pos.1, tex_pos.2, tex_pos.3,
+            transform.apply_to_quad(&self.inline_pos)
         );
     }
     #[inline]
@@ -206,9 +258,10 @@ impl super::ElementContent for Image {
         if self.tex_id == -1 {
             return false;
         }
-        let pos = transform.apply_to_position(&self.inline_pos);
-        // debug!("testing {:?} in image pos {:?}", (x, y), pos);
-        point.in_position(&pos)
+        match transform.unproject(point) {
+            None => false,
+            Some(x) => x.in_position(&self.inline_pos),
+        }
     }
 }
 
@@ -229,8 +282,20 @@ pub struct ImageLoader {
     status: ImageLoaderStatus,
     img_id: i32,
     tex_id: i32,
+    atlas_image: Option<Rc<AtlasImage>>,
     width: i32,
     height: i32,
+    is_vector: bool,
+    error_code: i32,
+    error_reason: String,
+}
+
+fn load_error_reason(code: i32) -> &'static str {
+    match code {
+        IMAGE_LOAD_ERROR_READ => "The image cannot be read",
+        IMAGE_LOAD_ERROR_DECODE => "The image is corrupt or in an unsupported format",
+        _ => "The image failed to load",
+    }
 }
 
 impl ImageLoader {
@@ -242,8 +307,12 @@ impl ImageLoader {
             status: ImageLoaderStatus::NotLoaded,
             img_id: ResourceManager::alloc_image_id(),
             tex_id: -1,
+            atlas_image: None,
             width: 0,
             height: 0,
+            is_vector: false,
+            error_code: 0,
+            error_reason: String::new(),
         }
     }
 
@@ -283,15 +352,91 @@ impl ImageLoader {
     pub fn is_loading(&self) -> bool {
         self.status == ImageLoaderStatus::Loading
     }
+    /// The error code from the backend if failed to load, or `0` otherwise.
+    #[inline]
+    pub fn error_code(&self) -> i32 {
+        self.error_code
+    }
+    /// The reason if failed to load, i.e. the message from the backend, or a general one of the error code if unknown.
+    #[inline]
+    pub fn error_reason(&self) -> Option<&str> {
+        match self.status {
+            ImageLoaderStatus::LoadFailed => Some(&self.error_reason),
+            _ => None,
+        }
+    }
     #[inline]
     pub fn size(&self) -> (i32, i32) {
         (self.width, self.height)
     }
+    /// The texture containing the image, which may be a page of the image atlas shared with other images.
+    #[inline]
+    pub fn tex_id(&self) -> i32 {
+        match self.atlas_image {
+            None => self.tex_id,
+            Some(ref x) => x.tex_id(),
+        }
+    }
+    /// The normalized position of the image in the texture of `tex_id`.
+    #[inline]
+    pub fn tex_pos(&self) -> (f64, f64, f64, f64) {
+        match self.atlas_image {
+            None => (0., 0., 1., 1.),
+            Some(ref x) => x.tex_pos(),
+        }
+    }
+    /// Use the image in the atlas instead of the own texture, which should be deleted by the caller.
+    pub(crate) fn set_atlas_image(&mut self, image: Rc<AtlasImage>) {
+        self.tex_id = -1;
+        self.atlas_image = Some(image);
+    }
+    /// Whether the image is a vector image, i.e. SVG.
+    /// The texture of `tex_id` is rendered with the natural size, and can be rendered with other sizes by `tex_from_image_with_size`.
+    #[inline]
+    pub fn is_vector(&self) -> bool {
+        self.is_vector
+    }
+    /// Create a texture of the vector image rendered with `size` in pixels.
+    pub(crate) fn tex_from_image_with_size(&self, tex_id: i32, size: (i32, i32)) {
+        self.canvas_config.backend().tex_from_image_with_size(self.canvas_config.index, tex_id, self.img_id, size.0, size.1);
+    }
+    #[inline]
+    fn start_loading(&mut self) {
+        assert_eq!(self.status, ImageLoaderStatus::NotLoaded);
+        self.status = ImageLoaderStatus::Loading;
+    }
+    /// Load an image from a url, which is mapped by the image resolver of the canvas first.
+    /// The `data:` URIs are decoded and loaded as bytes.
     pub fn load<T: Into<Vec<u8>>>(self_rc: Rc<RefCell<Self>>, url: T) {
+        let url = String::from_utf8_lossy(&url.into()).into_owned();
+        let resolved = match self_rc.borrow().canvas_config.image_resolver() {
+            None => None,
+            Some(x) => x.resolve(&url),
+        };
+        let url = match resolved {
+            None => url,
+            Some(ImageSource::Url(x)) => x,
+            Some(ImageSource::Bytes(x)) => return Self::load_from_bytes(self_rc, x),
+        };
+        if is_data_uri(&url) {
+            let bytes = match decode_data_uri(&url) {
+                Some(x) => x,
+                None => {
+                    warn!("Invalid data URI for image: {}", url);
+                    vec![]
+                },
+            };
+            return Self::load_from_bytes(self_rc, bytes);
+        }
+        let mut self_ref = self_rc.borrow_mut();
+        self_ref.start_loading();
+        self_ref.canvas_config.backend().image_load_url(self_ref.img_id, &url, lib_callback!(ImageLoaderCallback(self_rc.clone())));
+    }
+    /// Load an image from the encoded bytes, e.g. the content of a PNG or SVG file.
+    pub fn load_from_bytes<T: AsRef<[u8]>>(self_rc: Rc<RefCell<Self>>, bytes: T) {
         let mut self_ref = self_rc.borrow_mut();
-        assert_eq!(self_ref.status, ImageLoaderStatus::NotLoaded);
-        self_ref.status = ImageLoaderStatus::Loading;
-        lib!(image_load_url(self_ref.img_id, CString::new(url).unwrap().into_raw(), lib_callback!(ImageLoaderCallback(self_rc.clone()))));
+        self_ref.start_loading();
+        self_ref.canvas_config.backend().image_load_bytes(self_ref.img_id, bytes.as_ref(), lib_callback!(ImageLoaderCallback(self_rc.clone())));
     }
 }
 
@@ -300,10 +445,12 @@ lib_define_callback! (ImageLoaderCallback (Rc<RefCell<ImageLoader>>) {
         let mut nodes = {
             let mut loader = self.0.borrow_mut();
             assert_eq!(loader.status, ImageLoaderStatus::Loading);
+            let backend = loader.canvas_config.backend();
             if ret_code == 0 {
                 loader.status = ImageLoaderStatus::Loaded;
-                loader.width = lib!(image_get_natural_width(loader.img_id));
-                loader.height = lib!(image_get_natural_height(loader.img_id));
+                loader.width = backend.image_get_natural_width(loader.img_id);
+                loader.height = backend.image_get_natural_height(loader.img_id);
+                loader.is_vector = backend.image_is_vector(loader.img_id);
                 if loader.width > IMAGE_SIZE_WARN {
                     warn!("Image width ({}) exceeds max size ({}). May not display properly.", loader.width, IMAGE_SIZE_WARN);
                 }
@@ -313,19 +460,36 @@ lib_define_callback! (ImageLoaderCallback (Rc<RefCell<ImageLoader>>) {
                 let rm = loader.canvas_config.resource_manager();
                 loader.tex_id = rm.borrow_mut().alloc_tex_id();
                 log!("Image loaded: {}", loader.img_id);
-                lib!(tex_from_image(loader.canvas_config.index, loader.tex_id, loader.img_id));
+                backend.tex_from_image(loader.canvas_config.index, loader.tex_id, loader.img_id);
+                rm.borrow_mut().request_image_atlas(&self.0);
             } else {
                 loader.status = ImageLoaderStatus::LoadFailed;
+                loader.error_code = ret_code;
+                loader.error_reason = backend.image_get_error(loader.img_id);
+                if loader.error_reason.is_empty() {
+                    loader.error_reason = String::from(load_error_reason(ret_code));
+                }
+            }
+            // the vector image is kept to be rendered with other sizes
+            if !loader.is_vector {
+                backend.image_unload(loader.img_id);
+                ResourceManager::free_image_id(loader.img_id);
             }
-            lib!(image_unload(loader.img_id));
-            ResourceManager::free_image_id(loader.img_id);
             loader.binded_tree_nodes.clone()
         };
         nodes.iter_mut().for_each(|x| {
             match x.upgrade() {
                 None => { },
                 Some(x) => {
-                    x.borrow_mut().downcast_mut::<Image>().unwrap().update_from_loader();
+                    // the node may use the loader for the image content or for the background image
+                    let mut x = x.borrow_mut();
+                    if is_content_loader(&x, &self.0) {
+                        x.downcast_mut::<Image>().unwrap().update_from_loader();
+                        let (name, detail) = loader_event(&self.0.borrow());
+                        x.dispatch_event(name, detail, false);
+                    } else {
+                        x.mark_paint_dirty();
+                    }
                 }
             }
         });
@@ -333,12 +497,138 @@ lib_define_callback! (ImageLoaderCallback (Rc<RefCell<ImageLoader>>) {
     }
 });
 
+// dispatch the event of a loader which has been loaded or failed when bound to the image
+lib_define_callback! (ImageLoaderEventCallback (ForestNodeWeak<Element>, Rc<RefCell<ImageLoader>>) {
+    fn callback(&mut self, _: i32, _: i32, _: i32, _: i32) -> bool {
+        match self.0.upgrade() {
+            None => { },
+            Some(x) => {
+                let mut x = x.borrow_mut();
+                // skipped if another loader is set since then
+                if is_content_loader(&x, &self.1) {
+                    let (name, detail) = loader_event(&self.1.borrow());
+                    x.dispatch_event(name, detail, false);
+                }
+            }
+        }
+        false
+    }
+});
+
+fn is_content_loader(element: &Element, loader: &Rc<RefCell<ImageLoader>>) -> bool {
+    match element.downcast_ref::<Image>() {
+        None => false,
+        Some(image) => {
+            match image.loader {
+                None => false,
+                Some(ref x) => Rc::ptr_eq(x, loader),
+            }
+        },
+    }
+}
+
+// the "load" or "error" event of a loaded or failed loader
+fn loader_event(loader: &ImageLoader) -> (String, Box<Any>) {
+    match loader.status {
+        ImageLoaderStatus::LoadFailed => (String::from("error"), Box::new(ImageErrorEventDetail {
+            code: loader.error_code,
+            reason: loader.error_reason.clone(),
+        })),
+        _ => (String::from("load"), Box::new(ImageLoadEventDetail {
+            natural_width: loader.width,
+            natural_height: loader.height,
+        })),
+    }
+}
+
 impl Drop for ImageLoader {
     fn drop(&mut self) {
+        if self.is_vector {
+            self.canvas_config.backend().image_unload(self.img_id);
+            ResourceManager::free_image_id(self.img_id);
+        }
         if self.tex_id != -1 {
-            lib!(tex_delete(self.canvas_config.index, self.tex_id));
+            self.canvas_config.backend().tex_delete(self.canvas_config.index, self.tex_id);
             let rm = self.canvas_config.resource_manager();
             rm.borrow_mut().free_tex_id(self.tex_id);
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::time::Instant;
+    use rc_forest::ForestNodeRc;
+    use super::{Image, ImageLoader, ImageLoaderStatus};
+    use super::super::{Element, Event, ImageErrorEventDetail};
+    use super::super::super::super::lib_interfaces::{Call, TestCanvas, run_canvas_test, IMAGE_LOAD_ERROR};
+    use super::super::super::super::frame;
+
+    const REASON: &'static str = "Images are not loaded by RecordingBackend";
+
+    // an image element appended to the root, with the codes of the error events received
+    fn image_with_listener(t: &TestCanvas) -> (ForestNodeRc<Element>, Rc<RefCell<Vec<i32>>>) {
+        let ctx = t.canvas.context();
+        let mut ctx = ctx.borrow_mut();
+        let cfg = ctx.canvas_config();
+        let root = ctx.root();
+        let mut root = root.borrow_mut();
+        let image = root.create_another(Element::new(&cfg, Box::new(Image::new(&cfg))));
+        root.append(image.clone());
+        let errors = Rc::new(RefCell::new(vec![]));
+        let e = errors.clone();
+        image.deref_mut_with(&mut root).add_event_listener(String::from("error"), Rc::new(RefCell::new(move |_: &mut Element, event: &Event| {
+            let detail = event.detail.downcast_ref::<ImageErrorEventDetail>().unwrap();
+            assert_eq!(detail.reason, REASON);
+            e.borrow_mut().push(detail.code);
+        })));
+        (image, errors)
+    }
+
+    #[test]
+    fn load_events() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            // bound before the loader fails
+            let (image, errors) = image_with_listener(&t);
+            image.borrow_mut().content_mut().downcast_mut::<Image>().unwrap().load_from_bytes(b"x");
+            assert_eq!(t.backend.run_callbacks(), 1);
+            assert_eq!(*errors.borrow(), vec![IMAGE_LOAD_ERROR]);
+            // bound after the loader fails, and the event is dispatched later
+            let loader = Rc::new(RefCell::new(ImageLoader::new_with_canvas_config(t.canvas.context().borrow_mut().canvas_config())));
+            ImageLoader::load_from_bytes(loader.clone(), b"x");
+            assert_eq!(t.backend.run_callbacks(), 1);
+            assert_eq!(loader.borrow().status(), ImageLoaderStatus::LoadFailed);
+            assert_eq!(loader.borrow().error_code(), IMAGE_LOAD_ERROR);
+            assert_eq!(loader.borrow().error_reason(), Some(REASON));
+            let (image, errors) = image_with_listener(&t);
+            image.borrow_mut().content_mut().downcast_mut::<Image>().unwrap().set_loader(loader.clone());
+            assert_eq!(errors.borrow().len(), 0);
+            assert_eq!(t.backend.run_callbacks(), 1);
+            assert_eq!(*errors.borrow(), vec![IMAGE_LOAD_ERROR]);
+            assert_eq!(t.backend.run_callbacks(), 0);
+        });
+    }
+
+    #[test]
+    fn vector_tex_follows_device_pixel_ratio() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            t.backend.set_image(Some((20, 10, true)));
+            let (image, _) = image_with_listener(&t);
+            image.borrow_mut().content_mut().downcast_mut::<Image>().unwrap().load_from_bytes(b"<svg/>");
+            t.backend.run_callbacks();
+            let vector_tex_sizes = || t.backend.calls().into_iter().filter_map(|x| match x {
+                Call::TexFromImageWithSize { size, .. } => Some(size),
+                _ => None,
+            }).collect::<Vec<_>>();
+            frame::generate(Instant::now());
+            assert_eq!(vector_tex_sizes(), vec![(20, 10)]);
+            t.canvas.context().borrow_mut().set_canvas_size(100, 100, 2.);
+            frame::generate(Instant::now());
+            assert_eq!(vector_tex_sizes(), vec![(20, 10), (40, 20)]);
+        });
+    }
+}
@@ -1,6 +1,6 @@
 #![macro_use]
 
-use std::cell::Cell;
+use std::cell::{Cell, RefCell};
 use std::ops::{Deref, DerefMut};
 use std::fmt::Debug;
 use std::any::Any;
@@ -8,26 +8,37 @@ use std::rc::Rc;
 use std::fmt;
 use downcast_rs::Downcast;
 use super::CanvasConfig;
-use super::resource::DrawState;
+use super::resource::{DrawState, DrawShape};
 use rc_forest::{ForestNodeContent, ForestNode, ForestNodeRc, ForestNodeSelf, ForestNodeWeak};
 
+const SCROLLBAR_WIDTH: f64 = 4.;
+const SCROLLBAR_MARGIN: f64 = 2.;
+const SCROLLBAR_MIN_LENGTH: f64 = 16.;
+const SCROLLBAR_COLOR: (f32, f32, f32, f32) = (0., 0., 0., 0.4);
+const GRADIENT_TEX_MAX_SIZE: i32 = 512;
+const BACKGROUND_TILES_MAX: f64 = 4096.;
+
 pub mod style;
 pub use self::style::*;
 mod positioning;
-pub use self::positioning::{Position, Size, Point, Bounds};
+pub use self::positioning::{Position, Size, Point, Bounds, RoundedRect};
 use self::positioning::{PositionOffset, InlineAllocator};
 mod transform;
 pub use self::transform::Transform;
 
 mod empty_element;
 pub use self::empty_element::Empty;
+mod custom_draw_element;
+pub use self::custom_draw_element::{CustomDraw, CustomDrawContext};
 mod image_element;
 pub use self::image_element::{Image, ImageLoader, ImageLoaderStatus};
+mod path_element;
+pub use self::path_element::{Path, FillRule, LineJoin, LineCap};
 mod text_element;
 pub use self::text_element::Text;
 
 mod event;
-pub use self::event::{Event, EventReceiver, EventCallback};
+pub use self::event::{Event, EventReceiver, EventCallback, ScrollEventDetail, ImageLoadEventDetail, ImageErrorEventDetail};
 
 pub trait ElementContent: Downcast {
     fn name(&self) -> &'static str;
@@ -35,7 +46,7 @@ pub trait ElementContent: Downcast {
     fn clone(&self) -> Box<ElementContent>;
     #[inline]
     fn associate_element(&mut self, _element: *mut Element) { }
-    fn draw(&mut self, transform: &Transform);
+    fn draw(&mut self, viewport: Position, transform: &Transform);
     #[inline]
     fn suggest_size(&mut self, _suggested_size: Size, _inline_allocator: &mut InlineAllocator, _style: &ElementStyle) -> Size {
         Size::new(0., 0.)
@@ -50,10 +61,24 @@ pub trait ElementContent: Downcast {
     }
     fn drawing_bounds(&self) -> Bounds;
     fn is_under_point(&self, point: Point, transform: Transform) -> bool;
+    /// Whether the points in the border box hit the element, otherwise only the points passing `is_under_point` do.
+    #[inline]
+    fn is_box_hit(&self) -> bool {
+        true
+    }
 }
 
 impl_downcast!(ElementContent);
 
+/// The bounds of the last painting of an element, in canvas coordinates.
+#[derive(Clone, Copy, Default, PartialEq)]
+struct PaintedBounds {
+    self_bounds: Option<Bounds>, // background, borders and content
+    bounds: Option<Bounds>, // including children
+    layout_bounds: Option<Bounds>, // the drawing bounds of the layout, which change if the element is moved
+    blur_bounds: Option<Bounds>, // the blur regions of the background, borders and content
+}
+
 pub struct Element {
     canvas_config: Rc<CanvasConfig>,
     tree_node: Option<ForestNodeSelf<Element>>,
@@ -63,6 +88,11 @@ pub struct Element {
     base_size: Size,
     base_font_size: f32,
     draw_separate_tex: Cell<i32>,
+    paint_dirty: Cell<bool>,
+    painted_bounds: Cell<PaintedBounds>,
+    scroll_offset: Size,
+    background_loader: Option<(String, Rc<RefCell<ImageLoader>>)>,
+    border_image_loader: Option<(String, Rc<RefCell<ImageLoader>>)>,
     content: Box<ElementContent>,
 }
 
@@ -83,11 +113,116 @@ impl Clone for Element {
             base_size: Size::new(0., 0.),
             base_font_size: 0.,
             draw_separate_tex: Cell::new(-1),
+            paint_dirty: Cell::new(true),
+            painted_bounds: Cell::new(Default::default()),
+            scroll_offset: Size::new(0., 0.),
+            background_loader: None,
+            border_image_loader: None,
             content: self.content.clone(),
         }
     }
 }
 
+// the darker or lighter color for the 3D border styles
+#[inline]
+fn border_shade(color: (f32, f32, f32, f32), dark: bool) -> (f32, f32, f32, f32) {
+    if dark {
+        (color.0 * 2. / 3., color.1 * 2. / 3., color.2 * 2. / 3., color.3)
+    } else {
+        (color.0 + (1. - color.0) / 3., color.1 + (1. - color.1) / 3., color.2 + (1. - color.2) / 3., color.3)
+    }
+}
+
+// split a side into dashes of about `dash` with gaps of about `gap`, starting and ending with a dash
+fn border_segments(length: f64, dash: f64, gap: f64) -> Vec<(f64, f64)> {
+    if length <= dash || dash <= 0. { return vec![(0., length)] }
+    let count = ((length + gap) / (dash + gap)).round().max(1.) as usize;
+    let scale = length / (dash * count as f64 + gap * (count - 1) as f64);
+    let (dash, gap) = (dash * scale, gap * scale);
+    (0..count).map(|i| {
+        let start = (dash + gap) * i as f64;
+        (start, start + dash)
+    }).collect()
+}
+
+// the size of a background image tile in the box of `area`, the area size is used for the unknown natural size
+fn background_tile_size(size: BackgroundSize, natural: Option<Size>, area: Size) -> Size {
+    let natural = match natural {
+        Some(x) => {
+            if x.width() > 0. && x.height() > 0. { Some(x) } else { None }
+        },
+        None => None,
+    };
+    match size {
+        BackgroundSize::Cover | BackgroundSize::Contain => {
+            match natural {
+                None => area,
+                Some(n) => {
+                    let (sx, sy) = (area.width() / n.width(), area.height() / n.height());
+                    let scale = if size == BackgroundSize::Cover { sx.max(sy) } else { sx.min(sy) };
+                    Size::new(n.width() * scale, n.height() * scale)
+                },
+            }
+        },
+        BackgroundSize::Explicit(w, h) => {
+            let w = w.map(|x| x.resolve(area.width()));
+            let h = h.map(|x| x.resolve(area.height()));
+            match (w, h, natural) {
+                (Some(w), Some(h), _) => Size::new(w, h),
+                (Some(w), None, Some(n)) => Size::new(w, w / n.width() * n.height()),
+                (None, Some(h), Some(n)) => Size::new(h / n.height() * n.width(), h),
+                (None, None, Some(n)) => n,
+                (w, h, None) => Size::new(w.unwrap_or(area.width()), h.unwrap_or(area.height())),
+            }
+        },
+    }
+}
+
+// the tiles of a border image part along an axis, as (start, length, source start ratio, source end ratio),
+// the partial tiles at both ends of `Repeat` use the part of the source covered
+// the number of the tiles along a side of the border image, without allocating them
+fn border_image_tile_count(length: f64, tile: f64, repeat: BorderImageRepeatType) -> f64 {
+    if length <= 0. { return 0. }
+    if repeat == BorderImageRepeatType::Stretch || !(tile > 0.) || !tile.is_finite() { return 1. }
+    match repeat {
+        BorderImageRepeatType::Round => (length / tile).round().max(1.),
+        BorderImageRepeatType::Space => (length / tile).floor(),
+        _ => {
+            let offset = (length - tile) / 2.;
+            ((offset / tile).ceil() + (length - offset) / tile).ceil()
+        },
+    }
+}
+
+fn border_image_tiles(start: f64, length: f64, tile: f64, repeat: BorderImageRepeatType) -> Vec<(f64, f64, f64, f64)> {
+    if length <= 0. { return vec![] }
+    if repeat == BorderImageRepeatType::Stretch || !(tile > 0.) || !tile.is_finite() {
+        return vec![(start, length, 0., 1.)];
+    }
+    let count = border_image_tile_count(length, tile, repeat);
+    match repeat {
+        BorderImageRepeatType::Round => {
+            let tile = length / count;
+            (0..count as usize).map(|i| (start + tile * i as f64, tile, 0., 1.)).collect()
+        },
+        BorderImageRepeatType::Space => {
+            let gap = (length - tile * count) / (count + 1.);
+            (0..count as usize).map(|i| (start + gap + (tile + gap) * i as f64, tile, 0., 1.)).collect()
+        },
+        _ => {
+            // the tiles are centered
+            let end = start + length;
+            let offset = (length - tile) / 2.;
+            let first = start + offset - (offset / tile).ceil() * tile;
+            (0..count as usize).filter_map(|i| {
+                let x = first + tile * i as f64;
+                let (a, b) = (x.max(start), (x + tile).min(end));
+                if b > a { Some((a, b - a, (a - x) / tile, (b - x) / tile)) } else { None }
+            }).collect()
+        },
+    }
+}
+
 impl Element {
     pub fn new(cfg: &Rc<CanvasConfig>, content: Box<ElementContent>) -> Self {
         Element {
@@ -99,6 +234,11 @@ impl Element {
             base_size: Size::new(0., 0.),
             base_font_size: 0.,
             draw_separate_tex: Cell::new(-1),
+            paint_dirty: Cell::new(true),
+            painted_bounds: Cell::new(Default::default()),
+            scroll_offset: Size::new(0., 0.),
+            background_loader: None,
+            border_image_loader: None,
             content,
         }
     }
@@ -211,12 +351,14 @@ impl Element {
     pub(crate) fn mark_class_dirty_dfs(&self) {
         // class dirty always causes layout dirty, so we can do this
         self.style.get_and_mark_class_dirty(true);
+        self.mark_paint_dirty();
         self.node().for_each_child(|c| {
             c.mark_class_dirty_dfs();
         });
     }
     #[inline]
     pub(crate) fn mark_self_class_dirty(&self) -> bool {
+        self.mark_paint_dirty();
         if self.style.get_and_mark_class_dirty(true) {
             return true;
         }
@@ -272,6 +414,81 @@ impl Element {
     pub(crate) fn is_layout_dirty(&self) -> bool {
         self.position_offset.is_dirty()
     }
+    /// Mark the element and its children to be repainted in the next frame.
+    #[inline]
+    pub(crate) fn mark_paint_dirty(&self) {
+        self.paint_dirty.set(true);
+        self.canvas_config.request_repaint();
+    }
+    /// Repaint the area painted last time, used when the element is no longer drawn.
+    #[inline]
+    fn damage_painted_bounds(&self) {
+        let old = self.painted_bounds.replace(Default::default());
+        self.canvas_config.add_damage(old.bounds);
+    }
+    fn update_painted_bounds(&self, painted_bounds: PaintedBounds, unchanged: bool) {
+        let old = self.painted_bounds.replace(painted_bounds);
+        self.paint_dirty.set(false);
+        if !unchanged {
+            // usually damaged by `collect_damage` already, unless painted out of the layout bounds
+            self.canvas_config.add_damage(old.bounds);
+            self.canvas_config.add_damage(painted_bounds.bounds);
+        }
+    }
+    // whether the element is painted the same as last time, i.e. not marked and not moved
+    #[inline]
+    fn is_paint_unchanged(&self, layout_bounds: &Bounds) -> bool {
+        !self.paint_dirty.get() && self.painted_bounds.get().layout_bounds == Some(*layout_bounds)
+    }
+    #[inline]
+    fn transform_for_drawing(&self, mut transform: Transform) -> Transform {
+        let allocated_position = Position::from((self.position_offset.allocated_point(), self.position_offset.requested_size()));
+        transform.mul_clone(Transform::new().offset(allocated_position.left_top() - Point::new(0., 0.))).mul_clone(&self.position_offset.transform(&self.style))
+    }
+    // whether the subtree is out of the viewport, behind the viewer or its back face is hidden
+    #[inline]
+    fn is_hidden_in(&self, viewport: Position, child_transform: &Transform, layout_bounds: &Bounds) -> bool {
+        !layout_bounds.intersects(&viewport.into()) || child_transform.is_behind_viewer(&self.position_offset.drawing_bounds()) || self.is_back_face_hidden()
+    }
+    #[inline]
+    fn clip_viewport(viewport: Position, clip: &Position, child_transform: &Transform) -> Position {
+        let mut bounds: Bounds = viewport.into();
+        bounds.intersection(&child_transform.apply_to_bounds(&(*clip).into()));
+        bounds.into()
+    }
+    /// Add the areas changed since the last painting to the damage, before drawing.
+    /// The marked or moved subtrees damage their old painted bounds and new layout bounds,
+    /// and the blur regions of the unchanged elements are collected.
+    pub(crate) fn collect_damage(&self, viewport: Position, transform: Transform, blur_regions: &mut Vec<Bounds>) {
+        let painted = self.painted_bounds.get();
+        if self.style.get_display() == style::DisplayType::None {
+            self.canvas_config.add_damage(painted.bounds);
+            return;
+        }
+        let mut child_transform = self.transform_for_drawing(transform);
+        let layout_bounds = child_transform.apply_to_bounds(&self.position_offset.drawing_bounds());
+        let hidden = self.is_hidden_in(viewport, &child_transform, &layout_bounds);
+        if !self.is_paint_unchanged(&layout_bounds) || hidden {
+            self.canvas_config.add_damage(painted.bounds);
+            if !hidden {
+                self.canvas_config.add_damage(Some(layout_bounds));
+            }
+            return;
+        }
+        blur_regions.extend(painted.blur_bounds);
+        if self.content.is_terminated() { return }
+        let (viewport, mut scrolled_transform) = match self.overflow_clip() {
+            None => (viewport, child_transform),
+            Some(clip) => (
+                Self::clip_viewport(viewport, &clip, &child_transform),
+                child_transform.mul_clone(Transform::new().offset(Size::new(-self.scroll_left(), -self.scroll_top()))),
+            ),
+        };
+        let children_transform = scrolled_transform.mul_clone(&self.position_offset.children_transform(&self.style));
+        self.node().for_each_child(|child| {
+            child.collect_damage(viewport, children_transform, blur_regions);
+        });
+    }
     #[inline]
     pub(crate) fn dfs_update_position_offset(&mut self, suggested_size: Size) {
         self.position_offset.suggest_size(suggested_size, &mut InlineAllocator::new(), false, false);
@@ -280,75 +497,888 @@ impl Element {
     }
 
     #[inline]
-    fn draw_rect(&mut self, color: (f32, f32, f32, f32), position: Position) {
+    fn draw_rect(&mut self, color: (f32, f32, f32, f32), quad: [Point; 4]) {
         let rm = self.canvas_config.resource_manager();
         let mut rm = rm.borrow_mut();
         rm.set_draw_state(DrawState::new().color(color));
-        // debug!("Try drawing rect at {:?} colored {:?}", position, color);
+        // debug!("Try drawing rect at {:?} colored {:?}", quad, color);
         rm.request_draw(
             -2, true,
             0., 0., 1., 1.,
-            position.into()
+            quad
+        );
+    }
+    // the border box with the border radii in element-local coordinates
+    fn border_box_shape(&self) -> RoundedRect {
+        let style = &self.style;
+        let background_rect = self.position_offset.get_background_rect();
+        let position = Position::new(
+            background_rect.left() - style.get_border_left_width(),
+            background_rect.top() - style.get_border_top_width(),
+            background_rect.width() + style.get_border_left_width() + style.get_border_right_width(),
+            background_rect.height() + style.get_border_top_width() + style.get_border_bottom_width(),
+        );
+        RoundedRect::new(position, (
+            style.get_border_top_left_radius(),
+            style.get_border_top_right_radius(),
+            style.get_border_bottom_right_radius(),
+            style.get_border_bottom_left_radius(),
+        ))
+    }
+    // the padding box, i.e. the border box inside the borders
+    #[inline]
+    fn padding_box_shape(&self) -> RoundedRect {
+        let style = &self.style;
+        self.border_box_shape().inset(
+            style.get_border_top_width(),
+            style.get_border_right_width(),
+            style.get_border_bottom_width(),
+            style.get_border_left_width(),
+        )
+    }
+    #[inline]
+    fn draw_shape(&self, shape: &RoundedRect, border: (f64, f64, f64, f64), child_transform: &Transform) -> DrawShape {
+        let position = shape.position();
+        let r = shape.radii();
+        DrawShape {
+            size: (position.width() as f32, position.height() as f32),
+            radii: (r.0 as f32, r.1 as f32, r.2 as f32, r.3 as f32),
+            border: (border.0 as f32, border.1 as f32, border.2 as f32, border.3 as f32),
+            pixel_scale: (self.canvas_config.device_pixel_ratio.get() * child_transform.get_area_scale()) as f32,
+        }
+    }
+    // draw a part of a rounded rect (or its borders) with anti-aliased edges
+    fn draw_shape_rect(&mut self, color: (f32, f32, f32, f32), shape: DrawShape, shape_position: Position, position: Position, child_transform: &Transform) {
+        if position.width() <= 0. || position.height() <= 0. { return }
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        rm.set_draw_state(DrawState::new().color(color));
+        rm.set_draw_shape(shape);
+        rm.request_draw(
+            -3, true,
+            position.left() - shape_position.left(), position.top() - shape_position.top(), position.width(), position.height(),
+            child_transform.apply_to_quad(&position)
         );
     }
     #[inline]
     fn draw_background_color(&mut self, child_transform: &Transform) {
         let color = self.style.get_background_color();
         if color.3 > 0. {
-            let position = self.position_offset.get_background_rect();
-            self.draw_rect(color, child_transform.apply_to_position(&position));
+            let shape = self.background_box_shape(self.style.get_background_clip());
+            if shape.is_rect() {
+                self.draw_rect(color, child_transform.apply_to_quad(&shape.position()));
+            } else {
+                let draw_shape = self.draw_shape(&shape, (0., 0., 0., 0.), child_transform);
+                self.draw_shape_rect(color, draw_shape, shape.position(), shape.position(), child_transform);
+            }
+        }
+        self.draw_background_image(child_transform);
+    }
+    // the border box, padding box or content box
+    fn background_box_shape(&self, box_type: BackgroundBoxType) -> RoundedRect {
+        let style = &self.style;
+        match box_type {
+            BackgroundBoxType::BorderBox => self.border_box_shape(),
+            BackgroundBoxType::PaddingBox => self.padding_box_shape(),
+            BackgroundBoxType::ContentBox => self.padding_box_shape().inset(
+                style.get_padding_top(),
+                style.get_padding_right(),
+                style.get_padding_bottom(),
+                style.get_padding_left(),
+            ),
+        }
+    }
+    // the loader of the background image url, shared with other elements in the canvas using the same url
+    #[inline]
+    fn background_image_loader(&mut self, url: &str) -> Rc<RefCell<ImageLoader>> {
+        let mut current = self.background_loader.take();
+        let loader = self.url_image_loader(&mut current, url);
+        self.background_loader = current;
+        loader
+    }
+    #[inline]
+    fn border_image_loader(&mut self, url: &str) -> Rc<RefCell<ImageLoader>> {
+        let mut current = self.border_image_loader.take();
+        let loader = self.url_image_loader(&mut current, url);
+        self.border_image_loader = current;
+        loader
+    }
+    // replace the `current` loader with the one of the url, and bind this element to be repainted when it is loaded
+    fn url_image_loader(&self, current: &mut Option<(String, Rc<RefCell<ImageLoader>>)>, url: &str) -> Rc<RefCell<ImageLoader>> {
+        match *current {
+            Some((ref u, ref loader)) => {
+                if u == url { return loader.clone() }
+            },
+            None => { },
+        }
+        let node = self.rc().downgrade();
+        match current.take() {
+            Some((_, loader)) => loader.borrow_mut().unbind_tree_node(&node),
+            None => { },
+        }
+        let rm = self.canvas_config.resource_manager();
+        let cached = rm.borrow_mut().image_loader(url);
+        let loader = match cached {
+            Some(x) => x,
+            None => {
+                let loader = Rc::new(RefCell::new(ImageLoader::new_with_canvas_config(self.canvas_config.clone())));
+                rm.borrow_mut().set_image_loader(url.to_string(), &loader);
+                ImageLoader::load(loader.clone(), url);
+                loader
+            },
+        };
+        loader.borrow_mut().bind_tree_node(node);
+        *current = Some((url.to_string(), loader.clone()));
+        loader
+    }
+    // draw the background image over the background color, repeated as tiles over the clipping box
+    fn draw_background_image(&mut self, child_transform: &Transform) {
+        let image = self.style.get_background_image();
+        // the texture of an url image, its position in the texture, and its natural size
+        let loaded = match image {
+            BackgroundImage::None => return,
+            BackgroundImage::Url(ref url) => {
+                let loader = self.background_image_loader(url);
+                let loader = loader.borrow();
+                if loader.status() != ImageLoaderStatus::Loaded || loader.tex_id() == -1 { return }
+                let (w, h) = loader.size();
+                Some((loader.tex_id(), loader.tex_pos(), Size::new(w as f64, h as f64)))
+            },
+            _ => None,
+        };
+        let origin = self.background_box_shape(self.style.get_background_origin()).position();
+        let clip = self.background_box_shape(self.style.get_background_clip());
+        let clip_position = clip.position();
+        if clip_position.width() <= 0. || clip_position.height() <= 0. { return }
+        let tile = background_tile_size(self.style.get_background_size(), loaded.map(|x| x.2), Size::new(origin.width(), origin.height()));
+        if !(tile.width() > 0.) || !(tile.height() > 0.) { return }
+        let (tex_id, tex_pos) = match loaded {
+            Some((tex_id, tex_pos, _)) => (tex_id, tex_pos),
+            None => {
+                // the texture has the device pixels of the tile, but not too large since gradients are smooth
+                let scale = self.canvas_config.device_pixel_ratio.get() * child_transform.get_area_scale();
+                let tex_size = |x: f64| ((x * scale).ceil() as i32).max(1).min(GRADIENT_TEX_MAX_SIZE);
+                let tex_size = (tex_size(tile.width()), tex_size(tile.height()));
+                let rm = self.canvas_config.resource_manager();
+                let tex_id = rm.borrow_mut().gradient_texture(&image, tile, tex_size);
+                (tex_id, (0., 0., 1., 1.))
+            },
+        };
+        // the tiles along an axis, starting from the positioned one and repeated to cover the clipping box
+        let (pos_x, pos_y) = self.style.get_background_position();
+        let (repeat_x, repeat_y) = match self.style.get_background_repeat() {
+            BackgroundRepeatType::Repeat => (true, true),
+            BackgroundRepeatType::RepeatX => (true, false),
+            BackgroundRepeatType::RepeatY => (false, true),
+            BackgroundRepeatType::NoRepeat => (false, false),
+        };
+        let tiles = |start: f64, length: f64, clip_start: f64, clip_length: f64, repeat: bool| -> (f64, usize) {
+            if !repeat { return (start, 1) }
+            let first = start - ((start - clip_start) / length).ceil() * length;
+            (first, ((clip_start + clip_length - first) / length).ceil().max(0.) as usize)
+        };
+        let (left, count_x) = tiles(origin.left() + pos_x.resolve(origin.width() - tile.width()), tile.width(), clip_position.left(), clip_position.width(), repeat_x);
+        let (top, count_y) = tiles(origin.top() + pos_y.resolve(origin.height() - tile.height()), tile.height(), clip_position.top(), clip_position.height(), repeat_y);
+        if count_x as f64 * count_y as f64 > BACKGROUND_TILES_MAX {
+            warn!("Too many background image tiles ({} x {}), skipped.", count_x, count_y);
+            return;
+        }
+        // no clipping is needed if the only tile is inside the clipping box
+        let covered = Bounds::from(Position::new(left, top, tile.width(), tile.height()));
+        let need_clip = count_x * count_y > 1 || !clip.is_rect() || {
+            let mut b = covered;
+            b.intersection(&Bounds::from(clip_position));
+            b != covered
+        };
+        let clip_shape = if clip.is_rect() { None } else { Some(self.draw_shape(&clip, (0., 0., 0., 0.), child_transform)) };
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        if need_clip {
+            match clip_shape {
+                None => rm.push_clip_quad(child_transform.apply_to_quad(&clip_position)),
+                Some(clip_shape) => rm.push_clip_shape(child_transform.apply_to_quad(&clip_position), clip_shape),
+            }
+        }
+        for j in 0..count_y {
+            for i in 0..count_x {
+                let position = Position::new(left + tile.width() * i as f64, top + tile.height() * j as f64, tile.width(), tile.height());
+                rm.request_draw(
+                    tex_id, false,
+                    tex_pos.0, tex_pos.1, tex_pos.2, tex_pos.3,
+                    child_transform.apply_to_quad(&position)
+                );
+            }
+        }
+        if need_clip {
+            rm.pop_clip();
+        }
+    }
+    // the border box extended by the border image outsets
+    fn border_image_area(&self) -> Position {
+        let (widths, _, _) = self.border_sides();
+        let outsets = self.style.get_border_image_outset();
+        let outset = |i: usize| match outsets[i] {
+            BorderImageLength::Number(x) => x * widths[i],
+            BorderImageLength::Length(LengthPercentage::Px(x)) => x,
+            _ => 0.,
+        };
+        let position = self.border_box_shape().position();
+        Position::new(
+            position.left() - outset(3),
+            position.top() - outset(0),
+            position.width() + outset(3) + outset(1),
+            position.height() + outset(0) + outset(2),
+        )
+    }
+    // draw the border image as the nine parts of the sliced image,
+    // or return false if there is no border image or it is not loaded, so that the border styles are drawn instead
+    fn draw_border_image(&mut self, child_transform: &Transform) -> bool {
+        let image = self.style.get_border_image_source();
+        // the texture of an url image, its position in the texture, and its natural size
+        let loaded = match image {
+            BackgroundImage::None => return false,
+            BackgroundImage::Url(ref url) => {
+                let loader = self.border_image_loader(url);
+                let loader = loader.borrow();
+                if loader.status() != ImageLoaderStatus::Loaded || loader.tex_id() == -1 { return false }
+                let (w, h) = loader.size();
+                Some((loader.tex_id(), loader.tex_pos(), Size::new(w as f64, h as f64)))
+            },
+            _ => None,
+        };
+        let area = self.border_image_area();
+        if area.width() <= 0. || area.height() <= 0. { return true }
+        // a gradient is an image of the area size
+        let (tex_id, tex_pos, image_size) = match loaded {
+            Some(x) => x,
+            None => {
+                let scale = self.canvas_config.device_pixel_ratio.get() * child_transform.get_area_scale();
+                let tex_size = |x: f64| ((x * scale).ceil() as i32).max(1).min(GRADIENT_TEX_MAX_SIZE);
+                let tex_size = (tex_size(area.width()), tex_size(area.height()));
+                let size = Size::new(area.width(), area.height());
+                let rm = self.canvas_config.resource_manager();
+                let tex_id = rm.borrow_mut().gradient_texture(&image, size, tex_size);
+                (tex_id, (0., 0., 1., 1.), size)
+            },
+        };
+        let (iw, ih) = (image_size.width(), image_size.height());
+        if iw <= 0. || ih <= 0. { return true }
+        // the slices and the widths of the sides, in the order of top, right, bottom, left
+        let slice = self.style.get_border_image_slice();
+        let slices: Vec<f64> = (0..4).map(|i| {
+            let length = if i % 2 == 0 { ih } else { iw };
+            slice.offsets[i].resolve(length).max(0.).min(length)
+        }).collect();
+        let (border_widths, _, _) = self.border_sides();
+        let image_widths = self.style.get_border_image_width();
+        let mut widths: Vec<f64> = (0..4).map(|i| match image_widths[i] {
+            BorderImageLength::Number(x) => x * border_widths[i],
+            BorderImageLength::Length(x) => x.resolve(if i % 2 == 0 { area.height() } else { area.width() }),
+            BorderImageLength::Auto => slices[i],
+        }).collect();
+        // the opposite sides should not overlap
+        let scale = (area.width() / (widths[1] + widths[3])).min(area.height() / (widths[0] + widths[2]));
+        if scale < 1. {
+            for w in widths.iter_mut() { *w *= scale }
+        }
+        let (repeat_x, repeat_y) = self.style.get_border_image_repeat();
+        // the scale from the slice to the drawn side, or `None` if it cannot be scaled
+        let side_scale = |i: usize| if slices[i] > 0. && widths[i] > 0. { Some(widths[i] / slices[i]) } else { None };
+        let middle_x = side_scale(0).or(side_scale(2)).unwrap_or(1.);
+        let middle_y = side_scale(3).or(side_scale(1)).unwrap_or(1.);
+        let (sl, st, sr, sb) = (slices[3], slices[0], slices[1], slices[2]);
+        let (wl, wt, wr, wb) = (widths[3], widths[0], widths[1], widths[2]);
+        let (l, t, w, h) = (area.left(), area.top(), area.width(), area.height());
+        let (inner_w, inner_h) = (w - wl - wr, h - wt - wb);
+        let (slice_w, slice_h) = (iw - sl - sr, ih - st - sb);
+        // the parts as (source rect in the image, destination rect, tile size, repeat types)
+        let stretch = BorderImageRepeatType::Stretch;
+        let mut parts = vec![
+            ((0., 0., sl, st), Position::new(l, t, wl, wt), (0., 0.), (stretch, stretch)),
+            ((iw - sr, 0., sr, st), Position::new(l + w - wr, t, wr, wt), (0., 0.), (stretch, stretch)),
+            ((iw - sr, ih - sb, sr, sb), Position::new(l + w - wr, t + h - wb, wr, wb), (0., 0.), (stretch, stretch)),
+            ((0., ih - sb, sl, sb), Position::new(l, t + h - wb, wl, wb), (0., 0.), (stretch, stretch)),
+            ((sl, 0., slice_w, st), Position::new(l + wl, t, inner_w, wt), (slice_w * side_scale(0).unwrap_or(0.), 0.), (repeat_x, stretch)),
+            ((sl, ih - sb, slice_w, sb), Position::new(l + wl, t + h - wb, inner_w, wb), (slice_w * side_scale(2).unwrap_or(0.), 0.), (repeat_x, stretch)),
+            ((0., st, sl, slice_h), Position::new(l, t + wt, wl, inner_h), (0., slice_h * side_scale(3).unwrap_or(0.)), (stretch, repeat_y)),
+            ((iw - sr, st, sr, slice_h), Position::new(l + w - wr, t + wt, wr, inner_h), (0., slice_h * side_scale(1).unwrap_or(0.)), (stretch, repeat_y)),
+        ];
+        if slice.fill {
+            parts.push(((sl, st, slice_w, slice_h), Position::new(l + wl, t + wt, inner_w, inner_h), (slice_w * middle_x, slice_h * middle_y), (repeat_x, repeat_y)));
+        }
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        for (src, dest, tile, (rx, ry)) in parts {
+            if src.2 <= 0. || src.3 <= 0. { continue }
+            let count_x = border_image_tile_count(dest.width(), tile.0, rx);
+            let count_y = border_image_tile_count(dest.height(), tile.1, ry);
+            if count_x * count_y > BACKGROUND_TILES_MAX {
+                warn!("Too many border image tiles ({} x {}), skipped.", count_x, count_y);
+                continue;
+            }
+            let xs = border_image_tiles(dest.left(), dest.width(), tile.0, rx);
+            let ys = border_image_tiles(dest.top(), dest.height(), tile.1, ry);
+            for &(y, height, ya, yb) in ys.iter() {
+                for &(x, width, xa, xb) in xs.iter() {
+                    rm.request_draw(
+                        tex_id, false,
+                        tex_pos.0 + (src.0 + src.2 * xa) / iw * tex_pos.2, tex_pos.1 + (src.1 + src.3 * ya) / ih * tex_pos.3,
+                        src.2 * (xb - xa) / iw * tex_pos.2, src.3 * (yb - ya) / ih * tex_pos.3,
+                        child_transform.apply_to_quad(&Position::new(x, y, width, height))
+                    );
+                }
+            }
         }
+        true
+    }
+    // the widths, styles and colors of the borders, in the order of top, right, bottom, left
+    fn border_sides(&self) -> ([f64; 4], [BorderStyleType; 4], [(f32, f32, f32, f32); 4]) {
+        let style = &self.style;
+        let color = |c: (f32, f32, f32, f32)| if c.0 < 0. { style.get_color() } else { c };
+        (
+            [style.get_border_top_width(), style.get_border_right_width(), style.get_border_bottom_width(), style.get_border_left_width()],
+            [style.get_border_top_style(), style.get_border_right_style(), style.get_border_bottom_style(), style.get_border_left_style()],
+            [color(style.get_border_top_color()), color(style.get_border_right_color()), color(style.get_border_bottom_color()), color(style.get_border_left_color())],
+        )
+    }
+    // draw the solid borders of a rounded border box, each side covers a rect including the corners near it
+    fn draw_rounded_borders(&mut self, shape: &RoundedRect, child_transform: &Transform) {
+        let (widths, styles, colors) = self.border_sides();
+        let solid = |i: usize| styles[i] == BorderStyleType::Solid;
+        let width = |i: usize| if solid(i) { widths[i] } else { 0. };
+        let border = (width(0), width(1), width(2), width(3));
+        let position = shape.position();
+        let (w, h) = (position.width(), position.height());
+        let r = shape.radii();
+        let top = if solid(0) { border.0.max(r.0).max(r.1).min(h) } else { 0. };
+        let bottom = if solid(2) { border.2.max(r.3).max(r.2).min(h - top) } else { 0. };
+        let left = if solid(3) { border.3.max(r.0).max(r.3).min(w) } else { 0. };
+        let right = if solid(1) { border.1.max(r.1).max(r.2).min(w - left) } else { 0. };
+        let draw_shape = self.draw_shape(shape, border, child_transform);
+        let (l, t) = (position.left(), position.top());
+        self.draw_shape_rect(colors[0], draw_shape, position, Position::new(l, t, w, top), child_transform);
+        self.draw_shape_rect(colors[2], draw_shape, position, Position::new(l, t + h - bottom, w, bottom), child_transform);
+        self.draw_shape_rect(colors[3], draw_shape, position, Position::new(l, t + top, left, h - top - bottom), child_transform);
+        self.draw_shape_rect(colors[1], draw_shape, position, Position::new(l + w - right, t + top, right, h - top - bottom), child_transform);
     }
     #[inline]
     fn draw_borders(&mut self, child_transform: &Transform) {
+        if self.draw_border_image(child_transform) { return }
+        let shape = self.border_box_shape();
+        let (widths, styles, colors) = self.border_sides();
+        let visible: Vec<usize> = (0..4).filter(|&i| styles[i] != BorderStyleType::None && widths[i] > 0.).collect();
+        if visible.len() == 0 { return }
+        // solid borders in the same color do not need the corner joins
+        let uniform = visible.iter().all(|&i| styles[i] == BorderStyleType::Solid && colors[i] == colors[visible[0]]);
+        if !uniform {
+            for side in visible {
+                self.draw_border_side(&shape, side, child_transform);
+            }
+            return;
+        }
+        if !shape.is_rect() {
+            self.draw_rounded_borders(&shape, child_transform);
+            return;
+        }
         let position = self.position_offset.get_background_rect();
-        if self.style.get_border_top_style() == BorderStyleType::Solid {
-            let color = self.style.get_border_top_color();
+        if styles[0] == BorderStyleType::Solid {
             let position = Position::new(
-                position.left() - self.style.get_border_left_width(),
-                position.top() - self.style.get_border_top_width(),
-                position.width() + self.style.get_border_left_width() + self.style.get_border_right_width(),
-                self.style.get_border_top_width(),
+                position.left() - widths[3],
+                position.top() - widths[0],
+                position.width() + widths[3] + widths[1],
+                widths[0],
             );
-            self.draw_rect(color, child_transform.apply_to_position(&position));
+            self.draw_rect(colors[0], child_transform.apply_to_quad(&position));
         }
-        if self.style.get_border_bottom_style() == BorderStyleType::Solid {
-            let color = self.style.get_border_bottom_color();
+        if styles[2] == BorderStyleType::Solid {
             let position = Position::new(
-                position.left() - self.style.get_border_left_width(),
+                position.left() - widths[3],
                 position.bottom(),
-                position.width() + self.style.get_border_left_width() + self.style.get_border_right_width(),
-                self.style.get_border_bottom_width(),
+                position.width() + widths[3] + widths[1],
+                widths[2],
             );
-            self.draw_rect(color, child_transform.apply_to_position(&position));
+            self.draw_rect(colors[2], child_transform.apply_to_quad(&position));
         }
-        if self.style.get_border_left_style() == BorderStyleType::Solid {
-            let color = self.style.get_border_left_color();
+        if styles[3] == BorderStyleType::Solid {
             let position = Position::new(
-                position.left() - self.style.get_border_left_width(),
+                position.left() - widths[3],
                 position.top(),
-                self.style.get_border_left_width(),
+                widths[3],
                 position.height(),
             );
-            self.draw_rect(color, child_transform.apply_to_position(&position));
+            self.draw_rect(colors[3], child_transform.apply_to_quad(&position));
         }
-        if self.style.get_border_right_style() == BorderStyleType::Solid {
-            let color = self.style.get_border_right_color();
+        if styles[1] == BorderStyleType::Solid {
             let position = Position::new(
                 position.right(),
                 position.top(),
-                self.style.get_border_right_width(),
+                widths[1],
                 position.height(),
             );
-            self.draw_rect(color, child_transform.apply_to_position(&position));
+            self.draw_rect(colors[1], child_transform.apply_to_quad(&position));
+        }
+    }
+    // draw a border side (0 to 3 for top, right, bottom, left) clipped to its part of the border box,
+    // which is separated from the adjacent sides by the lines from the outer corners through the inner corners
+    fn draw_border_side(&mut self, shape: &RoundedRect, side: usize, child_transform: &Transform) {
+        let (widths, styles, colors) = self.border_sides();
+        let (width, style, color) = (widths[side], styles[side], colors[side]);
+        let position = shape.position();
+        let (x, y, w, h) = (position.left(), position.top(), position.width(), position.height());
+        let r = shape.radii();
+        let radii = [r.0, r.1, r.2, r.3];
+        // the outer corners and the directions to the inner corners, clockwise from the top left
+        let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+        let inward = [(widths[3], widths[0]), (-widths[1], widths[0]), (-widths[1], -widths[2]), (widths[3], -widths[2])];
+        let (start, end) = (side, (side + 1) % 4);
+        let (length, breadth) = if side % 2 == 0 { (w, h) } else { (h, w) };
+        let depth = width.max(radii[start]).max(radii[end]).min(breadth);
+        // the lines from the two corners may meet before reaching the depth
+        let adjacent = widths[(side + 3) % 4] + widths[(side + 1) % 4];
+        let k = if adjacent > 0. { (depth / width).min(length / adjacent) } else { depth / width };
+        let quad = [
+            corners[start],
+            corners[end],
+            (corners[end].0 + inward[end].0 * k, corners[end].1 + inward[end].1 * k),
+            (corners[start].0 + inward[start].0 * k, corners[start].1 + inward[start].1 * k),
+        ];
+        let quad = [
+            child_transform.apply_to_point(Point::new(quad[0].0, quad[0].1)),
+            child_transform.apply_to_point(Point::new(quad[1].0, quad[1].1)),
+            child_transform.apply_to_point(Point::new(quad[2].0, quad[2].1)),
+            child_transform.apply_to_point(Point::new(quad[3].0, quad[3].1)),
+        ];
+        // the part of the side strip between `a` and `b` along the side, from its start corner
+        let strip = |a: f64, b: f64| match side {
+            0 => Position::new(x + a, y, b - a, depth),
+            1 => Position::new(x + w - depth, y + a, depth, b - a),
+            2 => Position::new(x + w - b, y + h - depth, b - a, depth),
+            _ => Position::new(x, y + h - b, depth, b - a),
+        };
+        // the bands of the border from the outer edge, as the ratios of the border widths, with the colors
+        let top_left = side == 0 || side == 3;
+        let (dark, light) = (border_shade(color, true), border_shade(color, false));
+        let bands: Vec<(f64, f64, (f32, f32, f32, f32))> = match style {
+            BorderStyleType::None => vec![],
+            BorderStyleType::Double if width >= 3. => vec![(0., 1. / 3., color), (2. / 3., 1., color)],
+            BorderStyleType::Groove => vec![(0., 0.5, if top_left { dark } else { light }), (0.5, 1., if top_left { light } else { dark })],
+            BorderStyleType::Ridge => vec![(0., 0.5, if top_left { light } else { dark }), (0.5, 1., if top_left { dark } else { light })],
+            BorderStyleType::Inset => vec![(0., 1., if top_left { dark } else { light })],
+            BorderStyleType::Outset => vec![(0., 1., if top_left { light } else { dark })],
+            _ => vec![(0., 1., color)],
+        };
+        // the parts along the side, dashes or dots are fitted so that both ends of the side are covered
+        let segments = match style {
+            BorderStyleType::Dashed => border_segments(length, width * 2., width),
+            BorderStyleType::Dotted => border_segments(length, width, width),
+            _ => vec![(0., length)],
+        };
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().push_clip_quad(quad);
+        if style == BorderStyleType::Dotted && shape.is_rect() {
+            // round dots in the middle of the border
+            let dot_shape = self.draw_shape(&RoundedRect::new(Position::new(0., 0., width, width), (width / 2., width / 2., width / 2., width / 2.)), (0., 0., 0., 0.), child_transform);
+            for (a, b) in segments {
+                let center = (a + b) / 2. - width / 2.;
+                let dot = match side {
+                    0 => Position::new(x + center, y, width, width),
+                    1 => Position::new(x + w - width, y + center, width, width),
+                    2 => Position::new(x + w - center - width, y + h - width, width, width),
+                    _ => Position::new(x, y + h - center - width, width, width),
+                };
+                self.draw_shape_rect(color, dot_shape, dot, dot, child_transform);
+            }
+        } else {
+            for (a, b, color) in bands {
+                let band = shape.inset(widths[0] * a, widths[1] * a, widths[2] * a, widths[3] * a);
+                let border = (widths[0] * (b - a), widths[1] * (b - a), widths[2] * (b - a), widths[3] * (b - a));
+                let draw_shape = self.draw_shape(&band, border, child_transform);
+                for &(sa, sb) in segments.iter() {
+                    self.draw_shape_rect(color, draw_shape, band.position(), strip(sa, sb), child_transform);
+                }
+            }
+        }
+        rm.borrow_mut().pop_clip();
+    }
+    #[inline]
+    fn shadow_color(&self, shadow: &Shadow) -> (f32, f32, f32, f32) {
+        if shadow.color.0 < 0. { self.style.get_color() } else { shadow.color }
+    }
+    // draw something with `f` into a temporary texture, then blur it and draw its alpha in `color` onto `region` (in canvas coordinates)
+    // the texture is in device pixels, and `f` is given the transform from canvas coordinates to it
+    fn draw_blurred<F: FnOnce(&mut Self, Transform)>(&mut self, color: (f32, f32, f32, f32), sigma: f64, region: Bounds, f: F) {
+        let canvas_size = self.canvas_config.canvas_size.get();
+        let (w, h) = (canvas_size.width(), canvas_size.height());
+        let mut region = region;
+        region.intersection(&Bounds::new(0., 0., w, h));
+        if region.width() <= 0. || region.height() <= 0. { return }
+        let dpr = self.canvas_config.device_pixel_ratio.get();
+        let (dw, dh) = ((w * dpr).ceil(), (h * dpr).ceil());
+        let mut device_transform = Transform::new();
+        device_transform.scale(dpr, dpr);
+        let rm = self.canvas_config.resource_manager();
+        // the result depends on the content drawn up to 3 sigma around the region
+        let mut source = region;
+        source.extend(sigma * 3. + 1.);
+        source.intersection(&Bounds::new(0., 0., w, h));
+        rm.borrow_mut().add_blur_region(source);
+        // the draws into the textures are not painted onto the canvas
+        let drawn_bounds = rm.borrow_mut().take_drawn_bounds();
+        let (tex_a, tex_b) = rm.borrow_mut().blur_textures();
+        rm.borrow_mut().bind_rendering_target(tex_a, dw as i32, dh as i32);
+        f(self, device_transform);
+        let mut rm = rm.borrow_mut();
+        rm.unbind_rendering_target();
+        // the horizontal pass covers the rows needed by the vertical pass
+        let mut rows = region;
+        rows.extend_top(-sigma * 3.);
+        rows.extend_bottom(sigma * 3.);
+        rows.intersection(&Bounds::new(0., 0., w, h));
+        let tex_pos = |b: &Bounds| (b.left() * dpr / dw, b.top() * dpr / dh, b.width() * dpr / dw, b.height() * dpr / dh);
+        rm.bind_rendering_target(tex_b, dw as i32, dh as i32);
+        rm.set_draw_blur((1. / dw, 0.), sigma * dpr);
+        rm.set_draw_state(DrawState::new().color((1., 1., 1., 1.)));
+        let (tx, ty, tw, th) = tex_pos(&rows);
+        rm.request_draw(tex_a, false, tx, ty, tw, th, device_transform.apply_to_quad(&rows.into()));
+        rm.unbind_rendering_target();
+        rm.take_drawn_bounds();
+        rm.add_drawn_bounds(drawn_bounds);
+        rm.set_draw_blur((0., 1. / dh), sigma * dpr);
+        rm.set_draw_state(DrawState::new().color(color));
+        let (tx, ty, tw, th) = tex_pos(&region);
+        rm.request_draw(tex_b, true, tx, ty, tw, th, Position::from(region).into());
+        rm.set_draw_blur((0., 0.), 0.);
+    }
+    // draw the outer box shadows (under the background) or the inset ones (above the background), the first one on the top
+    fn draw_box_shadows(&mut self, child_transform: &Transform, inset: bool) {
+        let shadows = self.style.get_box_shadow();
+        if shadows.len() == 0 { return }
+        let border_box = self.border_box_shape();
+        let padding_box = self.padding_box_shape();
+        let scale = child_transform.get_area_scale();
+        for shadow in shadows.iter().rev() {
+            if shadow.inset != inset { continue }
+            let color = self.shadow_color(shadow);
+            if color.3 <= 0. { continue }
+            let offset = Size::new(shadow.offset_x, shadow.offset_y);
+            let extent = shadow.blur_extent();
+            let sigma = shadow.blur / 2. * scale;
+            if !inset {
+                let shape = border_box.shadow(offset, shadow.spread);
+                let draw_shape = self.draw_shape(&shape, (0., 0., 0., 0.), child_transform);
+                let position = shape.position();
+                if sigma <= 0. {
+                    self.draw_shape_rect(color, draw_shape, position, position, child_transform);
+                    continue;
+                }
+                let mut region: Bounds = position.into();
+                region.extend(extent);
+                self.draw_blurred(color, sigma, child_transform.apply_to_bounds(&region), |this, mut device_transform| {
+                    this.draw_shape_rect((1., 1., 1., 1.), draw_shape, position, position, &device_transform.mul_clone(child_transform));
+                });
+            } else {
+                // a ring around the hole, wide enough to cover the padding box after the offset and the blur
+                let hole = padding_box.shadow(offset, -shadow.spread);
+                let ring_width = extent + shadow.spread.abs() + shadow.offset_x.abs() + shadow.offset_y.abs() + 1.;
+                let hole_position = hole.position();
+                let position = Position::new(
+                    hole_position.left() - ring_width,
+                    hole_position.top() - ring_width,
+                    hole_position.width() + ring_width * 2.,
+                    hole_position.height() + ring_width * 2.,
+                );
+                let r = hole.radii();
+                let ring = RoundedRect::new(position, (r.0 + ring_width, r.1 + ring_width, r.2 + ring_width, r.3 + ring_width));
+                let draw_shape = self.draw_shape(&ring, (ring_width, ring_width, ring_width, ring_width), child_transform);
+                let clip = padding_box.position();
+                let clip_shape = self.draw_shape(&padding_box, (0., 0., 0., 0.), child_transform);
+                {
+                    let rm = self.canvas_config.resource_manager();
+                    let mut rm = rm.borrow_mut();
+                    if padding_box.is_rect() && child_transform.is_axis_aligned() {
+                        let clip = child_transform.apply_to_position(&clip);
+                        rm.push_clip_rect(clip.left(), clip.top(), clip.width(), clip.height());
+                    } else {
+                        rm.push_clip_shape(child_transform.apply_to_quad(&clip), clip_shape);
+                    }
+                }
+                if sigma <= 0. {
+                    self.draw_shape_rect(color, draw_shape, position, position, child_transform);
+                } else {
+                    let region = child_transform.apply_to_bounds(&clip.into());
+                    self.draw_blurred(color, sigma, region, |this, mut device_transform| {
+                        this.draw_shape_rect((1., 1., 1., 1.), draw_shape, position, position, &device_transform.mul_clone(child_transform));
+                    });
+                }
+                self.canvas_config.resource_manager().borrow_mut().pop_clip();
+            }
+        }
+    }
+    // draw the text shadows under the text content, the first one on the top
+    fn draw_text_shadows(&mut self, viewport: Position, transform: &Transform) {
+        if !self.content.is::<Text>() { return }
+        let shadows = self.style.get_text_shadow();
+        if shadows.len() == 0 { return }
+        let content_bounds = self.content.drawing_bounds();
+        let scale = transform.get_area_scale();
+        for shadow in shadows.iter().rev() {
+            let color = self.shadow_color(shadow);
+            if color.3 <= 0. { continue }
+            let extent = shadow.blur_extent();
+            let mut bounds = content_bounds + Size::new(shadow.offset_x, shadow.offset_y);
+            bounds.extend(extent);
+            let region = transform.apply_to_bounds(&bounds);
+            // the glyphs out of the viewport may be blurred into it
+            let mut shadow_viewport: Bounds = viewport.into();
+            shadow_viewport.extend(extent * scale);
+            let shadow_transform = transform.clone().mul_clone(Transform::new().offset(Size::new(shadow.offset_x, shadow.offset_y)));
+            self.draw_blurred(color, shadow.blur / 2. * scale, region, |this, mut device_transform| {
+                let viewport = device_transform.apply_to_bounds(&shadow_viewport);
+                this.content.draw(viewport.into(), &device_transform.mul_clone(&shadow_transform));
+            });
+        }
+    }
+    // the drawing bounds extended by the box shadows, the text shadows and the border image outsets
+    pub(crate) fn shadow_drawing_bounds(&self, drawing_bounds: Bounds) -> Bounds {
+        let mut ret = drawing_bounds;
+        if self.style.get_display() != DisplayType::Inline {
+            if self.style.get_border_image_source() != BackgroundImage::None {
+                ret.union(&self.border_image_area().into());
+            }
+            let border_box = self.border_box_shape();
+            for shadow in self.style.get_box_shadow().iter() {
+                if shadow.inset { continue }
+                let mut bounds: Bounds = border_box.shadow(Size::new(shadow.offset_x, shadow.offset_y), shadow.spread).position().into();
+                let extent = shadow.blur_extent();
+                bounds.extend(extent);
+                ret.union(&bounds);
+            }
+        }
+        if self.content.is::<Text>() {
+            let content_bounds = self.content.drawing_bounds();
+            for shadow in self.style.get_text_shadow().iter() {
+                let mut bounds = content_bounds + Size::new(shadow.offset_x, shadow.offset_y);
+                let extent = shadow.blur_extent();
+                bounds.extend(extent);
+                ret.union(&bounds);
+            }
+        }
+        ret
+    }
+    // the clip rect of the overflow style in element-local coordinates, or None if not clipped
+    fn overflow_clip(&self) -> Option<Position> {
+        if self.style.get_display() == DisplayType::Inline { return None }
+        let clip_x = self.style.get_overflow_x() != OverflowType::Visible;
+        let clip_y = self.style.get_overflow_y() != OverflowType::Visible;
+        if !clip_x && !clip_y { return None }
+        let background_rect = self.position_offset.get_background_rect();
+        let drawing_bounds = self.position_offset.drawing_bounds();
+        let (left, right) = if clip_x {
+            (background_rect.left(), background_rect.right())
+        } else {
+            (drawing_bounds.left(), drawing_bounds.right())
+        };
+        let (top, bottom) = if clip_y {
+            (background_rect.top(), background_rect.bottom())
+        } else {
+            (drawing_bounds.top(), drawing_bounds.bottom())
+        };
+        Some(Bounds::new(left, top, right, bottom).into())
+    }
+    // the rounded padding box if the overflow clip has rounded corners
+    fn overflow_clip_shape(&self) -> Option<RoundedRect> {
+        if self.style.get_overflow_x() == OverflowType::Visible || self.style.get_overflow_y() == OverflowType::Visible {
+            return None;
+        }
+        let shape = self.padding_box_shape();
+        if shape.is_rect() { None } else { Some(shape) }
+    }
+    #[inline]
+    fn is_point_in_overflow_clip(&self, point: Point, clip: &Position, transform: &Transform) -> bool {
+        match self.overflow_clip_shape() {
+            None => point.in_quad(&transform.apply_to_quad(clip)),
+            Some(shape) => point.in_rounded_quad(&transform.apply_to_quad(&shape.position()), &shape),
+        }
+    }
+
+    /// The horizontal scroll offset of the content.
+    #[inline]
+    pub fn scroll_left(&self) -> f64 {
+        self.scroll_offset.width()
+    }
+    /// The vertical scroll offset of the content.
+    #[inline]
+    pub fn scroll_top(&self) -> f64 {
+        self.scroll_offset.height()
+    }
+    #[inline]
+    pub fn set_scroll_left(&mut self, left: f64) -> bool {
+        let top = self.scroll_top();
+        self.scroll_to(left, top)
+    }
+    #[inline]
+    pub fn set_scroll_top(&mut self, top: f64) -> bool {
+        let left = self.scroll_left();
+        self.scroll_to(left, top)
+    }
+    #[inline]
+    pub fn scroll_by(&mut self, delta_left: f64, delta_top: f64) -> bool {
+        let (left, top) = (self.scroll_left() + delta_left, self.scroll_top() + delta_top);
+        self.scroll_to(left, top)
+    }
+    /// Scroll the content, limited by the overflowing size of the last layout.
+    /// Only the axes with hidden, scroll or auto overflow can be scrolled.
+    /// Returns false if the offset is not changed, otherwise a `scroll` event is dispatched.
+    pub fn scroll_to(&mut self, left: f64, top: f64) -> bool {
+        let offset = self.clamp_scroll_offset(Size::new(left, top));
+        if offset == self.scroll_offset { return false }
+        self.scroll_offset = offset;
+        self.mark_paint_dirty();
+        self.dispatch_event(String::from("scroll"), Box::new(ScrollEventDetail {
+            scroll_left: offset.width(),
+            scroll_top: offset.height(),
+        }), false);
+        true
+    }
+    // the content size may be changed by layout, so the scroll offsets are limited again
+    pub(crate) fn dfs_clamp_scroll_offset(&mut self) {
+        if self.style.get_display() == DisplayType::None { return }
+        if self.scroll_offset != Size::new(0., 0.) {
+            let offset = self.scroll_offset;
+            self.scroll_to(offset.width(), offset.height());
+        }
+        if self.content.is_terminated() { return }
+        self.node_mut().for_each_child_mut(|child| {
+            child.dfs_clamp_scroll_offset();
+        });
+    }
+    /// The size of the padding box extended by the overflowing children.
+    pub fn scroll_size(&self) -> Size {
+        let bounds = self.scroll_bounds();
+        let background_rect = self.position_offset.get_background_rect();
+        Size::new(bounds.right() - background_rect.left(), bounds.bottom() - background_rect.top())
+    }
+    /// The size of the padding box, a.k.a. the visible size of a scroll container.
+    #[inline]
+    pub fn client_size(&self) -> Size {
+        let background_rect = self.position_offset.get_background_rect();
+        Size::new(background_rect.width(), background_rect.height())
+    }
+    fn scroll_bounds(&self) -> Bounds {
+        let mut bounds: Bounds = self.position_offset.get_background_rect().into();
+        if self.content.is_terminated() {
+            bounds.union(&self.content.drawing_bounds());
+        } else {
+            self.node().for_each_child(|child| {
+                if child.style.get_display() == DisplayType::None { return }
+                bounds.union(&child.position_offset.drawing_bounds_in_parent(&child.style));
+            });
+        }
+        bounds
+    }
+    fn max_scroll_offset(&self) -> Size {
+        let scroll_size = self.scroll_size();
+        let client_size = self.client_size();
+        let scrollable = |overflow| overflow != OverflowType::Visible && overflow != OverflowType::Clip;
+        Size::new(
+            if scrollable(self.style.get_overflow_x()) { (scroll_size.width() - client_size.width()).max(0.) } else { 0. },
+            if scrollable(self.style.get_overflow_y()) { (scroll_size.height() - client_size.height()).max(0.) } else { 0. },
+        )
+    }
+    #[inline]
+    fn clamp_scroll_offset(&self, offset: Size) -> Size {
+        let max = self.max_scroll_offset();
+        Size::new(offset.width().min(max.width()).max(0.), offset.height().min(max.height()).max(0.))
+    }
+    /// Check if the user can scroll the element by wheel or touch, i.e. the overflow is scroll or auto.
+    /// A zero delta checks if it can be scrolled in any direction.
+    pub(crate) fn can_user_scroll_by(&self, delta_left: f64, delta_top: f64) -> bool {
+        if self.overflow_clip().is_none() { return false }
+        let max = self.max_scroll_offset();
+        let user_scrollable = |overflow| overflow == OverflowType::Scroll || overflow == OverflowType::Auto;
+        let can_scroll = |overflow, delta: f64, offset: f64, max: f64| {
+            user_scrollable(overflow) && if delta > 0. {
+                offset < max
+            } else if delta < 0. {
+                offset > 0.
+            } else {
+                max > 0.
+            }
+        };
+        let x = can_scroll(self.style.get_overflow_x(), delta_left, self.scroll_left(), max.width());
+        let y = can_scroll(self.style.get_overflow_y(), delta_top, self.scroll_top(), max.height());
+        if delta_left == 0. && delta_top == 0. {
+            x || y
+        } else {
+            delta_left != 0. && x || delta_top != 0. && y
+        }
+    }
+    fn draw_scrollbars(&mut self, child_transform: &Transform) {
+        let background_rect = self.position_offset.get_background_rect();
+        let scroll_size = self.scroll_size();
+        let client_size = self.client_size();
+        let show = |overflow, scroll_length: f64, client_length: f64| {
+            overflow == OverflowType::Scroll || overflow == OverflowType::Auto && scroll_length > client_length
+        };
+        let thumb = |offset: f64, scroll_length: f64, client_length: f64| {
+            let track = client_length - SCROLLBAR_MARGIN * 2.;
+            let length = (track * client_length / scroll_length.max(client_length)).max(SCROLLBAR_MIN_LENGTH).min(track);
+            let max_offset = scroll_length - client_length;
+            let start = if max_offset > 0. { (track - length) * offset / max_offset } else { 0. };
+            (SCROLLBAR_MARGIN + start, length)
+        };
+        if show(self.style.get_overflow_y(), scroll_size.height(), client_size.height()) {
+            let (top, height) = thumb(self.scroll_top(), scroll_size.height(), client_size.height());
+            let position = Position::new(
+                background_rect.right() - SCROLLBAR_MARGIN - SCROLLBAR_WIDTH,
+                background_rect.top() + top,
+                SCROLLBAR_WIDTH,
+                height,
+            );
+            self.draw_rect(SCROLLBAR_COLOR, child_transform.apply_to_quad(&position));
+        }
+        if show(self.style.get_overflow_x(), scroll_size.width(), client_size.width()) {
+            let (left, width) = thumb(self.scroll_left(), scroll_size.width(), client_size.width());
+            let position = Position::new(
+                background_rect.left() + left,
+                background_rect.bottom() - SCROLLBAR_MARGIN - SCROLLBAR_WIDTH,
+                width,
+                SCROLLBAR_WIDTH,
+            );
+            self.draw_rect(SCROLLBAR_COLOR, child_transform.apply_to_quad(&position));
         }
     }
-    pub(crate) fn draw(&mut self, viewport: Position, mut transform: Transform) {
-        if self.style.get_display() == style::DisplayType::None { return }
+    /// Draw the subtree in the `viewport`.
+    /// The unchanged subtrees out of the `damaged` area are skipped, since they are kept on the canvas.
+    pub(crate) fn draw(&mut self, viewport: Position, damaged: Bounds, transform: Transform) {
+        if self.style.get_display() == style::DisplayType::None {
+            self.damage_painted_bounds();
+            return;
+        }
         // debug!("Drawing {:?}", self);
-        let allocated_point = self.position_offset.allocated_point();
-        let requested_size = self.position_offset.requested_size();
-        let allocated_position = Position::from((allocated_point, requested_size));
+        let mut child_transform = self.transform_for_drawing(transform);
+
+        // skip the whole subtree if it is hidden
+        let layout_bounds = child_transform.apply_to_bounds(&self.position_offset.drawing_bounds());
+        if self.is_hidden_in(viewport, &child_transform, &layout_bounds) {
+            self.damage_painted_bounds();
+            // the painted bounds of the children are outdated, so repaint all of them when it is visible again
+            self.paint_dirty.set(true);
+            return;
+        }
+        let rm = self.canvas_config.resource_manager();
+        let unchanged = self.is_paint_unchanged(&layout_bounds);
+        let old_painted_bounds = self.painted_bounds.get();
+        if unchanged {
+            let painted = old_painted_bounds.bounds.map_or(false, |x| x.intersects(&damaged));
+            if !painted && !layout_bounds.intersects(&damaged) {
+                rm.borrow_mut().add_drawn_bounds(old_painted_bounds.bounds);
+                return;
+            }
+        }
+
+        let parent_drawn_bounds = rm.borrow_mut().take_drawn_bounds();
+        let blur_region_start = rm.borrow().blur_region_count();
 
         // check if drawing on separate tex is needed
         if self.style.get_opacity() < 1. && self.style.get_opacity() >= 0. {
@@ -360,31 +1390,96 @@ impl Element {
         let canvas_size = self.canvas_config.canvas_size.get();
         let drawing_tex_position = Position::new(0., 0., canvas_size.width(), canvas_size.height());
         if tex_id >= 0 {
-            let rm = self.canvas_config.resource_manager();
             let mut rm = rm.borrow_mut();
             rm.bind_rendering_target(tex_id, drawing_tex_position.width() as i32, drawing_tex_position.height() as i32);
         }
 
-        let child_transform = transform.mul_clone(Transform::new().offset(allocated_position.left_top() - Point::new(0., 0.))).mul_clone(&self.style.transform_ref());
-
         // draw content and child
         if self.style.get_display() != DisplayType::Inline {
+            self.draw_box_shadows(&child_transform, false);
             self.draw_background_color(&child_transform);
+            self.draw_box_shadows(&child_transform, true);
             self.draw_borders(&child_transform);
         }
+
+        // clip the content and the children if overflow is not visible, and scroll them in the clip
+        let clip = self.overflow_clip();
+        let mut scrolled_transform = match clip {
+            None => child_transform,
+            Some(_) => child_transform.mul_clone(Transform::new().offset(Size::new(-self.scroll_left(), -self.scroll_top()))),
+        };
+        let viewport = match clip {
+            None => viewport,
+            Some(clip) => {
+                let clip_shape = self.overflow_clip_shape().map(|shape| (shape, self.draw_shape(&shape, (0., 0., 0., 0.), &child_transform)));
+                let mut rm = rm.borrow_mut();
+                match clip_shape {
+                    Some((shape, draw_shape)) => {
+                        rm.push_clip_shape(child_transform.apply_to_quad(&shape.position()), draw_shape);
+                    },
+                    None => {
+                        if child_transform.is_axis_aligned() {
+                            let clip = child_transform.apply_to_position(&clip);
+                            rm.push_clip_rect(clip.left(), clip.top(), clip.width(), clip.height());
+                        } else {
+                            rm.push_clip_quad(child_transform.apply_to_quad(&clip));
+                        }
+                    },
+                }
+                Self::clip_viewport(viewport, &clip, &child_transform)
+            },
+        };
+        let self_bounds;
+        let blur_bounds;
+        let children_bounds;
         {
-            self.content.draw(&child_transform);
+            // the content out of the damaged area is not needed
+            let content_viewport = Self::clip_viewport(viewport, &damaged.into(), &Transform::new());
+            self.draw_text_shadows(content_viewport, &scrolled_transform);
+            self.content.draw(content_viewport, &scrolled_transform);
+            self_bounds = rm.borrow_mut().take_drawn_bounds();
+            blur_bounds = rm.borrow().blur_bounds_since(blur_region_start);
             if !self.content.is_terminated() {
+                let children_transform = scrolled_transform.mul_clone(&self.position_offset.children_transform(&self.style));
+                let depth_order = self.children_depth_order();
                 let node = self.node_mut();
-                node.for_each_child_mut(|child| {
-                    child.draw(viewport, child_transform);
-                });
+                match depth_order {
+                    None => {
+                        node.for_each_child_mut(|child| {
+                            child.draw(viewport, damaged, children_transform);
+                        });
+                    },
+                    Some(order) => {
+                        for i in order {
+                            node.child_mut(i).unwrap().draw(viewport, damaged, children_transform);
+                        }
+                    },
+                }
+            }
+            if clip.is_some() {
+                self.draw_scrollbars(&child_transform);
             }
+            children_bounds = rm.borrow_mut().take_drawn_bounds();
         }
+        if clip.is_some() {
+            rm.borrow_mut().pop_clip();
+        }
+        // the content of an unchanged element may be drawn partially, but it is still painted as before
+        let (self_bounds, blur_bounds) = if unchanged {
+            (old_painted_bounds.self_bounds, old_painted_bounds.blur_bounds)
+        } else {
+            (self_bounds, blur_bounds)
+        };
+        let bounds = match (self_bounds, children_bounds) {
+            (Some(mut a), Some(b)) => {
+                a.union(&b);
+                Some(a)
+            },
+            (a, b) => a.or(b),
+        };
 
         // recover tex
         if tex_id >= 0 {
-            let rm = self.canvas_config.resource_manager();
             let mut rm = rm.borrow_mut();
             rm.unbind_rendering_target();
 
@@ -408,13 +1503,27 @@ impl Element {
                 rm.set_draw_state(DrawState::new().alpha(original_alpha));
             }
         }
+
+        // the separate tex covers the whole canvas, so the bounds of the children are used instead
+        {
+            let mut rm = rm.borrow_mut();
+            rm.take_drawn_bounds();
+            rm.add_drawn_bounds(parent_drawn_bounds);
+            rm.add_drawn_bounds(bounds);
+        }
+        self.update_painted_bounds(PaintedBounds {
+            self_bounds,
+            bounds,
+            layout_bounds: Some(layout_bounds),
+            blur_bounds,
+        }, unchanged);
     }
     #[inline]
     pub(crate) fn enable_draw_separate_tex(&self) {
         if self.draw_separate_tex.get() != -1 { return };
         let rm = self.canvas_config.resource_manager();
         let tex_id = rm.borrow_mut().alloc_tex_id();
-        lib!(tex_create_empty(self.canvas_config.index, tex_id, 0, 0));
+        self.canvas_config.backend().tex_create_empty(self.canvas_config.index, tex_id, 0, 0);
         self.draw_separate_tex.set(tex_id);
     }
     #[inline]
@@ -422,48 +1531,172 @@ impl Element {
         if self.draw_separate_tex.get() == -1 { return };
         let tex_id = self.draw_separate_tex.replace(-1);
         let rm = self.canvas_config.resource_manager();
-        lib!(tex_delete(self.canvas_config.index, tex_id));
+        self.canvas_config.backend().tex_delete(self.canvas_config.index, tex_id);
         rm.borrow_mut().free_tex_id(tex_id);
     }
 
     // find the node under point
-    // TODO check the transform correctness
     fn get_node_under_point(&self, point: Point, mut transform: Transform) -> Option<ForestNodeRc<Element>> {
         if self.style().get_display() == style::DisplayType::None { return None }
         let position_offset = &self.position_offset;
         let allocated_point = position_offset.allocated_point();
-        let self_transform = transform.mul_clone(Transform::new().offset(allocated_point.into())).mul_clone(&self.style().get_transform());
+        let mut self_transform = transform.mul_clone(Transform::new().offset(allocated_point.into())).mul_clone(&position_offset.transform(self.style()));
         let drawing_bounds = self_transform.apply_to_bounds(&position_offset.drawing_bounds());
         // debug!("testing {:?} in bounds {:?}", (x, y), drawing_bounds);
-        if !point.in_bounds(&drawing_bounds) {
+        if !point.in_bounds(&drawing_bounds) || self_transform.is_behind_viewer(&position_offset.drawing_bounds()) || self.is_back_face_hidden() {
             return None;
         }
+        // the point in the local coordinates, and nothing is hit if the element is scaled to zero or viewed edge-on
+        let local_point = match self_transform.unproject(point) {
+            None => return None,
+            Some(x) => x,
+        };
         let content = &self.content;
-        if content.is_terminated() {
+        let (in_clip, mut scrolled_transform) = match self.overflow_clip() {
+            None => (true, self_transform),
+            Some(clip) => (
+                self.is_point_in_overflow_clip(point, &clip, &self_transform),
+                self_transform.mul_clone(Transform::new().offset(Size::new(-self.scroll_left(), -self.scroll_top()))),
+            ),
+        };
+        // the content and the children are clipped out unless the point is in the clip
+        if in_clip && content.is_terminated() {
             // debug!("testing {:?} in terminated {:?}", (x, y), content.name());
-            if content.is_under_point(point, self_transform) {
+            if content.is_under_point(point, scrolled_transform) {
                 return Some(self.rc());
             }
-        } else {
+        } else if in_clip {
             let self_node = self.node();
-            for child in self_node.iter().rev() {
-                let child_match = child.deref_with(self_node).get_node_under_point(point, self_transform);
+            let children_transform = scrolled_transform.mul_clone(&position_offset.children_transform(self.style()));
+            let order = self.children_depth_order().unwrap_or_else(|| (0..self_node.len()).collect());
+            for i in order.into_iter().rev() {
+                let child_match = self_node.child(i).unwrap().get_node_under_point(point, children_transform);
                 if child_match.is_some() {
                     return child_match;
                 }
             }
         }
-        let allocated_position = self_transform.apply_to_position(&Position::from((Point::new(0., 0.), position_offset.requested_size())));
-        // debug!("testing {:?} in allocated_position {:?}", (x, y), allocated_position);
-        if point.in_position(&allocated_position) {
-            return None;
+        let in_self = content.is_box_hit() && {
+            let shape = self.border_box_shape();
+            if shape.is_rect() {
+                local_point.in_position(&Position::from((Point::new(0., 0.), position_offset.requested_size())))
+            } else {
+                local_point.in_rounded_quad(&shape.position().into(), &shape)
+            }
+        };
+        if in_self {
+            return Some(self.rc());
         }
-        Some(self.rc())
+        None
     }
     pub fn node_under_point(&self, point: Point) -> Option<ForestNodeRc<Element>> {
         self.get_node_under_point(point, Transform::new())
     }
 
+    // the transform from the local coordinates to the canvas coordinates
+    fn global_transform(&self) -> Transform {
+        let mut parent_transform = match self.node().parent() {
+            None => Transform::new(),
+            Some(parent) => parent.children_global_transform(),
+        };
+        parent_transform.mul_clone(Transform::new().offset(self.position_offset.allocated_point().into())).mul_clone(&self.position_offset.transform(&self.style))
+    }
+    // the transform of the children with scrolling
+    fn children_global_transform(&self) -> Transform {
+        let mut transform = self.global_transform();
+        let mut scrolled_transform = match self.overflow_clip() {
+            None => transform,
+            Some(_) => transform.mul_clone(Transform::new().offset(Size::new(-self.scroll_left(), -self.scroll_top()))),
+        };
+        scrolled_transform.mul_clone(&self.position_offset.children_transform(&self.style))
+    }
+    // the transform from the local coordinates to the 3D rendering context, i.e. the plane of the nearest flat ancestor
+    fn transform_in_3d_context(&self) -> Transform {
+        let local = Transform::new().offset(self.position_offset.allocated_point().into()).mul_clone(&self.position_offset.transform(&self.style));
+        let parent = match self.node().parent() {
+            None => return local,
+            Some(parent) => parent,
+        };
+        let mut parent_transform = if parent.style.get_transform_style() == TransformStyleType::Preserve3d {
+            parent.transform_in_3d_context()
+        } else {
+            Transform::new()
+        };
+        if parent.overflow_clip().is_some() {
+            parent_transform.offset(Size::new(-parent.scroll_left(), -parent.scroll_top()));
+        }
+        parent_transform.mul_clone(&parent.position_offset.perspective_transform(&parent.style)).mul_clone(&local)
+    }
+    #[inline]
+    fn is_back_face_hidden(&self) -> bool {
+        self.style.get_backface_visibility() == BackfaceVisibilityType::Hidden && self.transform_in_3d_context().is_back_face()
+    }
+    // the children in the order from far to near if they are in the 3D space of this element
+    // only the siblings are sorted, so the descendants of different children do not intersect
+    fn children_depth_order(&self) -> Option<Vec<usize>> {
+        if self.style.get_transform_style() != TransformStyleType::Preserve3d { return None }
+        let node = self.node();
+        let mut depths: Vec<(usize, f64)> = (0..node.len()).map(|i| {
+            let child = node.child(i).unwrap();
+            let position_offset = &child.position_offset;
+            let size = position_offset.requested_size();
+            let transform = Transform::new().offset(position_offset.allocated_point().into()).mul_clone(&position_offset.transform(&child.style));
+            (i, transform.apply_to_point_3d((size.width() / 2., size.height() / 2., 0.)).2)
+        }).collect();
+        depths.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        Some(depths.into_iter().map(|(i, _)| i).collect())
+    }
+    /// Convert a point in the element, which is relative to the left-top of the element before transformed, to the canvas coordinates.
+    pub fn local_to_global(&self, point: Point) -> Point {
+        self.global_transform().apply_to_point(point)
+    }
+    /// Convert a point in the canvas coordinates to the element coordinates.
+    /// Returns `None` if the element or an ancestor is scaled to zero or viewed edge-on.
+    pub fn global_to_local(&self, point: Point) -> Option<Point> {
+        self.global_transform().unproject(point)
+    }
+    /// The axis-aligned rect containing the transformed element, in the canvas coordinates.
+    pub fn bounding_client_rect(&self) -> Position {
+        let position = Position::from((Point::new(0., 0.), self.position_offset.requested_size()));
+        self.global_transform().apply_to_bounds(&position.into()).into()
+    }
+
+    // find the innermost element under point which can be scrolled by the user
+    fn get_scroll_container_under_point(&self, point: Point, mut transform: Transform, delta: Size) -> Option<ForestNodeRc<Element>> {
+        if self.style().get_display() == style::DisplayType::None { return None }
+        let position_offset = &self.position_offset;
+        let mut self_transform = transform.mul_clone(Transform::new().offset(position_offset.allocated_point().into())).mul_clone(&position_offset.transform(self.style()));
+        if !point.in_bounds(&self_transform.apply_to_bounds(&position_offset.drawing_bounds())) || self_transform.is_behind_viewer(&position_offset.drawing_bounds()) {
+            return None;
+        }
+        let (in_clip, mut scrolled_transform) = match self.overflow_clip() {
+            None => (true, self_transform),
+            Some(clip) => (
+                self.is_point_in_overflow_clip(point, &clip, &self_transform),
+                self_transform.mul_clone(Transform::new().offset(Size::new(-self.scroll_left(), -self.scroll_top()))),
+            ),
+        };
+        if !in_clip { return None }
+        if !self.content.is_terminated() {
+            let self_node = self.node();
+            let children_transform = scrolled_transform.mul_clone(&position_offset.children_transform(self.style()));
+            for child in self_node.iter().rev() {
+                let child_match = child.deref_with(self_node).get_scroll_container_under_point(point, children_transform, delta);
+                if child_match.is_some() {
+                    return child_match;
+                }
+            }
+        }
+        if self.can_user_scroll_by(delta.width(), delta.height()) {
+            return Some(self.rc());
+        }
+        None
+    }
+    /// Find the innermost scroll container under point which can be scrolled by the delta.
+    pub(crate) fn scroll_container_under_point(&self, point: Point, delta: Size) -> Option<ForestNodeRc<Element>> {
+        self.get_scroll_container_under_point(point, Transform::new(), delta)
+    }
+
     fn get_node_by_id(top: &ForestNode<Element>, node: &ForestNode<Element>, id: &str) -> Option<ForestNodeRc<Element>> {
         for child_rc in node.iter() {
             let child = child_rc.deref_with(top);
@@ -499,11 +1732,18 @@ impl ForestNodeContent for Element {
     }
     #[inline]
     fn parent_node_changed(&mut self) {
+        self.damage_painted_bounds();
+        self.mark_paint_dirty();
         self.style_mut().parent_node_changed();
+        let class_dirty = self.style.is_class_dirty();
         match self.node_mut().parent_mut() {
             None => { },
             Some(parent_node) => {
                 parent_node.mark_layout_dirty();
+                // the classes of a detached subtree are updated only after the ancestors know about it
+                if class_dirty {
+                    parent_node.mark_child_class_dirty();
+                }
             }
         }
     }
@@ -524,6 +1764,7 @@ impl DerefMut for Element {
 
 impl Drop for Element {
     fn drop(&mut self) {
+        self.damage_painted_bounds();
         self.disable_draw_separate_tex();
     }
 }
@@ -608,3 +1849,178 @@ macro_rules! element_class {
         c
     }}
 }
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+    use rc_forest::ForestNodeRc;
+    use super::{Element, Empty, Text, Transform, Point, Size, Bounds, Shadow, BorderImageRepeatType, border_image_tile_count, border_image_tiles};
+    use super::style::DisplayType;
+    use super::super::super::lib_interfaces::{Call, TestCanvas, run_canvas_test};
+    use super::super::super::frame;
+
+    // a 100x100 block with the perspective, containing a 50x50 child with the transform
+    fn perspective_child(t: &TestCanvas, transform: Transform) -> ForestNodeRc<Element> {
+        let ctx = t.canvas.context();
+        let mut ctx = ctx.borrow_mut();
+        let cfg = ctx.canvas_config();
+        let root = ctx.root();
+        let mut root = root.borrow_mut();
+        let parent = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        let child = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        {
+            let parent = parent.deref_mut_with(&mut root);
+            parent.style_mut().display(DisplayType::Block);
+            parent.style_mut().width(100.);
+            parent.style_mut().height(100.);
+            parent.style_mut().perspective(200.);
+        }
+        {
+            let c = child.deref_mut_with(&mut root);
+            c.style_mut().display(DisplayType::Block);
+            c.style_mut().width(50.);
+            c.style_mut().height(50.);
+            c.style_mut().background_color((1., 0., 0., 1.));
+            c.style_mut().transform(transform);
+        }
+        parent.deref_mut_with(&mut root).append(child.clone());
+        root.append(parent);
+        child
+    }
+
+    #[test]
+    fn global_to_local() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let child = perspective_child(&t, Transform::new().rotate_y(0.5).rotate(0.3).translate(Size::new(10., 5.)).clone());
+            frame::generate(Instant::now());
+            let child = child.borrow();
+            for &(x, y) in [(0., 0.), (25., 25.), (50., 10.)].iter() {
+                let p = child.global_to_local(child.local_to_global(Point::new(x, y))).unwrap();
+                assert!((p.left() - x).abs() < 1e-9 && (p.top() - y).abs() < 1e-9);
+            }
+        });
+    }
+
+    #[test]
+    fn behind_perspective_origin() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let child = perspective_child(&t, Transform::new().translate_z(100.).clone());
+            let root = t.canvas.context().borrow_mut().root();
+            let hit = |x, y| root.borrow().node_under_point(Point::new(x, y)).map_or(false, |n| ForestNodeRc::ptr_eq(&n, &child));
+            let draws = || t.backend.count_calls(|x| match x { Call::TexDraw { .. } => true, _ => false });
+            frame::generate(Instant::now());
+            assert_eq!(draws(), 1);
+            assert!(hit(40., 40.));
+            // the perspective distance is 200, so it is behind the viewer and not drawn
+            child.borrow_mut().style_mut().transform(Transform::new().translate_z(250.).clone());
+            t.backend.clear_calls();
+            frame::generate(Instant::now());
+            assert!(t.backend.calls().contains(&Call::Clear));
+            assert_eq!(draws(), 0);
+            assert!(!hit(60., 60.));
+        });
+    }
+
+    #[test]
+    fn repaint_changed_text() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(200, 100);
+            let texts: Vec<ForestNodeRc<Element>> = {
+                let ctx = t.canvas.context();
+                let mut ctx = ctx.borrow_mut();
+                let cfg = ctx.canvas_config();
+                let root = ctx.root();
+                let mut root = root.borrow_mut();
+                ["first", "second"].iter().map(|s| {
+                    let block = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                    let text = root.create_another(Element::new(&cfg, Box::new(Text::new(&cfg))));
+                    text.deref_mut_with(&mut root).content_mut().downcast_mut::<Text>().unwrap().set_text(*s);
+                    block.deref_mut_with(&mut root).style_mut().display(DisplayType::Block);
+                    block.deref_mut_with(&mut root).append(text.clone());
+                    root.append(block);
+                    text
+                }).collect()
+            };
+            // the resizing repaints the first two frames
+            frame::generate(Instant::now());
+            frame::generate(Instant::now());
+            let old = texts[1].borrow().painted_bounds.get();
+            texts[1].borrow_mut().content_mut().downcast_mut::<Text>().unwrap().set_text("second, longer");
+            t.backend.clear_calls();
+            frame::generate(Instant::now());
+            let new = texts[1].borrow().painted_bounds.get();
+            let mut expected = old.bounds.unwrap();
+            expected.union(&new.layout_bounds.unwrap());
+            expected.union(&new.bounds.unwrap());
+            let calls = t.backend.calls();
+            assert_eq!(calls.iter().filter(|x| match x { Call::TexPushClipRect { .. } | Call::TexPopClip => true, _ => false }).count(), 2);
+            let push = calls.iter().position(|x| *x == Call::TexPushClipRect { rect: (expected.left() as f32, expected.top() as f32, expected.width() as f32, expected.height() as f32) }).unwrap();
+            // everything is drawn inside the clip, and the unchanged text is not drawn
+            let pop = calls.iter().position(|x| *x == Call::TexPopClip).unwrap();
+            let is_drawing = |x: &Call| match x { Call::Clear | Call::TexDraw { .. } => true, _ => false };
+            assert!(!calls[..push].iter().any(&is_drawing) && !calls[pop + 1..].iter().any(&is_drawing));
+            assert!(!texts[0].borrow().painted_bounds.get().bounds.unwrap().intersects(&expected));
+            for x in calls.iter() {
+                match x {
+                    Call::TexDraw { quad, .. } => {
+                        let b = Bounds::new(quad[0].0 as f64, quad[0].1 as f64, quad[2].0 as f64, quad[2].1 as f64);
+                        assert!(b.intersects(&expected));
+                    },
+                    _ => { },
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn border_image_tile_counts() {
+        for &repeat in [BorderImageRepeatType::Stretch, BorderImageRepeatType::Repeat, BorderImageRepeatType::Round, BorderImageRepeatType::Space].iter() {
+            for &(length, tile) in [(100., 30.), (90., 30.), (10., 30.), (0., 30.), (100., 0.)].iter() {
+                assert_eq!(border_image_tiles(5., length, tile, repeat).len() as f64, border_image_tile_count(length, tile, repeat));
+            }
+        }
+        // (100 - 30) / 2 = 35 on each side of the centered tile
+        let tiles = border_image_tiles(0., 100., 30., BorderImageRepeatType::Repeat);
+        assert_eq!(tiles[0], (0., 5., 25. / 30., 1.));
+        assert_eq!(tiles[1], (5., 30., 0., 1.));
+        assert_eq!(border_image_tile_count(1e12, 1e-3, BorderImageRepeatType::Repeat), 1e15 + 1.);
+    }
+
+    #[test]
+    fn blur_in_device_pixels() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            {
+                let ctx = t.canvas.context();
+                let mut ctx = ctx.borrow_mut();
+                ctx.set_canvas_size(100, 100, 2.);
+                let cfg = ctx.canvas_config();
+                let root = ctx.root();
+                let mut root = root.borrow_mut();
+                let elem = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                {
+                    let elem = elem.deref_mut_with(&mut root);
+                    elem.style_mut().display(DisplayType::Block);
+                    elem.style_mut().width(20.);
+                    elem.style_mut().height(20.);
+                    elem.style_mut().box_shadow(vec![Shadow { offset_x: 0., offset_y: 0., blur: 4., spread: 0., color: (0., 0., 0., 1.), inset: false }]);
+                }
+                root.append(elem);
+            }
+            frame::generate(Instant::now());
+            let calls = t.backend.calls();
+            let targets: Vec<&Call> = calls.iter().filter(|x| match x { Call::TexBindRenderingTarget { .. } => true, _ => false }).collect();
+            assert_eq!(targets.len(), 2);
+            assert!(targets.iter().all(|x| match x { Call::TexBindRenderingTarget { size, .. } => *size == (200, 200), _ => false }));
+            // the sigma is 2 logical pixels, so 4 device pixels
+            assert!(calls.contains(&Call::TexSetDrawBlur { step: (1. / 200., 0.), sigma: 4. }));
+            assert!(calls.contains(&Call::TexSetDrawBlur { step: (0., 1. / 200.), sigma: 4. }));
+            assert!(calls.iter().any(|x| match x {
+                Call::TexDraw { tex_shader_index: -3, quad, .. } => *quad == [(0., 0.), (0., 40.), (40., 40.), (40., 0.)],
+                _ => false,
+            }));
+        });
+    }
+}
@@ -0,0 +1,326 @@
+use std::rc::Rc;
+use std::cell::{RefCell, Ref};
+use super::super::CanvasConfig;
+use super::super::path::{PathSegment, PathStyle, PathShape, parse_path_data, path_bounds, build_path_shape, path_shape_contains};
+use super::{Element, ElementStyle, InlineAllocator, Transform, Position, Bounds, Size, Point};
+use rc_forest::ForestNode;
+
+const PATH_TEX_MAX_SIZE: i32 = 2048;
+const PATH_TOLERANCE: f64 = 0.25;
+
+/// The rule to determine the inside of a path, the same as the SVG `fill-rule`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+/// The shape at the corners of the stroke, the same as the SVG `stroke-linejoin`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// The shape at the ends of the open sub paths, the same as the SVG `stroke-linecap`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+// vector path element
+
+pub struct Path {
+    element: *mut Element,
+    canvas_config: Rc<CanvasConfig>,
+    data: String,
+    segments: Vec<PathSegment>,
+    view_box: Option<Position>,
+    path_style: PathStyle,
+    inline_pos: Position,
+    shape: RefCell<Option<(f64, PathShape)>>,
+}
+
+impl Path {
+    pub fn new(cfg: &Rc<CanvasConfig>) -> Self {
+        Path {
+            element: 0 as *mut Element,
+            canvas_config: cfg.clone(),
+            data: String::new(),
+            segments: vec![],
+            view_box: None,
+            path_style: PathStyle {
+                fill_color: (0., 0., 0., 1.),
+                fill_rule: FillRule::NonZero,
+                stroke_color: (0., 0., 0., 0.),
+                stroke_width: 1.,
+                line_join: LineJoin::Miter,
+                line_cap: LineCap::Butt,
+                miter_limit: 4.,
+            },
+            inline_pos: Position::new(0., 0., 0., 0.),
+            shape: RefCell::new(None),
+        }
+    }
+    #[inline]
+    fn element<'a>(&'a self) -> &'a Element {
+        unsafe { &*self.element }
+    }
+    #[inline]
+    fn element_mut<'a>(&'a mut self) -> &'a mut Element {
+        unsafe { &mut *self.element }
+    }
+    #[inline]
+    fn node_mut<'a>(&'a mut self) -> &'a mut ForestNode<Element> {
+        self.element_mut().node_mut()
+    }
+
+    fn mark_dirty(&mut self, layout: bool) {
+        *self.shape.get_mut() = None;
+        if self.element.is_null() { return }
+        if layout { self.element_mut().mark_layout_dirty(); }
+        self.element().mark_paint_dirty();
+    }
+    /// Set the SVG path data, e.g. `"M 0 0 L 10 10 Z"`.
+    /// If the data has errors, the segments before the first error are still drawn, the same as SVG.
+    pub fn set_data<T>(&mut self, s: T) where String: From<T> {
+        self.data = String::from(s);
+        let (segments, err) = parse_path_data(&self.data);
+        match err {
+            None => { },
+            Some(pos) => warn!("Invalid path data at {}: {:?}", pos, self.data),
+        }
+        self.segments = segments;
+        self.mark_dirty(true);
+    }
+    pub fn get_data(&self) -> String {
+        self.data.clone()
+    }
+    /// Set the area of the path coordinates which is stretched to the element.
+    /// If not set, it is the area from (0, 0) to the right-bottom of the path, including the stroke.
+    pub fn set_view_box(&mut self, view_box: Position) {
+        self.view_box = Some(view_box);
+        self.mark_dirty(true);
+    }
+    pub fn set_fill_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.path_style.fill_color = color;
+        self.mark_dirty(false);
+    }
+    pub fn set_fill_rule(&mut self, fill_rule: FillRule) {
+        self.path_style.fill_rule = fill_rule;
+        self.mark_dirty(false);
+    }
+    pub fn set_stroke_color(&mut self, color: (f32, f32, f32, f32)) {
+        self.path_style.stroke_color = color;
+        self.mark_dirty(true);
+    }
+    /// Set the stroke width in the path coordinates.
+    pub fn set_stroke_width(&mut self, width: f64) {
+        self.path_style.stroke_width = width;
+        self.mark_dirty(true);
+    }
+    pub fn set_line_join(&mut self, line_join: LineJoin) {
+        self.path_style.line_join = line_join;
+        self.mark_dirty(false);
+    }
+    pub fn set_line_cap(&mut self, line_cap: LineCap) {
+        self.path_style.line_cap = line_cap;
+        self.mark_dirty(false);
+    }
+    pub fn set_miter_limit(&mut self, miter_limit: f64) {
+        self.path_style.miter_limit = miter_limit;
+        self.mark_dirty(false);
+    }
+
+    // the area of the path coordinates (left, top, width, height) shown in the element
+    fn view_box(&self) -> (f64, f64, f64, f64) {
+        match self.view_box {
+            Some(x) => (x.left(), x.top(), x.width(), x.height()),
+            None => {
+                let stroke = if self.path_style.stroke_color.3 > 0. { self.path_style.stroke_width.max(0.) / 2. } else { 0. };
+                match path_bounds(&self.segments) {
+                    None => (0., 0., 0., 0.),
+                    Some(b) => (0., 0., (b.2 + stroke).max(0.), (b.3 + stroke).max(0.)),
+                }
+            }
+        }
+    }
+    // the shape with the curves flattened for the scale from the path coordinates to the pixels
+    // the cached one is used if it is flattened for a larger scale
+    fn shape(&self, scale: f64) -> Ref<PathShape> {
+        let cached = match *self.shape.borrow() {
+            None => false,
+            Some((s, _)) => s >= scale,
+        };
+        if !cached {
+            let shape = build_path_shape(&self.segments, &self.path_style, PATH_TOLERANCE / scale.max(1e-6));
+            self.shape.replace(Some((scale, shape)));
+        }
+        Ref::map(self.shape.borrow(), |x| &x.as_ref().unwrap().1)
+    }
+}
+
+impl super::ElementContent for Path {
+    #[inline]
+    fn name(&self) -> &'static str {
+        "Path"
+    }
+    #[inline]
+    fn is_terminated(&self) -> bool {
+        true
+    }
+    fn clone(&self) -> Box<super::ElementContent> {
+        Box::new(Path {
+            element: 0 as *mut Element,
+            canvas_config: self.canvas_config.clone(),
+            data: self.data.clone(),
+            segments: self.segments.clone(),
+            view_box: self.view_box,
+            path_style: self.path_style,
+            inline_pos: Position::new(0., 0., 0., 0.),
+            shape: RefCell::new(None),
+        })
+    }
+    #[inline]
+    fn associate_element(&mut self, element: *mut Element) {
+        self.element = element;
+    }
+    fn suggest_size(&mut self, suggested_size: Size, inline_allocator: &mut InlineAllocator, style: &ElementStyle) -> Size {
+        let base_requested_top = inline_allocator.get_current_height();
+        let (_, _, natural_width, natural_height) = self.view_box();
+        let spec_width = style.get_width().is_finite();
+        let spec_height = style.get_height().is_finite();
+        let width;
+        let height;
+        if spec_width {
+            if spec_height {
+                width = style.get_width();
+                height = style.get_height();
+            } else {
+                width = style.get_width();
+                if natural_width == 0. { height = 0.; }
+                else { height = width / natural_width * natural_height; }
+            }
+        } else {
+            if spec_height {
+                height = style.get_height();
+                if natural_height == 0. { width = 0.; }
+                else { width = height / natural_height * natural_width; }
+            } else {
+                width = natural_width;
+                height = natural_height;
+            }
+        }
+        let baseline_top = height / 2.; // FIXME vertical-align middle
+        inline_allocator.start_node(self.node_mut(), height, baseline_top);
+        let (left, line_baseline_top) = inline_allocator.add_width(self.node_mut(), width, true).into();
+        self.inline_pos = Position::new(left, line_baseline_top - baseline_top - base_requested_top, width, height);
+        Size::new(suggested_size.width(), height - base_requested_top)
+    }
+    #[inline]
+    fn adjust_baseline_offset(&mut self, add_offset: f64) {
+        self.inline_pos.move_size(Size::new(0., add_offset));
+    }
+    #[inline]
+    fn adjust_text_align_offset(&mut self, add_offset: f64) {
+        self.inline_pos.move_size(Size::new(add_offset, 0.));
+    }
+    fn draw(&mut self, _viewport: Position, transform: &Transform) {
+        let view_box = self.view_box();
+        if self.inline_pos.width() <= 0. || self.inline_pos.height() <= 0. || view_box.2 <= 0. || view_box.3 <= 0. {
+            return;
+        }
+        // rasterize with the device pixels, and again if the scale changed
+        let scale = self.canvas_config.device_pixel_ratio.get() * transform.get_area_scale();
+        let tex_size = |x: f64| ((x * scale).ceil() as i32).max(1).min(PATH_TEX_MAX_SIZE);
+        let tex_size = (tex_size(self.inline_pos.width()), tex_size(self.inline_pos.height()));
+        let rm = self.canvas_config.resource_manager();
+        let mut rm = rm.borrow_mut();
+        let tex_id = rm.path_texture(&self.segments, &self.path_style, view_box, tex_size);
+        rm.request_draw(
+            tex_id, false,
+            0., 0., 1., 1.,
+            transform.apply_to_quad(&self.inline_pos)
+        );
+    }
+    #[inline]
+    fn drawing_bounds(&self) -> Bounds {
+        self.inline_pos.into()
+    }
+    fn is_under_point(&self, point: Point, transform: Transform) -> bool {
+        let point = match transform.unproject(point) {
+            None => return false,
+            Some(x) => x,
+        };
+        if !point.in_position(&self.inline_pos) {
+            return false;
+        }
+        let view_box = self.view_box();
+        if view_box.2 <= 0. || view_box.3 <= 0. {
+            return false;
+        }
+        // test the filled shape in the path coordinates
+        let (sx, sy) = (self.inline_pos.width() / view_box.2, self.inline_pos.height() / view_box.3);
+        let x = (point.left() - self.inline_pos.left()) / sx + view_box.0;
+        let y = (point.top() - self.inline_pos.top()) / sy + view_box.1;
+        path_shape_contains(&self.shape(sx.max(sy)), &self.path_style, (x, y))
+    }
+    #[inline]
+    fn is_box_hit(&self) -> bool {
+        // only the filled shape and the stroke are hit
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+    use super::Path;
+    use super::super::{Element, Empty};
+    use super::super::style::DisplayType;
+    use super::super::super::super::lib_interfaces::{Call, TestCanvas, run_canvas_test};
+    use super::super::super::super::frame;
+
+    #[test]
+    fn cached_texture() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let path = {
+                let ctx = t.canvas.context();
+                let mut ctx = ctx.borrow_mut();
+                let cfg = ctx.canvas_config();
+                let root = ctx.root();
+                let mut root = root.borrow_mut();
+                let block = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                let path = root.create_another(Element::new(&cfg, Box::new(Path::new(&cfg))));
+                path.deref_mut_with(&mut root).content_mut().downcast_mut::<Path>().unwrap().set_data("M0 0 L10 0 L10 10 Z");
+                block.deref_mut_with(&mut root).style_mut().display(DisplayType::Block);
+                block.deref_mut_with(&mut root).append(path.clone());
+                root.append(block);
+                path
+            };
+            let created = |calls: &[Call]| calls.iter().filter_map(|x| match x {
+                Call::TexCreateFromPixels { tex_id, size } => Some((*tex_id, *size)),
+                _ => None,
+            }).collect::<Vec<_>>();
+            // the resizing repaints the first two frames, with the same texture
+            frame::generate(Instant::now());
+            frame::generate(Instant::now());
+            let calls = t.backend.calls();
+            let tex = created(&calls);
+            assert_eq!(tex.len(), 1);
+            assert_eq!(tex[0].1, (10, 10));
+            // the old texture is released after the frame drawing the new one
+            path.borrow_mut().content_mut().downcast_mut::<Path>().unwrap().set_fill_color((1., 0., 0., 1.));
+            t.backend.clear_calls();
+            frame::generate(Instant::now());
+            let calls = t.backend.calls();
+            assert_eq!(created(&calls).len(), 1);
+            let delete = calls.iter().position(|x| *x == Call::TexDelete { tex_id: tex[0].0 }).unwrap();
+            assert!(calls[..delete].iter().any(|x| match x { Call::TexDraw { .. } => true, _ => false }));
+        });
+    }
+}
@@ -172,8 +172,7 @@ pub fn allocate_position(element: &mut Element, style: &ElementStyle, allocated_
         element.position_offset.background_rect.width(),
         element.position_offset.background_rect.height(),
     );
-    let allocated_position = Position::from((allocated_point, requested_size));
-    let mut drawing_bounds: Bounds = allocated_position.into();
+    let mut drawing_bounds = Bounds::new(0., 0., requested_size.width(), requested_size.height());
     if element.content().is_terminated() {
         drawing_bounds.union(&element.content().drawing_bounds());
     } else {
@@ -52,11 +52,10 @@ pub fn suggest_size(element: &mut Element, style: &ElementStyle, suggested_size:
 
 #[inline]
 pub fn allocate_position(element: &mut Element, _style: &ElementStyle, allocated_point: Point, relative_point: Point) -> (Point, Bounds) {
-    let requested_size = element.position_offset.requested_size();
     let drawing_bounds = if element.content().is_terminated() {
         element.content().drawing_bounds()
     } else {
-        let mut drawing_bounds = Bounds::new(requested_size.width(), allocated_point.top(), requested_size.width(), allocated_point.top());
+        let mut drawing_bounds = Bounds::new(0., 0., 0., 0.);
         let mut current_top = 0.;
         let node = element.node_mut();
         node.for_each_child_mut(|child| {
@@ -97,6 +97,5 @@ pub fn allocate_position(element: &mut Element, _style: &ElementStyle, allocated
             }
         });
     }
-    drawing_bounds.move_size(element.position_offset.inline_position_offset);
     (allocated_point + element.position_offset.inline_position_offset, drawing_bounds)
 }
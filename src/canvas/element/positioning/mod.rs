@@ -1,11 +1,11 @@
 use std::f64;
 use std::cell::Cell;
 use super::style::*;
-use super::{Element};
+use super::{Element, Transform};
 use rc_forest::ForestNode;
 
 mod position_types;
-pub use self::position_types::{Position, Point, Size, Bounds};
+pub use self::position_types::{Position, Point, Size, Bounds, RoundedRect};
 mod inline_allocator;
 pub(crate) use self::inline_allocator::{InlineAllocator, InlineAllocatorState};
 mod box_sizing;
@@ -27,7 +27,7 @@ pub struct PositionOffset {
     content_size: Size, // the content size for flex
     allocated_point: Point, // left-top corner relative to content box of parent node
     relative_point: Point, // left-top corner relative to content box of relative node
-    drawing_bounds: Bounds, // drawing bounds relative to content box of parent node
+    drawing_bounds: Bounds, // drawing bounds relative to the left-top corner of this node, without its transform
     min_max_width: (f64, f64), // min and max width
     position_dirty: Cell<bool>,
     min_max_width_dirty: Cell<bool>,
@@ -256,7 +256,7 @@ impl PositionOffset {
 
         // layout edge-cutting
         if !self.position_dirty.get() && !is_inline && allocated_point == self.allocated_point && relative_point == self.relative_point {
-            return self.drawing_bounds
+            return self.drawing_bounds_in_parent(style)
         }
 
         let relative_point = match position {
@@ -282,10 +282,66 @@ impl PositionOffset {
         };
 
         self.allocated_point = allocated_point;
-        self.drawing_bounds = drawing_bounds;
+        // the children may be enlarged by the perspective projection
+        let mut drawing_bounds = drawing_bounds;
+        if style.get_perspective() > 0. && !element.is_terminated() {
+            let perspective = self.perspective_transform(style);
+            element.node().for_each_child(|child| {
+                if child.style().get_display() == DisplayType::None { return }
+                let position_offset = &child.position_offset;
+                let mut transform = perspective.clone();
+                let transform = transform.translate(position_offset.allocated_point - Point::new(0., 0.)).mul_clone(&position_offset.transform(child.style()));
+                // the children behind the viewer are not drawn
+                if transform.is_behind_viewer(&position_offset.drawing_bounds) { return }
+                drawing_bounds.union(&transform.apply_to_bounds(&position_offset.drawing_bounds));
+            });
+        }
+        // the shadows are drawn out of the box
+        self.drawing_bounds = element.shadow_drawing_bounds(drawing_bounds);
         self.position_dirty.set(false);
         debug!("Allocated position for {:?} with {:?} drawing bounds {:?}", element, self.allocated_point, self.drawing_bounds);
-        drawing_bounds
+        self.drawing_bounds_in_parent(style)
+    }
+    #[inline]
+    pub(crate) fn drawing_bounds_in_parent(&self, style: &super::ElementStyle) -> Bounds {
+        self.transform(style).apply_to_bounds(&self.drawing_bounds) + (self.allocated_point - Point::new(0., 0.))
+    }
+    // the border box relative to the allocated point
+    fn border_box(&self, style: &super::ElementStyle) -> Position {
+        let rect = self.background_rect;
+        Position::new(
+            rect.left() - style.get_border_left_width(),
+            rect.top() - style.get_border_top_width(),
+            rect.width() + style.get_border_left_width() + style.get_border_right_width(),
+            rect.height() + style.get_border_top_width() + style.get_border_bottom_width(),
+        )
+    }
+    #[inline]
+    fn resolve_origin(&self, style: &super::ElementStyle, (x, y): (LengthPercentage, LengthPercentage)) -> Size {
+        let border_box = self.border_box(style);
+        Size::new(border_box.left() + x.resolve(border_box.width()), border_box.top() + y.resolve(border_box.height()))
+    }
+    /// The transform of the element around the transform origin, which is relative to the border box.
+    pub(crate) fn transform(&self, style: &super::ElementStyle) -> Transform {
+        let mut transform = style.get_transform();
+        if transform == Transform::new() { return transform }
+        let origin = self.resolve_origin(style, style.get_transform_origin());
+        Transform::new().offset(origin).mul_clone(&transform.translate(-origin))
+    }
+    /// The perspective projection of the children around the perspective origin.
+    pub(crate) fn perspective_transform(&self, style: &super::ElementStyle) -> Transform {
+        let perspective = style.get_perspective();
+        if !(perspective > 0.) { return Transform::new() }
+        let origin = self.resolve_origin(style, style.get_perspective_origin());
+        Transform::new().offset(origin).perspective(perspective).translate(-origin).clone()
+    }
+    /// The transform applied to the children, i.e. the perspective projection, and the flattening to the plane of the element unless it is `preserve-3d`.
+    pub(crate) fn children_transform(&self, style: &super::ElementStyle) -> Transform {
+        let mut ret = self.perspective_transform(style);
+        if style.get_transform_style() == TransformStyleType::Flat {
+            ret.flatten();
+        }
+        ret
     }
 
     fn allocate_position_absolute(&mut self, style: &super::ElementStyle, relative_point: Point) -> Point {
@@ -309,7 +365,7 @@ impl PositionOffset {
         )
     }
 
-    pub(super) fn get_background_rect(&mut self) -> Position {
+    pub(super) fn get_background_rect(&self) -> Position {
         self.background_rect
     }
 }
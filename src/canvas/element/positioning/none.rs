@@ -13,8 +13,5 @@ pub fn suggest_size(_element: &Element, _style: &ElementStyle, _suggested_size:
 
 #[inline]
 pub fn allocate_position(_element: &Element, _style: &ElementStyle, allocated_point: Point, _relative_point: Point) -> (Point, Bounds) {
-    (
-        allocated_point,
-        Bounds::new(allocated_point.left(), allocated_point.top(), allocated_point.left(), allocated_point.top())
-    )
+    (allocated_point, Bounds::new(0., 0., 0., 0.))
 }
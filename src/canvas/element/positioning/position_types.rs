@@ -24,6 +24,13 @@ pub struct Bounds {
     point2: Point,
 }
 
+/// A rect with circular corners, the radii are in the order of left-top, right-top, right-bottom and left-bottom.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct RoundedRect {
+    position: Position,
+    radii: (f64, f64, f64, f64),
+}
+
 impl Point {
     #[inline]
     pub fn new(left: f64, top: f64) -> Self {
@@ -59,6 +66,31 @@ impl Point {
         if self.top >= pos.bottom() { return false }
         true
     }
+    /// Whether the point is in a convex quad, in either winding order.
+    pub fn in_quad(&self, quad: &[Point; 4]) -> bool {
+        let mut has_pos = false;
+        let mut has_neg = false;
+        for i in 0..4 {
+            let a = quad[i];
+            let b = quad[(i + 1) % 4];
+            let cross = (b.left - a.left) * (self.top - a.top) - (b.top - a.top) * (self.left - a.left);
+            if cross > 0. { has_pos = true }
+            if cross < 0. { has_neg = true }
+        }
+        !(has_pos && has_neg)
+    }
+    /// Whether the point is in a rounded rect which is transformed to a quad, e.g. by `Transform::apply_to_quad`.
+    pub fn in_rounded_quad(&self, quad: &[Point; 4], rect: &RoundedRect) -> bool {
+        let (e1x, e1y) = (quad[3].left - quad[0].left, quad[3].top - quad[0].top);
+        let (e2x, e2y) = (quad[1].left - quad[0].left, quad[1].top - quad[0].top);
+        let (dx, dy) = (self.left - quad[0].left, self.top - quad[0].top);
+        let det = e1x * e2y - e1y * e2x;
+        if det == 0. { return false }
+        let u = (dx * e2y - dy * e2x) / det;
+        let v = (e1x * dy - e1y * dx) / det;
+        let pos = rect.position();
+        rect.contains(Point::new(pos.left() + u * pos.width(), pos.top() + v * pos.height()))
+    }
 }
 
 impl Add<Size> for Point {
@@ -236,6 +268,73 @@ impl From<(Point, Size)> for Position {
     }
 }
 
+impl RoundedRect {
+    /// Create a rounded rect. The radii are scaled down if the adjacent ones do not fit the sides, the same as CSS.
+    pub fn new(position: Position, radii: (f64, f64, f64, f64)) -> Self {
+        let r = (radii.0.max(0.), radii.1.max(0.), radii.2.max(0.), radii.3.max(0.));
+        let fit = |length: f64, sum: f64| if sum > length { length / sum } else { 1. };
+        let (w, h) = (position.width().abs(), position.height().abs());
+        let f = fit(w, r.0 + r.1).min(fit(h, r.1 + r.2)).min(fit(w, r.2 + r.3)).min(fit(h, r.3 + r.0));
+        Self {
+            position,
+            radii: (r.0 * f, r.1 * f, r.2 * f, r.3 * f),
+        }
+    }
+    #[inline]
+    pub fn position(&self) -> Position {
+        self.position
+    }
+    #[inline]
+    pub fn radii(&self) -> (f64, f64, f64, f64) {
+        self.radii
+    }
+    #[inline]
+    pub fn is_rect(&self) -> bool {
+        self.radii == (0., 0., 0., 0.)
+    }
+    /// The rounded rect inside the borders. The radii are reduced by the wider adjacent border.
+    pub fn inset(&self, top: f64, right: f64, bottom: f64, left: f64) -> Self {
+        let mut position = self.position;
+        position.shrink(Size::new(left, top), Size::new(right, bottom));
+        let r = self.radii;
+        Self::new(position, (
+            (r.0 - left.max(top)).max(0.),
+            (r.1 - right.max(top)).max(0.),
+            (r.2 - right.max(bottom)).max(0.),
+            (r.3 - left.max(bottom)).max(0.),
+        ))
+    }
+    /// The rounded rect moved by `offset` and expanded by `spread` (shrunk if negative), the same as the shape of `box-shadow`.
+    pub fn shadow(&self, offset: Size, spread: f64) -> Self {
+        let pos = self.position;
+        let width = (pos.width() + spread * 2.).max(0.);
+        let height = (pos.height() + spread * 2.).max(0.);
+        let position = Position::new(
+            pos.left() + offset.width() + (pos.width() - width) / 2.,
+            pos.top() + offset.height() + (pos.height() - height) / 2.,
+            width,
+            height,
+        );
+        let r = self.radii;
+        let grow = |r: f64| if r > 0. { (r + spread).max(0.) } else { 0. };
+        Self::new(position, (grow(r.0), grow(r.1), grow(r.2), grow(r.3)))
+    }
+    pub fn contains(&self, p: Point) -> bool {
+        if !p.in_position(&self.position) { return false }
+        let pos = self.position;
+        let r = self.radii;
+        let outside_corner = |cx: f64, cy: f64, r: f64| {
+            let (dx, dy) = (p.left - cx, p.top - cy);
+            dx * dx + dy * dy > r * r
+        };
+        if p.left < pos.left() + r.0 && p.top < pos.top() + r.0 && outside_corner(pos.left() + r.0, pos.top() + r.0, r.0) { return false }
+        if p.left > pos.right() - r.1 && p.top < pos.top() + r.1 && outside_corner(pos.right() - r.1, pos.top() + r.1, r.1) { return false }
+        if p.left > pos.right() - r.2 && p.top > pos.bottom() - r.2 && outside_corner(pos.right() - r.2, pos.bottom() - r.2, r.2) { return false }
+        if p.left < pos.left() + r.3 && p.top > pos.bottom() - r.3 && outside_corner(pos.left() + r.3, pos.bottom() - r.3, r.3) { return false }
+        true
+    }
+}
+
 impl From<Bounds> for Position {
     fn from(other: Bounds) -> Position {
         Position {
@@ -288,6 +387,13 @@ impl Bounds {
     pub fn extend_bottom(&mut self, v: f64) {
         self.point2.top += v;
     }
+    /// Extend all sides by `v`.
+    pub fn extend(&mut self, v: f64) {
+        self.point1.left -= v;
+        self.point1.top -= v;
+        self.point2.left += v;
+        self.point2.top += v;
+    }
     pub fn move_size(&mut self, s: Size) {
         self.point1.move_size(s);
         self.point2.move_size(s);
@@ -304,6 +410,12 @@ impl Bounds {
         if self.point2.left > other.point2.left { self.point2.left = other.point2.left };
         if self.point2.top > other.point2.top { self.point2.top = other.point2.top };
     }
+    #[inline]
+    pub fn intersects(&self, other: &Self) -> bool {
+        // bounds that are not comparable (NaN) are treated as intersected
+        !(self.point2.left <= other.point1.left || other.point2.left <= self.point1.left ||
+            self.point2.top <= other.point1.top || other.point2.top <= self.point1.top)
+    }
 }
 
 impl Add<Size> for Bounds {
@@ -328,6 +440,18 @@ impl Sub<Size> for Bounds {
     }
 }
 
+/// The corners in the order of left-top, left-bottom, right-bottom and right-top, the same as `Transform::apply_to_quad`.
+impl From<Position> for [Point; 4] {
+    fn from(other: Position) -> [Point; 4] {
+        [
+            Point::new(other.left(), other.top()),
+            Point::new(other.left(), other.bottom()),
+            Point::new(other.right(), other.bottom()),
+            Point::new(other.right(), other.top()),
+        ]
+    }
+}
+
 impl From<Position> for Bounds {
     fn from(other: Position) -> Bounds {
         Bounds {
@@ -201,7 +201,7 @@ macro_rules! impl_style_item {
                 } else {
                     $default_value
                 };
-                let changed = self.$name.equal(r, &val);
+                let changed = !self.$name.equal(r, &val);
                 self.$name.set(r, val);
                 changed
             };
@@ -31,11 +31,18 @@ enum ClassDirtyStatus {
 element_style! {
     display: DisplayType, Absolute(DisplayType::Inline), 0x02, (layout_dirty);
     opacity: f32, Absolute(1.), 0x03, ();
-    transform: Transform, Absolute(Transform::new()), 0x04, ();
+    transform: Transform, Absolute(Transform::new()), 0x04, (layout_dirty);
+    overflow_x: OverflowType, Absolute(OverflowType::Visible), 0x05, ();
+    overflow_y: OverflowType, Absolute(OverflowType::Visible), 0x06, ();
+    transform_origin: (LengthPercentage, LengthPercentage), Absolute((LengthPercentage::Ratio(0.5), LengthPercentage::Ratio(0.5))), 0x07, (layout_dirty);
 
     box_sizing: BoxSizingType, Absolute(BoxSizingType::ContentBox), 0x08, (layout_dirty);
     width: f64, Auto(DEFAULT_F64), 0x09, (layout_dirty, horizontal_relative);
     height: f64, Auto(DEFAULT_F64), 0x0a, (layout_dirty, vertical_relative);
+    transform_style: TransformStyleType, Absolute(TransformStyleType::Flat), 0x0b, (layout_dirty);
+    perspective: f64, Absolute(0.), 0x0c, (layout_dirty);
+    perspective_origin: (LengthPercentage, LengthPercentage), Absolute((LengthPercentage::Ratio(0.5), LengthPercentage::Ratio(0.5))), 0x0d, (layout_dirty);
+    backface_visibility: BackfaceVisibilityType, Absolute(BackfaceVisibilityType::Visible), 0x0e, ();
 
     position: PositionType, Absolute(PositionType::Static), 0x10, (layout_dirty);
     left: f64, Auto(DEFAULT_F64), 0x11, (layout_dirty, horizontal_relative);
@@ -54,6 +61,14 @@ element_style! {
     text_align: TextAlignType, Absolute(TextAlignType::Left), 0x33, (layout_dirty, inherit);
     color: (f32, f32, f32, f32), Absolute((0., 0., 0., 1.)), 0x34, (inherit);
     background_color: (f32, f32, f32, f32), Absolute((-1., -1., -1., -1.)), 0x35, ();
+    box_shadow: Vec<Shadow>, Absolute(Vec::<Shadow>::new()), 0x36, (layout_dirty);
+    text_shadow: Vec<Shadow>, Absolute(Vec::<Shadow>::new()), 0x37, (layout_dirty, inherit);
+    background_image: BackgroundImage, Absolute(BackgroundImage::None), 0x38, ();
+    background_size: BackgroundSize, Absolute(BackgroundSize::Explicit(None, None)), 0x39, ();
+    background_position: (LengthPercentage, LengthPercentage), Absolute((LengthPercentage::Ratio(0.), LengthPercentage::Ratio(0.))), 0x3a, ();
+    background_repeat: BackgroundRepeatType, Absolute(BackgroundRepeatType::Repeat), 0x3b, ();
+    background_origin: BackgroundBoxType, Absolute(BackgroundBoxType::PaddingBox), 0x3c, ();
+    background_clip: BackgroundBoxType, Absolute(BackgroundBoxType::BorderBox), 0x3d, ();
 
     margin_left: f64, Absolute(0.), 0x40, (layout_dirty, horizontal_relative);
     margin_right: f64, Absolute(0.), 0x41, (layout_dirty, horizontal_relative);
@@ -76,6 +91,15 @@ element_style! {
     border_right_color: (f32, f32, f32, f32), Absolute((-1., -1., -1., -1.)), 0x59, ();
     border_top_color: (f32, f32, f32, f32), Absolute((-1., -1., -1., -1.)), 0x5a, ();
     border_bottom_color: (f32, f32, f32, f32), Absolute((-1., -1., -1., -1.)), 0x5b, ();
+    border_top_left_radius: f64, Absolute(0.), 0x5c, ();
+    border_top_right_radius: f64, Absolute(0.), 0x5d, ();
+    border_bottom_right_radius: f64, Absolute(0.), 0x5e, ();
+    border_bottom_left_radius: f64, Absolute(0.), 0x5f, ();
+    border_image_source: BackgroundImage, Absolute(BackgroundImage::None), 0x60, (layout_dirty);
+    border_image_slice: BorderImageSlice, Absolute(BorderImageSlice { offsets: [LengthPercentage::Ratio(1.); 4], fill: false }), 0x61, ();
+    border_image_width: [BorderImageLength; 4], Absolute([BorderImageLength::Number(1.); 4]), 0x62, ();
+    border_image_outset: [BorderImageLength; 4], Absolute([BorderImageLength::Number(0.); 4]), 0x63, (layout_dirty);
+    border_image_repeat: (BorderImageRepeatType, BorderImageRepeatType), Absolute((BorderImageRepeatType::Stretch, BorderImageRepeatType::Stretch)), 0x64, ();
 }
 
 impl ElementStyle {
@@ -132,16 +156,29 @@ impl ElementStyle {
     }
     pub(super) fn get_and_mark_class_dirty(&self, is_self_dirty: bool) -> bool {
         match is_self_dirty {
-            false => self.class_dirty.replace(ClassDirtyStatus::ChildDirty) == ClassDirtyStatus::ChildDirty,
+            false => match self.class_dirty.get() {
+                ClassDirtyStatus::NotDirty => {
+                    self.class_dirty.set(ClassDirtyStatus::ChildDirty);
+                    false
+                },
+                _ => true,
+            },
             true => self.class_dirty.replace(ClassDirtyStatus::SelfDirty) == ClassDirtyStatus::SelfDirty,
         }
     }
+    #[inline]
+    pub(super) fn is_class_dirty(&self) -> bool {
+        self.class_dirty.get() != ClassDirtyStatus::NotDirty
+    }
     pub(super) fn clear_class_dirty(&self) -> bool {
-        if self.class_dirty.replace(ClassDirtyStatus::NotDirty) != ClassDirtyStatus::SelfDirty {
-            return false;
+        match self.class_dirty.replace(ClassDirtyStatus::NotDirty) {
+            ClassDirtyStatus::NotDirty => false,
+            ClassDirtyStatus::ChildDirty => true,
+            ClassDirtyStatus::SelfDirty => {
+                self.reload_classes();
+                true
+            },
         }
-        self.reload_classes();
-        true
     }
 }
 
@@ -179,11 +216,26 @@ impl ElementStyle {
         self.parent_updated();
     }
     #[inline]
+    pub fn overflow(&mut self, val: OverflowType) {
+        self.overflow_x(val);
+        self.overflow_y(val);
+    }
+    #[inline]
+    pub fn border_radius(&mut self, val: f64) {
+        self.border_top_left_radius(val);
+        self.border_top_right_radius(val);
+        self.border_bottom_right_radius(val);
+        self.border_bottom_left_radius(val);
+    }
+    #[inline]
     pub fn transform_ref(&mut self) -> &Transform {
         self.transform.get_value_ref()
     }
     #[inline]
     pub fn transform_mut(&mut self) -> &mut Transform {
+        // the drawing bounds of the ancestors depend on the transform
+        self.element().mark_layout_dirty();
+        self.element().mark_paint_dirty();
         self.transform.get_value_mut()
     }
 }
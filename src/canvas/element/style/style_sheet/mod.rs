@@ -284,7 +284,20 @@ impl StyleSheetGroup {
 
 #[cfg(test)]
 mod test {
-    use super::{StyleSheet, StyleSheetGroup, StyleName};
+    use super::{StyleSheet, StyleSheetGroup, StyleName, ElementClass, StyleValue, OverflowType, Shadow};
+    use super::{BackgroundImage, ColorStop, GradientDirection, LengthPercentage, RadialGradientSize};
+    use super::{BackgroundSize, BackgroundRepeatType, BackgroundBoxType};
+    use super::{BorderImageSlice, BorderImageLength, BorderImageRepeatType};
+    use super::super::super::{Transform, Size, Point};
+
+    // parse an inline style and collect the values of type `T`, in the order of the rules
+    fn parse<T: 'static + Clone>(text: &str) -> Vec<T> {
+        let mut c = ElementClass::new();
+        StyleSheet::parse_inline_style(&mut c, text);
+        c._iter_rules().filter_map(|(_, v)| {
+            v.downcast_ref::<StyleValue<T>>().map(|x| x.get().1)
+        }).collect()
+    }
 
     #[test]
     fn query_declarations() {
@@ -308,4 +321,115 @@ mod test {
         assert_eq!(classes[1]._iter_rules().next().unwrap().0, StyleName::display);
         assert_eq!(classes[2]._iter_rules().next().unwrap().0, StyleName::left);
     }
+
+    #[test]
+    fn overflow_shorthand() {
+        let parse = parse::<OverflowType>;
+        assert_eq!(parse("overflow: hidden"), vec![OverflowType::Hidden, OverflowType::Hidden]);
+        assert_eq!(parse("overflow: clip visible"), vec![OverflowType::Clip, OverflowType::Visible]);
+        assert_eq!(parse("overflow: hidden auto"), vec![OverflowType::Hidden, OverflowType::Auto]);
+        let mut c = ElementClass::new();
+        StyleSheet::parse_inline_style(&mut c, "overflow: hidden");
+        assert_eq!(c._iter_rules().map(|(name, _)| name.clone()).collect::<Vec<_>>(), vec![StyleName::overflow_x, StyleName::overflow_y]);
+    }
+
+    #[test]
+    fn border_radius_shorthand() {
+        let parse = parse::<f64>;
+        assert_eq!(parse("border-radius: 4px"), vec![4., 4., 4., 4.]);
+        assert_eq!(parse("border-radius: 1px 2px"), vec![1., 2., 1., 2.]);
+        assert_eq!(parse("border-radius: 1px 2px 3px"), vec![1., 2., 3., 2.]);
+        assert_eq!(parse("border-radius: 1px 2px 3px 4px"), vec![1., 2., 3., 4.]);
+    }
+
+    #[test]
+    fn shadow_list() {
+        let parse = parse::<Vec<Shadow>>;
+        let red = (1., 0., 0., 1.);
+        assert_eq!(parse("box-shadow: none"), vec![vec![]]);
+        assert_eq!(parse("box-shadow: 1px 2px red"), vec![vec![Shadow::new(1., 2., 0., red)]]);
+        assert_eq!(parse("box-shadow: inset 1px 2px 3px 4px, red 0 0 5px"), vec![vec![
+            Shadow { spread: 4., inset: true, ..Shadow::new(1., 2., 3., (-1., -1., -1., -1.)) },
+            Shadow::new(0., 0., 5., red),
+        ]]);
+        assert_eq!(parse("box-shadow: 1px 2px -3px").len(), 0);
+        assert_eq!(parse("text-shadow: 1px 1px 2px red"), vec![vec![Shadow::new(1., 1., 2., red)]]);
+        assert_eq!(parse("text-shadow: 1px 1px 2px 3px").len(), 0);
+    }
+
+    #[test]
+    fn gradient() {
+        let parse = parse::<BackgroundImage>;
+        let stop = |color, position| ColorStop { color, position };
+        let (red, blue) = ((1., 0., 0., 1.), (0., 0., 1., 1.));
+        assert_eq!(parse("background-image: linear-gradient(red, blue)"), vec![BackgroundImage::LinearGradient {
+            direction: GradientDirection::Angle(180f64.to_radians()),
+            stops: vec![stop(red, None), stop(blue, None)],
+        }]);
+        assert_eq!(parse("background: linear-gradient(0.25turn, red 10px, blue 50%)"), vec![BackgroundImage::LinearGradient {
+            direction: GradientDirection::Angle(90f64.to_radians()),
+            stops: vec![stop(red, Some(LengthPercentage::Px(10.))), stop(blue, Some(LengthPercentage::Ratio(0.5)))],
+        }]);
+        assert_eq!(parse("background-image: linear-gradient(to top left, red, blue)"), vec![BackgroundImage::LinearGradient {
+            direction: GradientDirection::ToCorner { right: false, bottom: false },
+            stops: vec![stop(red, None), stop(blue, None)],
+        }]);
+        assert_eq!(parse("background-image: radial-gradient(circle closest-side at top 20px, red, blue)"), vec![BackgroundImage::RadialGradient {
+            circle: true,
+            size: RadialGradientSize::ClosestSide,
+            center: (LengthPercentage::Px(20.), LengthPercentage::Ratio(0.)),
+            stops: vec![stop(red, None), stop(blue, None)],
+        }]);
+        assert_eq!(parse("background-image: radial-gradient(10px 25%, red, blue)"), vec![BackgroundImage::RadialGradient {
+            circle: false,
+            size: RadialGradientSize::Explicit(LengthPercentage::Px(10.), LengthPercentage::Ratio(0.25)),
+            center: (LengthPercentage::Ratio(0.5), LengthPercentage::Ratio(0.5)),
+            stops: vec![stop(red, None), stop(blue, None)],
+        }]);
+        assert_eq!(parse("background-image: none"), vec![BackgroundImage::None]);
+        assert_eq!(parse("background-image: linear-gradient(red)").len(), 0);
+        assert_eq!(parse("background-image: radial-gradient(circle 10%, red, blue)").len(), 0);
+    }
+
+    #[test]
+    fn background() {
+        assert_eq!(parse::<BackgroundImage>("background-image: url(a.png)"), vec![BackgroundImage::Url(String::from("a.png"))]);
+        assert_eq!(parse::<BackgroundImage>("background-image: url(\"b c.png\")"), vec![BackgroundImage::Url(String::from("b c.png"))]);
+        assert_eq!(parse::<BackgroundSize>("background-size: cover"), vec![BackgroundSize::Cover]);
+        assert_eq!(parse::<BackgroundSize>("background-size: 10px"), vec![BackgroundSize::Explicit(Some(LengthPercentage::Px(10.)), None)]);
+        assert_eq!(parse::<BackgroundSize>("background-size: auto 50%"), vec![BackgroundSize::Explicit(None, Some(LengthPercentage::Ratio(0.5)))]);
+        assert_eq!(parse::<(LengthPercentage, LengthPercentage)>("background-position: bottom 10px"), vec![(LengthPercentage::Px(10.), LengthPercentage::Ratio(1.))]);
+        assert_eq!(parse::<BackgroundRepeatType>("background-repeat: repeat-y"), vec![BackgroundRepeatType::RepeatY]);
+        assert_eq!(parse::<BackgroundBoxType>("background-clip: content-box"), vec![BackgroundBoxType::ContentBox]);
+        assert_eq!(parse::<BackgroundRepeatType>("background: red url(a.png) no-repeat"), vec![BackgroundRepeatType::NoRepeat]);
+        assert_eq!(parse::<BackgroundImage>("background: url(a.png) no-repeat"), vec![BackgroundImage::Url(String::from("a.png"))]);
+    }
+
+    #[test]
+    fn transform() {
+        assert_eq!(parse::<Transform>("transform: none"), vec![Transform::new()]);
+        assert_eq!(parse::<Transform>("transform: translate(10px, 20px) scale(2)"), vec![Transform::new().translate(Size::new(10., 20.)).scale(2., 2.).clone()]);
+        assert_eq!(parse::<Transform>("transform: matrix(1, 2, 3, 4, 5, 6)"), vec![Transform::new().matrix(1., 2., 3., 4., 5., 6.).clone()]);
+        assert_eq!(parse::<Transform>("transform: rotateY(1rad) translateZ(10px)"), vec![Transform::new().rotate_y(1.).translate_z(10.).clone()]);
+        assert_eq!(parse::<Transform>("transform: perspective(100px) scale3d(1, 2, 3)"), vec![Transform::new().perspective(100.).scale(1., 2.).scale_z(3.).clone()]);
+        let p = parse::<Transform>("transform: translateX(10px) rotate(90deg)")[0].apply_to_point(Point::new(1., 0.));
+        assert!((p.left() - 10.).abs() < 1e-9 && (p.top() - 1.).abs() < 1e-9);
+        assert_eq!(parse::<(LengthPercentage, LengthPercentage)>("transform-origin: left bottom"), vec![(LengthPercentage::Ratio(0.), LengthPercentage::Ratio(1.))]);
+        let mut c = ElementClass::new();
+        StyleSheet::parse_inline_style(&mut c, "perspective: 200px; transform-style: preserve-3d; backface-visibility: hidden");
+        assert_eq!(c._iter_rules().map(|(name, _)| name.clone()).collect::<Vec<_>>(), vec![StyleName::perspective, StyleName::transform_style, StyleName::backface_visibility]);
+    }
+
+    #[test]
+    fn border_image() {
+        let px = |x| LengthPercentage::Px(x);
+        assert_eq!(parse::<BorderImageSlice>("border-image-slice: 10 25% fill"), vec![BorderImageSlice { offsets: [px(10.), LengthPercentage::Ratio(0.25), px(10.), LengthPercentage::Ratio(0.25)], fill: true }]);
+        assert_eq!(parse::<BorderImageSlice>("border-image-slice: -1").len(), 0);
+        assert_eq!(parse::<[BorderImageLength; 4]>("border-image-width: 2 auto 5px"), vec![[BorderImageLength::Number(2.), BorderImageLength::Auto, BorderImageLength::Length(px(5.)), BorderImageLength::Auto]]);
+        assert_eq!(parse::<[BorderImageLength; 4]>("border-image-outset: 10%").len(), 0);
+        assert_eq!(parse::<(BorderImageRepeatType, BorderImageRepeatType)>("border-image-repeat: round"), vec![(BorderImageRepeatType::Round, BorderImageRepeatType::Round)]);
+        assert_eq!(parse::<(BorderImageRepeatType, BorderImageRepeatType)>("border-image: url(a.png) 30 / 10px / 2 repeat space"), vec![(BorderImageRepeatType::Repeat, BorderImageRepeatType::Space)]);
+        assert_eq!(parse::<[BorderImageLength; 4]>("border-image: url(a.png) 30 / 10px / 2 repeat"), vec![[BorderImageLength::Length(px(10.)); 4], [BorderImageLength::Number(2.); 4]]);
+        assert_eq!(parse::<BackgroundImage>("border-image: url(a.png) 30 round"), vec![BackgroundImage::Url(String::from("a.png"))]);
+    }
 }
@@ -4,6 +4,7 @@ use std::borrow::Cow;
 use std::any::Any;
 use glayout_element_style_macro::*;
 use super::*;
+use super::super::super::Size;
 
 type ValueParsingResult<'a, T> = Result<Box<StyleValue<T>>, ParseError<'a, ()>>;
 
@@ -29,6 +30,33 @@ style_value_syntax! {
     "bottom": bottom(LengthF64);
     "width": width(LengthF64);
     "height": height(LengthF64);
+    "transform": transform(TransformValue);
+    "transform-origin": transform_origin(PositionPairValue);
+    "transform-style": transform_style(Enum {
+        "flat" => TransformStyleType::Flat,
+        "preserve-3d" => TransformStyleType::Preserve3d,
+    });
+    "perspective": perspective(PerspectiveValue);
+    "perspective-origin": perspective_origin(PositionPairValue);
+    "backface-visibility": backface_visibility(Enum {
+        "visible" => BackfaceVisibilityType::Visible,
+        "hidden" => BackfaceVisibilityType::Hidden,
+    });
+    "overflow-x": overflow_x(Enum {
+        "visible" => OverflowType::Visible,
+        "hidden" => OverflowType::Hidden,
+        "clip" => OverflowType::Clip,
+        "scroll" => OverflowType::Scroll,
+        "auto" => OverflowType::Auto,
+    });
+    "overflow-y": overflow_y(Enum {
+        "visible" => OverflowType::Visible,
+        "hidden" => OverflowType::Hidden,
+        "clip" => OverflowType::Clip,
+        "scroll" => OverflowType::Scroll,
+        "auto" => OverflowType::Auto,
+    });
+    "overflow": [overflow_x overflow_y] | both;
 
     "font-family": font_family(FontFamily);
     "font-size": font_size(LengthF32);
@@ -42,7 +70,28 @@ style_value_syntax! {
 
     "color": color(Color);
     "background-color": background_color(Color);
-    "background": [background_color];
+    "box-shadow": box_shadow(BoxShadow);
+    "text-shadow": text_shadow(TextShadow);
+    "background-image": background_image(BackgroundImage);
+    "background-size": background_size(BackgroundSize);
+    "background-position": background_position(PositionPairValue);
+    "background-repeat": background_repeat(Enum {
+        "repeat" => BackgroundRepeatType::Repeat,
+        "repeat-x" => BackgroundRepeatType::RepeatX,
+        "repeat-y" => BackgroundRepeatType::RepeatY,
+        "no-repeat" => BackgroundRepeatType::NoRepeat,
+    });
+    "background-origin": background_origin(Enum {
+        "border-box" => BackgroundBoxType::BorderBox,
+        "padding-box" => BackgroundBoxType::PaddingBox,
+        "content-box" => BackgroundBoxType::ContentBox,
+    });
+    "background-clip": background_clip(Enum {
+        "border-box" => BackgroundBoxType::BorderBox,
+        "padding-box" => BackgroundBoxType::PaddingBox,
+        "content-box" => BackgroundBoxType::ContentBox,
+    });
+    "background": [background_color background_image background_repeat | background_image background_repeat];
     "opacity": opacity(Number);
 
     "margin-left": margin_left(LengthF64);
@@ -65,24 +114,52 @@ style_value_syntax! {
     "border-left-style": border_left_style(Enum {
         "none" => BorderStyleType::None,
         "solid" => BorderStyleType::Solid,
+        "dashed" => BorderStyleType::Dashed,
+        "dotted" => BorderStyleType::Dotted,
+        "double" => BorderStyleType::Double,
+        "groove" => BorderStyleType::Groove,
+        "ridge" => BorderStyleType::Ridge,
+        "inset" => BorderStyleType::Inset,
+        "outset" => BorderStyleType::Outset,
     });
     "border-left-color": border_left_color(Color);
     "border-right-width": border_right_width(LengthF64);
     "border-right-style": border_right_style(Enum {
         "none" => BorderStyleType::None,
         "solid" => BorderStyleType::Solid,
+        "dashed" => BorderStyleType::Dashed,
+        "dotted" => BorderStyleType::Dotted,
+        "double" => BorderStyleType::Double,
+        "groove" => BorderStyleType::Groove,
+        "ridge" => BorderStyleType::Ridge,
+        "inset" => BorderStyleType::Inset,
+        "outset" => BorderStyleType::Outset,
     });
     "border-right-color": border_right_color(Color);
     "border-top-width": border_top_width(LengthF64);
     "border-top-style": border_top_style(Enum {
         "none" => BorderStyleType::None,
         "solid" => BorderStyleType::Solid,
+        "dashed" => BorderStyleType::Dashed,
+        "dotted" => BorderStyleType::Dotted,
+        "double" => BorderStyleType::Double,
+        "groove" => BorderStyleType::Groove,
+        "ridge" => BorderStyleType::Ridge,
+        "inset" => BorderStyleType::Inset,
+        "outset" => BorderStyleType::Outset,
     });
     "border-top-color": border_top_color(Color);
     "border-bottom-width": border_bottom_width(LengthF64);
     "border-bottom-style": border_bottom_style(Enum {
         "none" => BorderStyleType::None,
         "solid" => BorderStyleType::Solid,
+        "dashed" => BorderStyleType::Dashed,
+        "dotted" => BorderStyleType::Dotted,
+        "double" => BorderStyleType::Double,
+        "groove" => BorderStyleType::Groove,
+        "ridge" => BorderStyleType::Ridge,
+        "inset" => BorderStyleType::Inset,
+        "outset" => BorderStyleType::Outset,
     });
     "border-bottom-color": border_bottom_color(Color);
     "border-left": [border_left_width border_left_style border_left_color];
@@ -98,6 +175,22 @@ style_value_syntax! {
         border_bottom_width border_bottom_style border_bottom_color,
         border_left_width border_left_style border_left_color
     ] | border_around;
+    "border-top-left-radius": border_top_left_radius(LengthF64);
+    "border-top-right-radius": border_top_right_radius(LengthF64);
+    "border-bottom-right-radius": border_bottom_right_radius(LengthF64);
+    "border-bottom-left-radius": border_bottom_left_radius(LengthF64);
+    "border-radius": [border_top_left_radius border_top_right_radius border_bottom_right_radius border_bottom_left_radius] | around;
+    "border-image-source": border_image_source(BackgroundImage);
+    "border-image-slice": border_image_slice(BorderImageSlice);
+    "border-image-width": border_image_width(BorderImageWidth);
+    "border-image-outset": border_image_outset(BorderImageOutset);
+    "border-image-repeat": border_image_repeat(BorderImageRepeat);
+    "border-image": [
+        border_image_source border_image_slice "/" border_image_width "/" border_image_outset border_image_repeat |
+        border_image_source border_image_slice "/" border_image_width border_image_repeat |
+        border_image_source border_image_slice border_image_repeat |
+        border_image_source border_image_repeat
+    ];
 
     "flex-basis": flex_basis(LengthF64);
     "flex-grow": flex_grow(Number);
@@ -246,6 +339,466 @@ mod ParseBase {
         }
     }
 
+    // a length in px, the relative ones are not allowed
+    fn AbsoluteLength<'a>(parser: &mut Parser<'a, '_>) -> Result<f64, ParseError<'a, ()>> {
+        match Length::<f64>(parser)?.get() {
+            (StyleValueReferrer::Absolute, v) => Ok(v),
+            _ => Err(parser.new_custom_error(())),
+        }
+    }
+
+    fn ShadowList<'a>(parser: &mut Parser<'a, '_>, is_box_shadow: bool) -> ValueParsingResult<'a, Vec<Shadow>> {
+        if parser.r#try(|parser| parser.expect_ident_matching("none")).is_ok() {
+            return Ok(Box::new(absolute(vec![])));
+        }
+        let ret = parser.parse_comma_separated(|parser| {
+            let mut lengths = vec![];
+            let mut color = (-1., -1., -1., -1.);
+            let mut has_color = false;
+            let mut inset = false;
+            while !parser.is_exhausted() {
+                if is_box_shadow && !inset && parser.r#try(|parser| parser.expect_ident_matching("inset")).is_ok() {
+                    inset = true;
+                    continue;
+                }
+                if !has_color {
+                    match parser.r#try(|parser| Color(parser)) {
+                        Ok(c) => {
+                            color = c.get().1;
+                            has_color = true;
+                            continue;
+                        },
+                        Err(_) => { }
+                    }
+                }
+                lengths.push(AbsoluteLength(parser)?);
+            }
+            let max_lengths = if is_box_shadow { 4 } else { 3 };
+            if lengths.len() < 2 || lengths.len() > max_lengths || lengths.get(2).map(|x| *x < 0.).unwrap_or(false) {
+                return Err(parser.new_custom_error(()));
+            }
+            Ok(Shadow {
+                offset_x: lengths[0],
+                offset_y: lengths[1],
+                blur: lengths.get(2).cloned().unwrap_or(0.),
+                spread: lengths.get(3).cloned().unwrap_or(0.),
+                color,
+                inset,
+            })
+        })?;
+        Ok(Box::new(absolute(ret)))
+    }
+    pub(super) fn BoxShadow<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, Vec<Shadow>> {
+        ShadowList(parser, true)
+    }
+    pub(super) fn TextShadow<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, Vec<Shadow>> {
+        ShadowList(parser, false)
+    }
+
+    // a length in px or a percentage
+    fn LengthPercentageValue<'a>(parser: &mut Parser<'a, '_>) -> Result<LengthPercentage, ParseError<'a, ()>> {
+        match parser.r#try(|parser| {
+            match parser.next()?.clone() {
+                Token::Percentage {unit_value, has_sign: _, int_value: _} => Ok(unit_value as f64),
+                _ => Err(parser.new_custom_error::<_, ()>(())),
+            }
+        }) {
+            Ok(x) => Ok(LengthPercentage::Ratio(x)),
+            Err(_) => Ok(LengthPercentage::Px(AbsoluteLength(parser)?)),
+        }
+    }
+
+    // an angle in radians
+    fn Angle<'a>(parser: &mut Parser<'a, '_>) -> Result<f64, ParseError<'a, ()>> {
+        let token = parser.next()?.clone();
+        match token {
+            Token::Dimension {value, unit, has_sign: _, int_value: _} => {
+                let value = value as f64;
+                match unit.as_ref() {
+                    "deg" => Ok(value.to_radians()),
+                    "rad" => Ok(value),
+                    "grad" => Ok(value * std::f64::consts::PI / 200.),
+                    "turn" => Ok(value * std::f64::consts::PI * 2.),
+                    _ => Err(parser.new_custom_error(())),
+                }
+            },
+            Token::Number {value, has_sign: _, int_value: _} if value == 0. => Ok(0.),
+            _ => Err(parser.new_custom_error(())),
+        }
+    }
+
+    // `to <side-or-corner>` or an angle
+    fn GradientDirectionValue<'a>(parser: &mut Parser<'a, '_>) -> Result<GradientDirection, ParseError<'a, ()>> {
+        if parser.r#try(|parser| parser.expect_ident_matching("to")).is_err() {
+            return Ok(GradientDirection::Angle(Angle(parser)?));
+        }
+        let mut horizontal = None;
+        let mut vertical = None;
+        loop {
+            let ident = match parser.r#try(|parser| parser.expect_ident().map(|x| x.clone())) {
+                Ok(x) => x,
+                Err(_) => break,
+            };
+            match ident.as_ref() {
+                "left" if horizontal.is_none() => horizontal = Some(false),
+                "right" if horizontal.is_none() => horizontal = Some(true),
+                "top" if vertical.is_none() => vertical = Some(false),
+                "bottom" if vertical.is_none() => vertical = Some(true),
+                _ => return Err(parser.new_custom_error(())),
+            }
+        }
+        match (horizontal, vertical) {
+            (None, None) => Err(parser.new_custom_error(())),
+            (Some(right), None) => Ok(GradientDirection::Angle(if right { 90f64.to_radians() } else { 270f64.to_radians() })),
+            (None, Some(bottom)) => Ok(GradientDirection::Angle(if bottom { 180f64.to_radians() } else { 0. })),
+            (Some(right), Some(bottom)) => Ok(GradientDirection::ToCorner { right, bottom }),
+        }
+    }
+
+    // a component of a position, with whether it is a vertical keyword (`None` if it is not a keyword of either axis)
+    fn PositionComponent<'a>(parser: &mut Parser<'a, '_>) -> Result<(LengthPercentage, Option<bool>), ParseError<'a, ()>> {
+        match parser.r#try(|parser| parser.expect_ident().map(|x| x.clone())) {
+            Ok(ident) => {
+                match ident.as_ref() {
+                    "left" => Ok((LengthPercentage::Ratio(0.), Some(false))),
+                    "right" => Ok((LengthPercentage::Ratio(1.), Some(false))),
+                    "top" => Ok((LengthPercentage::Ratio(0.), Some(true))),
+                    "bottom" => Ok((LengthPercentage::Ratio(1.), Some(true))),
+                    "center" => Ok((LengthPercentage::Ratio(0.5), None)),
+                    _ => Err(parser.new_custom_error(())),
+                }
+            },
+            Err(_) => LengthPercentageValue(parser).map(|x| (x, None)),
+        }
+    }
+
+    // one or two position components, the omitted one is `center`
+    fn PositionPair<'a>(parser: &mut Parser<'a, '_>) -> Result<(LengthPercentage, LengthPercentage), ParseError<'a, ()>> {
+        let first = PositionComponent(parser)?;
+        let center = LengthPercentage::Ratio(0.5);
+        let second = match parser.r#try(|parser| PositionComponent(parser)) {
+            Ok(x) => x,
+            Err(_) => {
+                return Ok(if first.1 == Some(true) { (center, first.0) } else { (first.0, center) });
+            },
+        };
+        if first.1 == Some(true) || second.1 == Some(false) {
+            if first.1 == Some(true) && second.1 == Some(true) || first.1 == Some(false) && second.1 == Some(false) {
+                return Err(parser.new_custom_error(()));
+            }
+            Ok((second.0, first.0))
+        } else {
+            Ok((first.0, second.0))
+        }
+    }
+
+    // the color stops after the leading part of a gradient
+    fn ColorStopList<'a>(parser: &mut Parser<'a, '_>) -> Result<Vec<ColorStop>, ParseError<'a, ()>> {
+        let stops = parser.parse_comma_separated(|parser| {
+            let color = Color(parser)?.get().1;
+            let position = if parser.is_exhausted() { None } else { Some(LengthPercentageValue(parser)?) };
+            Ok(ColorStop { color, position })
+        })?;
+        if stops.len() < 2 {
+            return Err(parser.new_custom_error(()));
+        }
+        Ok(stops)
+    }
+
+    fn LinearGradient<'a>(parser: &mut Parser<'a, '_>) -> Result<BackgroundImage, ParseError<'a, ()>> {
+        let direction = match parser.r#try(|parser| GradientDirectionValue(parser)) {
+            Ok(x) => {
+                parser.expect_comma()?;
+                x
+            },
+            Err(_) => GradientDirection::Angle(std::f64::consts::PI),
+        };
+        let stops = ColorStopList(parser)?;
+        Ok(BackgroundImage::LinearGradient { direction, stops })
+    }
+
+    fn RadialGradient<'a>(parser: &mut Parser<'a, '_>) -> Result<BackgroundImage, ParseError<'a, ()>> {
+        let mut circle = None;
+        let mut size = None;
+        let mut center = (LengthPercentage::Ratio(0.5), LengthPercentage::Ratio(0.5));
+        let mut has_leading = false;
+        loop {
+            if circle.is_none() {
+                match parser.r#try(|parser| parser.expect_ident_matching("circle")) {
+                    Ok(_) => { circle = Some(true); has_leading = true; continue },
+                    Err(_) => { },
+                }
+                match parser.r#try(|parser| parser.expect_ident_matching("ellipse")) {
+                    Ok(_) => { circle = Some(false); has_leading = true; continue },
+                    Err(_) => { },
+                }
+            }
+            if size.is_none() {
+                let keyword = parser.r#try(|parser| {
+                    let ident = parser.expect_ident()?.clone();
+                    match ident.as_ref() {
+                        "closest-side" => Ok(RadialGradientSize::ClosestSide),
+                        "closest-corner" => Ok(RadialGradientSize::ClosestCorner),
+                        "farthest-side" => Ok(RadialGradientSize::FarthestSide),
+                        "farthest-corner" => Ok(RadialGradientSize::FarthestCorner),
+                        _ => Err(parser.new_custom_error::<_, ()>(())),
+                    }
+                });
+                match keyword {
+                    Ok(x) => { size = Some(x); has_leading = true; continue },
+                    Err(_) => { },
+                }
+                match parser.r#try(|parser| LengthPercentageValue(parser)) {
+                    Ok(a) => {
+                        let b = parser.r#try(|parser| LengthPercentageValue(parser)).ok();
+                        size = Some(RadialGradientSize::Explicit(a, b.unwrap_or(a)));
+                        // a single length is the radius of a circle
+                        if b.is_none() && circle.is_none() {
+                            circle = Some(true);
+                        }
+                        has_leading = true;
+                        continue;
+                    },
+                    Err(_) => { },
+                }
+            }
+            break;
+        }
+        if parser.r#try(|parser| parser.expect_ident_matching("at")).is_ok() {
+            center = PositionPair(parser)?;
+            has_leading = true;
+        }
+        if has_leading {
+            parser.expect_comma()?;
+        }
+        let size = size.unwrap_or(RadialGradientSize::FarthestCorner);
+        let circle = circle.unwrap_or(false);
+        match size {
+            // a circle cannot be sized with percentages or with two lengths
+            RadialGradientSize::Explicit(a, b) if circle => {
+                match a {
+                    LengthPercentage::Px(_) if a == b => { },
+                    _ => return Err(parser.new_custom_error(())),
+                }
+            },
+            _ => { },
+        }
+        let stops = ColorStopList(parser)?;
+        Ok(BackgroundImage::RadialGradient { circle, size, center, stops })
+    }
+
+    pub(super) fn BackgroundImage<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, BackgroundImage> {
+        if parser.r#try(|parser| parser.expect_ident_matching("none")).is_ok() {
+            return Ok(Box::new(absolute(BackgroundImage::None)));
+        }
+        match parser.r#try(|parser| parser.expect_url().map(|x| x.clone())) {
+            Ok(url) => return Ok(Box::new(absolute(BackgroundImage::Url(url.as_ref().to_string())))),
+            Err(_) => { },
+        }
+        let name = parser.expect_function()?.clone();
+        let image = match name.as_ref() {
+            "linear-gradient" => parser.parse_nested_block(|parser| LinearGradient(parser))?,
+            "radial-gradient" => parser.parse_nested_block(|parser| RadialGradient(parser))?,
+            _ => return Err(parser.new_custom_error(())),
+        };
+        Ok(Box::new(absolute(image)))
+    }
+
+    // a length of `background-size`, or `None` for `auto`
+    fn BackgroundSizeLength<'a>(parser: &mut Parser<'a, '_>) -> Result<Option<LengthPercentage>, ParseError<'a, ()>> {
+        if parser.r#try(|parser| parser.expect_ident_matching("auto")).is_ok() {
+            return Ok(None);
+        }
+        LengthPercentageValue(parser).map(|x| Some(x))
+    }
+
+    pub(super) fn BackgroundSize<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, BackgroundSize> {
+        if parser.r#try(|parser| parser.expect_ident_matching("cover")).is_ok() {
+            return Ok(Box::new(absolute(BackgroundSize::Cover)));
+        }
+        if parser.r#try(|parser| parser.expect_ident_matching("contain")).is_ok() {
+            return Ok(Box::new(absolute(BackgroundSize::Contain)));
+        }
+        let width = BackgroundSizeLength(parser)?;
+        let height = parser.r#try(|parser| BackgroundSizeLength(parser)).unwrap_or(None);
+        Ok(Box::new(absolute(BackgroundSize::Explicit(width, height))))
+    }
+
+    // the values of the four sides, in the order of top, right, bottom, left, and the omitted ones are copied from the opposite sides
+    fn FourSides<'a, T: Copy>(parser: &mut Parser<'a, '_>, f: fn(&mut Parser<'a, '_>) -> Result<T, ParseError<'a, ()>>) -> Result<[T; 4], ParseError<'a, ()>> {
+        let mut v = vec![f(parser)?];
+        while v.len() < 4 {
+            match parser.r#try(|parser| f(parser)) {
+                Ok(x) => v.push(x),
+                Err(_) => break,
+            }
+        }
+        let right = *v.get(1).unwrap_or(&v[0]);
+        Ok([v[0], right, *v.get(2).unwrap_or(&v[0]), *v.get(3).unwrap_or(&right)])
+    }
+
+    pub(super) fn BorderImageSlice<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, BorderImageSlice> {
+        let fill_first = parser.r#try(|parser| parser.expect_ident_matching("fill")).is_ok();
+        let offsets = FourSides(parser, |parser| {
+            match parser.next()?.clone() {
+                Token::Number {value, has_sign: _, int_value: _} if value >= 0. => Ok(LengthPercentage::Px(value as f64)),
+                Token::Percentage {unit_value, has_sign: _, int_value: _} if unit_value >= 0. => Ok(LengthPercentage::Ratio(unit_value as f64)),
+                _ => Err(parser.new_custom_error(())),
+            }
+        })?;
+        let fill = fill_first || parser.r#try(|parser| parser.expect_ident_matching("fill")).is_ok();
+        Ok(Box::new(absolute(BorderImageSlice { offsets, fill })))
+    }
+
+    pub(super) fn BorderImageWidth<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, [BorderImageLength; 4]> {
+        let widths = FourSides(parser, |parser| {
+            if parser.r#try(|parser| parser.expect_ident_matching("auto")).is_ok() {
+                return Ok(BorderImageLength::Auto);
+            }
+            let v = match parser.r#try(|parser| parser.expect_number()) {
+                Ok(x) => BorderImageLength::Number(x as f64),
+                Err(_) => BorderImageLength::Length(LengthPercentageValue(parser)?),
+            };
+            match v {
+                BorderImageLength::Number(x) | BorderImageLength::Length(LengthPercentage::Px(x)) | BorderImageLength::Length(LengthPercentage::Ratio(x)) if x < 0. => Err(parser.new_custom_error(())),
+                _ => Ok(v),
+            }
+        })?;
+        Ok(Box::new(absolute(widths)))
+    }
+
+    pub(super) fn BorderImageOutset<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, [BorderImageLength; 4]> {
+        let outsets = FourSides(parser, |parser| {
+            let v = match parser.r#try(|parser| parser.expect_number()) {
+                Ok(x) => BorderImageLength::Number(x as f64),
+                Err(_) => BorderImageLength::Length(LengthPercentage::Px(AbsoluteLength(parser)?)),
+            };
+            match v {
+                BorderImageLength::Number(x) | BorderImageLength::Length(LengthPercentage::Px(x)) if x < 0. => Err(parser.new_custom_error(())),
+                _ => Ok(v),
+            }
+        })?;
+        Ok(Box::new(absolute(outsets)))
+    }
+
+    pub(super) fn BorderImageRepeat<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, (BorderImageRepeatType, BorderImageRepeatType)> {
+        fn repeat<'a>(parser: &mut Parser<'a, '_>) -> Result<BorderImageRepeatType, ParseError<'a, ()>> {
+            let ident = parser.expect_ident()?.clone();
+            match ident.as_ref() {
+                "stretch" => Ok(BorderImageRepeatType::Stretch),
+                "repeat" => Ok(BorderImageRepeatType::Repeat),
+                "round" => Ok(BorderImageRepeatType::Round),
+                "space" => Ok(BorderImageRepeatType::Space),
+                _ => Err(parser.new_custom_error(())),
+            }
+        }
+        let x = repeat(parser)?;
+        let y = parser.r#try(|parser| repeat(parser)).unwrap_or(x);
+        Ok(Box::new(absolute((x, y))))
+    }
+
+    pub(super) fn PositionPairValue<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, (LengthPercentage, LengthPercentage)> {
+        Ok(Box::new(absolute(PositionPair(parser)?)))
+    }
+
+    // a transform function, applied to `transform`
+    fn TransformFunction<'a>(parser: &mut Parser<'a, '_>, transform: &mut Transform) -> Result<(), ParseError<'a, ()>> {
+        let name = parser.expect_function()?.clone();
+        parser.parse_nested_block(|parser| {
+            // the second argument after a comma
+            let optional = |parser: &mut Parser<'a, '_>, f: fn(&mut Parser<'a, '_>) -> Result<f64, ParseError<'a, ()>>| -> Result<Option<f64>, ParseError<'a, ()>> {
+                if parser.is_exhausted() { return Ok(None) }
+                parser.expect_comma()?;
+                f(parser).map(|x| Some(x))
+            };
+            let number = |parser: &mut Parser<'a, '_>| -> Result<f64, ParseError<'a, ()>> {
+                parser.expect_number().map(|x| x as f64).map_err(|e| e.into())
+            };
+            match name.as_ref() {
+                "translate" => {
+                    let x = AbsoluteLength(parser)?;
+                    let y = optional(parser, AbsoluteLength)?.unwrap_or(0.);
+                    transform.translate(Size::new(x, y));
+                },
+                "translateX" => { transform.translate(Size::new(AbsoluteLength(parser)?, 0.)); },
+                "translateY" => { transform.translate(Size::new(0., AbsoluteLength(parser)?)); },
+                "translateZ" => { transform.translate_z(AbsoluteLength(parser)?); },
+                "translate3d" => {
+                    let x = AbsoluteLength(parser)?;
+                    parser.expect_comma()?;
+                    let y = AbsoluteLength(parser)?;
+                    parser.expect_comma()?;
+                    let z = AbsoluteLength(parser)?;
+                    transform.translate(Size::new(x, y)).translate_z(z);
+                },
+                "scale" => {
+                    let x = number(parser)?;
+                    let y = optional(parser, number)?.unwrap_or(x);
+                    transform.scale(x, y);
+                },
+                "scaleX" => { transform.scale(number(parser)?, 1.); },
+                "scaleY" => { transform.scale(1., number(parser)?); },
+                "scaleZ" => { transform.scale_z(number(parser)?); },
+                "scale3d" => {
+                    let x = number(parser)?;
+                    parser.expect_comma()?;
+                    let y = number(parser)?;
+                    parser.expect_comma()?;
+                    let z = number(parser)?;
+                    transform.scale(x, y).scale_z(z);
+                },
+                "rotate" | "rotateZ" => { transform.rotate(Angle(parser)?); },
+                "rotateX" => { transform.rotate_x(Angle(parser)?); },
+                "rotateY" => { transform.rotate_y(Angle(parser)?); },
+                "skew" => {
+                    let x = Angle(parser)?;
+                    let y = optional(parser, Angle)?.unwrap_or(0.);
+                    transform.skew(x, y);
+                },
+                "skewX" => { transform.skew(Angle(parser)?, 0.); },
+                "skewY" => { transform.skew(0., Angle(parser)?); },
+                "matrix" => {
+                    let mut m = [0.; 6];
+                    for i in 0..6 {
+                        if i > 0 { parser.expect_comma()?; }
+                        m[i] = number(parser)?;
+                    }
+                    transform.matrix(m[0], m[1], m[2], m[3], m[4], m[5]);
+                },
+                "matrix3d" => {
+                    let mut m = [0.; 16];
+                    for i in 0..16 {
+                        if i > 0 { parser.expect_comma()?; }
+                        m[i] = number(parser)?;
+                    }
+                    transform.matrix3d(m);
+                },
+                "perspective" => { transform.perspective(AbsoluteLength(parser)?); },
+                _ => return Err(parser.new_custom_error(())),
+            }
+            Ok(())
+        })
+    }
+
+    pub(super) fn TransformValue<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, Transform> {
+        let mut transform = Transform::new();
+        if parser.r#try(|parser| parser.expect_ident_matching("none")).is_ok() {
+            return Ok(Box::new(absolute(transform)));
+        }
+        TransformFunction(parser, &mut transform)?;
+        while !parser.is_exhausted() {
+            TransformFunction(parser, &mut transform)?;
+        }
+        Ok(Box::new(absolute(transform)))
+    }
+
+    pub(super) fn PerspectiveValue<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, f64> {
+        if parser.r#try(|parser| parser.expect_ident_matching("none")).is_ok() {
+            return Ok(Box::new(absolute(0.)));
+        }
+        Ok(Box::new(absolute(AbsoluteLength(parser)?)))
+    }
+
     pub(super) fn FontFamily<'a>(parser: &mut Parser<'a, '_>) -> ValueParsingResult<'a, Cow<'static, str>> {
         {
             let mut ret = vec![];
@@ -331,6 +884,15 @@ mod ParsePost {
         arround!(class, top, right, bottom, left);
     }
 
+    pub(super) fn both<T: 'static + Clone + Send + Sized + Debug>(class: &mut ElementClass, x: (StyleName, Option<Box<T>>), y: (StyleName, Option<Box<T>>)) {
+        let (x_k, x_v) = x;
+        let (y_k, y_v) = y;
+        let x_v = x_v.unwrap();
+        let y_v = y_v.unwrap_or_else(|| x_v.clone());
+        parse_append_rule(class, x_k, x_v);
+        parse_append_rule(class, y_k, y_v);
+    }
+
     pub(super) fn border_around<T: 'static + Clone + Send + Sized + Debug>(class: &mut ElementClass,
         top_width: (StyleName, Option<Box<T>>), top_style: (StyleName, OptionValue<BorderStyleType>), top_color: (StyleName, OptionValue<(f32, f32, f32, f32)>), _: (StyleName, Option<Box<T>>),
         right_width: (StyleName, Option<Box<T>>), right_style: (StyleName, OptionValue<BorderStyleType>), right_color: (StyleName, OptionValue<(f32, f32, f32, f32)>), _: (StyleName, Option<Box<T>>),
@@ -35,6 +35,34 @@ pub enum BoxSizingType {
 pub enum BorderStyleType {
     None,
     Solid,
+    Dashed,
+    Dotted,
+    Double,
+    Groove,
+    Ridge,
+    Inset,
+    Outset,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransformStyleType {
+    Flat,
+    Preserve3d,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackfaceVisibilityType {
+    Visible,
+    Hidden,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OverflowType {
+    Visible,
+    Hidden,
+    Clip,
+    Scroll,
+    Auto,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -44,3 +72,142 @@ pub enum FlexDirectionType {
     Column,
     ColumnReverse,
 }
+
+/// A shadow of `box-shadow` or `text-shadow`.
+/// The color is the current text color if it is negative.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Shadow {
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub blur: f64,
+    pub spread: f64,
+    pub color: (f32, f32, f32, f32),
+    pub inset: bool,
+}
+
+impl Shadow {
+    pub fn new(offset_x: f64, offset_y: f64, blur: f64, color: (f32, f32, f32, f32)) -> Self {
+        Self {
+            offset_x,
+            offset_y,
+            blur,
+            spread: 0.,
+            color,
+            inset: false,
+        }
+    }
+    /// The extent of the blurred edge, outside of which the shadow is invisible.
+    #[inline]
+    pub fn blur_extent(&self) -> f64 {
+        self.blur.max(0.) * 1.5
+    }
+}
+
+/// A length in px or a percentage of a reference length (e.g. the gradient line or the box size).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LengthPercentage {
+    Px(f64),
+    Ratio(f64),
+}
+
+impl LengthPercentage {
+    #[inline]
+    pub fn resolve(&self, reference: f64) -> f64 {
+        match self {
+            LengthPercentage::Px(x) => *x,
+            LengthPercentage::Ratio(x) => *x * reference,
+        }
+    }
+}
+
+/// A color stop of a gradient. The position is interpolated from the neighbors if it is `None`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub color: (f32, f32, f32, f32),
+    pub position: Option<LengthPercentage>,
+}
+
+/// The direction of a linear gradient.
+/// The angle is in radians, clockwise from the direction to the top.
+/// The corner direction depends on the box size, so that the corners beside it have the same color.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GradientDirection {
+    Angle(f64),
+    ToCorner { right: bool, bottom: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RadialGradientSize {
+    ClosestSide,
+    ClosestCorner,
+    FarthestSide,
+    FarthestCorner,
+    Explicit(LengthPercentage, LengthPercentage),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BackgroundImage {
+    None,
+    LinearGradient {
+        direction: GradientDirection,
+        stops: Vec<ColorStop>,
+    },
+    /// The ending shape is a circle if `circle` is set, otherwise an ellipse.
+    RadialGradient {
+        circle: bool,
+        size: RadialGradientSize,
+        center: (LengthPercentage, LengthPercentage),
+        stops: Vec<ColorStop>,
+    },
+    /// An image loaded from the url.
+    Url(String),
+}
+
+/// The size of a background image. An `Explicit` length is `auto` if it is `None`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundSize {
+    Cover,
+    Contain,
+    Explicit(Option<LengthPercentage>, Option<LengthPercentage>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundRepeatType {
+    Repeat,
+    RepeatX,
+    RepeatY,
+    NoRepeat,
+}
+
+/// The box which a background is positioned in or clipped to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BackgroundBoxType {
+    BorderBox,
+    PaddingBox,
+    ContentBox,
+}
+
+/// The inward offsets of the border image slices from the image edges, in the order of top, right, bottom, left.
+/// A `Px` offset is in the image pixels. The middle part is drawn only if `fill` is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BorderImageSlice {
+    pub offsets: [LengthPercentage; 4],
+    pub fill: bool,
+}
+
+/// A side of `border-image-width` or `border-image-outset`.
+/// A `Number` is a multiple of the border width, and `Auto` is the size of the image slice.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BorderImageLength {
+    Number(f64),
+    Length(LengthPercentage),
+    Auto,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BorderImageRepeatType {
+    Stretch,
+    Repeat,
+    Round,
+    Space,
+}
@@ -12,7 +12,6 @@ const DEFAULT_DPR: f64 = 2.;
 pub struct Text {
     element: *mut Element,
     canvas_config: Rc<CanvasConfig>,
-    device_pixel_ratio: f64,
     text: String,
     characters: Box<[(Rc<Character>, f32, f32)]>,
     need_update: bool,
@@ -29,7 +28,6 @@ impl Text {
         Self {
             element: 0 as *mut Element,
             canvas_config: cfg.clone(),
-            device_pixel_ratio: if cfg.device_pixel_ratio == 1. { DEFAULT_DPR } else { cfg.device_pixel_ratio },
             text: String::from(""),
             characters: Box::new([]),
             need_update: false,
@@ -62,6 +60,7 @@ impl Text {
         self.need_update = true;
         self.text = String::from(s);
         self.element_mut().mark_layout_dirty();
+        self.element().mark_paint_dirty();
     }
     pub fn get_text(&mut self) -> String {
         self.text.clone()
@@ -81,8 +80,12 @@ impl Text {
             return;
         }
     }
+    fn device_pixel_ratio(&self) -> f64 {
+        let dpr = self.canvas_config.device_pixel_ratio.get();
+        if dpr == 1. { DEFAULT_DPR } else { dpr }
+    }
     fn measure_tex_font_size(&mut self, font_size: f32) -> i32 {
-        let min_font_size = (font_size * self.device_pixel_ratio as f32).ceil();
+        let min_font_size = (font_size * self.device_pixel_ratio() as f32).ceil();
         min_font_size as i32
     }
     fn update(&mut self, style: &ElementStyle) {
@@ -113,7 +116,6 @@ impl super::ElementContent for Text {
         Box::new(Self {
             element: 0 as *mut Element,
             canvas_config: cfg.clone(),
-            device_pixel_ratio: if cfg.device_pixel_ratio == 1. { DEFAULT_DPR } else { cfg.device_pixel_ratio },
             text: self.text.clone(),
             characters: self.characters.clone(),
             need_update: false,
@@ -175,9 +177,9 @@ impl super::ElementContent for Text {
             self.characters[i].1 += add_offset as f32;
         }
     }
-    fn draw(&mut self, transform: &Transform) {
+    fn draw(&mut self, viewport: Position, transform: &Transform) {
         // debug!("Attempted to draw Text at {:?}", transform.apply_to_position(&(0., 0., 0., 0.)));
-        // FIXME whole element edge cutting
+        let viewport: Bounds = viewport.into();
         for (character, left, top) in self.characters.iter() {
             if character.tex_id() == -1 {
                 /* empty */
@@ -185,13 +187,17 @@ impl super::ElementContent for Text {
                 let char_pos = character.position();
                 let width = char_pos.4 * self.size_ratio as f64;
                 let height = char_pos.5 * self.size_ratio as f64;
+                let position = Position::new(*left as f64, *top as f64, width, height);
+                if !transform.apply_to_bounds(&position.into()).intersects(&viewport) {
+                    continue;
+                }
                 let rm = self.canvas_config.resource_manager();
                 let mut rm = rm.borrow_mut();
                 rm.set_draw_state(DrawState::new().color(self.element().style().get_color()));
                 rm.request_draw(
                     character.tex_id(), true,
                     char_pos.0, char_pos.1, char_pos.2, char_pos.3,
-                    transform.apply_to_position(&Position::new(*left as f64, *top as f64, width, height)).into()
+                    transform.apply_to_quad(&position)
                 );
             }
         }
@@ -202,6 +208,10 @@ impl super::ElementContent for Text {
     }
     fn is_under_point(&self, point: Point, transform: Transform) -> bool {
         // FIXME use area detection
+        let point = match transform.unproject(point) {
+            None => return false,
+            Some(x) => x,
+        };
         for (character, left, top) in self.characters.iter() {
             if character.tex_id() == -1 {
                 /* empty */
@@ -209,7 +219,7 @@ impl super::ElementContent for Text {
                 let char_pos = character.position();
                 let width = char_pos.4 * self.size_ratio as f64;
                 let height = char_pos.5 * self.size_ratio as f64;
-                let pos = transform.apply_to_position(&Position::new(*left as f64, *top as f64, width, height));
+                let pos = Position::new(*left as f64, *top as f64, width, height);
                 // debug!("testing {:?} in text pos {:?}", (x, y), pos);
                 if !point.in_position(&pos) {
                     continue;
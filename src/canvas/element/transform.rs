@@ -1,10 +1,14 @@
 use super::{Position, Size, Point, Bounds};
 
+// the points with a smaller w are behind the viewer (or too close to be projected)
+const W_EPSILON: f64 = 1e-6;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
     x: (f64, f64, f64, f64),
     y: (f64, f64, f64, f64),
     z: (f64, f64, f64, f64),
+    w: (f64, f64, f64, f64),
 }
 
 impl Transform {
@@ -13,17 +17,20 @@ impl Transform {
             x: (1., 0., 0., 0.),
             y: (0., 1., 0., 0.),
             z: (0., 0., 1., 0.),
+            w: (0., 0., 0., 1.),
         }
     }
     pub fn reset(&mut self) -> &mut Self {
         self.x = (1., 0., 0., 0.);
         self.y = (0., 1., 0., 0.);
         self.z = (0., 0., 1., 0.);
+        self.w = (0., 0., 0., 1.);
         self
     }
     pub fn offset(&mut self, s: Size) -> &mut Self {
-        self.x.3 += s.width();
-        self.y.3 += s.height();
+        let (w, h) = (s.width(), s.height());
+        self.x = (self.x.0 + w * self.w.0, self.x.1 + w * self.w.1, self.x.2 + w * self.w.2, self.x.3 + w * self.w.3);
+        self.y = (self.y.0 + h * self.w.0, self.y.1 + h * self.w.1, self.y.2 + h * self.w.2, self.y.3 + h * self.w.3);
         self
     }
     #[inline]
@@ -35,57 +42,309 @@ impl Transform {
         self.y.1 = scale_y;
         self
     }
+    /// Scale in the local coordinates, i.e. before the existing transform is applied.
     pub fn scale(&mut self, scale_x: f64, scale_y: f64) -> &mut Self {
         self.x.0 *= scale_x;
+        self.y.0 *= scale_x;
+        self.z.0 *= scale_x;
+        self.w.0 *= scale_x;
+        self.x.1 *= scale_y;
         self.y.1 *= scale_y;
+        self.z.1 *= scale_y;
+        self.w.1 *= scale_y;
+        self
+    }
+    /// Scale the z axis in the local coordinates.
+    pub fn scale_z(&mut self, scale_z: f64) -> &mut Self {
+        self.x.2 *= scale_z;
+        self.y.2 *= scale_z;
+        self.z.2 *= scale_z;
+        self.w.2 *= scale_z;
+        self
+    }
+    /// Translate in the local coordinates, i.e. before the existing transform is applied.
+    /// It is different from `offset` which translates after the existing transform.
+    pub fn translate(&mut self, s: Size) -> &mut Self {
+        self.matrix(1., 0., 0., 1., s.width(), s.height())
+    }
+    /// Translate along the z axis in the local coordinates, and the positive direction is toward the viewer.
+    pub fn translate_z(&mut self, z: f64) -> &mut Self {
+        self.matrix3d([1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., z, 1.])
+    }
+    /// Rotate clockwise by `angle` in radians, in the local coordinates.
+    pub fn rotate(&mut self, angle: f64) -> &mut Self {
+        let (sin, cos) = angle.sin_cos();
+        self.matrix(cos, sin, -sin, cos, 0., 0.)
+    }
+    /// Rotate around the x axis by `angle` in radians, the same as the CSS `rotateX()`.
+    pub fn rotate_x(&mut self, angle: f64) -> &mut Self {
+        let (sin, cos) = angle.sin_cos();
+        self.matrix3d([1., 0., 0., 0., 0., cos, sin, 0., 0., -sin, cos, 0., 0., 0., 0., 1.])
+    }
+    /// Rotate around the y axis by `angle` in radians, the same as the CSS `rotateY()`.
+    pub fn rotate_y(&mut self, angle: f64) -> &mut Self {
+        let (sin, cos) = angle.sin_cos();
+        self.matrix3d([cos, 0., -sin, 0., 0., 1., 0., 0., sin, 0., cos, 0., 0., 0., 0., 1.])
+    }
+    /// Skew by the angles in radians along the x and y axes, in the local coordinates.
+    pub fn skew(&mut self, angle_x: f64, angle_y: f64) -> &mut Self {
+        self.matrix(1., angle_y.tan(), angle_x.tan(), 1., 0., 0.)
+    }
+    /// Apply the perspective projection, in which the viewer is at `distance` from the z = 0 plane.
+    /// A non-positive distance is ignored.
+    pub fn perspective(&mut self, distance: f64) -> &mut Self {
+        if !(distance > 0.) { return self }
+        self.matrix3d([1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., -1. / distance, 0., 0., 0., 1.])
+    }
+    /// Apply a 2D matrix in the local coordinates, the same as the CSS `matrix(a, b, c, d, e, f)`.
+    pub fn matrix(&mut self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) -> &mut Self {
+        self.matrix3d([a, b, 0., 0., c, d, 0., 0., 0., 0., 1., 0., e, f, 0., 1.])
+    }
+    /// Apply a 4x4 matrix in the local coordinates, in the column-major order the same as the CSS `matrix3d()`.
+    pub fn matrix3d(&mut self, m: [f64; 16]) -> &mut Self {
+        let t = Self {
+            x: (m[0], m[4], m[8], m[12]),
+            y: (m[1], m[5], m[9], m[13]),
+            z: (m[2], m[6], m[10], m[14]),
+            w: (m[3], m[7], m[11], m[15]),
+        };
+        *self = self.mul_clone(&t);
+        self
+    }
+    /// Project the result to the z = 0 plane, i.e. drop the z coordinates after transformed.
+    pub fn flatten(&mut self) -> &mut Self {
+        self.z = (0., 0., 0., 0.);
         self
     }
     #[inline]
     pub fn get_scale(&self) -> (f64, f64) {
         (self.x.0, self.y.1)
     }
-    pub fn mul_clone(&mut self, t: &Self) -> Self {
-        let new_x = (
-            self.x.0 * t.x.0 + self.x.1 * t.y.0 + self.x.2 * t.z.0,
-            self.x.0 * t.x.1 + self.x.1 * t.y.1 + self.x.2 * t.z.1,
-            self.x.0 * t.x.2 + self.x.1 * t.y.2 + self.x.2 * t.z.2,
-            self.x.0 * t.x.3 + self.x.1 * t.y.3 + self.x.2 * t.z.3 + self.x.3,
-        );
-        let new_y = (
-            self.y.0 * t.x.0 + self.y.1 * t.y.0 + self.y.2 * t.z.0,
-            self.y.0 * t.x.1 + self.y.1 * t.y.1 + self.y.2 * t.z.1,
-            self.y.0 * t.x.2 + self.y.1 * t.y.2 + self.y.2 * t.z.2,
-            self.y.0 * t.x.3 + self.y.1 * t.y.3 + self.y.2 * t.z.3 + self.y.3,
-        );
-        let new_z = (
-            self.z.0 * t.x.0 + self.z.1 * t.y.0 + self.z.2 * t.z.0,
-            self.z.0 * t.x.1 + self.z.1 * t.y.1 + self.z.2 * t.z.1,
-            self.z.0 * t.x.2 + self.z.1 * t.y.2 + self.z.2 * t.z.2,
-            self.z.0 * t.x.3 + self.z.1 * t.y.3 + self.z.2 * t.z.3 + self.z.3,
-        );
+    /// The average scale factor of the 2D part, i.e. the square root of the area scale.
+    #[inline]
+    pub fn get_area_scale(&self) -> f64 {
+        (self.x.0 * self.y.1 - self.x.1 * self.y.0).abs().sqrt() / self.w.3.abs()
+    }
+    #[inline]
+    fn rows(&self) -> [[f64; 4]; 4] {
+        [
+            [self.x.0, self.x.1, self.x.2, self.x.3],
+            [self.y.0, self.y.1, self.y.2, self.y.3],
+            [self.z.0, self.z.1, self.z.2, self.z.3],
+            [self.w.0, self.w.1, self.w.2, self.w.3],
+        ]
+    }
+    #[inline]
+    fn from_rows(m: [[f64; 4]; 4]) -> Self {
         Self {
-            x: new_x,
-            y: new_y,
-            z: new_z,
+            x: (m[0][0], m[0][1], m[0][2], m[0][3]),
+            y: (m[1][0], m[1][1], m[1][2], m[1][3]),
+            z: (m[2][0], m[2][1], m[2][2], m[2][3]),
+            w: (m[3][0], m[3][1], m[3][2], m[3][3]),
+        }
+    }
+    pub fn mul_clone(&mut self, t: &Self) -> Self {
+        let (a, b) = (self.rows(), t.rows());
+        let mut m = [[0.; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                m[i][j] = a[i][0] * b[0][j] + a[i][1] * b[1][j] + a[i][2] * b[2][j] + a[i][3] * b[3][j];
+            }
+        }
+        Self::from_rows(m)
+    }
+    /// The inverse transform, or `None` if it is not invertible, e.g. scaled to zero.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = self.rows();
+        // the cofactors of the 2x2 minors in the upper and the lower two rows
+        let s = [
+            m[0][0] * m[1][1] - m[1][0] * m[0][1],
+            m[0][0] * m[1][2] - m[1][0] * m[0][2],
+            m[0][0] * m[1][3] - m[1][0] * m[0][3],
+            m[0][1] * m[1][2] - m[1][1] * m[0][2],
+            m[0][1] * m[1][3] - m[1][1] * m[0][3],
+            m[0][2] * m[1][3] - m[1][2] * m[0][3],
+        ];
+        let c = [
+            m[2][0] * m[3][1] - m[3][0] * m[2][1],
+            m[2][0] * m[3][2] - m[3][0] * m[2][2],
+            m[2][0] * m[3][3] - m[3][0] * m[2][3],
+            m[2][1] * m[3][2] - m[3][1] * m[2][2],
+            m[2][1] * m[3][3] - m[3][1] * m[2][3],
+            m[2][2] * m[3][3] - m[3][2] * m[2][3],
+        ];
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if det == 0. || !det.is_finite() { return None }
+        let r = 1. / det;
+        Some(Self::from_rows([
+            [
+                (m[1][1] * c[5] - m[1][2] * c[4] + m[1][3] * c[3]) * r,
+                (-m[0][1] * c[5] + m[0][2] * c[4] - m[0][3] * c[3]) * r,
+                (m[3][1] * s[5] - m[3][2] * s[4] + m[3][3] * s[3]) * r,
+                (-m[2][1] * s[5] + m[2][2] * s[4] - m[2][3] * s[3]) * r,
+            ],
+            [
+                (-m[1][0] * c[5] + m[1][2] * c[2] - m[1][3] * c[1]) * r,
+                (m[0][0] * c[5] - m[0][2] * c[2] + m[0][3] * c[1]) * r,
+                (-m[3][0] * s[5] + m[3][2] * s[2] - m[3][3] * s[1]) * r,
+                (m[2][0] * s[5] - m[2][2] * s[2] + m[2][3] * s[1]) * r,
+            ],
+            [
+                (m[1][0] * c[4] - m[1][1] * c[2] + m[1][3] * c[0]) * r,
+                (-m[0][0] * c[4] + m[0][1] * c[2] - m[0][3] * c[0]) * r,
+                (m[3][0] * s[4] - m[3][1] * s[2] + m[3][3] * s[0]) * r,
+                (-m[2][0] * s[4] + m[2][1] * s[2] - m[2][3] * s[0]) * r,
+            ],
+            [
+                (-m[1][0] * c[3] + m[1][1] * c[1] - m[1][2] * c[0]) * r,
+                (m[0][0] * c[3] - m[0][1] * c[1] + m[0][2] * c[0]) * r,
+                (-m[3][0] * s[3] + m[3][1] * s[1] - m[3][2] * s[0]) * r,
+                (m[2][0] * s[3] - m[2][1] * s[1] + m[2][2] * s[0]) * r,
+            ],
+        ]))
+    }
+    /// Map a point back to the z = 0 plane of the local coordinates.
+    /// Returns `None` if the plane is degenerate, e.g. scaled to zero or viewed edge-on.
+    pub fn unproject(&self, point: Point) -> Option<Point> {
+        // only the x, y and w of the points in the plane matter, so the z is kept as is
+        let plane = Self {
+            x: (self.x.0, self.x.1, 0., self.x.3),
+            y: (self.y.0, self.y.1, 0., self.y.3),
+            z: (0., 0., 1., 0.),
+            w: (self.w.0, self.w.1, 0., self.w.3),
+        };
+        plane.inverse().map(|t| t.apply_to_point(point))
+    }
+    /// Whether the back face of the z = 0 plane is toward the viewer, i.e. the CSS `backface-visibility` is applied.
+    pub fn is_back_face(&self) -> bool {
+        match self.inverse() {
+            None => false,
+            Some(t) => t.z.2 < 0.,
         }
     }
     #[inline]
     pub fn apply_to_point(&self, pointer: Point) -> Point {
-        Point::new(
-            self.x.0 * pointer.left() + self.x.1 * pointer.top() + self.x.3,
-            self.y.0 * pointer.left() + self.y.1 * pointer.top() + self.y.3,
+        let (x, y, _) = self.apply_to_point_3d((pointer.left(), pointer.top(), 0.));
+        Point::new(x, y)
+    }
+    #[inline]
+    fn w_of(&self, (x, y, z): (f64, f64, f64)) -> f64 {
+        self.w.0 * x + self.w.1 * y + self.w.2 * z + self.w.3
+    }
+    /// Transform a 3D point, which is divided by the w after projected.
+    /// The points behind the viewer are clamped to the near plane, so the result is always finite for finite points.
+    #[inline]
+    pub fn apply_to_point_3d(&self, (x, y, z): (f64, f64, f64)) -> (f64, f64, f64) {
+        let w = self.w_of((x, y, z)).max(W_EPSILON);
+        (
+            (self.x.0 * x + self.x.1 * y + self.x.2 * z + self.x.3) / w,
+            (self.y.0 * x + self.y.1 * y + self.y.2 * z + self.y.3) / w,
+            (self.z.0 * x + self.z.1 * y + self.z.2 * z + self.z.3) / w,
         )
     }
+    /// Transform a position, which is only correct if the transform `is_axis_aligned`, otherwise use `apply_to_quad`.
     #[inline]
     pub fn apply_to_position(&self, pos: &Position) -> Position {
         let (x, y) = self.apply_to_point(Point::new(pos.left(), pos.top())).into();
         let (xw, yh) = self.apply_to_point(Point::new(pos.right(), pos.bottom())).into();
         Position::new(x, y, xw - x, yh - y)
     }
+    /// The bounds containing the transformed bounds.
     #[inline]
     pub fn apply_to_bounds(&self, pos: &Bounds) -> Bounds {
-        let (x, y) = self.apply_to_point(Point::new(pos.left(), pos.top())).into();
-        let (xw, yh) = self.apply_to_point(Point::new(pos.right(), pos.bottom())).into();
-        Bounds::new(x, y, xw, yh)
+        let quad = [
+            self.apply_to_point(Point::new(pos.left(), pos.top())),
+            self.apply_to_point(Point::new(pos.left(), pos.bottom())),
+            self.apply_to_point(Point::new(pos.right(), pos.bottom())),
+            self.apply_to_point(Point::new(pos.right(), pos.top())),
+        ];
+        let mut ret = Bounds::from((quad[0], quad[0]));
+        for p in quad.iter() {
+            ret.union(&Bounds::from((*p, *p)));
+        }
+        ret
+    }
+    /// The corners of the transformed position, in the order of left-top, left-bottom, right-bottom and right-top.
+    #[inline]
+    pub fn apply_to_quad(&self, pos: &Position) -> [Point; 4] {
+        [
+            self.apply_to_point(Point::new(pos.left(), pos.top())),
+            self.apply_to_point(Point::new(pos.left(), pos.bottom())),
+            self.apply_to_point(Point::new(pos.right(), pos.bottom())),
+            self.apply_to_point(Point::new(pos.right(), pos.top())),
+        ]
+    }
+    /// Whether any corner of the bounds is behind the viewer after the perspective projection, which cannot be drawn correctly.
+    #[inline]
+    pub fn is_behind_viewer(&self, pos: &Bounds) -> bool {
+        if self.w.0 == 0. && self.w.1 == 0. { return !(self.w.3 > W_EPSILON) }
+        [(pos.left(), pos.top()), (pos.left(), pos.bottom()), (pos.right(), pos.bottom()), (pos.right(), pos.top())].iter().any(|&(x, y)| {
+            !(self.w_of((x, y, 0.)) > W_EPSILON)
+        })
+    }
+    /// Whether rects are still axis-aligned rects after transformed.
+    #[inline]
+    pub fn is_axis_aligned(&self) -> bool {
+        self.w.0 == 0. && self.w.1 == 0. &&
+        ((self.x.1 == 0. && self.y.0 == 0.) || (self.x.0 == 0. && self.y.1 == 0.))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Transform;
+    use super::super::{Point, Size, Bounds};
+
+    fn assert_identity(t: &Transform) {
+        for (i, row) in t.rows().iter().enumerate() {
+            for (j, x) in row.iter().enumerate() {
+                assert!((x - if i == j { 1. } else { 0. }).abs() < 1e-9, "{:?} is not the identity", t);
+            }
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let transforms = [
+            Transform::new().rotate(0.7).rotate_x(-0.3).clone(),
+            Transform::new().scale(2., -0.5).scale_z(3.).clone(),
+            Transform::new().skew(0.3, -0.2).clone(),
+            Transform::new().offset(Size::new(50., 50.)).perspective(200.).rotate_y(0.5).translate(Size::new(10., 20.)).translate_z(30.).clone(),
+        ];
+        for t in transforms.iter() {
+            let inv = t.inverse().unwrap();
+            assert_identity(&t.clone().mul_clone(&inv));
+            assert_identity(&inv.clone().mul_clone(t));
+        }
+        assert_eq!(Transform::new().scale(0., 1.).inverse(), None);
+        assert_eq!(Transform::new().rotate(0.7).flatten().inverse(), None);
+    }
+
+    #[test]
+    fn unproject() {
+        let t = Transform::new().offset(Size::new(50., 50.)).perspective(200.).rotate_x(0.4).rotate_y(-0.6).translate(Size::new(-20., 5.)).clone();
+        for &(x, y) in [(0., 0.), (10., -3.), (120.5, 44.)].iter() {
+            let p = t.unproject(t.apply_to_point(Point::new(x, y))).unwrap();
+            assert!((p.left() - x).abs() < 1e-9 && (p.top() - y).abs() < 1e-9);
+        }
+        // the x and z axes are swapped, so the z = 0 plane is viewed edge-on though the transform is invertible
+        let t = Transform::new().matrix3d([0., 0., 1., 0., 0., 1., 0., 0., 1., 0., 0., 0., 0., 0., 0., 1.]).clone();
+        assert!(t.inverse().is_some());
+        assert_eq!(t.unproject(Point::new(1., 1.)), None);
+    }
+
+    #[test]
+    fn behind_viewer() {
+        let bounds = Bounds::new(-10., -10., 10., 10.);
+        let t = Transform::new().perspective(100.).translate_z(50.).clone();
+        assert!(!t.is_behind_viewer(&bounds));
+        let t = Transform::new().perspective(100.).translate_z(150.).clone();
+        assert!(t.is_behind_viewer(&bounds));
+        let (x, y, _) = t.apply_to_point_3d((10., 10., 0.));
+        assert!(x.is_finite() && y.is_finite() && x > 0. && y > 0.);
+        // only a part of it is behind the viewer
+        let t = Transform::new().perspective(100.).rotate_y(1.).clone();
+        assert!(!t.is_behind_viewer(&bounds));
+        assert!(t.is_behind_viewer(&Bounds::new(-200., -10., 200., 10.)));
     }
 }
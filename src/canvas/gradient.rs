@@ -0,0 +1,156 @@
+use super::element::Size;
+use super::element::style::{BackgroundImage, ColorStop, GradientDirection, RadialGradientSize};
+
+// the color stops with positions resolved to the ratio of the gradient line
+fn resolve_stops(stops: &[ColorStop], line_length: f64) -> Vec<(f64, [f32; 4])> {
+    let len = stops.len();
+    let mut positions: Vec<Option<f64>> = stops.iter().map(|stop| {
+        stop.position.map(|x| if line_length > 0. { x.resolve(line_length) / line_length } else { 0. })
+    }).collect();
+    if positions[0].is_none() { positions[0] = Some(0.) }
+    if positions[len - 1].is_none() { positions[len - 1] = Some(1.) }
+    // a position before a previous one is moved to it
+    let mut max = positions[0].unwrap();
+    for p in positions.iter_mut() {
+        match *p {
+            None => { },
+            Some(x) => {
+                if x < max { *p = Some(max) } else { max = x }
+            },
+        }
+    }
+    // the positions not specified are evenly spaced between the specified ones
+    let mut i = 0;
+    while i < len {
+        if positions[i].is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i - 1;
+        let mut end = i;
+        while positions[end].is_none() { end += 1 }
+        let (a, b) = (positions[start].unwrap(), positions[end].unwrap());
+        for j in (start + 1)..end {
+            positions[j] = Some(a + (b - a) * (j - start) as f64 / (end - start) as f64);
+        }
+        i = end;
+    }
+    stops.iter().zip(positions.into_iter()).map(|(stop, p)| {
+        let c = stop.color;
+        (p.unwrap(), [c.0 * c.3, c.1 * c.3, c.2 * c.3, c.3])
+    }).collect()
+}
+
+// the premultiplied color at a position of the gradient line
+fn color_at(stops: &[(f64, [f32; 4])], t: f64) -> [f32; 4] {
+    if t <= stops[0].0 { return stops[0].1 }
+    for w in stops.windows(2) {
+        let ((p1, c1), (p2, c2)) = (w[0], w[1]);
+        if t < p2 {
+            let r = if p2 > p1 { ((t - p1) / (p2 - p1)) as f32 } else { 1. };
+            return [c1[0] + (c2[0] - c1[0]) * r, c1[1] + (c2[1] - c1[1]) * r, c1[2] + (c2[2] - c1[2]) * r, c1[3] + (c2[3] - c1[3]) * r];
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+/// Render a gradient in a box of `size` into premultiplied RGBA pixels of `tex_size`, rows from top to bottom.
+pub(crate) fn render_gradient(image: &BackgroundImage, size: Size, tex_size: (i32, i32)) -> Vec<u8> {
+    let (w, h) = (size.width(), size.height());
+    let (tex_w, tex_h) = tex_size;
+    // the gradient line length, and the mapping from a point in the box to the ratio of the gradient line
+    let (stops, line_length, position_at): (&Vec<ColorStop>, f64, Box<Fn(f64, f64) -> f64>) = match image {
+        BackgroundImage::None | BackgroundImage::Url(_) => return vec![0; (tex_w * tex_h * 4) as usize],
+        BackgroundImage::LinearGradient { direction, stops } => {
+            let (dx, dy) = linear_direction(*direction, w, h);
+            let line_length = (w * dx).abs() + (h * dy).abs();
+            (stops, line_length, Box::new(move |x, y| {
+                if line_length <= 0. { return 0. }
+                ((x - w / 2.) * dx + (y - h / 2.) * dy) / line_length + 0.5
+            }))
+        },
+        BackgroundImage::RadialGradient { circle, size: radial_size, center, stops } => {
+            let (cx, cy) = (center.0.resolve(w), center.1.resolve(h));
+            let (rx, ry) = radial_radii(*circle, *radial_size, (cx, cy), w, h);
+            let (sx, sy) = (rx.max(1e-6), ry.max(1e-6));
+            (stops, rx, Box::new(move |x, y| {
+                let (nx, ny) = ((x - cx) / sx, (y - cy) / sy);
+                (nx * nx + ny * ny).sqrt()
+            }))
+        },
+    };
+    let stops = resolve_stops(stops, line_length);
+    let mut ret = Vec::with_capacity((tex_w * tex_h * 4) as usize);
+    for py in 0..tex_h {
+        let y = (py as f64 + 0.5) / tex_h as f64 * h;
+        for px in 0..tex_w {
+            let x = (px as f64 + 0.5) / tex_w as f64 * w;
+            let c = color_at(&stops, position_at(x, y));
+            for i in 0..4 {
+                ret.push((c[i].max(0.).min(1.) * 255. + 0.5) as u8);
+            }
+        }
+    }
+    ret
+}
+
+// the unit vector of the gradient line
+fn linear_direction(direction: GradientDirection, w: f64, h: f64) -> (f64, f64) {
+    match direction {
+        GradientDirection::Angle(angle) => (angle.sin(), -angle.cos()),
+        GradientDirection::ToCorner { right, bottom } => {
+            // perpendicular to the diagonal between the other two corners
+            let (dx, dy) = (if right { h } else { -h }, if bottom { w } else { -w });
+            let len = (dx * dx + dy * dy).sqrt();
+            if len > 0. { (dx / len, dy / len) } else { (0., 1.) }
+        },
+    }
+}
+
+// the horizontal and vertical radii of the ending shape
+fn radial_radii(circle: bool, size: RadialGradientSize, center: (f64, f64), w: f64, h: f64) -> (f64, f64) {
+    let (cx, cy) = center;
+    let (side_x_min, side_x_max) = (cx.abs().min((w - cx).abs()), cx.abs().max((w - cx).abs()));
+    let (side_y_min, side_y_max) = (cy.abs().min((h - cy).abs()), cy.abs().max((h - cy).abs()));
+    // the ellipse through the corner with the same aspect ratio as the one touching the sides
+    let corner = |sx: f64, sy: f64| {
+        if circle {
+            let r = (sx * sx + sy * sy).sqrt();
+            (r, r)
+        } else if sx <= 0. || sy <= 0. {
+            (sx, sy)
+        } else {
+            let k = sy / sx;
+            let rx = (sx * sx + (sy / k) * (sy / k)).sqrt();
+            (rx, rx * k)
+        }
+    };
+    match size {
+        RadialGradientSize::ClosestSide => {
+            if circle {
+                let r = side_x_min.min(side_y_min);
+                (r, r)
+            } else {
+                (side_x_min, side_y_min)
+            }
+        },
+        RadialGradientSize::FarthestSide => {
+            if circle {
+                let r = side_x_max.max(side_y_max);
+                (r, r)
+            } else {
+                (side_x_max, side_y_max)
+            }
+        },
+        RadialGradientSize::ClosestCorner => corner(side_x_min, side_y_min),
+        RadialGradientSize::FarthestCorner => corner(side_x_max, side_y_max),
+        RadialGradientSize::Explicit(a, b) => {
+            if circle {
+                let r = a.resolve(w);
+                (r, r)
+            } else {
+                (a.resolve(w), b.resolve(h))
+            }
+        },
+    }
+}
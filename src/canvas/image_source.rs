@@ -0,0 +1,120 @@
+use std::borrow::Cow;
+
+/// The content of an image resolved from a url.
+pub enum ImageSource {
+    /// A url (or file path) loaded by the backend.
+    Url(String),
+    /// The encoded bytes of an image, e.g. the content of a PNG or SVG file.
+    Bytes(Cow<'static, [u8]>),
+}
+
+/// Map the urls of images to their content, e.g. the assets embedded with `include_bytes!` or in an archive.
+/// It is called for every image loaded by url, and the url is loaded as usual if `None` is returned.
+pub trait ImageResolver {
+    fn resolve(&self, url: &str) -> Option<ImageSource>;
+}
+
+impl<F> ImageResolver for F where F: Fn(&str) -> Option<ImageSource> {
+    #[inline]
+    fn resolve(&self, url: &str) -> Option<ImageSource> {
+        self(url)
+    }
+}
+
+#[inline]
+pub(crate) fn is_data_uri(url: &str) -> bool {
+    url.as_bytes().get(..5).map_or(false, |x| x.eq_ignore_ascii_case(b"data:"))
+}
+
+/// Decode the content of a `data:` URI, either base64 or percent-encoded.
+pub(crate) fn decode_data_uri(url: &str) -> Option<Vec<u8>> {
+    if !is_data_uri(url) { return None }
+    let comma = url.find(',')?;
+    let header = &url[5..comma];
+    let data = &url[(comma + 1)..];
+    let is_base64 = header.split(';').skip(1).any(|x| x.trim().eq_ignore_ascii_case("base64"));
+    let data = percent_decode(data)?;
+    if is_base64 {
+        base64_decode(&data)
+    } else {
+        Some(data)
+    }
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    let mut ret = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'%' {
+            if i + 2 >= s.len() { return None }
+            ret.push(hex_value(s[i + 1])? * 16 + hex_value(s[i + 2])?);
+            i += 3;
+        } else {
+            ret.push(s[i]);
+            i += 1;
+        }
+    }
+    Some(ret)
+}
+
+fn base64_value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' | b'-' => Some(62),
+        b'/' | b'_' => Some(63),
+        _ => None,
+    }
+}
+
+// the whitespaces are skipped, and the padding is optional
+fn base64_decode(s: &[u8]) -> Option<Vec<u8>> {
+    let mut ret = Vec::with_capacity(s.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    let mut padding = false;
+    for &c in s {
+        if c == b' ' || c == b'\t' || c == b'\r' || c == b'\n' { continue }
+        if c == b'=' {
+            padding = true;
+            continue;
+        }
+        if padding { return None }
+        bits = (bits << 6) | base64_value(c)?;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            ret.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(ret)
+}
+
+#[cfg(test)]
+mod test {
+    use super::decode_data_uri;
+
+    #[test]
+    fn data_uri() {
+        assert_eq!(decode_data_uri("data:image/png;base64,iVBORw0K").unwrap(), b"\x89PNG\r\n");
+        assert_eq!(decode_data_uri("DATA:;base64,aGk=").unwrap(), b"hi");
+        assert_eq!(decode_data_uri("data:image/svg+xml,%3Csvg%3E<%2Fsvg>").unwrap(), b"<svg></svg>");
+        assert_eq!(decode_data_uri("data:image/svg+xml;charset=utf-8,a%2"), None);
+        assert_eq!(decode_data_uri("data:;base64,a*b"), None);
+        assert_eq!(decode_data_uri("image.png"), None);
+        assert_eq!(decode_data_uri("图片/a.png"), None);
+        assert_eq!(decode_data_uri("data"), None);
+    }
+}
@@ -1,15 +1,27 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::{Instant, Duration};
 use super::frame;
+use super::lib_interfaces::{Backend, DefaultBackend};
 use rc_forest::{Forest, ForestNodeRc};
 
 pub mod element;
 mod config;
 mod character;
 mod resource;
+mod capture;
+mod display_list;
+mod gradient;
+mod atlas;
+mod image_source;
+pub(crate) mod path;
+mod scroll;
 
 pub(crate) type CanvasConfig = config::CanvasConfig;
 pub use element::*;
+pub use self::capture::{FrameCapture, CaptureSize};
+pub use self::display_list::{DisplayList, DrawCommand};
+pub use self::image_source::{ImageSource, ImageResolver};
 
 #[derive(Default, Clone, Debug, PartialEq)]
 pub struct KeyDescriptor {
@@ -29,6 +41,7 @@ pub struct CanvasContext {
     touching: bool,
     touch_point: element::Point,
     last_key: KeyDescriptor,
+    scroll_gesture: scroll::ScrollGesture,
 }
 
 pub struct Canvas {
@@ -37,14 +50,20 @@ pub struct Canvas {
 }
 
 impl Canvas {
+    #[inline]
     pub fn new(index: i32) -> Self {
-        lib!(bind_canvas(index));
+        Self::new_with_backend(index, Rc::new(DefaultBackend::new()))
+    }
+    /// Create a canvas which draws with the specified backend instead of the lib functions.
+    pub fn new_with_backend(index: i32, backend: Rc<Backend>) -> Self {
+        backend.bind_canvas(index);
         let canvas_config = Rc::new(CanvasConfig::new(
+            backend.clone(),
             index,
-            lib!(tex_get_size(index)) as i32,
-            lib!(tex_get_count(index)) as i32,
-            lib!(tex_get_max_draws()) as i32,
-            lib!(get_device_pixel_ratio(index)) as f64
+            backend.tex_get_size(index),
+            backend.tex_get_count(index),
+            backend.tex_get_max_draws(),
+            backend.get_device_pixel_ratio(index)
         ));
         log!("Canvas binded: {}", index);
         let root_node = ForestNodeRc::new(&mut Forest::new(), Element::new(&canvas_config, Box::new(Empty::new(&canvas_config))));
@@ -52,21 +71,33 @@ impl Canvas {
         let ctx = Rc::new(RefCell::new(CanvasContext {
             canvas_config,
             root_node,
-            need_redraw: 0,
+            need_redraw: 1,
             all_dirty: false,
             touching: false,
             touch_point: element::Point::new(0., 0.),
             last_key: Default::default(),
+            scroll_gesture: scroll::ScrollGesture::new(),
         }));
         let frame_ctx = ctx.clone();
-        let frame_fn = frame::FrameCallback::new(Box::new(move |_time| {
+        let frame_fn = frame::FrameCallback::new(Box::new(move |time| {
+            let inertia = frame_ctx.borrow_mut().scroll_gesture.inertia_step(time);
+            match inertia {
+                None => { },
+                Some((target, dx, dy)) => {
+                    // stop at the edges
+                    if !target.borrow_mut().scroll_by(dx, dy) {
+                        frame_ctx.borrow_mut().scroll_gesture.stop();
+                    }
+                }
+            }
             frame_ctx.borrow_mut().generate_frame();
             true
         }));
-        frame::bind(frame_fn.clone(), frame::FramePriority::Low); // FIXME only bind when neccessary
-        lib!(bind_touch_events(index, lib_callback!(TouchEventCallback(ctx.clone()))));
-        lib!(bind_keyboard_events(index, lib_callback!(KeyboardEventCallback(ctx.clone()))));
-        lib!(bind_canvas_size_change(index, lib_callback!(CanvasSizeChangeCallback(ctx.clone()))));
+        frame::bind_with_backend(frame_fn.clone(), frame::FramePriority::Low, backend.clone()); // FIXME only bind when neccessary
+        backend.bind_touch_events(index, lib_callback!(TouchEventCallback(ctx.clone())));
+        backend.bind_wheel_events(index, lib_callback!(WheelEventCallback(ctx.clone())));
+        backend.bind_keyboard_events(index, lib_callback!(KeyboardEventCallback(ctx.clone())));
+        backend.bind_canvas_size_change(index, lib_callback!(CanvasSizeChangeCallback(ctx.clone())));
         return Canvas {
             context: ctx,
             frame_fn,
@@ -89,7 +120,7 @@ impl Canvas {
 impl Drop for CanvasContext {
     fn drop(&mut self) {
         log!("Canvas unbinded: {}", self.canvas_config.index);
-        lib!(unbind_canvas(self.canvas_config.index));
+        self.canvas_config.backend().unbind_canvas(self.canvas_config.index);
     }
 }
 
@@ -101,33 +132,51 @@ impl CanvasContext {
     fn set_canvas_size_inner(&mut self, w: i32, h: i32, pixel_ratio: f64, update_logical_size: bool) {
         log!("Canvas size changed: {}", self.canvas_config.index);
         self.canvas_config.canvas_size.set(element::Size::new(w as f64, h as f64));
-        lib!(set_canvas_size(self.canvas_config.index, w, h, pixel_ratio, update_logical_size as i32));
+        self.canvas_config.backend().set_canvas_size(self.canvas_config.index, w, h, pixel_ratio, update_logical_size);
         if !self.all_dirty {
             self.all_dirty = true;
             self.root_node.borrow_mut().mark_layout_dirty_dfs();
             // need redraw two frames on some pc opengl
             self.redraw_times(2);
         }
+        if self.canvas_config.device_pixel_ratio.get() != pixel_ratio {
+            // the textures rasterized for the old ratio should be updated
+            self.canvas_config.device_pixel_ratio.set(pixel_ratio);
+            self.root_node.borrow_mut().mark_paint_dirty();
+        }
     }
     pub fn set_canvas_size(&mut self, w: i32, h: i32, pixel_ratio: f64) {
         self.set_canvas_size_inner(w, h, pixel_ratio, true);
     }
+    /// Call `f` after `dur`, scheduled by the backend of the canvas.
+    pub fn set_timeout<F>(&self, f: F, dur: Duration) where F: Fn() + 'static {
+        self.canvas_config.backend().timeout(super::duration_to_ms(dur), super::timeout_callback(f));
+    }
     #[inline]
     pub fn device_pixel_ratio(&self) -> f64 {
-        lib!(get_device_pixel_ratio(self.canvas_config.index))
+        self.canvas_config.backend().get_device_pixel_ratio(self.canvas_config.index)
     }
     #[inline]
     pub fn canvas_size(&self) -> (i32, i32) {
-        (lib!(get_canvas_width(self.canvas_config.index)), lib!(get_canvas_height(self.canvas_config.index)))
+        let backend = self.canvas_config.backend();
+        (backend.get_canvas_width(self.canvas_config.index), backend.get_canvas_height(self.canvas_config.index))
     }
     pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
         self.canvas_config.set_clear_color((r, g, b, a));
-        lib!(set_clear_color(self.canvas_config.index, r, g, b, a));
+        self.canvas_config.backend().set_clear_color(self.canvas_config.index, r, g, b, a);
+        self.redraw();
+    }
+    /// Set the max bytes of the textures of the characters, which are released when exceeded if not used by any text.
+    pub fn set_glyph_tex_budget(&mut self, bytes: usize) {
+        self.canvas_config.character_manager().borrow_mut().set_tex_budget(bytes);
+    }
+    /// Set the resolver which maps the urls of the images to their content, or `None` to load the urls as usual.
+    pub fn set_image_resolver(&mut self, resolver: Option<Rc<ImageResolver>>) {
+        self.canvas_config.set_image_resolver(resolver);
     }
     pub fn clear(&mut self) {
-        let (r, g, b, a) = self.canvas_config.clear_color();
-        lib!(set_clear_color(self.canvas_config.index, r, g, b, a));
-        lib!(clear(self.canvas_config.index));
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().clear(self.canvas_config.clear_color());
     }
     #[inline]
     pub fn root(&mut self) -> ForestNodeRc<Element> {
@@ -158,29 +207,128 @@ impl CanvasContext {
         last_key
     }
 
+    /// Redraw the whole canvas and read the pixels back.
+    pub fn capture_frame(&mut self, size: CaptureSize) -> FrameCapture {
+        self.redraw_times(1);
+        self.generate_frame();
+        let canvas_size = self.canvas_config.canvas_size.get();
+        let (w, h) = (canvas_size.width().round() as i32, canvas_size.height().round() as i32);
+        let device_w = (canvas_size.width() * self.canvas_config.device_pixel_ratio.get()).round() as i32;
+        let device_h = (canvas_size.height() * self.canvas_config.device_pixel_ratio.get()).round() as i32;
+        let mut buf: Vec<u8> = vec![0; (device_w * device_h * 4) as usize];
+        self.canvas_config.backend().tex_get_pixels(self.canvas_config.index, 0, 0, device_w, device_h, &mut buf);
+        match size {
+            CaptureSize::Logical => FrameCapture::from_premultiplied(device_w, device_h, buf, w, h),
+            CaptureSize::DevicePixel => FrameCapture::from_premultiplied(device_w, device_h, buf, device_w, device_h),
+        }
+    }
+
+    /// Redraw the whole canvas and return the drawing commands.
+    pub fn record_frame(&mut self) -> DisplayList {
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().start_recording();
+        self.redraw_times(1);
+        self.generate_frame();
+        let ret = rm.borrow_mut().stop_recording();
+        ret.unwrap()
+    }
+    /// Draw a display list recorded by `record_frame` of this canvas again.
+    /// The textures are not recorded, so the ones referenced should not be released since recording.
+    pub fn replay_frame(&mut self, display_list: &DisplayList) {
+        let rm = self.canvas_config.resource_manager();
+        rm.borrow_mut().flush_draw();
+        display_list.replay(&*self.canvas_config.backend(), self.canvas_config.index);
+        rm.borrow_mut().resend_draw_state();
+    }
+
     fn generate_frame(&mut self) {
         self.root_node.borrow().clear_class_dirty(); // any child or itself need update style
         let dirty = self.root_node.borrow().is_layout_dirty(); // any child or itself need update position offset
-        if dirty || self.need_redraw > 0 {
+        let repaint_requested = self.canvas_config.take_repaint_requested(); // any element need repaint
+        if dirty || repaint_requested || self.need_redraw > 0 {
+            let full_repaint = self.need_redraw > 0;
             if self.need_redraw > 0 {
                 self.need_redraw -= 1;
             }
             let now = start_measure_time!();
-            self.clear();
             let root_node_rc = self.root();
             let size = self.canvas_config.canvas_size.get();
             if dirty {
                 self.all_dirty = false;
                 root_node_rc.borrow_mut().dfs_update_position_offset(size);
+                root_node_rc.borrow_mut().dfs_clamp_scroll_offset();
             }
-            root_node_rc.borrow_mut().draw(element::Position::new(0., 0., size.width(), size.height()), element::Transform::new());
+
             let rm = self.canvas_config.resource_manager();
-            rm.borrow_mut().flush_draw();
+            let viewport = element::Position::new(0., 0., size.width(), size.height());
+            let canvas_bounds = element::Bounds::new(0., 0., size.width(), size.height());
+            if full_repaint {
+                rm.borrow_mut().defer_drawing();
+                root_node_rc.borrow_mut().draw(viewport, canvas_bounds, element::Transform::new());
+                let display_list = rm.borrow_mut().take_deferred();
+                rm.borrow_mut().take_blur_regions();
+                self.canvas_config.take_damage();
+                self.clear();
+                rm.borrow_mut().send_display_list(&display_list);
+            } else {
+                // the damaged area is known before drawing, so the unchanged subtrees out of it are skipped
+                let mut blur_regions = vec![];
+                root_node_rc.borrow().collect_damage(viewport, element::Transform::new(), &mut blur_regions);
+                let mut damaged = self.canvas_config.take_damage();
+                loop {
+                    let mut area = match damaged {
+                        None => break,
+                        Some(x) => x,
+                    };
+                    extend_damage_by_blur(&mut area, &blur_regions);
+                    area.intersection(&canvas_bounds);
+                    if !(area.width() > 0. && area.height() > 0.) { break }
+                    rm.borrow_mut().defer_drawing();
+                    root_node_rc.borrow_mut().draw(viewport, area, element::Transform::new());
+                    let display_list = rm.borrow_mut().take_deferred();
+                    blur_regions.append(&mut rm.borrow_mut().take_blur_regions());
+                    // the area grows if painted out of the layout bounds or blurred across it, then the commands are dropped and drawn again
+                    let mut grown = area;
+                    match self.canvas_config.take_damage() {
+                        None => { },
+                        Some(x) => grown.union(&x),
+                    }
+                    extend_damage_by_blur(&mut grown, &blur_regions);
+                    grown.intersection(&canvas_bounds);
+                    if grown == area {
+                        rm.borrow_mut().push_clip_rect(area.left(), area.top(), area.width(), area.height());
+                        self.clear();
+                        rm.borrow_mut().send_display_list(&display_list);
+                        rm.borrow_mut().pop_clip();
+                        break;
+                    }
+                    damaged = Some(grown);
+                }
+            }
+            rm.borrow_mut().release_unused_textures();
+            rm.borrow_mut().update_image_atlas();
+            self.canvas_config.character_manager().borrow_mut().gabbage_collect();
             debug!("Redraw time: {}ms", end_measure_time!(now));
         }
     }
 }
 
+// a blurred draw is only correct if all the content it samples is repainted, so the damaged area grows to cover the blur regions it touches
+fn extend_damage_by_blur(damaged: &mut element::Bounds, blur_regions: &[element::Bounds]) {
+    let mut merged = vec![false; blur_regions.len()];
+    loop {
+        let mut grown = false;
+        for (i, region) in blur_regions.iter().enumerate() {
+            if !merged[i] && region.intersects(damaged) {
+                damaged.union(region);
+                merged[i] = true;
+                grown = true;
+            }
+        }
+        if !grown { break }
+    }
+}
+
 const TOUCHSTART: i32 = 1;
 const TOUCHMOVE: i32 = 2;
 const TOUCHEND: i32 = 3;
@@ -192,23 +340,32 @@ pub struct TouchEventDetail {
 }
 lib_define_callback! (TouchEventCallback (Rc<RefCell<CanvasContext>>) {
     fn callback(&mut self, touch_type: i32, x: i32, y: i32, _: i32) -> bool {
+        let point = element::Point::new(x as f64, y as f64);
+        let now = Instant::now();
+        let mut scroll = None;
         let node = {
             let mut ctx = self.0.borrow_mut();
             match touch_type {
                 TOUCHSTART => {
                     ctx.touching = true;
-                    ctx.touch_point = element::Point::new(x as f64, y as f64);
+                    ctx.touch_point = point;
+                    let root = ctx.root();
+                    ctx.scroll_gesture.touch_start(&root, point, now);
                 },
                 TOUCHMOVE => {
-                    ctx.touch_point = element::Point::new(x as f64, y as f64);
+                    ctx.touch_point = point;
+                    scroll = ctx.scroll_gesture.touch_move(point, now);
                 },
                 TOUCHEND => {
-                    ctx.touch_point = element::Point::new(x as f64, y as f64);
+                    ctx.touch_point = point;
                     ctx.touching = false;
+                    scroll = ctx.scroll_gesture.touch_move(point, now);
+                    ctx.scroll_gesture.touch_end(now);
                 },
                 TOUCHCANCEL => {
-                    ctx.touch_point = element::Point::new(x as f64, y as f64);
+                    ctx.touch_point = point;
                     ctx.touching = false;
+                    ctx.scroll_gesture.stop();
                 },
                 FREEMOVE => {
                     ctx.touch_point = element::Point::new(x as f64, y as f64);
@@ -217,8 +374,14 @@ lib_define_callback! (TouchEventCallback (Rc<RefCell<CanvasContext>>) {
                     panic!();
                 }
             }
-            ctx.root().borrow_mut().node_under_point(element::Point::new(x as f64, y as f64))
+            ctx.root().borrow_mut().node_under_point(point)
         };
+        match scroll {
+            None => { },
+            Some((target, dx, dy)) => {
+                target.borrow_mut().scroll_by(dx, dy);
+            }
+        }
         if node.is_some() {
             let event_name = String::from(match touch_type {
                 TOUCHSTART => "touchstart",
@@ -236,6 +399,26 @@ lib_define_callback! (TouchEventCallback (Rc<RefCell<CanvasContext>>) {
     }
 });
 
+lib_define_callback! (WheelEventCallback (Rc<RefCell<CanvasContext>>) {
+    fn callback(&mut self, x: i32, y: i32, delta_x: i32, delta_y: i32) -> bool {
+        let (dx, dy) = (delta_x as f64, delta_y as f64);
+        let target = {
+            let mut ctx = self.0.borrow_mut();
+            ctx.scroll_gesture.stop();
+            let root = ctx.root();
+            let ret = root.borrow().scroll_container_under_point(element::Point::new(x as f64, y as f64), element::Size::new(dx, dy));
+            ret
+        };
+        match target {
+            None => { },
+            Some(target) => {
+                target.borrow_mut().scroll_by(dx, dy);
+            }
+        }
+        true
+    }
+});
+
 const KEY_DOWN: i32 = 1;
 const KEY_PRESS: i32 = 2;
 const KEY_UP: i32 = 3;
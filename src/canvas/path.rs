@@ -0,0 +1,686 @@
+use std::f64::consts::PI;
+use super::element::{FillRule, LineJoin, LineCap};
+
+const SUBSAMPLES: i32 = 4;
+const ROUND_STEP_MAX: f64 = PI / 8.;
+const CURVE_STEPS_MAX: usize = 256;
+
+/// A segment of the path data, with the absolute coordinates.
+/// Quadratic curves and arcs are converted to cubic curves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum PathSegment {
+    MoveTo(f64, f64),
+    LineTo(f64, f64),
+    CubicTo(f64, f64, f64, f64, f64, f64),
+    Close,
+}
+
+/// The painting options of a path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct PathStyle {
+    pub(crate) fill_color: (f32, f32, f32, f32),
+    pub(crate) fill_rule: FillRule,
+    pub(crate) stroke_color: (f32, f32, f32, f32),
+    pub(crate) stroke_width: f64,
+    pub(crate) line_join: LineJoin,
+    pub(crate) line_cap: LineCap,
+    pub(crate) miter_limit: f64,
+}
+
+impl PathStyle {
+    #[inline]
+    fn has_fill(&self) -> bool {
+        self.fill_color.3 > 0.
+    }
+    #[inline]
+    fn has_stroke(&self) -> bool {
+        self.stroke_color.3 > 0. && self.stroke_width > 0.
+    }
+}
+
+/// The polygons of the filled area and the stroke outlines.
+/// The stroke polygons are all in the same orientation, so they can be filled with the non-zero rule to get the union.
+pub(crate) struct PathShape {
+    fill: Vec<Vec<(f64, f64)>>,
+    stroke: Vec<Vec<(f64, f64)>>,
+}
+
+// path data parser
+
+struct PathDataParser<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PathDataParser<'a> {
+    fn skip_separators(&mut self, allow_comma: bool) {
+        let mut comma = !allow_comma;
+        while self.pos < self.s.len() {
+            match self.s[self.pos] {
+                b' ' | b'\t' | b'\n' | b'\r' | b'\x0c' => { },
+                b',' if !comma => { comma = true },
+                _ => break,
+            }
+            self.pos += 1;
+        }
+    }
+    fn next_is_number(&mut self) -> bool {
+        self.skip_separators(false);
+        match self.s.get(self.pos) {
+            Some(b'0'..=b'9') | Some(b'.') | Some(b'-') | Some(b'+') => true,
+            _ => false,
+        }
+    }
+    fn number(&mut self) -> Option<f64> {
+        self.skip_separators(true);
+        let start = self.pos;
+        let s = self.s;
+        let mut p = self.pos;
+        if p < s.len() && (s[p] == b'-' || s[p] == b'+') { p += 1 }
+        let digits_start = p;
+        while p < s.len() && s[p].is_ascii_digit() { p += 1 }
+        let mut has_digits = p > digits_start;
+        if p < s.len() && s[p] == b'.' {
+            p += 1;
+            let frac_start = p;
+            while p < s.len() && s[p].is_ascii_digit() { p += 1 }
+            has_digits = has_digits || p > frac_start;
+        }
+        if !has_digits { return None }
+        if p < s.len() && (s[p] == b'e' || s[p] == b'E') {
+            let mut q = p + 1;
+            if q < s.len() && (s[q] == b'-' || s[q] == b'+') { q += 1 }
+            let exp_start = q;
+            while q < s.len() && s[q].is_ascii_digit() { q += 1 }
+            if q > exp_start { p = q }
+        }
+        self.pos = p;
+        ::std::str::from_utf8(&s[start..p]).unwrap().parse().ok()
+    }
+    fn flag(&mut self) -> Option<bool> {
+        self.skip_separators(true);
+        let ret = match self.s.get(self.pos) {
+            Some(b'0') => false,
+            Some(b'1') => true,
+            _ => return None,
+        };
+        self.pos += 1;
+        Some(ret)
+    }
+    fn numbers(&mut self, buf: &mut [f64]) -> Option<()> {
+        for x in buf.iter_mut() {
+            *x = self.number()?;
+        }
+        Some(())
+    }
+}
+
+/// Parse the SVG path data.
+/// Like SVG, the segments before the first error are still returned, with the byte offset of the error.
+pub(crate) fn parse_path_data(data: &str) -> (Vec<PathSegment>, Option<usize>) {
+    let mut ret = vec![];
+    let mut p = PathDataParser { s: data.as_bytes(), pos: 0 };
+    let mut current = (0., 0.);
+    let mut start = (0., 0.);
+    // the reflected control point for the smooth curves
+    let mut last_cubic_ctrl: Option<(f64, f64)> = None;
+    let mut last_quad_ctrl: Option<(f64, f64)> = None;
+    let mut cmd = 0u8;
+    loop {
+        p.skip_separators(false);
+        if p.pos >= p.s.len() { break }
+        let error_pos = p.pos;
+        let c = p.s[p.pos];
+        if c.is_ascii_alphabetic() {
+            cmd = c;
+            p.pos += 1;
+        } else if cmd == 0 || cmd == b'z' || cmd == b'Z' || !p.next_is_number() {
+            return (ret, Some(error_pos));
+        } else if cmd == b'm' {
+            // the following pairs of a move are implicit lines
+            cmd = b'l';
+        } else if cmd == b'M' {
+            cmd = b'L';
+        }
+        if ret.is_empty() && cmd != b'm' && cmd != b'M' {
+            return (ret, Some(error_pos));
+        }
+        let relative = cmd.is_ascii_lowercase();
+        let (ox, oy) = if relative { current } else { (0., 0.) };
+        let mut a = [0.; 7];
+        let mut cubic_ctrl = None;
+        let mut quad_ctrl = None;
+        let ok = match cmd.to_ascii_uppercase() {
+            b'Z' => {
+                ret.push(PathSegment::Close);
+                current = start;
+                Some(())
+            },
+            b'M' => p.numbers(&mut a[0..2]).map(|_| {
+                current = (ox + a[0], oy + a[1]);
+                start = current;
+                ret.push(PathSegment::MoveTo(current.0, current.1));
+            }),
+            b'L' => p.numbers(&mut a[0..2]).map(|_| {
+                current = (ox + a[0], oy + a[1]);
+                ret.push(PathSegment::LineTo(current.0, current.1));
+            }),
+            b'H' => p.numbers(&mut a[0..1]).map(|_| {
+                current = (ox + a[0], current.1);
+                ret.push(PathSegment::LineTo(current.0, current.1));
+            }),
+            b'V' => p.numbers(&mut a[0..1]).map(|_| {
+                current = (current.0, oy + a[0]);
+                ret.push(PathSegment::LineTo(current.0, current.1));
+            }),
+            b'C' | b'S' => {
+                let smooth = cmd.to_ascii_uppercase() == b'S';
+                let r = if smooth { p.numbers(&mut a[2..6]) } else { p.numbers(&mut a[0..6]) };
+                r.map(|_| {
+                    let c1 = if smooth {
+                        match last_cubic_ctrl {
+                            None => current,
+                            Some(c) => (2. * current.0 - c.0, 2. * current.1 - c.1),
+                        }
+                    } else {
+                        (ox + a[0], oy + a[1])
+                    };
+                    let c2 = (ox + a[2], oy + a[3]);
+                    current = (ox + a[4], oy + a[5]);
+                    cubic_ctrl = Some(c2);
+                    ret.push(PathSegment::CubicTo(c1.0, c1.1, c2.0, c2.1, current.0, current.1));
+                })
+            },
+            b'Q' | b'T' => {
+                let smooth = cmd.to_ascii_uppercase() == b'T';
+                let r = if smooth { p.numbers(&mut a[2..4]) } else { p.numbers(&mut a[0..4]) };
+                r.map(|_| {
+                    let c = if smooth {
+                        match last_quad_ctrl {
+                            None => current,
+                            Some(c) => (2. * current.0 - c.0, 2. * current.1 - c.1),
+                        }
+                    } else {
+                        (ox + a[0], oy + a[1])
+                    };
+                    let from = current;
+                    current = (ox + a[2], oy + a[3]);
+                    quad_ctrl = Some(c);
+                    ret.push(PathSegment::CubicTo(
+                        from.0 + (c.0 - from.0) * 2. / 3., from.1 + (c.1 - from.1) * 2. / 3.,
+                        current.0 + (c.0 - current.0) * 2. / 3., current.1 + (c.1 - current.1) * 2. / 3.,
+                        current.0, current.1,
+                    ));
+                })
+            },
+            b'A' => {
+                let r = (|| {
+                    p.numbers(&mut a[0..3])?;
+                    let large_arc = p.flag()?;
+                    let sweep = p.flag()?;
+                    p.numbers(&mut a[3..5])?;
+                    Some((large_arc, sweep))
+                })();
+                r.map(|(large_arc, sweep)| {
+                    let from = current;
+                    current = (ox + a[3], oy + a[4]);
+                    arc_to_cubics(&mut ret, from, (a[0], a[1]), a[2], large_arc, sweep, current);
+                })
+            },
+            _ => None,
+        };
+        if ok.is_none() {
+            return (ret, Some(error_pos));
+        }
+        last_cubic_ctrl = cubic_ctrl;
+        last_quad_ctrl = quad_ctrl;
+    }
+    (ret, None)
+}
+
+// convert an elliptical arc to cubic curves, see the implementation notes of SVG
+fn arc_to_cubics(ret: &mut Vec<PathSegment>, from: (f64, f64), radii: (f64, f64), rotation: f64, large_arc: bool, sweep: bool, to: (f64, f64)) {
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if from == to { return }
+    if rx == 0. || ry == 0. {
+        ret.push(PathSegment::LineTo(to.0, to.1));
+        return;
+    }
+    let (sin, cos) = (rotation * PI / 180.).sin_cos();
+    let (dx, dy) = ((from.0 - to.0) / 2., (from.1 - to.1) / 2.);
+    let (x1, y1) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+    // scale up the radii if the end point is not reachable
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1. {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let den = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let mut k = if den > 0. { (num / den).max(0.).sqrt() } else { 0. };
+    if large_arc == sweep { k = -k }
+    let (cx1, cy1) = (k * rx * y1 / ry, -k * ry * x1 / rx);
+    let cx = cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.;
+    let cy = sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.;
+    let angle = |ux: f64, uy: f64, vx: f64, vy: f64| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let (ux, uy) = ((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let (vx, vy) = ((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    let theta = angle(1., 0., ux, uy);
+    let mut delta = angle(ux, uy, vx, vy);
+    if !sweep && delta > 0. { delta -= 2. * PI }
+    if sweep && delta < 0. { delta += 2. * PI }
+    // each part is not larger than a quarter
+    let n = (delta.abs() / (PI / 2.)).ceil().max(1.) as usize;
+    let step = delta / n as f64;
+    let t = 4. / 3. * (step / 4.).tan();
+    let point = |a: f64| {
+        let (s, c) = a.sin_cos();
+        (cx + rx * c * cos - ry * s * sin, cy + rx * c * sin + ry * s * cos)
+    };
+    let derivative = |a: f64| {
+        let (s, c) = a.sin_cos();
+        (-rx * s * cos - ry * c * sin, -rx * s * sin + ry * c * cos)
+    };
+    for i in 0..n {
+        let a1 = theta + step * i as f64;
+        let a2 = a1 + step;
+        let (p1, d1) = (point(a1), derivative(a1));
+        let (p2, d2) = (if i == n - 1 { to } else { point(a2) }, derivative(a2));
+        ret.push(PathSegment::CubicTo(p1.0 + t * d1.0, p1.1 + t * d1.1, p2.0 - t * d2.0, p2.1 - t * d2.1, p2.0, p2.1));
+    }
+}
+
+/// Convert a circular arc from the `start` angle to the `end` angle to cubic curves, the same as the canvas `arc`.
+/// The start point of the arc is not added.
+pub(crate) fn circle_arc_to_cubics(ret: &mut Vec<PathSegment>, center: (f64, f64), radius: f64, start: f64, end: f64, anticlockwise: bool) {
+    let mut delta = end - start;
+    if !anticlockwise && delta >= 2. * PI || anticlockwise && delta <= -2. * PI {
+        delta = if anticlockwise { -2. * PI } else { 2. * PI };
+    } else {
+        delta %= 2. * PI;
+        if !anticlockwise && delta < 0. { delta += 2. * PI }
+        if anticlockwise && delta > 0. { delta -= 2. * PI }
+    }
+    if delta == 0. || radius <= 0. { return }
+    let n = (delta.abs() / (PI / 2.)).ceil().max(1.) as usize;
+    let step = delta / n as f64;
+    let t = 4. / 3. * (step / 4.).tan();
+    for i in 0..n {
+        let a1 = start + step * i as f64;
+        let a2 = a1 + step;
+        let ((s1, c1), (s2, c2)) = (a1.sin_cos(), a2.sin_cos());
+        ret.push(PathSegment::CubicTo(
+            center.0 + radius * (c1 - t * s1), center.1 + radius * (s1 + t * c1),
+            center.0 + radius * (c2 + t * s2), center.1 + radius * (s2 - t * c2),
+            center.0 + radius * c2, center.1 + radius * s2,
+        ));
+    }
+}
+
+/// The bounds of the path points, including the control points.
+pub(crate) fn path_bounds(segments: &[PathSegment]) -> Option<(f64, f64, f64, f64)> {
+    let mut ret: Option<(f64, f64, f64, f64)> = None;
+    {
+        let mut add = |x: f64, y: f64| {
+            ret = Some(match ret {
+                None => (x, y, x, y),
+                Some(b) => (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y)),
+            });
+        };
+        for seg in segments {
+            match *seg {
+                PathSegment::MoveTo(x, y) | PathSegment::LineTo(x, y) => add(x, y),
+                PathSegment::CubicTo(x1, y1, x2, y2, x, y) => {
+                    add(x1, y1);
+                    add(x2, y2);
+                    add(x, y);
+                },
+                PathSegment::Close => { },
+            }
+        }
+    }
+    ret
+}
+
+// geometry
+
+// the polylines of the sub paths, and whether they are closed
+fn flatten(segments: &[PathSegment], tolerance: f64) -> Vec<(Vec<(f64, f64)>, bool)> {
+    let mut ret: Vec<(Vec<(f64, f64)>, bool)> = vec![];
+    let mut current: Vec<(f64, f64)> = vec![];
+    let mut start = (0., 0.);
+    let mut closed = false;
+    for seg in segments {
+        // drawing after closing starts a new sub path at the same point
+        if closed {
+            match *seg {
+                PathSegment::MoveTo(..) | PathSegment::Close => { },
+                _ => current.push(start),
+            }
+            closed = false;
+        }
+        match *seg {
+            PathSegment::MoveTo(x, y) => {
+                if !current.is_empty() {
+                    ret.push((::std::mem::replace(&mut current, vec![]), false));
+                }
+                start = (x, y);
+                current.push(start);
+            },
+            PathSegment::LineTo(x, y) => current.push((x, y)),
+            PathSegment::CubicTo(x1, y1, x2, y2, x, y) => {
+                let p0 = *current.last().unwrap();
+                let dd = ((p0.0 - 2. * x1 + x2).abs() + (p0.1 - 2. * y1 + y2).abs())
+                    .max((x1 - 2. * x2 + x).abs() + (y1 - 2. * y2 + y).abs());
+                let n = ((dd * 0.75 / tolerance).sqrt().ceil() as usize).max(1).min(CURVE_STEPS_MAX);
+                for i in 1..(n + 1) {
+                    let t = i as f64 / n as f64;
+                    let u = 1. - t;
+                    let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+                    current.push((a * p0.0 + b * x1 + c * x2 + d * x, a * p0.1 + b * y1 + c * y2 + d * y));
+                }
+            },
+            PathSegment::Close => {
+                if !current.is_empty() {
+                    ret.push((::std::mem::replace(&mut current, vec![]), true));
+                }
+                closed = true;
+            },
+        }
+    }
+    if !current.is_empty() {
+        ret.push((current, false));
+    }
+    ret
+}
+
+fn signed_area(polygon: &[(f64, f64)]) -> f64 {
+    let mut ret = 0.;
+    for i in 0..polygon.len() {
+        let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+        ret += a.0 * b.1 - b.0 * a.1;
+    }
+    ret / 2.
+}
+
+fn circle(center: (f64, f64), r: f64, tolerance: f64) -> Vec<(f64, f64)> {
+    let step = if r > tolerance { (2. * (1. - tolerance / r).acos()).min(ROUND_STEP_MAX) } else { ROUND_STEP_MAX };
+    let n = ((2. * PI / step).ceil() as usize).max(8);
+    (0..n).map(|i| {
+        let a = 2. * PI * i as f64 / n as f64;
+        (center.0 + r * a.cos(), center.1 + r * a.sin())
+    }).collect()
+}
+
+// the outline polygons of the stroke of a polyline
+fn stroke_polyline(ret: &mut Vec<Vec<(f64, f64)>>, points: &[(f64, f64)], closed: bool, style: &PathStyle, tolerance: f64) {
+    let hw = style.stroke_width / 2.;
+    let mut points: Vec<(f64, f64)> = points.iter().cloned().fold(vec![], |mut v, p| {
+        if v.last() != Some(&p) { v.push(p) }
+        v
+    });
+    if closed && points.len() > 1 && points[0] == points[points.len() - 1] {
+        points.pop();
+    }
+    if points.len() == 1 {
+        // a zero-length sub path only has the caps
+        let p = points[0];
+        match style.line_cap {
+            LineCap::Butt => { },
+            LineCap::Round => ret.push(circle(p, hw, tolerance)),
+            LineCap::Square => ret.push(vec![(p.0 - hw, p.1 - hw), (p.0 + hw, p.1 - hw), (p.0 + hw, p.1 + hw), (p.0 - hw, p.1 + hw)]),
+        }
+        return;
+    }
+    let len = points.len();
+    let seg_count = if closed { len } else { len - 1 };
+    let dir = |i: usize| {
+        let (a, b) = (points[i % len], points[(i + 1) % len]);
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let l = (dx * dx + dy * dy).sqrt();
+        (dx / l, dy / l)
+    };
+    for i in 0..seg_count {
+        let (a, b) = (points[i], points[(i + 1) % len]);
+        let d = dir(i);
+        let n = (-d.1 * hw, d.0 * hw);
+        ret.push(vec![(a.0 + n.0, a.1 + n.1), (b.0 + n.0, b.1 + n.1), (b.0 - n.0, b.1 - n.1), (a.0 - n.0, a.1 - n.1)]);
+    }
+    // joins
+    let joins = if closed { 0..len } else { 1..(len - 1) };
+    for i in joins {
+        let v = points[i];
+        let d1 = dir((i + len - 1) % len);
+        let d2 = dir(i);
+        let cross = d1.0 * d2.1 - d1.1 * d2.0;
+        let dot = d1.0 * d2.0 + d1.1 * d2.1;
+        if cross.abs() < 1e-9 && dot > 0. { continue }
+        // the join is on the outer side of the turn
+        let s = if cross > 0. { -hw } else { hw };
+        let (n1, n2) = ((-d1.1, d1.0), (-d2.1, d2.0));
+        let (o1, o2) = ((v.0 + n1.0 * s, v.1 + n1.1 * s), (v.0 + n2.0 * s, v.1 + n2.1 * s));
+        match style.line_join {
+            LineJoin::Round => ret.push(circle(v, hw, tolerance)),
+            LineJoin::Bevel => ret.push(vec![v, o1, o2]),
+            LineJoin::Miter => {
+                let (mx, my) = (n1.0 + n2.0, n1.1 + n2.1);
+                let m2 = mx * mx + my * my;
+                if m2 > 0. && 2. / m2.sqrt() <= style.miter_limit {
+                    ret.push(vec![v, o1, (v.0 + mx * 2. * s / m2, v.1 + my * 2. * s / m2), o2]);
+                } else {
+                    ret.push(vec![v, o1, o2]);
+                }
+            },
+        }
+    }
+    // caps
+    if !closed {
+        let caps = [(points[0], { let d = dir(0); (-d.0, -d.1) }), (points[len - 1], dir(len - 2))];
+        for &(p, d) in caps.iter() {
+            match style.line_cap {
+                LineCap::Butt => { },
+                LineCap::Round => ret.push(circle(p, hw, tolerance)),
+                LineCap::Square => {
+                    let n = (-d.1 * hw, d.0 * hw);
+                    let e = (p.0 + d.0 * hw, p.1 + d.1 * hw);
+                    ret.push(vec![(p.0 + n.0, p.1 + n.1), (e.0 + n.0, e.1 + n.1), (e.0 - n.0, e.1 - n.1), (p.0 - n.0, p.1 - n.1)]);
+                },
+            }
+        }
+    }
+}
+
+/// Build the polygons of the path, with the curves flattened to the `tolerance`.
+pub(crate) fn build_path_shape(segments: &[PathSegment], style: &PathStyle, tolerance: f64) -> PathShape {
+    let polylines = flatten(segments, tolerance);
+    let fill = if style.has_fill() {
+        polylines.iter().filter(|x| x.0.len() > 2).map(|x| x.0.clone()).collect()
+    } else {
+        vec![]
+    };
+    let mut stroke = vec![];
+    if style.has_stroke() {
+        for (points, closed) in polylines.iter() {
+            stroke_polyline(&mut stroke, points, *closed, style, tolerance);
+        }
+        for polygon in stroke.iter_mut() {
+            if signed_area(polygon) < 0. { polygon.reverse() }
+        }
+    }
+    PathShape { fill, stroke }
+}
+
+fn winding_number(polygons: &[Vec<(f64, f64)>], point: (f64, f64)) -> i32 {
+    let (x, y) = point;
+    let mut ret = 0;
+    for polygon in polygons {
+        for i in 0..polygon.len() {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            let side = (b.0 - a.0) * (y - a.1) - (x - a.0) * (b.1 - a.1);
+            if a.1 <= y {
+                if b.1 > y && side > 0. { ret += 1 }
+            } else {
+                if b.1 <= y && side < 0. { ret -= 1 }
+            }
+        }
+    }
+    ret
+}
+
+#[inline]
+fn is_inside(winding: i32, fill_rule: FillRule) -> bool {
+    match fill_rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    }
+}
+
+/// Whether the point is in the filled area or the stroke of the path.
+pub(crate) fn path_shape_contains(shape: &PathShape, style: &PathStyle, point: (f64, f64)) -> bool {
+    is_inside(winding_number(&shape.fill, point), style.fill_rule) || winding_number(&shape.stroke, point) != 0
+}
+
+// the coverage of the polygons in the pixels, mapped with the scale and the offset
+fn rasterize(polygons: &[Vec<(f64, f64)>], fill_rule: FillRule, map: &Fn((f64, f64)) -> (f64, f64), tex_size: (i32, i32)) -> Vec<f32> {
+    let (tex_w, tex_h) = tex_size;
+    let mut ret = vec![0f32; (tex_w * tex_h) as usize];
+    // the edges (top, bottom, x of top, dx per y, winding) sorted by the top
+    let mut edges: Vec<(f64, f64, f64, f64, i32)> = vec![];
+    for polygon in polygons {
+        for i in 0..polygon.len() {
+            let (a, b) = (map(polygon[i]), map(polygon[(i + 1) % polygon.len()]));
+            if a.1 == b.1 { continue }
+            let (top, bottom, winding) = if a.1 < b.1 { (a, b, 1) } else { (b, a, -1) };
+            edges.push((top.1, bottom.1, top.0, (bottom.0 - top.0) / (bottom.1 - top.1), winding));
+        }
+    }
+    edges.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+    let mut next_edge = 0;
+    let mut active: Vec<usize> = vec![];
+    let mut crossings: Vec<(f64, i32)> = vec![];
+    let weight = 1. / SUBSAMPLES as f32;
+    for py in 0..tex_h {
+        let row = &mut ret[(py * tex_w) as usize..((py + 1) * tex_w) as usize];
+        for sub in 0..SUBSAMPLES {
+            let y = py as f64 + (sub as f64 + 0.5) / SUBSAMPLES as f64;
+            while next_edge < edges.len() && edges[next_edge].0 <= y {
+                active.push(next_edge);
+                next_edge += 1;
+            }
+            active.retain(|&i| edges[i].1 > y);
+            crossings.clear();
+            for &i in active.iter() {
+                let e = &edges[i];
+                if e.0 <= y {
+                    crossings.push((e.2 + (y - e.0) * e.3, e.4));
+                }
+            }
+            crossings.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+            let mut winding = 0;
+            for i in 0..crossings.len() {
+                winding += crossings[i].1;
+                if i + 1 == crossings.len() || !is_inside(winding, fill_rule) { continue }
+                // the horizontal span covers parts of the pixels at the ends
+                let x1 = crossings[i].0.max(0.).min(tex_w as f64);
+                let x2 = crossings[i + 1].0.max(0.).min(tex_w as f64);
+                if x2 <= x1 { continue }
+                let (p1, p2) = (x1.floor() as i32, x2.floor() as i32);
+                if p1 == p2 {
+                    row[p1 as usize] += (x2 - x1) as f32 * weight;
+                    continue;
+                }
+                row[p1 as usize] += (p1 as f64 + 1. - x1) as f32 * weight;
+                for px in (p1 + 1)..p2 {
+                    row[px as usize] += weight;
+                }
+                if p2 < tex_w {
+                    row[p2 as usize] += (x2 - p2 as f64) as f32 * weight;
+                }
+            }
+        }
+    }
+    ret
+}
+
+/// Render the path into premultiplied RGBA pixels of `tex_size`, rows from top to bottom.
+/// The `view_box` (left, top, width, height) of the path is stretched to the texture.
+pub(crate) fn render_path(shape: &PathShape, style: &PathStyle, view_box: (f64, f64, f64, f64), tex_size: (i32, i32)) -> Vec<u8> {
+    let (tex_w, tex_h) = tex_size;
+    let (sx, sy) = (tex_w as f64 / view_box.2, tex_h as f64 / view_box.3);
+    let map = move |p: (f64, f64)| ((p.0 - view_box.0) * sx, (p.1 - view_box.1) * sy);
+    let fill = rasterize(&shape.fill, style.fill_rule, &map, tex_size);
+    let stroke = rasterize(&shape.stroke, FillRule::NonZero, &map, tex_size);
+    let premultiply = |c: (f32, f32, f32, f32)| [c.0 * c.3, c.1 * c.3, c.2 * c.3, c.3];
+    let (fc, sc) = (premultiply(style.fill_color), premultiply(style.stroke_color));
+    let mut ret = Vec::with_capacity((tex_w * tex_h * 4) as usize);
+    for i in 0..(tex_w * tex_h) as usize {
+        let (f, s) = (fill[i].min(1.), stroke[i].min(1.));
+        // the stroke is painted over the fill
+        let sa = sc[3] * s;
+        for j in 0..4 {
+            let c = sc[j] * s + fc[j] * f * (1. - sa);
+            ret.push((c.max(0.).min(1.) * 255. + 0.5) as u8);
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn path_data() {
+        let (segs, err) = parse_path_data("M10 20l5-5H0v.5.5z m-9-19 Q2 2 3 1 T5 1");
+        assert_eq!(err, None);
+        assert_eq!(&segs[0..6], &[
+            PathSegment::MoveTo(10., 20.),
+            PathSegment::LineTo(15., 15.),
+            PathSegment::LineTo(0., 15.),
+            PathSegment::LineTo(0., 15.5),
+            PathSegment::LineTo(0., 16.),
+            PathSegment::Close,
+        ]);
+        assert_eq!(segs[6], PathSegment::MoveTo(1., 1.));
+        assert_eq!(segs[7], PathSegment::CubicTo(1. + 2. / 3., 1. + 2. / 3., 3. - 2. / 3., 1. + 2. / 3., 3., 1.));
+        assert_eq!(segs[8], PathSegment::CubicTo(3. + 2. / 3., 1. - 2. / 3., 5. - 2. / 3., 1. - 2. / 3., 5., 1.));
+        let (segs, err) = parse_path_data("M0 0 L10 0 L10 x");
+        assert_eq!(segs.len(), 2);
+        assert_eq!(err, Some(11));
+        let (segs, err) = parse_path_data("L0 0");
+        assert_eq!(segs.len(), 0);
+        assert_eq!(err, Some(0));
+        let (segs, err) = parse_path_data("M0 0a5 5 0 1110 0");
+        assert_eq!(err, None);
+        match segs[segs.len() - 1] {
+            PathSegment::CubicTo(_, _, _, _, x, y) => assert_eq!((x, y), (10., 0.)),
+            _ => panic!(),
+        }
+        let b = path_bounds(&segs).unwrap();
+        assert!(b.1 < -4.9 && b.1 > -7. && b.3.abs() < 1e-9);
+    }
+
+    #[test]
+    fn path_shape() {
+        let style = PathStyle {
+            fill_color: (0., 0., 0., 1.),
+            fill_rule: FillRule::EvenOdd,
+            stroke_color: (1., 0., 0., 1.),
+            stroke_width: 2.,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            miter_limit: 4.,
+        };
+        let (segs, _) = parse_path_data("M0 0H20V20H0Z M5 5H15V15H5Z");
+        let shape = build_path_shape(&segs, &style, 0.1);
+        assert!(path_shape_contains(&shape, &style, (2.5, 2.5)));
+        assert!(!path_shape_contains(&shape, &style, (10., 10.)));
+        assert!(path_shape_contains(&shape, &style, (-0.9, -0.9)));
+        assert!(!path_shape_contains(&shape, &style, (-1.1, 10.)));
+        let style = PathStyle { fill_rule: FillRule::NonZero, ..style };
+        assert!(path_shape_contains(&shape, &style, (10., 10.)));
+        let pixels = render_path(&shape, &style, (0., 0., 20., 20.), (20, 20));
+        assert_eq!(&pixels[(10 * 20 + 10) * 4..(10 * 20 + 11) * 4], &[0, 0, 0, 255]);
+        assert_eq!(&pixels[(10 * 20 + 0) * 4..(10 * 20 + 1) * 4], &[255, 0, 0, 255]);
+    }
+}
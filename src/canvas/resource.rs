@@ -1,12 +1,22 @@
+use std::rc::{Rc, Weak};
+use std::mem;
 use std::cell::RefCell;
-use std::collections::{BTreeMap, BinaryHeap};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
 use super::super::utils::PretendSend;
+use super::super::lib_interfaces::Backend;
+use super::display_list::{DisplayList, DrawCommand};
+use super::element::{Bounds, Point, Size, ImageLoader};
+use super::element::style::BackgroundImage;
+use super::gradient::render_gradient;
+use super::path::{PathSegment, PathStyle, build_path_shape, render_path};
+use super::atlas::{ImageAtlas, AtlasImage, ATLAS_SIZE, ATLAS_PADDING};
 
 lazy_static! {
     static ref IMAGE_ID_INC: PretendSend<RefCell<ResourceIdAllocator>> = PretendSend::new(RefCell::new(ResourceIdAllocator::new()));
 }
 
 const TEX_SHADER_INDEX_MAX: i32 = 16;
+const BLUR_MAX_STEPS: f64 = 32.;
 
 struct ResourceIdAllocator {
     inc: i32,
@@ -74,7 +84,34 @@ impl DrawState {
     }
 }
 
+/// The rounded rect for shape draws and shape clips, see `Backend::tex_set_draw_shape`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DrawShape {
+    pub size: (f32, f32),
+    pub radii: (f32, f32, f32, f32), // left-top, right-top, right-bottom, left-bottom
+    pub border: (f32, f32, f32, f32), // top, right, bottom, left
+    pub pixel_scale: f32,
+}
+
+struct GradientTexture {
+    image: BackgroundImage,
+    size: Size,
+    tex_size: (i32, i32),
+    tex_id: i32,
+    used: bool,
+}
+
+struct PathTexture {
+    segments: Vec<PathSegment>,
+    style: PathStyle,
+    view_box: (f64, f64, f64, f64),
+    tex_size: (i32, i32),
+    tex_id: i32,
+    used: bool,
+}
+
 pub struct ResourceManager {
+    backend: Rc<Backend>,
     canvas_index: i32,
     tex_max_draws: i32,
     tex_id_allocator: ResourceIdAllocator,
@@ -82,11 +119,26 @@ pub struct ResourceManager {
     used_shader_tex: i32,
     tex_shader_index_map: BTreeMap<i32, i32>,
     current_draw_state: DrawState,
+    current_draw_shape: Option<DrawShape>,
+    current_draw_blur: (f32, f32, f32),
+    blur_textures: Option<(i32, i32)>,
+    gradient_textures: Vec<GradientTexture>,
+    path_textures: Vec<PathTexture>,
+    image_loaders: HashMap<String, Weak<RefCell<ImageLoader>>>,
+    image_atlas: ImageAtlas,
+    atlas_staging_tex: i32,
+    atlas_pending: Vec<Weak<RefCell<ImageLoader>>>,
+    recorder: Option<DisplayList>,
+    deferred: Option<DisplayList>,
+    drawn_bounds: Option<Bounds>,
+    clip_bounds: Vec<Bounds>,
+    blur_regions: Vec<Bounds>,
 }
 
 impl ResourceManager {
-    pub fn new(canvas_index: i32, tex_max_draws: i32) -> Self {
+    pub fn new(backend: Rc<Backend>, canvas_index: i32, tex_max_draws: i32) -> Self {
         Self {
+            backend,
             canvas_index,
             tex_max_draws,
             tex_id_allocator: ResourceIdAllocator::new(),
@@ -94,6 +146,20 @@ impl ResourceManager {
             used_shader_tex: 0,
             tex_shader_index_map: BTreeMap::new(),
             current_draw_state: *DrawState::new().color((0., 0., 0., 1.)).alpha(1.),
+            current_draw_shape: None,
+            current_draw_blur: (0., 0., 0.),
+            blur_textures: None,
+            gradient_textures: vec![],
+            path_textures: vec![],
+            image_loaders: HashMap::new(),
+            image_atlas: ImageAtlas::new(),
+            atlas_staging_tex: -1,
+            atlas_pending: vec![],
+            recorder: None,
+            deferred: None,
+            drawn_bounds: None,
+            clip_bounds: vec![],
+            blur_regions: vec![],
         }
     }
 
@@ -117,10 +183,178 @@ impl ResourceManager {
         debug!("Free image id: {}", image_id);
     }
 
+    /// Start recording the drawing commands.
+    pub fn start_recording(&mut self) {
+        self.recorder = Some(DisplayList::new());
+    }
+    /// Stop recording and return the recorded commands.
+    pub fn stop_recording(&mut self) -> Option<DisplayList> {
+        self.recorder.take()
+    }
+    #[inline]
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Keep the following commands in a list instead of sending them to the backend, until `take_deferred`.
+    pub fn defer_drawing(&mut self) {
+        self.flush_draw();
+        self.deferred = Some(DisplayList::new());
+        // the deferred commands may be dropped, so they should not depend on the state sent before
+        self.resend_draw_state();
+    }
+    /// Stop deferring and return the deferred commands.
+    pub fn take_deferred(&mut self) -> DisplayList {
+        self.flush_draw();
+        self.deferred.take().unwrap_or_default()
+    }
+    /// Send commands to the backend, e.g. the ones returned by `take_deferred`.
+    pub fn send_display_list(&mut self, display_list: &DisplayList) {
+        self.flush_draw();
+        for cmd in display_list.commands() {
+            self.send(cmd);
+        }
+    }
+    #[inline]
+    fn send(&mut self, cmd: &DrawCommand) {
+        match self.recorder {
+            None => { },
+            Some(ref mut x) => {
+                x.push(cmd.clone());
+            }
+        }
+        cmd.replay(&*self.backend, self.canvas_index);
+    }
+    #[inline]
+    fn emit(&mut self, cmd: DrawCommand) {
+        match self.deferred {
+            None => { },
+            Some(ref mut x) => {
+                x.push(cmd);
+                return;
+            }
+        }
+        self.send(&cmd);
+    }
+
+    /// Take the bounds of all draws requested since the last call, in the coordinates of draws.
+    #[inline]
+    pub fn take_drawn_bounds(&mut self) -> Option<Bounds> {
+        self.drawn_bounds.take()
+    }
+    /// Merge bounds into the ones returned by the next `take_drawn_bounds`.
+    #[inline]
+    pub fn add_drawn_bounds(&mut self, bounds: Option<Bounds>) {
+        match bounds {
+            None => { },
+            Some(b) => {
+                match self.drawn_bounds {
+                    None => {
+                        self.drawn_bounds = Some(b);
+                    },
+                    Some(ref mut x) => {
+                        x.union(&b);
+                    }
+                }
+            }
+        }
+    }
+    /// Record the area (in canvas coordinates) a blurred draw depends on, which should be repainted as a whole.
+    #[inline]
+    pub fn add_blur_region(&mut self, region: Bounds) {
+        self.blur_regions.push(region);
+    }
+    #[inline]
+    pub fn blur_region_count(&self) -> usize {
+        self.blur_regions.len()
+    }
+    /// The union of the areas recorded by `add_blur_region` after the first `start` ones.
+    pub fn blur_bounds_since(&self, start: usize) -> Option<Bounds> {
+        self.blur_regions[start..].iter().fold(None, |ret, x| match ret {
+            None => Some(*x),
+            Some(mut ret) => {
+                ret.union(x);
+                Some(ret)
+            },
+        })
+    }
+    /// Take the areas recorded by `add_blur_region` since the last call.
+    #[inline]
+    pub fn take_blur_regions(&mut self) -> Vec<Bounds> {
+        ::std::mem::replace(&mut self.blur_regions, vec![])
+    }
+
+    pub fn clear(&mut self, color: (f32, f32, f32, f32)) {
+        self.flush_draw();
+        self.emit(DrawCommand::Clear { color });
+    }
+    /// Limit the following draws and clears to a rect, intersected with the current clip.
+    pub fn push_clip_rect(&mut self, left: f64, top: f64, width: f64, height: f64) {
+        self.flush_draw();
+        self.push_clip_bounds(Bounds::new(left.min(left + width), top.min(top + height), left.max(left + width), top.max(top + height)));
+        self.emit(DrawCommand::PushClipRect { rect: (left as f32, top as f32, width as f32, height as f32) });
+    }
+    /// Limit the following draws to a convex quad, intersected with the current clip.
+    /// Clears are limited to the bounding rect of the quad.
+    pub fn push_clip_quad(&mut self, points: [Point; 4]) {
+        self.flush_draw();
+        let points = self.push_clip_quad_bounds(points);
+        self.emit(DrawCommand::PushClipQuad { points });
+    }
+    /// Limit the following draws to a shape stretched to a convex quad, intersected with the current clip.
+    /// The points are the left-top, left-bottom, right-bottom and right-top corners of the shape.
+    pub fn push_clip_shape(&mut self, points: [Point; 4], shape: DrawShape) {
+        self.set_draw_shape(shape);
+        self.flush_draw();
+        let points = self.push_clip_quad_bounds(points);
+        self.emit(DrawCommand::PushClipShape { points });
+    }
+    fn push_clip_quad_bounds(&mut self, points: [Point; 4]) -> [(f32, f32); 4] {
+        let mut bounds = Bounds::from((points[0], points[0]));
+        for p in points.iter() {
+            bounds.union(&Bounds::from((*p, *p)));
+        }
+        self.push_clip_bounds(bounds);
+        let p: Vec<(f32, f32)> = points.iter().map(|p| (p.left() as f32, p.top() as f32)).collect();
+        [p[0], p[1], p[2], p[3]]
+    }
+    pub fn pop_clip(&mut self) {
+        self.flush_draw();
+        self.clip_bounds.pop();
+        self.emit(DrawCommand::PopClip);
+    }
+    #[inline]
+    fn push_clip_bounds(&mut self, mut bounds: Bounds) {
+        match self.clip_bounds.last() {
+            None => { },
+            Some(x) => {
+                bounds.intersection(x);
+            }
+        }
+        self.clip_bounds.push(bounds);
+    }
+
     #[inline]
     pub fn draw_state(&self) -> DrawState {
         self.current_draw_state
     }
+    /// Send the current draw state to the backend again, used when the backend state is changed outside.
+    pub fn resend_draw_state(&mut self) {
+        self.flush_draw();
+        let ds = self.current_draw_state;
+        let color = (ds.color.0 * ds.color.3, ds.color.1 * ds.color.3, ds.color.2 * ds.color.3, ds.color.3);
+        self.emit(DrawCommand::SetDrawState { color, alpha: ds.alpha });
+        match self.current_draw_shape.take() {
+            None => { },
+            Some(shape) => {
+                self.set_draw_shape(shape);
+            }
+        }
+        if self.current_draw_blur.2 > 0. {
+            let (step_x, step_y, sigma) = self.current_draw_blur;
+            self.emit(DrawCommand::SetDrawBlur { step: (step_x, step_y), sigma });
+        }
+    }
     pub fn set_draw_state(&mut self, ds: &mut DrawState) {
         if ds.color.0 < 0. { ds.color = self.current_draw_state.color; }
         if ds.alpha < 0. { ds.alpha = self.current_draw_state.alpha; }
@@ -129,15 +363,255 @@ impl ResourceManager {
         }
         self.current_draw_state = *ds;
         self.flush_draw();
-        lib!(tex_set_draw_state(self.canvas_index, ds.color.0 * ds.color.3, ds.color.1 * ds.color.3, ds.color.2 * ds.color.3, ds.color.3, ds.alpha));
+        let color = (ds.color.0 * ds.color.3, ds.color.1 * ds.color.3, ds.color.2 * ds.color.3, ds.color.3);
+        self.emit(DrawCommand::SetDrawState { color, alpha: ds.alpha });
+    }
+    /// Set the rounded rect for the following draws with tex id -3.
+    pub fn set_draw_shape(&mut self, shape: DrawShape) {
+        if self.current_draw_shape == Some(shape) {
+            return;
+        }
+        self.current_draw_shape = Some(shape);
+        self.flush_draw();
+        self.emit(DrawCommand::SetDrawShape {
+            size: shape.size,
+            radii: shape.radii,
+            border: shape.border,
+            pixel_scale: shape.pixel_scale,
+        });
+    }
+    /// Blur the texture sampling of the following draws along `step` (in normalized tex position), or stop blurring if `sigma` is 0.
+    /// A large sigma is applied with larger steps, since the backend samples at most `BLUR_MAX_STEPS` steps on each side.
+    pub fn set_draw_blur(&mut self, step: (f64, f64), sigma: f64) {
+        let (step, sigma) = if sigma <= 0. {
+            ((0., 0.), 0.)
+        } else if sigma * 3. > BLUR_MAX_STEPS {
+            let k = sigma * 3. / BLUR_MAX_STEPS;
+            ((step.0 * k, step.1 * k), sigma / k)
+        } else {
+            (step, sigma)
+        };
+        let blur = (step.0 as f32, step.1 as f32, sigma as f32);
+        if self.current_draw_blur == blur {
+            return;
+        }
+        self.current_draw_blur = blur;
+        self.flush_draw();
+        self.emit(DrawCommand::SetDrawBlur { step: (blur.0, blur.1), sigma: blur.2 });
     }
+    /// The two shared temporary textures for blurring, created on the first use.
+    pub fn blur_textures(&mut self) -> (i32, i32) {
+        match self.blur_textures {
+            Some(x) => x,
+            None => {
+                let a = self.alloc_tex_id();
+                let b = self.alloc_tex_id();
+                self.backend.tex_create_empty(self.canvas_index, a, 0, 0);
+                self.backend.tex_create_empty(self.canvas_index, b, 0, 0);
+                self.blur_textures = Some((a, b));
+                (a, b)
+            }
+        }
+    }
+    /// The texture of a gradient in a box of `size`, rendered with `tex_size` pixels.
+    /// The texture is cached until a frame does not use it, see `release_unused_textures`.
+    pub fn gradient_texture(&mut self, image: &BackgroundImage, size: Size, tex_size: (i32, i32)) -> i32 {
+        for t in self.gradient_textures.iter_mut() {
+            if t.image == *image && t.size == size && t.tex_size == tex_size {
+                t.used = true;
+                return t.tex_id;
+            }
+        }
+        let pixels = render_gradient(image, size, tex_size);
+        let tex_id = self.alloc_tex_id();
+        self.backend.tex_create_from_pixels(self.canvas_index, tex_id, tex_size.0, tex_size.1, &pixels);
+        self.gradient_textures.push(GradientTexture {
+            image: image.clone(),
+            size,
+            tex_size,
+            tex_id,
+            used: true,
+        });
+        tex_id
+    }
+    /// The texture of a path with the `view_box` (left, top, width, height) stretched to `tex_size` pixels.
+    /// The texture is cached the same as the gradients.
+    pub(crate) fn path_texture(&mut self, segments: &[PathSegment], style: &PathStyle, view_box: (f64, f64, f64, f64), tex_size: (i32, i32)) -> i32 {
+        for t in self.path_textures.iter_mut() {
+            if t.segments.as_slice() == segments && t.style == *style && t.view_box == view_box && t.tex_size == tex_size {
+                t.used = true;
+                return t.tex_id;
+            }
+        }
+        let scale = (tex_size.0 as f64 / view_box.2).max(tex_size.1 as f64 / view_box.3);
+        let shape = build_path_shape(segments, style, 0.25 / scale);
+        let pixels = render_path(&shape, style, view_box, tex_size);
+        let tex_id = self.alloc_tex_id();
+        self.backend.tex_create_from_pixels(self.canvas_index, tex_id, tex_size.0, tex_size.1, &pixels);
+        self.path_textures.push(PathTexture {
+            segments: segments.to_vec(),
+            style: *style,
+            view_box,
+            tex_size,
+            tex_id,
+            used: true,
+        });
+        tex_id
+    }
+    /// Get the image loader of the url, which is shared by the elements using it as the background image.
+    pub fn image_loader(&mut self, url: &str) -> Option<Rc<RefCell<ImageLoader>>> {
+        match self.image_loaders.get(url) {
+            None => None,
+            Some(x) => x.upgrade(),
+        }
+    }
+    pub fn set_image_loader(&mut self, url: String, loader: &Rc<RefCell<ImageLoader>>) {
+        self.image_loaders.retain(|_, x| x.upgrade().is_some());
+        self.image_loaders.insert(url, Rc::downgrade(loader));
+    }
+    /// Delete the cached textures which are not used since the last call, should be called after drawing a frame.
+    pub fn release_unused_textures(&mut self) {
+        let textures = mem::replace(&mut self.gradient_textures, vec![]);
+        for mut t in textures.into_iter() {
+            if t.used {
+                t.used = false;
+                self.gradient_textures.push(t);
+            } else {
+                self.backend.tex_delete(self.canvas_index, t.tex_id);
+                self.free_tex_id(t.tex_id);
+            }
+        }
+        let textures = mem::replace(&mut self.path_textures, vec![]);
+        for mut t in textures.into_iter() {
+            if t.used {
+                t.used = false;
+                self.path_textures.push(t);
+            } else {
+                self.backend.tex_delete(self.canvas_index, t.tex_id);
+                self.free_tex_id(t.tex_id);
+            }
+        }
+    }
+    /// Move the texture of a loaded image into the image atlas after drawing the current frame, if it is small enough.
+    pub fn request_image_atlas(&mut self, loader: &Rc<RefCell<ImageLoader>>) {
+        self.atlas_pending.push(Rc::downgrade(loader));
+    }
+    /// Pack the requested images into the atlas and release the unused pages, should be called after drawing a frame.
+    /// The commands are not a part of the frame, so they are not recorded.
+    pub fn update_image_atlas(&mut self) {
+        let recorder = self.recorder.take();
+        let drawn_bounds = self.drawn_bounds.take();
+        let mut ds = self.current_draw_state;
+        for tex_id in self.image_atlas.release_unused_pages() {
+            self.backend.tex_delete(self.canvas_index, tex_id);
+            self.free_tex_id(tex_id);
+        }
+        let pending = mem::replace(&mut self.atlas_pending, vec![]);
+        for loader in pending.into_iter().filter_map(|x| x.upgrade()) {
+            let mut loader = loader.borrow_mut();
+            let tex_id = loader.tex_id();
+            let (width, height) = loader.size();
+            if tex_id == -1 || !ImageAtlas::fits(width, height) { continue }
+            match self.alloc_atlas_image(width, height) {
+                None => { },
+                Some(image) => {
+                    self.copy_to_atlas(tex_id, &image);
+                    loader.set_atlas_image(image);
+                    self.backend.tex_delete(self.canvas_index, tex_id);
+                    self.free_tex_id(tex_id);
+                }
+            }
+        }
+        self.set_draw_state(&mut ds);
+        self.flush_draw();
+        self.drawn_bounds = drawn_bounds;
+        self.recorder = recorder;
+    }
+    // allocate in the atlas, repacking it or adding a page if there is no space
+    fn alloc_atlas_image(&mut self, width: i32, height: i32) -> Option<Rc<AtlasImage>> {
+        match self.image_atlas.alloc(width, height) {
+            None => { },
+            Some(x) => return Some(x),
+        }
+        if self.image_atlas.should_repack() {
+            self.repack_image_atlas();
+            match self.image_atlas.alloc(width, height) {
+                None => { },
+                Some(x) => return Some(x),
+            }
+        }
+        if !self.image_atlas.can_add_page() {
+            return None;
+        }
+        let tex_id = self.alloc_tex_id();
+        self.backend.tex_create_empty(self.canvas_index, tex_id, ATLAS_SIZE, ATLAS_SIZE);
+        self.image_atlas.add_page(tex_id);
+        self.image_atlas.alloc(width, height)
+    }
+    // draw the alive images from the old pages to new pages, so that the space of dropped images is reused
+    fn repack_image_atlas(&mut self) {
+        let (mut atlas, moves) = self.image_atlas.repacked();
+        for page in 0..atlas.page_tex_ids().len() {
+            let tex_id = self.alloc_tex_id();
+            self.backend.tex_create_empty(self.canvas_index, tex_id, 0, 0);
+            atlas.set_page_tex_id(page, tex_id);
+            self.bind_rendering_target(tex_id, ATLAS_SIZE, ATLAS_SIZE);
+            for m in moves.iter().filter(|m| m.page == page) {
+                let (_, _, width, height) = m.image.rect();
+                self.draw_atlas_image(m.image.tex_id(), m.image.tex_pos(), width, height, m.left, m.top);
+            }
+            self.unbind_rendering_target();
+        }
+        for m in moves.iter() {
+            atlas.apply_move(m);
+        }
+        let old = mem::replace(&mut self.image_atlas, atlas);
+        for tex_id in old.page_tex_ids() {
+            self.backend.tex_delete(self.canvas_index, tex_id);
+            self.free_tex_id(tex_id);
+        }
+        debug!("Image atlas repacked: {} images", moves.len());
+    }
+    // the page cannot be a rendering target without clearing it, so the image is drawn to a staging texture and copied
+    fn copy_to_atlas(&mut self, tex_id: i32, image: &AtlasImage) {
+        if self.atlas_staging_tex == -1 {
+            self.atlas_staging_tex = self.alloc_tex_id();
+            self.backend.tex_create_empty(self.canvas_index, self.atlas_staging_tex, 0, 0);
+        }
+        let (left, top, width, height) = image.rect();
+        let p = ATLAS_PADDING;
+        let staging = self.atlas_staging_tex;
+        self.bind_rendering_target(staging, width + p * 2, height + p * 2);
+        self.draw_atlas_image(tex_id, (0., 0., 1., 1.), width, height, p, p);
+        self.flush_draw();
+        self.emit(DrawCommand::CopyTex { dest_tex_id: image.tex_id(), dest: (left - p, top - p), src: (0, 0), size: (width + p * 2, height + p * 2) });
+        self.unbind_rendering_target();
+    }
+    // draw a texture pixel to pixel, with its edges extended to the padding so that sampling near the edges is not affected by other images
+    fn draw_atlas_image(&mut self, tex_id: i32, tex_pos: (f64, f64, f64, f64), width: i32, height: i32, left: i32, top: i32) {
+        self.set_draw_state(DrawState::new().alpha(1.));
+        let p = ATLAS_PADDING as f64;
+        // the (offset, size, tex offset, tex size) of the padding, the image and the other padding along an axis
+        let parts = |length: f64| [(-p, p, 0., 1. / length), (0., length, 0., 1.), (length, p, 1. - 1. / length, 1. / length)];
+        for &(y, h, ty, th) in parts(height as f64).iter() {
+            for &(x, w, tx, tw) in parts(width as f64).iter() {
+                let (l, t) = (left as f64 + x, top as f64 + y);
+                self.request_draw(
+                    tex_id, false,
+                    tex_pos.0 + tx * tex_pos.2, tex_pos.1 + ty * tex_pos.3, tw * tex_pos.2, th * tex_pos.3,
+                    [Point::new(l, t), Point::new(l, t + h), Point::new(l + w, t + h), Point::new(l + w, t)]
+                );
+            }
+        }
+    }
+    /// Draw a part of the texture to a quad.
+    /// The corners are in the order of left-top, left-bottom, right-bottom and right-top of the texture, the same as `Transform::apply_to_quad`.
     #[inline]
     pub fn request_draw(&mut self,
         tex_id: i32, use_color: bool,
         tex_left: f64, tex_top: f64, tex_width: f64, tex_height: f64,
-        (left, top, width, height): (f64, f64, f64, f64)
+        quad: [Point; 4]
     ) {
-        // FIXME ignore draws that exceed viewport
         if self.pending_draws == self.tex_max_draws {
             self.flush_draw();
         }
@@ -161,39 +635,62 @@ impl ResourceManager {
         } else {
             tex_shader_index = tex_id;
         }
-        lib!(tex_draw(self.canvas_index,
-            self.pending_draws, tex_shader_index + (if tex_id < 0 || use_color { 0 } else { 256 }),
-            tex_left as f32, tex_top as f32, tex_width as f32, tex_height as f32,
-            left as f32, top as f32, width as f32, height as f32
-        ));
+        let tex_shader_index = tex_shader_index + (if tex_id < 0 || use_color { 0 } else { 256 });
+        let draw_index = self.pending_draws;
+        self.emit(DrawCommand::Draw {
+            draw_index,
+            tex_shader_index,
+            tex_pos: (tex_left as f32, tex_top as f32, tex_width as f32, tex_height as f32),
+            quad: [
+                (quad[0].left() as f32, quad[0].top() as f32),
+                (quad[1].left() as f32, quad[1].top() as f32),
+                (quad[2].left() as f32, quad[2].top() as f32),
+                (quad[3].left() as f32, quad[3].top() as f32),
+            ],
+        });
         self.pending_draws += 1;
+        let mut bounds = Bounds::from((quad[0], quad[0]));
+        for p in quad.iter() {
+            bounds.union(&Bounds::from((*p, *p)));
+        }
+        match self.clip_bounds.last() {
+            None => { },
+            Some(x) => {
+                bounds.intersection(x);
+                if bounds.width() <= 0. || bounds.height() <= 0. {
+                    return;
+                }
+            }
+        }
+        self.add_drawn_bounds(Some(bounds));
     }
     #[inline]
     pub fn flush_draw(&mut self) {
         if self.pending_draws == 0 { return }
         let mut t_max = 0;
-        for (tex_id, t) in self.tex_shader_index_map.iter() {
-            lib!(tex_set_active_texture(self.canvas_index, *t, *tex_id));
+        let tex_shader_index_map = ::std::mem::replace(&mut self.tex_shader_index_map, BTreeMap::new());
+        for (tex_id, t) in tex_shader_index_map.iter() {
+            self.emit(DrawCommand::SetActiveTexture { tex_shader_index: *t, tex_id: *tex_id });
             if t_max < *t {
                 t_max = *t;
             }
         }
         for t in t_max + 1 .. TEX_SHADER_INDEX_MAX {
-            lib!(tex_set_active_texture(self.canvas_index, t, -1));
+            self.emit(DrawCommand::SetActiveTexture { tex_shader_index: t, tex_id: -1 });
         }
-        lib!(tex_draw_end(self.canvas_index, self.pending_draws));
+        let draw_count = self.pending_draws;
+        self.emit(DrawCommand::DrawEnd { draw_count });
         self.pending_draws = 0;
         self.used_shader_tex = 0;
-        self.tex_shader_index_map.clear();
     }
     #[inline]
     pub fn bind_rendering_target(&mut self, tex_id: i32, width: i32, height: i32) {
         self.flush_draw();
-        lib!(tex_bind_rendering_target(self.canvas_index, tex_id, width, height));
+        self.emit(DrawCommand::BindRenderingTarget { tex_id, width, height });
     }
     #[inline]
     pub fn unbind_rendering_target(&mut self) {
         self.flush_draw();
-        lib!(tex_unbind_rendering_target(self.canvas_index));
+        self.emit(DrawCommand::UnbindRenderingTarget);
     }
 }
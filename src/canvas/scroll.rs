@@ -0,0 +1,232 @@
+use std::time::Instant;
+use rc_forest::ForestNodeRc;
+use super::element::{Element, Point, Size};
+
+// the velocity is in px/ms
+const VELOCITY_SMOOTHING: f64 = 0.8;
+const INERTIA_DECAY_PER_MS: f64 = 0.997;
+const INERTIA_MIN_VELOCITY: f64 = 0.02;
+const RELEASE_TIMEOUT_MS: f64 = 100.;
+
+/// The touch-dragging state of a canvas, which keeps scrolling with inertial momentum after the touch ends.
+/// The velocity is in the coordinates of the container, so are the deltas of the inertial scrolling.
+pub(crate) struct ScrollGesture {
+    target: Option<ForestNodeRc<Element>>,
+    last_point: Point,
+    last_time: Instant,
+    velocity: (f64, f64),
+    inertia: bool,
+}
+
+#[inline]
+fn elapsed_ms(from: Instant, to: Instant) -> f64 {
+    if to <= from { return 0. }
+    let d = to - from;
+    d.as_secs() as f64 * 1000. + d.subsec_nanos() as f64 / 1000000.
+}
+
+impl ScrollGesture {
+    pub(crate) fn new() -> Self {
+        Self {
+            target: None,
+            last_point: Point::new(0., 0.),
+            last_time: Instant::now(),
+            velocity: (0., 0.),
+            inertia: false,
+        }
+    }
+    /// Start dragging the innermost scroll container under the point. Any inertial scrolling is stopped.
+    pub(crate) fn touch_start(&mut self, root: &ForestNodeRc<Element>, point: Point, time: Instant) {
+        self.target = root.borrow().scroll_container_under_point(point, Size::new(0., 0.));
+        self.last_point = point;
+        self.last_time = time;
+        self.velocity = (0., 0.);
+        self.inertia = false;
+    }
+    /// Returns the container and the scroll delta for the move.
+    pub(crate) fn touch_move(&mut self, point: Point, time: Instant) -> Option<(ForestNodeRc<Element>, f64, f64)> {
+        if self.inertia { return None }
+        let target = match self.target {
+            None => return None,
+            Some(ref x) => x.clone(),
+        };
+        // the delta is in the coordinates of the container, so that the content follows the touch if it is transformed
+        let delta = {
+            let t = target.borrow();
+            match (t.global_to_local(self.last_point), t.global_to_local(point)) {
+                (Some(a), Some(b)) => (a.left() - b.left(), a.top() - b.top()),
+                _ => (0., 0.),
+            }
+        };
+        let dt = elapsed_ms(self.last_time, time);
+        if dt > 0. {
+            self.velocity = (
+                self.velocity.0 * (1. - VELOCITY_SMOOTHING) + delta.0 / dt * VELOCITY_SMOOTHING,
+                self.velocity.1 * (1. - VELOCITY_SMOOTHING) + delta.1 / dt * VELOCITY_SMOOTHING,
+            );
+        }
+        self.last_point = point;
+        self.last_time = time;
+        Some((target, delta.0, delta.1))
+    }
+    /// Stop dragging, and start inertial scrolling if the touch is released while moving.
+    pub(crate) fn touch_end(&mut self, time: Instant) {
+        if self.target.is_none() || self.inertia { return }
+        let (vx, vy) = self.velocity;
+        if elapsed_ms(self.last_time, time) > RELEASE_TIMEOUT_MS || vx.abs().max(vy.abs()) < INERTIA_MIN_VELOCITY {
+            self.stop();
+            return;
+        }
+        self.last_time = time;
+        self.inertia = true;
+    }
+    /// Returns the container and the scroll delta of inertial scrolling for a frame.
+    pub(crate) fn inertia_step(&mut self, time: Instant) -> Option<(ForestNodeRc<Element>, f64, f64)> {
+        if !self.inertia { return None }
+        let target = match self.target {
+            None => return None,
+            Some(ref x) => x.clone(),
+        };
+        let dt = elapsed_ms(self.last_time, time);
+        self.last_time = time;
+        let decay = INERTIA_DECAY_PER_MS.powf(dt);
+        let (vx, vy) = (self.velocity.0 * decay, self.velocity.1 * decay);
+        // the distance is the integral of the velocity in the frame
+        let ratio = if dt > 0. { (1. - decay) / -INERTIA_DECAY_PER_MS.ln() } else { 0. };
+        let delta = (self.velocity.0 * ratio, self.velocity.1 * ratio);
+        self.velocity = (vx, vy);
+        if vx.abs().max(vy.abs()) < INERTIA_MIN_VELOCITY {
+            self.stop();
+        }
+        Some((target, delta.0, delta.1))
+    }
+    #[inline]
+    pub(crate) fn stop(&mut self) {
+        self.target = None;
+        self.velocity = (0., 0.);
+        self.inertia = false;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use std::cell::RefCell;
+    use std::time::{Instant, Duration};
+    use rc_forest::ForestNodeRc;
+    use super::ScrollGesture;
+    use super::super::element::{Element, Empty, Event, Point, Transform, ScrollEventDetail};
+    use super::super::element::style::{DisplayType, OverflowType};
+    use super::super::super::lib_interfaces::{TestCanvas, run_canvas_test};
+    use super::super::super::frame;
+
+    // a 50x50 container scaled twice, with a 50x200 child; returns the container, the child and the offsets of the scroll events
+    fn scroll_container(t: &TestCanvas) -> (ForestNodeRc<Element>, ForestNodeRc<Element>, Rc<RefCell<Vec<(f64, f64)>>>) {
+        let events = Rc::new(RefCell::new(vec![]));
+        let ctx = t.canvas.context();
+        let mut ctx = ctx.borrow_mut();
+        let cfg = ctx.canvas_config();
+        let root = ctx.root();
+        let mut root = root.borrow_mut();
+        let container = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        let child = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+        {
+            let c = container.deref_mut_with(&mut root);
+            c.style_mut().display(DisplayType::Block);
+            c.style_mut().width(50.);
+            c.style_mut().height(50.);
+            c.style_mut().overflow(OverflowType::Scroll);
+            c.style_mut().transform(Transform::new().scale(2., 2.).clone());
+            let events = events.clone();
+            c.add_event_listener(String::from("scroll"), Rc::new(RefCell::new(move |_: &mut Element, event: &Event| {
+                let detail = event.detail.downcast_ref::<ScrollEventDetail>().unwrap();
+                events.borrow_mut().push((detail.scroll_left, detail.scroll_top));
+            })));
+        }
+        {
+            let c = child.deref_mut_with(&mut root);
+            c.style_mut().display(DisplayType::Block);
+            c.style_mut().width(50.);
+            c.style_mut().height(200.);
+        }
+        container.deref_mut_with(&mut root).append(child.clone());
+        root.append(container.clone());
+        (container, child, events)
+    }
+
+    #[test]
+    fn scroll_to() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let (container, _, events) = scroll_container(&t);
+            frame::generate(Instant::now());
+            let mut c = container.borrow_mut();
+            assert_eq!(c.scroll_size().height(), 200.);
+            assert!(c.scroll_to(10., 1000.));
+            assert_eq!((c.scroll_left(), c.scroll_top()), (0., 150.));
+            assert!(!c.scroll_to(0., 150.));
+            assert!(c.scroll_by(0., -50.));
+            assert_eq!(*events.borrow(), vec![(0., 150.), (0., 100.)]);
+        });
+    }
+
+    #[test]
+    fn clamp_after_layout() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let (container, child, events) = scroll_container(&t);
+            frame::generate(Instant::now());
+            container.borrow_mut().scroll_to(0., 150.);
+            child.borrow_mut().style_mut().height(100.);
+            frame::generate(Instant::now());
+            assert_eq!(container.borrow().scroll_top(), 50.);
+            assert_eq!(*events.borrow(), vec![(0., 150.), (0., 50.)]);
+        });
+    }
+
+    #[test]
+    fn touch_in_local_space() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let (container, _, events) = scroll_container(&t);
+            frame::generate(Instant::now());
+            // the container is scaled twice, so the content moves half of the touch
+            t.backend.touch(1, 50, 60);
+            t.backend.touch(2, 50, 40);
+            assert_eq!(container.borrow().scroll_top(), 10.);
+            t.backend.touch(4, 50, 40);
+            assert_eq!(*events.borrow(), vec![(0., 10.)]);
+        });
+    }
+
+    #[test]
+    fn inertia() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let (_, _, _) = scroll_container(&t);
+            frame::generate(Instant::now());
+            let root = t.canvas.context().borrow_mut().root();
+            let start = Instant::now();
+            let ms = |x| start + Duration::from_millis(x);
+            let mut gesture = ScrollGesture::new();
+            gesture.touch_start(&root, Point::new(50., 60.), start);
+            let (_, dx, dy) = gesture.touch_move(Point::new(50., 40.), ms(10)).unwrap();
+            assert_eq!((dx, dy), (0., 10.));
+            gesture.touch_end(ms(10));
+            assert!(gesture.touch_move(Point::new(50., 20.), ms(12)).is_none());
+            // 0.8px/ms in the container, decaying in the frame
+            let (_, dx, dy) = gesture.inertia_step(ms(26)).unwrap();
+            assert_eq!(dx, 0.);
+            assert!(dy > 12. && dy < 0.8 * 16.);
+            let (_, _, next) = gesture.inertia_step(ms(42)).unwrap();
+            assert!(next < dy);
+            gesture.inertia_step(ms(10000));
+            assert!(gesture.inertia_step(ms(10016)).is_none());
+            // no inertia if the touch is held before released
+            gesture.touch_start(&root, Point::new(50., 60.), ms(20000));
+            gesture.touch_move(Point::new(50., 40.), ms(20010));
+            gesture.touch_end(ms(20200));
+            assert!(gesture.inertia_step(ms(20216)).is_none());
+        });
+    }
+}
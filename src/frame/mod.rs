@@ -4,6 +4,7 @@ use std::rc::Rc;
 use std::cell::{RefCell};
 use std::time;
 use super::utils::PretendSend;
+use super::lib_interfaces::{Backend, DefaultBackend};
 
 pub enum FramePriority {
     High,
@@ -30,63 +31,71 @@ impl FrameCallback {
     }
 }
 
+// a frame callback with the backend scheduling it
+type BoundFrame = (FrameCallback, Rc<Backend>);
+
 #[derive(Clone)]
 struct FrameObjectsGroup {
-    count: u32,
-    high: Vec<FrameCallback>,
-    normal: Vec<FrameCallback>,
-    low: Vec<FrameCallback>,
+    high: Vec<BoundFrame>,
+    normal: Vec<BoundFrame>,
+    low: Vec<BoundFrame>,
 }
 
 impl FrameObjectsGroup {
     fn new() -> Self {
         Self {
-            count: 0,
             high: Vec::new(),
             normal: Vec::new(),
             low: Vec::new(),
         }
     }
     #[inline]
-    fn get(&mut self, priority: FramePriority) -> &mut Vec<FrameCallback> {
+    fn get(&mut self, priority: FramePriority) -> &mut Vec<BoundFrame> {
         match priority {
             FramePriority::High => &mut self.high,
             FramePriority::Normal => &mut self.normal,
             FramePriority::Low => &mut self.low,
         }
     }
+    fn is_backend_used(&self, backend: &Rc<Backend>) -> bool {
+        self.high.iter().chain(self.normal.iter()).chain(self.low.iter()).any(|x| Rc::ptr_eq(&x.1, backend))
+    }
 }
 
+/// Bind a frame callback scheduled by the lib functions of the current platform.
+#[inline]
 pub fn bind(f: FrameCallback, priority: FramePriority) {
+    bind_with_backend(f, priority, Rc::new(DefaultBackend::new()));
+}
+
+/// Bind a frame callback scheduled by the backend, e.g. the one of the canvas which draws in the callback.
+/// The animation frames of the backend are enabled while any callback is bound with it.
+pub fn bind_with_backend(f: FrameCallback, priority: FramePriority, backend: Rc<Backend>) {
     let mut fog = FRAME_OBJECTS.borrow_mut();
-    if fog.count == 0 {
-        lib!(enable_animation_frame());
+    if !fog.is_backend_used(&backend) {
+        backend.enable_animation_frame();
     }
-    fog.count += 1;
-    let frame_objects = fog.get(priority);
-    frame_objects.push(f);
+    fog.get(priority).push((f, backend));
 }
 
 pub fn unbind(f: FrameCallback, priority: FramePriority) -> bool {
     let mut fog = FRAME_OBJECTS.borrow_mut();
-    let ret = {
+    let removed = {
         let frame_objects = fog.get(priority);
-        let ret = match frame_objects.iter().position(|x| Rc::ptr_eq(&x.f, &f.f)) {
-            None => false,
-            Some(index) => {
-                frame_objects.remove(index);
-                true
-            }
-        };
-        ret
+        match frame_objects.iter().position(|x| Rc::ptr_eq(&x.0.f, &f.f)) {
+            None => None,
+            Some(index) => Some(frame_objects.remove(index).1),
+        }
     };
-    if ret {
-        fog.count -= 1;
-        if fog.count == 0 {
-            lib!(disable_animation_frame());
+    match removed {
+        None => false,
+        Some(backend) => {
+            if !fog.is_backend_used(&backend) {
+                backend.disable_animation_frame();
+            }
+            true
         }
     }
-    ret
 }
 
 #[macro_export]
@@ -98,11 +107,11 @@ macro_rules! frame {
 
 macro_rules! exec {
     ($x: expr, $y: expr) => {
-        |x: &mut FrameCallback| {
-            let f = &mut *x.f.borrow_mut();
+        |x: &mut BoundFrame| {
+            let f = &mut *x.0.f.borrow_mut();
             let ret = f($y);
             if ret == false {
-                unbind(x.clone(), $x);
+                unbind(x.0.clone(), $x);
             }
         }
     }
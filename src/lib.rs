@@ -11,6 +11,8 @@ extern crate image;
 extern crate euclid;
 #[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
 extern crate font_kit;
+#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+extern crate xml;
 
 pub extern crate rc_forest;
 extern crate glayout_element_style_macro;
@@ -43,9 +45,16 @@ lib_define_callback!(TimeoutCallback (Box<Fn() + 'static>) {
     }
 });
 
+pub(crate) fn duration_to_ms(dur: time::Duration) -> i32 {
+    dur.as_secs() as i32 * 1000 + (dur.subsec_nanos() as f64 / 1_000_000.).ceil() as i32
+}
+
+pub(crate) fn timeout_callback<F>(f: F) -> *mut Box<lib_interfaces::Callback> where F: Fn() + 'static {
+    lib_callback!(TimeoutCallback(Box::new(f)))
+}
+
 pub fn set_timeout<F>(f: F, dur: time::Duration) where F: Fn() + 'static {
-    let ms = dur.as_secs() as i32 * 1000 + (dur.subsec_nanos() as f64 / 1_000_000.).ceil() as i32;
-    lib!(timeout(ms, lib_callback!(TimeoutCallback(Box::new(f)))));
+    lib!(timeout(duration_to_ms(dur), timeout_callback(f)));
 }
 
 pub fn init() {
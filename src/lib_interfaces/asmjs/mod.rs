@@ -26,27 +26,40 @@ extern {
     pub fn set_clear_color(canvasIndex: i32, r: f32, g: f32, b: f32, a: f32);
     pub fn clear(canvasIndex: i32);
     pub fn bind_touch_events(canvasIndex: i32, cbPtr: *mut Box<Callback>);
+    pub fn bind_wheel_events(canvasIndex: i32, cbPtr: *mut Box<Callback>);
     pub fn bind_keyboard_events(canvasIndex: i32, cbPtr: *mut Box<Callback>);
     pub fn bind_canvas_size_change(canvasIndex: i32, cbPtr: *mut Box<Callback>);
 
     pub fn tex_get_size(canvasIndex: i32) -> i32;
     pub fn tex_get_count(canvasIndex: i32) -> i32;
     pub fn tex_get_max_draws() -> i32;
+    pub fn tex_get_pixels(canvasIndex: i32, left: i32, top: i32, width: i32, height: i32, buf: *mut u8);
     pub fn tex_create_empty(canvasIndex: i32, texId: i32, width: i32, height: i32);
+    pub fn tex_create_from_pixels(canvasIndex: i32, texId: i32, width: i32, height: i32, buf: *const u8);
     pub fn tex_copy(canvasIndex: i32, destTexId: i32, destLeft: i32, destTop: i32, srcLeft: i32, srcTop: i32, width: i32, height: i32);
     pub fn tex_bind_rendering_target(canvasIndex: i32, texId: i32, width: i32, height: i32);
     pub fn tex_unbind_rendering_target(canvasIndex: i32);
     pub fn tex_delete(canvasIndex: i32, texId: i32);
-    pub fn tex_draw(canvasIndex: i32, drawIndex: i32, texShaderIndex: i32, normalizedTexX: f32, normalizedTexY: f32, normalizedTexW: f32, normalizedTexH: f32, x: f32, y: f32, w: f32, h: f32);
+    pub fn tex_draw(canvasIndex: i32, drawIndex: i32, texShaderIndex: i32, normalizedTexX: f32, normalizedTexY: f32, normalizedTexW: f32, normalizedTexH: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
     pub fn tex_set_active_texture(canvasIndex: i32, texShaderIndex: i32, texId: i32);
     pub fn tex_draw_end(canvasIndex: i32, drawCount: i32);
     pub fn tex_set_draw_state(canvasIndex: i32, colorR: f32, colorG: f32, colorB: f32, colorA: f32, alpha: f32);
+    pub fn tex_set_draw_shape(canvasIndex: i32, width: f32, height: f32, radiusLT: f32, radiusRT: f32, radiusRB: f32, radiusLB: f32, borderTop: f32, borderRight: f32, borderBottom: f32, borderLeft: f32, pixelScale: f32);
+    pub fn tex_set_draw_blur(canvasIndex: i32, stepX: f32, stepY: f32, sigma: f32);
+    pub fn tex_push_clip_rect(canvasIndex: i32, left: f32, top: f32, width: f32, height: f32);
+    pub fn tex_push_clip_quad(canvasIndex: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
+    pub fn tex_push_clip_shape(canvasIndex: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
+    pub fn tex_pop_clip(canvasIndex: i32);
 
     pub fn image_load_url(id: i32, url: *mut c_char, cbPtr: *mut Box<Callback>);
+    pub fn image_load_bytes(id: i32, buf: *const u8, len: i32, cbPtr: *mut Box<Callback>);
     pub fn image_unload(id: i32);
+    pub fn image_get_error(id: i32, buf: *mut u8, len: i32) -> i32;
     pub fn image_get_natural_width(id: i32) -> i32;
     pub fn image_get_natural_height(id: i32) -> i32;
+    pub fn image_is_vector(id: i32) -> i32;
     pub fn tex_from_image(canvasIndex: i32, texId: i32, imgId: i32);
+    pub fn tex_from_image_with_size(canvasIndex: i32, texId: i32, imgId: i32, width: i32, height: i32);
 
     pub fn text_bind_font_family(id: i32, fontFamily: *mut c_char);
     pub fn text_unbind_font_family(id: i32);
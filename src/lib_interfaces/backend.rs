@@ -0,0 +1,331 @@
+use std::ffi::CString;
+use std::cell::Cell;
+use super::Callback;
+use super::super::utils::PretendSend;
+
+/// The error code of image loading for unknown reasons.
+pub const IMAGE_LOAD_ERROR: i32 = -1;
+/// The error code of image loading when the file or url cannot be read.
+pub const IMAGE_LOAD_ERROR_READ: i32 = -2;
+/// The error code of image loading when the content is corrupt or in an unsupported format.
+pub const IMAGE_LOAD_ERROR_DECODE: i32 = -3;
+
+/// The rendering backend of a canvas.
+/// The canvas, its resource manager, character manager and image loaders call the backend instead of the global lib functions,
+/// so that a canvas can be drawn by a custom backend (e.g. a mock backend which records calls).
+/// The methods mirror the lib functions, see `DefaultBackend` for the forwarding.
+pub trait Backend {
+    /// Call the callback after `ms` milliseconds.
+    fn timeout(&self, ms: i32, cb_ptr: *mut Box<Callback>);
+    /// Call `frame::generate` for every frame until `disable_animation_frame`.
+    fn enable_animation_frame(&self);
+    fn disable_animation_frame(&self);
+
+    fn bind_canvas(&self, canvas_index: i32);
+    fn unbind_canvas(&self, canvas_index: i32);
+    fn set_canvas_size(&self, canvas_index: i32, w: i32, h: i32, pixel_ratio: f64, update_logical_size: bool);
+    fn get_canvas_width(&self, canvas_index: i32) -> i32;
+    fn get_canvas_height(&self, canvas_index: i32) -> i32;
+    fn get_device_pixel_ratio(&self, canvas_index: i32) -> f64;
+    fn set_clear_color(&self, canvas_index: i32, r: f32, g: f32, b: f32, a: f32);
+    fn clear(&self, canvas_index: i32);
+    fn bind_touch_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>);
+    /// The callback is called with the position and the scroll delta in px, i.e. `(x, y, delta_x, delta_y)`.
+    fn bind_wheel_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>);
+    fn bind_keyboard_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>);
+    fn bind_canvas_size_change(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>);
+
+    fn tex_get_size(&self, canvas_index: i32) -> i32;
+    fn tex_get_count(&self, canvas_index: i32) -> i32;
+    fn tex_get_max_draws(&self) -> i32;
+    /// Read premultiplied RGBA pixels of the canvas, rows from top to bottom.
+    fn tex_get_pixels(&self, canvas_index: i32, left: i32, top: i32, width: i32, height: i32, buf: &mut [u8]);
+    fn tex_create_empty(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32);
+    /// Create a texture from premultiplied RGBA pixels, rows from top to bottom.
+    fn tex_create_from_pixels(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32, buf: &[u8]);
+    fn tex_copy(&self, canvas_index: i32, dest_tex_id: i32, dest_left: i32, dest_top: i32, src_left: i32, src_top: i32, width: i32, height: i32);
+    /// Draw into the texture in its pixels instead of the canvas.
+    /// The clips pushed before do not apply to it, and apply again after it is unbound.
+    fn tex_bind_rendering_target(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32);
+    fn tex_unbind_rendering_target(&self, canvas_index: i32);
+    fn tex_delete(&self, canvas_index: i32, tex_id: i32);
+    /// Set a draw of a part of the texture to a quad.
+    /// The corners are in the order of left-top, left-bottom, right-bottom and right-top of the texture.
+    fn tex_draw(&self, canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
+    fn tex_set_active_texture(&self, canvas_index: i32, tex_shader_index: i32, tex_id: i32);
+    fn tex_draw_end(&self, canvas_index: i32, draw_count: i32);
+    fn tex_set_draw_state(&self, canvas_index: i32, color_r: f32, color_g: f32, color_b: f32, color_a: f32, alpha: f32);
+    /// Set the rounded rect for the draws with `tex_shader_index` -3 and for `tex_push_clip_shape`.
+    /// The rounded rect spans from (0, 0) to (width, height) in the tex position of these draws.
+    /// The radii are in the order of left-top, right-top, right-bottom and left-bottom.
+    /// If any border width is positive, only the ring between the rect and the rect inside the borders is drawn.
+    /// The `pixel_scale` is the device pixels of one unit, used for anti-aliasing.
+    fn tex_set_draw_shape(&self, canvas_index: i32, width: f32, height: f32, radius_lt: f32, radius_rt: f32, radius_rb: f32, radius_lb: f32, border_top: f32, border_right: f32, border_bottom: f32, border_left: f32, pixel_scale: f32);
+    /// Set the gaussian blur for the following texture draws, which samples the texture in a line.
+    /// The step is a vector in the normalized tex position, and `sigma` (the standard deviation) is in steps.
+    /// The samples are at most `ceil(sigma * 3)` steps away, which should not be more than 32 steps.
+    /// There is no blur if `sigma` is not positive.
+    fn tex_set_draw_blur(&self, canvas_index: i32, step_x: f32, step_y: f32, sigma: f32);
+    /// Limit the following draws and clears to a rect (in the coordinates of draws), intersected with the current clip.
+    fn tex_push_clip_rect(&self, canvas_index: i32, left: f32, top: f32, width: f32, height: f32);
+    /// Limit the following draws to a convex quad, intersected with the current clip.
+    /// Clears are only limited to the bounding rect of the quad.
+    fn tex_push_clip_quad(&self, canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
+    /// Limit the following draws to the current draw shape stretched to a convex quad, intersected with the current clip.
+    /// The quad corners are the left-top, left-bottom, right-bottom and right-top corners of the shape.
+    /// Clears are only limited to the bounding rect of the quad.
+    fn tex_push_clip_shape(&self, canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32);
+    /// Restore the clip before the last `tex_push_clip_rect`, `tex_push_clip_quad` or `tex_push_clip_shape`.
+    fn tex_pop_clip(&self, canvas_index: i32);
+
+    /// Load an image. The callback should be called with `0` as the first argument when succeeded,
+    /// or an error code, i.e. `IMAGE_LOAD_ERROR`, `IMAGE_LOAD_ERROR_READ` or `IMAGE_LOAD_ERROR_DECODE`.
+    fn image_load_url(&self, id: i32, url: &str, cb_ptr: *mut Box<Callback>);
+    /// Load an image from the encoded bytes, e.g. the content of a PNG or SVG file. The callback is the same as `image_load_url`.
+    fn image_load_bytes(&self, id: i32, buf: &[u8], cb_ptr: *mut Box<Callback>);
+    fn image_unload(&self, id: i32);
+    /// The message of the last failure of loading the image, e.g. the IO or decoding error, or an empty string if unknown.
+    /// It should be called before `image_unload`.
+    fn image_get_error(&self, id: i32) -> String;
+    fn image_get_natural_width(&self, id: i32) -> i32;
+    fn image_get_natural_height(&self, id: i32) -> i32;
+    /// Whether the image is a vector image, i.e. SVG, which can be rendered at any size.
+    fn image_is_vector(&self, id: i32) -> bool;
+    fn tex_from_image(&self, canvas_index: i32, tex_id: i32, img_id: i32);
+    /// Create the texture of a vector image rendered with `width` x `height` pixels.
+    /// The raster images always use the natural size.
+    fn tex_from_image_with_size(&self, canvas_index: i32, tex_id: i32, img_id: i32, width: i32, height: i32);
+
+    fn text_bind_font_family(&self, id: i32, font_family: &str);
+    fn text_unbind_font_family(&self, id: i32);
+    fn text_set_font(&self, font_size: i32, line_height: i32, font_family_id: i32, italic: bool, bold: bool);
+    fn text_get_width(&self, text: &str) -> f64;
+    fn text_to_tex(&self, canvas_index: i32, tex_id: i32, tex_left: i32, tex_top: i32, text: &str, width: i32, height: i32, line_height: i32);
+}
+
+lazy_static! {
+    // the lib animation frames are shared by all default backends
+    static ref DEFAULT_ANIMATION_FRAME_USERS: PretendSend<Cell<u32>> = PretendSend::new(Cell::new(0));
+}
+
+/// The backend which forwards to the lib functions of the current platform.
+#[derive(Default, Clone, Copy, Debug)]
+pub struct DefaultBackend { }
+
+impl DefaultBackend {
+    pub fn new() -> Self {
+        Self { }
+    }
+}
+
+impl Backend for DefaultBackend {
+    #[inline]
+    fn timeout(&self, ms: i32, cb_ptr: *mut Box<Callback>) {
+        lib!(timeout(ms, cb_ptr))
+    }
+    #[inline]
+    fn enable_animation_frame(&self) {
+        let users = DEFAULT_ANIMATION_FRAME_USERS.get();
+        if users == 0 {
+            lib!(enable_animation_frame())
+        }
+        DEFAULT_ANIMATION_FRAME_USERS.set(users + 1);
+    }
+    #[inline]
+    fn disable_animation_frame(&self) {
+        let users = DEFAULT_ANIMATION_FRAME_USERS.get();
+        if users == 1 {
+            lib!(disable_animation_frame())
+        }
+        DEFAULT_ANIMATION_FRAME_USERS.set(users.saturating_sub(1));
+    }
+    #[inline]
+    fn bind_canvas(&self, canvas_index: i32) {
+        lib!(bind_canvas(canvas_index))
+    }
+    #[inline]
+    fn unbind_canvas(&self, canvas_index: i32) {
+        lib!(unbind_canvas(canvas_index))
+    }
+    #[inline]
+    fn set_canvas_size(&self, canvas_index: i32, w: i32, h: i32, pixel_ratio: f64, update_logical_size: bool) {
+        lib!(set_canvas_size(canvas_index, w, h, pixel_ratio, update_logical_size as i32))
+    }
+    #[inline]
+    fn get_canvas_width(&self, canvas_index: i32) -> i32 {
+        lib!(get_canvas_width(canvas_index))
+    }
+    #[inline]
+    fn get_canvas_height(&self, canvas_index: i32) -> i32 {
+        lib!(get_canvas_height(canvas_index))
+    }
+    #[inline]
+    fn get_device_pixel_ratio(&self, canvas_index: i32) -> f64 {
+        lib!(get_device_pixel_ratio(canvas_index))
+    }
+    #[inline]
+    fn set_clear_color(&self, canvas_index: i32, r: f32, g: f32, b: f32, a: f32) {
+        lib!(set_clear_color(canvas_index, r, g, b, a))
+    }
+    #[inline]
+    fn clear(&self, canvas_index: i32) {
+        lib!(clear(canvas_index))
+    }
+    #[inline]
+    fn bind_touch_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+        lib!(bind_touch_events(canvas_index, cb_ptr))
+    }
+    fn bind_wheel_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+        lib!(bind_wheel_events(canvas_index, cb_ptr))
+    }
+    #[inline]
+    fn bind_keyboard_events(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+        lib!(bind_keyboard_events(canvas_index, cb_ptr))
+    }
+    #[inline]
+    fn bind_canvas_size_change(&self, canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+        lib!(bind_canvas_size_change(canvas_index, cb_ptr))
+    }
+
+    #[inline]
+    fn tex_get_size(&self, canvas_index: i32) -> i32 {
+        lib!(tex_get_size(canvas_index))
+    }
+    #[inline]
+    fn tex_get_count(&self, canvas_index: i32) -> i32 {
+        lib!(tex_get_count(canvas_index))
+    }
+    #[inline]
+    fn tex_get_max_draws(&self) -> i32 {
+        lib!(tex_get_max_draws())
+    }
+    #[inline]
+    fn tex_get_pixels(&self, canvas_index: i32, left: i32, top: i32, width: i32, height: i32, buf: &mut [u8]) {
+        assert!(buf.len() >= (width * height * 4) as usize);
+        lib!(tex_get_pixels(canvas_index, left, top, width, height, buf.as_mut_ptr()))
+    }
+    #[inline]
+    fn tex_create_empty(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+        lib!(tex_create_empty(canvas_index, tex_id, width, height))
+    }
+    #[inline]
+    fn tex_create_from_pixels(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32, buf: &[u8]) {
+        assert!(buf.len() >= (width * height * 4) as usize);
+        lib!(tex_create_from_pixels(canvas_index, tex_id, width, height, buf.as_ptr()))
+    }
+    #[inline]
+    fn tex_copy(&self, canvas_index: i32, dest_tex_id: i32, dest_left: i32, dest_top: i32, src_left: i32, src_top: i32, width: i32, height: i32) {
+        lib!(tex_copy(canvas_index, dest_tex_id, dest_left, dest_top, src_left, src_top, width, height))
+    }
+    #[inline]
+    fn tex_bind_rendering_target(&self, canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+        lib!(tex_bind_rendering_target(canvas_index, tex_id, width, height))
+    }
+    #[inline]
+    fn tex_unbind_rendering_target(&self, canvas_index: i32) {
+        lib!(tex_unbind_rendering_target(canvas_index))
+    }
+    #[inline]
+    fn tex_delete(&self, canvas_index: i32, tex_id: i32) {
+        lib!(tex_delete(canvas_index, tex_id))
+    }
+    #[inline]
+    fn tex_draw(&self, canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        lib!(tex_draw(canvas_index, draw_index, tex_shader_index, normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h, x1, y1, x2, y2, x3, y3, x4, y4))
+    }
+    #[inline]
+    fn tex_set_active_texture(&self, canvas_index: i32, tex_shader_index: i32, tex_id: i32) {
+        lib!(tex_set_active_texture(canvas_index, tex_shader_index, tex_id))
+    }
+    #[inline]
+    fn tex_draw_end(&self, canvas_index: i32, draw_count: i32) {
+        lib!(tex_draw_end(canvas_index, draw_count))
+    }
+    #[inline]
+    fn tex_set_draw_state(&self, canvas_index: i32, color_r: f32, color_g: f32, color_b: f32, color_a: f32, alpha: f32) {
+        lib!(tex_set_draw_state(canvas_index, color_r, color_g, color_b, color_a, alpha))
+    }
+    fn tex_set_draw_shape(&self, canvas_index: i32, width: f32, height: f32, radius_lt: f32, radius_rt: f32, radius_rb: f32, radius_lb: f32, border_top: f32, border_right: f32, border_bottom: f32, border_left: f32, pixel_scale: f32) {
+        lib!(tex_set_draw_shape(canvas_index, width, height, radius_lt, radius_rt, radius_rb, radius_lb, border_top, border_right, border_bottom, border_left, pixel_scale))
+    }
+    fn tex_set_draw_blur(&self, canvas_index: i32, step_x: f32, step_y: f32, sigma: f32) {
+        lib!(tex_set_draw_blur(canvas_index, step_x, step_y, sigma))
+    }
+    #[inline]
+    fn tex_push_clip_rect(&self, canvas_index: i32, left: f32, top: f32, width: f32, height: f32) {
+        lib!(tex_push_clip_rect(canvas_index, left, top, width, height))
+    }
+    #[inline]
+    fn tex_push_clip_quad(&self, canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        lib!(tex_push_clip_quad(canvas_index, x1, y1, x2, y2, x3, y3, x4, y4))
+    }
+    fn tex_push_clip_shape(&self, canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        lib!(tex_push_clip_shape(canvas_index, x1, y1, x2, y2, x3, y3, x4, y4))
+    }
+    #[inline]
+    fn tex_pop_clip(&self, canvas_index: i32) {
+        lib!(tex_pop_clip(canvas_index))
+    }
+
+    #[inline]
+    fn image_load_url(&self, id: i32, url: &str, cb_ptr: *mut Box<Callback>) {
+        lib!(image_load_url(id, CString::new(url).unwrap().into_raw(), cb_ptr))
+    }
+    #[inline]
+    fn image_load_bytes(&self, id: i32, buf: &[u8], cb_ptr: *mut Box<Callback>) {
+        lib!(image_load_bytes(id, buf.as_ptr(), buf.len() as i32, cb_ptr))
+    }
+    #[inline]
+    fn image_unload(&self, id: i32) {
+        lib!(image_unload(id))
+    }
+    fn image_get_error(&self, id: i32) -> String {
+        // get the length first, then copy the message
+        let len = lib!(image_get_error(id, ::std::ptr::null_mut(), 0));
+        if len <= 0 { return String::new() }
+        let mut buf = vec![0u8; len as usize];
+        lib!(image_get_error(id, buf.as_mut_ptr(), len));
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+    #[inline]
+    fn image_get_natural_width(&self, id: i32) -> i32 {
+        lib!(image_get_natural_width(id))
+    }
+    #[inline]
+    fn image_get_natural_height(&self, id: i32) -> i32 {
+        lib!(image_get_natural_height(id))
+    }
+    #[inline]
+    fn image_is_vector(&self, id: i32) -> bool {
+        lib!(image_is_vector(id)) != 0
+    }
+    #[inline]
+    fn tex_from_image(&self, canvas_index: i32, tex_id: i32, img_id: i32) {
+        lib!(tex_from_image(canvas_index, tex_id, img_id))
+    }
+    #[inline]
+    fn tex_from_image_with_size(&self, canvas_index: i32, tex_id: i32, img_id: i32, width: i32, height: i32) {
+        lib!(tex_from_image_with_size(canvas_index, tex_id, img_id, width, height))
+    }
+
+    #[inline]
+    fn text_bind_font_family(&self, id: i32, font_family: &str) {
+        lib!(text_bind_font_family(id, CString::new(font_family).unwrap().into_raw()))
+    }
+    #[inline]
+    fn text_unbind_font_family(&self, id: i32) {
+        lib!(text_unbind_font_family(id))
+    }
+    #[inline]
+    fn text_set_font(&self, font_size: i32, line_height: i32, font_family_id: i32, italic: bool, bold: bool) {
+        lib!(text_set_font(font_size, line_height, font_family_id, italic as i32, bold as i32))
+    }
+    #[inline]
+    fn text_get_width(&self, text: &str) -> f64 {
+        lib!(text_get_width(CString::new(text).unwrap().into_raw()))
+    }
+    #[inline]
+    fn text_to_tex(&self, canvas_index: i32, tex_id: i32, tex_left: i32, tex_top: i32, text: &str, width: i32, height: i32, line_height: i32) {
+        lib!(text_to_tex(canvas_index, tex_id, tex_left, tex_top, CString::new(text).unwrap().into_raw(), width, height, line_height))
+    }
+}
@@ -1,64 +1,125 @@
-#ifdef GL_ES
-#else
-#define highp
-#define mediump
-#define lowp
-#endif
-
-varying highp vec2 vTexPos;
-varying highp float vTexIndex;
-uniform sampler2D uTex0;
-uniform sampler2D uTex1;
-uniform sampler2D uTex2;
-uniform sampler2D uTex3;
-uniform sampler2D uTex4;
-uniform sampler2D uTex5;
-uniform sampler2D uTex6;
-uniform sampler2D uTex7;
-uniform sampler2D uTex8;
-uniform sampler2D uTex9;
-uniform sampler2D uTex10;
-uniform sampler2D uTex11;
-uniform sampler2D uTex12;
-uniform sampler2D uTex13;
-uniform sampler2D uTex14;
-uniform sampler2D uTex15;
-uniform mediump vec4 uColor;
-uniform mediump float uAlpha;
-
-void main(void) {
-  mediump vec4 color;
-  highp int texIndex = int(vTexIndex);
-  bool useColorRGB = true;
-  // texIndex = if USE_COLOR { -2 } else { ~USE_COLOR_RGB << 8 | tex_id }
-  if (vTexIndex < -1.5) {
-    // draw rect instead of texture
-    gl_FragColor = uColor * uAlpha;
-  } else {
-    if (vTexIndex > 255.5) {
-      texIndex -= 256;
-      useColorRGB = false;
-    }
-    if (texIndex == 0) color = texture2D(uTex0, vTexPos);
-    if (texIndex == 1) color = texture2D(uTex1, vTexPos);
-    if (texIndex == 2) color = texture2D(uTex2, vTexPos);
-    if (texIndex == 3) color = texture2D(uTex3, vTexPos);
-    if (texIndex == 4) color = texture2D(uTex4, vTexPos);
-    if (texIndex == 5) color = texture2D(uTex5, vTexPos);
-    if (texIndex == 6) color = texture2D(uTex6, vTexPos);
-    if (texIndex == 7) color = texture2D(uTex7, vTexPos);
-    if (texIndex == 8) color = texture2D(uTex8, vTexPos);
-    if (texIndex == 9) color = texture2D(uTex9, vTexPos);
-    if (texIndex == 10) color = texture2D(uTex10, vTexPos);
-    if (texIndex == 11) color = texture2D(uTex11, vTexPos);
-    if (texIndex == 12) color = texture2D(uTex12, vTexPos);
-    if (texIndex == 13) color = texture2D(uTex13, vTexPos);
-    if (texIndex == 14) color = texture2D(uTex14, vTexPos);
-    if (texIndex == 15) color = texture2D(uTex15, vTexPos);
-    if (useColorRGB) {
-      gl_FragColor = uColor * color.a * uAlpha;
-    } else {
-      gl_FragColor = color * uAlpha;
-    }
-  }
-}
+#ifdef GL_ES
+#else
+#define highp
+#define mediump
+#define lowp
+#endif
+
+varying highp vec3 vTexPos;
+varying highp float vTexIndex;
+uniform sampler2D uTex0;
+uniform sampler2D uTex1;
+uniform sampler2D uTex2;
+uniform sampler2D uTex3;
+uniform sampler2D uTex4;
+uniform sampler2D uTex5;
+uniform sampler2D uTex6;
+uniform sampler2D uTex7;
+uniform sampler2D uTex8;
+uniform sampler2D uTex9;
+uniform sampler2D uTex10;
+uniform sampler2D uTex11;
+uniform sampler2D uTex12;
+uniform sampler2D uTex13;
+uniform sampler2D uTex14;
+uniform sampler2D uTex15;
+uniform mediump vec4 uColor;
+uniform mediump float uAlpha;
+uniform highp vec2 uShapeSize;
+uniform highp vec4 uShapeRadii; // left-top, right-top, right-bottom, left-bottom
+uniform highp vec4 uShapeBorder; // top, right, bottom, left
+uniform highp float uShapeScale;
+uniform highp vec3 uBlur; // step x, step y, sigma in steps
+
+#define BLUR_MAX_STEPS 32
+
+// the signed distance to a rounded rect from (0, 0) to size, negative inside
+highp float roundedRectDistance(highp vec2 p, highp vec2 size, highp vec4 radii) {
+  highp vec2 halfSize = size * 0.5;
+  highp vec2 q = p - halfSize;
+  highp float r = q.x < 0. ? (q.y < 0. ? radii.x : radii.w) : (q.y < 0. ? radii.y : radii.z);
+  highp vec2 d = abs(q) - halfSize + r;
+  return min(max(d.x, d.y), 0.) + length(max(d, 0.)) - r;
+}
+
+// the covered ratio of the pixel at p (the tex position) in the shape
+mediump float shapeCoverage(highp vec2 p) {
+  highp float outer = clamp(0.5 - roundedRectDistance(p, uShapeSize, uShapeRadii) * uShapeScale, 0., 1.);
+  highp vec4 b = uShapeBorder;
+  highp vec2 innerSize = uShapeSize - vec2(b.y + b.w, b.x + b.z);
+  if (b == vec4(0.) || innerSize.x <= 0. || innerSize.y <= 0.) return outer;
+  highp vec4 innerRadii = max(uShapeRadii - max(b.wyyw, b.xxzz), 0.);
+  highp float inner = clamp(0.5 - roundedRectDistance(p - b.wx, innerSize, innerRadii) * uShapeScale, 0., 1.);
+  return outer * (1. - inner);
+}
+
+mediump vec4 sampleTex(highp int texIndex, highp vec2 pos) {
+  if (texIndex == 0) return texture2D(uTex0, pos);
+  if (texIndex == 1) return texture2D(uTex1, pos);
+  if (texIndex == 2) return texture2D(uTex2, pos);
+  if (texIndex == 3) return texture2D(uTex3, pos);
+  if (texIndex == 4) return texture2D(uTex4, pos);
+  if (texIndex == 5) return texture2D(uTex5, pos);
+  if (texIndex == 6) return texture2D(uTex6, pos);
+  if (texIndex == 7) return texture2D(uTex7, pos);
+  if (texIndex == 8) return texture2D(uTex8, pos);
+  if (texIndex == 9) return texture2D(uTex9, pos);
+  if (texIndex == 10) return texture2D(uTex10, pos);
+  if (texIndex == 11) return texture2D(uTex11, pos);
+  if (texIndex == 12) return texture2D(uTex12, pos);
+  if (texIndex == 13) return texture2D(uTex13, pos);
+  if (texIndex == 14) return texture2D(uTex14, pos);
+  if (texIndex == 15) return texture2D(uTex15, pos);
+  return vec4(0.);
+}
+
+// the gaussian blurred sampling along uBlur.xy
+mediump vec4 sampleBlurred(highp int texIndex, highp vec2 pos) {
+  highp float sigma = uBlur.z;
+  highp float radius = min(ceil(sigma * 3.), float(BLUR_MAX_STEPS));
+  mediump vec4 sum = vec4(0.);
+  highp float weightSum = 0.;
+  for (int i = -BLUR_MAX_STEPS; i <= BLUR_MAX_STEPS; i++) {
+    highp float d = float(i);
+    if (abs(d) > radius) continue;
+    highp float w = exp(-d * d / (2. * sigma * sigma));
+    sum += sampleTex(texIndex, pos + uBlur.xy * d) * w;
+    weightSum += w;
+  }
+  return sum / weightSum;
+}
+
+void main(void) {
+  mediump vec4 color;
+  highp int texIndex = int(vTexIndex);
+  bool useColorRGB = true;
+  // perspective-correct interpolation
+  highp vec2 texPos = vTexPos.xy / vTexPos.z;
+  // texIndex = if USE_COLOR { -2 } else if USE_SHAPE { -3 } else if USE_SHAPE_MASK { -4 } else { ~USE_COLOR_RGB << 8 | tex_id }
+  if (vTexIndex < -3.5) {
+    // the hard-edged shape, for clipping
+    if (shapeCoverage(texPos) < 0.5) discard;
+    gl_FragColor = uColor * uAlpha;
+  } else if (vTexIndex < -2.5) {
+    // the anti-aliased shape
+    gl_FragColor = uColor * uAlpha * shapeCoverage(texPos);
+  } else if (vTexIndex < -1.5) {
+    // draw rect instead of texture
+    gl_FragColor = uColor * uAlpha;
+  } else {
+    if (vTexIndex > 255.5) {
+      texIndex -= 256;
+      useColorRGB = false;
+    }
+    if (uBlur.z > 0.) {
+      color = sampleBlurred(texIndex, texPos);
+    } else {
+      color = sampleTex(texIndex, texPos);
+    }
+    if (useColorRGB) {
+      gl_FragColor = uColor * color.a * uAlpha;
+    } else {
+      gl_FragColor = color * uAlpha;
+    }
+  }
+}
@@ -0,0 +1,203 @@
+use std::cmp::{Ord, Ordering};
+use std::sync::{Arc, Mutex, Condvar};
+use std::time::Duration;
+use std::thread;
+use std::collections::binary_heap::BinaryHeap;
+use std::time::{Instant, SystemTime};
+use super::super::super::utils::PretendSend;
+
+lazy_static! {
+    static ref LAYOUT_THREAD: Arc<(Mutex<LayoutThread>, Condvar)> = Arc::new((Mutex::new(LayoutThread::new()), Condvar::new()));
+}
+
+const ANIMATION_FRAME_INTERVAL: u32 = 16_666_666;
+
+#[derive(Debug)]
+pub enum EventDetail {
+    TimeoutEvent,
+    AnimationFrameEvent,
+    ImageLoadEvent,
+    CanvasSizeEvent,
+}
+
+struct Event {
+    event_id: usize,
+    time: Instant,
+    detail: EventDetail,
+    callback: PretendSend<Box<Fn(Instant, EventDetail)>>,
+}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.time == other.time && self.event_id == other.event_id
+    }
+}
+impl Eq for Event {}
+impl Ord for Event {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.time > other.time { Ordering::Less }
+        else if self.time < other.time { Ordering::Greater }
+        else if self.event_id > other.event_id { Ordering::Less }
+        else if self.event_id < other.event_id { Ordering::Greater }
+        else { Ordering::Equal }
+    }
+}
+
+impl Event {
+    fn dispatch(self) {
+        let callback = self.callback;
+        let detail = self.detail;
+        (*callback)(self.time, detail)
+    }
+}
+
+/// The headless "layout thread" is the thread which calls `init_lib`; events are dispatched in `run`.
+struct LayoutThread {
+    thread_id: thread::ThreadId,
+    events_queue: BinaryHeap<Event>,
+    event_id_inc: usize,
+    running: bool,
+    animation_frame_enabled: bool,
+    animation_frame_scheduled: bool,
+}
+
+impl LayoutThread {
+    fn new() -> Self {
+        Self {
+            thread_id: thread::current().id(),
+            events_queue: BinaryHeap::new(),
+            event_id_inc: 0,
+            running: false,
+            animation_frame_enabled: false,
+            animation_frame_scheduled: false,
+        }
+    }
+
+    fn push_event<F: 'static>(&mut self, time: Instant, detail: EventDetail, callback: Box<F>) where F: Fn(Instant, EventDetail) {
+        if self.events_queue.is_empty() {
+            self.event_id_inc = 0;
+        }
+        let event_id = self.event_id_inc;
+        self.event_id_inc += 1;
+        let thread_id = self.thread_id;
+        self.events_queue.push(Event {
+            event_id,
+            time,
+            detail,
+            callback: PretendSend::new_with_thread_id(callback, thread_id),
+        })
+    }
+}
+
+pub fn init() {
+    let (ref lt, _) = **LAYOUT_THREAD;
+    let mut lt = lt.lock().unwrap();
+    lt.thread_id = thread::current().id();
+    lt.event_id_inc = 0;
+}
+
+pub fn push_event_from_layout_thread<F: 'static>(time: Instant, detail: EventDetail, callback: F) where F: Fn(Instant, EventDetail) {
+    let (ref lt, _) = **LAYOUT_THREAD;
+    let mut lt = lt.lock().unwrap();
+    if thread::current().id() != lt.thread_id {
+        panic!("push_event_from_layout_thread can only be called in layout thread");
+    }
+    lt.push_event(time, detail, Box::new(callback));
+}
+
+pub fn push_event<F: 'static + Send>(time: Instant, detail: EventDetail, callback: F) where F: Fn(Instant, EventDetail) {
+    let (ref lt, _) = **LAYOUT_THREAD;
+    lt.lock().unwrap().push_event(time, detail, Box::new(callback));
+}
+
+pub fn wakeup() {
+    let (_, ref cvar) = **LAYOUT_THREAD;
+    cvar.notify_all();
+}
+
+/// Dispatch events in current thread until `exit` is called.
+pub fn run() {
+    let (ref lt, ref cvar) = **LAYOUT_THREAD;
+    {
+        let mut lt = lt.lock().unwrap();
+        if thread::current().id() != lt.thread_id {
+            panic!("main_loop can only be called in the thread which calls init");
+        }
+        lt.running = true;
+    }
+    loop {
+        let ev = {
+            let mut lt = lt.lock().unwrap();
+            loop {
+                if !lt.running {
+                    return;
+                }
+                let resume_time = match lt.events_queue.peek() {
+                    None => None,
+                    Some(ev) => {
+                        let now = Instant::now();
+                        if ev.time > now {
+                            Some(ev.time.duration_since(now))
+                        } else {
+                            break;
+                        }
+                    }
+                };
+                lt = match resume_time {
+                    None => cvar.wait(lt).unwrap(),
+                    Some(t) => cvar.wait_timeout(lt, t).unwrap().0,
+                };
+            }
+            lt.events_queue.pop().unwrap()
+        };
+        ev.dispatch();
+    }
+}
+
+pub fn exit() {
+    let (ref lt, ref cvar) = **LAYOUT_THREAD;
+    lt.lock().unwrap().running = false;
+    cvar.notify_all();
+}
+
+fn schedule_animation_frame(layout_thread: &mut LayoutThread, time: Instant) {
+    layout_thread.push_event(time, EventDetail::AnimationFrameEvent, Box::new(move |_time: Instant, _detail| {
+        let next_frame_time = Instant::now() + Duration::new(0, ANIMATION_FRAME_INTERVAL);
+        let (ref lt, _) = **LAYOUT_THREAD;
+        {
+            let mut layout_thread = lt.lock().unwrap();
+            if !layout_thread.animation_frame_enabled {
+                layout_thread.animation_frame_scheduled = false;
+                return;
+            }
+        }
+        let dur = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let secs = dur.as_secs() as f64;
+        let nanos = dur.subsec_nanos() as f64;
+        super::super::animation_frame(secs * 1000. + nanos / 1000_000.);
+        super::trigger_painting();
+        {
+            let mut layout_thread = lt.lock().unwrap();
+            schedule_animation_frame(&mut layout_thread, next_frame_time);
+        }
+    }));
+}
+
+pub fn set_animation_frame_enabled(enabled: bool) {
+    let (ref lt, ref cvar) = **LAYOUT_THREAD;
+    let mut layout_thread = lt.lock().unwrap();
+    if layout_thread.animation_frame_enabled == enabled {
+        return
+    }
+    layout_thread.animation_frame_enabled = enabled;
+    if enabled && !layout_thread.animation_frame_scheduled {
+        layout_thread.animation_frame_scheduled = true;
+        schedule_animation_frame(&mut layout_thread, Instant::now() + Duration::new(0, ANIMATION_FRAME_INTERVAL));
+        cvar.notify_all();
+    }
+}
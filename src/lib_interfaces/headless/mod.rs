@@ -0,0 +1,372 @@
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::time::{Instant, Duration};
+use std::ptr;
+use super::Callback;
+use super::super::utils::PretendSend;
+
+mod layout_thread;
+mod tex_manager;
+#[path = "../native/image_manager.rs"]
+mod image_manager;
+#[path = "../native/svg.rs"]
+mod svg;
+#[path = "../native/font_manager.rs"]
+mod font_manager;
+
+const DEFAULT_WINDOW_SIZE: (i32, i32) = (1280, 720);
+const DEFAULT_TEX_SIZE: i32 = 4096;
+const GL_DRAW_RECT_MAX: i32 = 65536 / 8;
+const TEXTURE_MAX: i32 = 16;
+
+lazy_static! {
+    static ref CANVASES: PretendSend<RefCell<HashMap<i32, HeadlessCanvas>>> = PretendSend::new(RefCell::new(HashMap::new()));
+}
+
+struct HeadlessCanvas {
+    title: String,
+    tex_manager: tex_manager::TexManager,
+    keyboard_event_handler: Option<*mut Box<Callback>>,
+    touch_event_handler: Option<*mut Box<Callback>>,
+    wheel_event_handler: Option<*mut Box<Callback>>,
+    canvas_size_listener: Option<*mut Box<Callback>>,
+}
+
+pub use self::tex_manager::PixelBuffer;
+
+pub fn init_lib() {
+    font_manager::init();
+    layout_thread::init();
+}
+pub fn trigger_painting() {
+    // nothing to present, the framebuffer is always up to date
+}
+pub fn main_loop(f: fn() -> ()) {
+    layout_thread::push_event(Instant::now(), layout_thread::EventDetail::TimeoutEvent,
+        move |_time, _detail| {
+            f()
+        });
+    layout_thread::run();
+}
+/// Make `main_loop` return after the current event.
+pub fn exit_main_loop() {
+    layout_thread::exit();
+}
+pub fn log_with_level(str: String, level: i32) {
+    println!("[glayout] [{}] {}", match level {
+        -1 => "debug",
+        1 => "info",
+        2 => "warn",
+        3 => "error",
+        _ => "log"
+    }, str)
+}
+pub fn timeout(ms: i32, cb_ptr: *mut Box<Callback>) {
+    layout_thread::push_event_from_layout_thread(
+        Instant::now() + Duration::new((ms / 1000) as u64, (ms % 1000 * 1000000) as u32),
+        layout_thread::EventDetail::TimeoutEvent,
+        move |_time, _detail| {
+            super::callback(cb_ptr, 0, 0, 0, 0);
+        }
+    );
+    layout_thread::wakeup();
+}
+pub fn enable_animation_frame() {
+    layout_thread::set_animation_frame_enabled(true);
+}
+pub fn disable_animation_frame() {
+    layout_thread::set_animation_frame_enabled(false);
+}
+
+pub fn bind_canvas(canvas_index: i32) {
+    CANVASES.borrow_mut().insert(canvas_index, HeadlessCanvas {
+        title: String::new(),
+        tex_manager: tex_manager::TexManager::new(DEFAULT_WINDOW_SIZE.0, DEFAULT_WINDOW_SIZE.1, 1.),
+        keyboard_event_handler: None,
+        touch_event_handler: None,
+        wheel_event_handler: None,
+        canvas_size_listener: None,
+    });
+}
+pub fn unbind_canvas(canvas_index: i32) {
+    CANVASES.borrow_mut().remove(&canvas_index).unwrap();
+}
+pub fn set_title(canvas_index: i32, title: String) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().title = title;
+}
+
+pub fn set_canvas_size(canvas_index: i32, w: i32, h: i32, pixel_ratio: f64, update_logical_size: i32) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().tex_manager.set_tex_draw_size(w, h, pixel_ratio);
+    if update_logical_size != 0 {
+        // notify the listener later like the resized event of the native window
+        layout_thread::push_event(Instant::now(), layout_thread::EventDetail::CanvasSizeEvent, move |_time, _detail| {
+            let cb = match CANVASES.borrow().get(&canvas_index) {
+                None => None,
+                Some(x) => x.canvas_size_listener,
+            };
+            match cb {
+                None => { },
+                Some(cb) => {
+                    super::callback(cb, w, h, (pixel_ratio * 100000000.) as i32, 0);
+                }
+            }
+        });
+        layout_thread::wakeup();
+    }
+}
+pub fn get_canvas_width(canvas_index: i32) -> i32 {
+    CANVASES.borrow()[&canvas_index].tex_manager.width()
+}
+pub fn get_canvas_height(canvas_index: i32) -> i32 {
+    CANVASES.borrow()[&canvas_index].tex_manager.height()
+}
+pub fn get_device_pixel_ratio(canvas_index: i32) -> f64 {
+    CANVASES.borrow()[&canvas_index].tex_manager.pixel_ratio()
+}
+
+pub fn set_clear_color(canvas_index: i32, r: f32, g: f32, b: f32, a: f32) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().tex_manager.set_clear_color(r, g, b, a);
+}
+pub fn clear(canvas_index: i32) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().tex_manager.clear();
+}
+pub fn bind_touch_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().touch_event_handler = Some(cb_ptr);
+}
+pub fn bind_wheel_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().wheel_event_handler = Some(cb_ptr);
+}
+pub fn bind_keyboard_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().keyboard_event_handler = Some(cb_ptr);
+}
+pub fn bind_canvas_size_change(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+    CANVASES.borrow_mut().get_mut(&canvas_index).unwrap().canvas_size_listener = Some(cb_ptr);
+}
+
+/// Simulate a touch event on a headless canvas.
+/// `touch_type` is the same as the native touch callback: 1 start, 2 move, 3 end, 4 cancel.
+pub fn dispatch_touch_event(canvas_index: i32, touch_type: i32, x: i32, y: i32) {
+    let cb = CANVASES.borrow()[&canvas_index].touch_event_handler;
+    match cb {
+        None => { },
+        Some(cb) => {
+            super::callback(cb, touch_type, x, y, 0);
+        }
+    }
+}
+/// Simulate a mouse wheel event on a headless canvas, the delta is in px.
+pub fn dispatch_wheel_event(canvas_index: i32, x: i32, y: i32, delta_x: i32, delta_y: i32) {
+    let cb = CANVASES.borrow()[&canvas_index].wheel_event_handler;
+    match cb {
+        None => { },
+        Some(cb) => {
+            super::callback(cb, x, y, delta_x, delta_y);
+        }
+    }
+}
+/// Simulate a keyboard event on a headless canvas.
+/// `event_type` is 1 for key down and 3 for key up.
+pub fn dispatch_keyboard_event(canvas_index: i32, event_type: i32, key_code: i32, special_keys: i32) {
+    let cb = CANVASES.borrow()[&canvas_index].keyboard_event_handler;
+    match cb {
+        None => { },
+        Some(cb) => {
+            super::callback(cb, event_type, key_code, 0, special_keys);
+        }
+    }
+}
+
+/// Visit the framebuffer of a headless canvas.
+/// The pixels are premultiplied RGBA in device pixels, rows from top to bottom.
+pub fn with_framebuffer<F, R>(canvas_index: i32, f: F) -> R where F: FnOnce(&PixelBuffer) -> R {
+    f(CANVASES.borrow()[&canvas_index].tex_manager.framebuffer())
+}
+
+pub fn tex_get_size(_canvas_index: i32) -> i32 {
+    DEFAULT_TEX_SIZE
+}
+pub fn tex_get_count(_canvas_index: i32) -> i32 {
+    TEXTURE_MAX
+}
+pub fn tex_get_max_draws() -> i32 {
+    GL_DRAW_RECT_MAX
+}
+pub fn tex_get_pixels(canvas_index: i32, left: i32, top: i32, width: i32, height: i32, buf: *mut u8) {
+    with_framebuffer(canvas_index, |fb| {
+        let row_size = (width * 4) as usize;
+        for y in 0..height {
+            let fy = top + y;
+            if fy < 0 || fy >= fb.height { continue }
+            let x_start = if left < 0 { -left } else { 0 };
+            let x_end = if left + width > fb.width { fb.width - left } else { width };
+            if x_start >= x_end { continue }
+            let src = ((left + x_start + fy * fb.width) * 4) as usize;
+            let len = ((x_end - x_start) * 4) as usize;
+            unsafe { ptr::copy_nonoverlapping(fb.data[src..].as_ptr(), buf.offset((y as usize * row_size + x_start as usize * 4) as isize), len) };
+        }
+    });
+}
+
+pub use self::tex_manager::{tex_create_empty, tex_create_from_pixels, tex_copy, tex_bind_rendering_target, tex_unbind_rendering_target, tex_delete, tex_draw, tex_set_active_texture, tex_draw_end, tex_set_draw_state, tex_set_draw_shape, tex_set_draw_blur, tex_push_clip_rect, tex_push_clip_quad, tex_push_clip_shape, tex_pop_clip};
+pub use self::image_manager::{image_load_url, image_load_bytes, image_unload, image_get_error, image_get_natural_width, image_get_natural_height, image_is_vector, tex_from_image, tex_from_image_with_size};
+pub use self::font_manager::{text_bind_font_family, text_unbind_font_family, text_set_font, text_get_width, text_to_tex, text_use_font_file};
+
+#[cfg(test)]
+mod test {
+    use super::super::run_canvas_test;
+    use super::{bind_canvas, unbind_canvas, set_canvas_size, with_framebuffer, tex_create_empty, tex_copy, tex_bind_rendering_target, tex_unbind_rendering_target, tex_draw, tex_set_active_texture, tex_draw_end, tex_set_draw_state, tex_push_clip_rect, tex_push_clip_quad, tex_pop_clip};
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const EMPTY: [u8; 4] = [0, 0, 0, 0];
+
+    fn canvas(index: i32, w: i32, h: i32, pixel_ratio: f64) {
+        bind_canvas(index);
+        set_canvas_size(index, w, h, pixel_ratio, 0);
+    }
+
+    fn pixel(index: i32, x: i32, y: i32) -> [u8; 4] {
+        with_framebuffer(index, |fb| {
+            let p = ((x + y * fb.width) * 4) as usize;
+            [fb.data[p], fb.data[p + 1], fb.data[p + 2], fb.data[p + 3]]
+        })
+    }
+
+    // draw the rect with a solid color, or the texture of the shader index 0 if `color` is `None`
+    fn draw_rect(index: i32, color: Option<(f32, f32, f32)>, (left, top, right, bottom): (f32, f32, f32, f32)) {
+        let (tex_shader_index, color) = match color {
+            Some(c) => (-2, c),
+            None => (256, (0., 0., 0.)),
+        };
+        tex_set_draw_state(index, color.0, color.1, color.2, 1., 1.);
+        tex_draw(index, 0, tex_shader_index, 0., 0., 1., 1., left, top, left, bottom, right, bottom, right, top);
+        tex_draw_end(index, 1);
+    }
+
+    #[test]
+    fn solid_rect() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            draw_rect(100, Some((1., 0., 0.)), (2., 2., 6., 6.));
+            assert_eq!(pixel(100, 2, 2), RED);
+            assert_eq!(pixel(100, 5, 5), RED);
+            assert_eq!(pixel(100, 1, 2), EMPTY);
+            assert_eq!(pixel(100, 6, 5), EMPTY);
+            unbind_canvas(100);
+            // in device pixels
+            canvas(100, 10, 10, 2.);
+            draw_rect(100, Some((1., 0., 0.)), (2., 2., 6., 6.));
+            assert_eq!(with_framebuffer(100, |fb| (fb.width, fb.height)), (20, 20));
+            assert_eq!(pixel(100, 4, 4), RED);
+            assert_eq!(pixel(100, 11, 11), RED);
+            assert_eq!(pixel(100, 3, 4), EMPTY);
+            assert_eq!(pixel(100, 12, 11), EMPTY);
+            unbind_canvas(100);
+        });
+    }
+
+    #[test]
+    fn scissor_clip() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            tex_push_clip_rect(100, 1., 2., 4., 5.);
+            tex_push_clip_rect(100, 3., 0., 10., 10.);
+            draw_rect(100, Some((1., 0., 0.)), (0., 0., 10., 10.));
+            tex_pop_clip(100);
+            tex_pop_clip(100);
+            assert_eq!(pixel(100, 3, 2), RED);
+            assert_eq!(pixel(100, 4, 6), RED);
+            assert_eq!(pixel(100, 2, 4), EMPTY);
+            assert_eq!(pixel(100, 5, 4), EMPTY);
+            assert_eq!(pixel(100, 3, 1), EMPTY);
+            assert_eq!(pixel(100, 3, 7), EMPTY);
+            // not clipped after popped
+            draw_rect(100, Some((0., 1., 0.)), (0., 0., 1., 1.));
+            assert_eq!(pixel(100, 0, 0), GREEN);
+            unbind_canvas(100);
+        });
+    }
+
+    #[test]
+    fn quad_clip() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            tex_push_clip_quad(100, 5., 0., 0., 5., 5., 10., 10., 5.);
+            draw_rect(100, Some((1., 0., 0.)), (0., 0., 10., 10.));
+            tex_pop_clip(100);
+            assert_eq!(pixel(100, 5, 5), RED);
+            assert_eq!(pixel(100, 4, 1), RED);
+            assert_eq!(pixel(100, 7, 6), RED);
+            assert_eq!(pixel(100, 1, 1), EMPTY);
+            assert_eq!(pixel(100, 8, 8), EMPTY);
+            unbind_canvas(100);
+        });
+    }
+
+    #[test]
+    fn rendering_target() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            // a red texture with a green top-left quarter
+            tex_create_empty(100, 1, 4, 4);
+            tex_bind_rendering_target(100, 1, 4, 4);
+            draw_rect(100, Some((1., 0., 0.)), (0., 0., 4., 4.));
+            draw_rect(100, Some((0., 1., 0.)), (0., 0., 2., 2.));
+            tex_unbind_rendering_target(100);
+            assert_eq!(pixel(100, 0, 0), EMPTY);
+            tex_set_active_texture(100, 0, 1);
+            draw_rect(100, None, (2., 2., 6., 6.));
+            assert_eq!(pixel(100, 2, 2), GREEN);
+            assert_eq!(pixel(100, 5, 2), RED);
+            assert_eq!(pixel(100, 2, 5), RED);
+            assert_eq!(pixel(100, 5, 5), RED);
+            assert_eq!(pixel(100, 6, 6), EMPTY);
+            unbind_canvas(100);
+        });
+    }
+
+    #[test]
+    fn rendering_target_clip() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            tex_create_empty(100, 1, 4, 4);
+            // the clip of the canvas does not apply to the texture, and applies again after unbound
+            tex_push_clip_rect(100, 0., 0., 2., 2.);
+            tex_bind_rendering_target(100, 1, 4, 4);
+            draw_rect(100, Some((1., 0., 0.)), (0., 0., 4., 4.));
+            tex_unbind_rendering_target(100);
+            tex_set_active_texture(100, 0, 1);
+            draw_rect(100, None, (0., 0., 4., 4.));
+            tex_pop_clip(100);
+            assert_eq!(pixel(100, 1, 1), RED);
+            assert_eq!(pixel(100, 3, 3), EMPTY);
+            draw_rect(100, None, (4., 4., 8., 8.));
+            assert_eq!(pixel(100, 7, 7), RED);
+            unbind_canvas(100);
+        });
+    }
+
+    #[test]
+    fn copy_from_rendering_target() {
+        run_canvas_test(|| {
+            canvas(100, 10, 10, 1.);
+            tex_create_empty(100, 1, 4, 4);
+            tex_create_empty(100, 2, 4, 4);
+            tex_bind_rendering_target(100, 1, 4, 4);
+            draw_rect(100, Some((1., 0., 0.)), (0., 0., 4., 4.));
+            draw_rect(100, Some((0., 1., 0.)), (0., 0., 2., 2.));
+            // the green quarter to the bottom-right of the other texture
+            tex_copy(100, 2, 2, 2, 0, 0, 2, 2);
+            tex_unbind_rendering_target(100);
+            tex_set_active_texture(100, 0, 2);
+            draw_rect(100, None, (0., 0., 4., 4.));
+            assert_eq!(pixel(100, 2, 2), GREEN);
+            assert_eq!(pixel(100, 3, 3), GREEN);
+            assert_eq!(pixel(100, 1, 1), EMPTY);
+            assert_eq!(pixel(100, 1, 3), EMPTY);
+            unbind_canvas(100);
+        });
+    }
+}
@@ -0,0 +1,640 @@
+use std::collections::HashMap;
+use std::mem;
+use std::slice;
+
+const GL_DRAW_RECT_MAX: i32 = super::GL_DRAW_RECT_MAX;
+const TEXTURE_MAX: i32 = super::TEXTURE_MAX;
+const BLUR_MAX_STEPS: i32 = 32; // the same as the shader
+
+macro_rules! paint {
+    ($canvas_index: expr, |$tex_manager: ident| $f: block) => {
+        let mut canvases = super::CANVASES.borrow_mut();
+        let $tex_manager: &mut TexManager = &mut canvases.get_mut(&$canvas_index).unwrap().tex_manager;
+        $f
+    }
+}
+
+/// A premultiplied RGBA pixel buffer.
+#[derive(Clone)]
+pub struct PixelBuffer {
+    pub width: i32,
+    pub height: i32,
+    pub data: Vec<u8>,
+}
+
+impl PixelBuffer {
+    pub fn new(width: i32, height: i32) -> Self {
+        let width = if width > 0 { width } else { 0 };
+        let height = if height > 0 { height } else { 0 };
+        Self {
+            width,
+            height,
+            data: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn fill(&mut self, color: (f32, f32, f32, f32)) {
+        let c = [to_u8(color.0), to_u8(color.1), to_u8(color.2), to_u8(color.3)];
+        for p in self.data.chunks_mut(4) {
+            p.copy_from_slice(&c);
+        }
+    }
+
+    /// Fill the pixels in `[left, right) x [top, bottom)`.
+    pub fn fill_rect(&mut self, color: (f32, f32, f32, f32), (left, top, right, bottom): (i32, i32, i32, i32)) {
+        let c = [to_u8(color.0), to_u8(color.1), to_u8(color.2), to_u8(color.3)];
+        for y in top..bottom {
+            for x in left..right {
+                let p = ((x + y * self.width) * 4) as usize;
+                self.data[p..(p + 4)].copy_from_slice(&c);
+            }
+        }
+    }
+
+    #[inline]
+    fn texel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = if x < 0 { 0 } else if x >= self.width { self.width - 1 } else { x };
+        let y = if y < 0 { 0 } else if y >= self.height { self.height - 1 } else { y };
+        let p = ((x + y * self.width) * 4) as usize;
+        [
+            self.data[p] as f32 / 255.,
+            self.data[p + 1] as f32 / 255.,
+            self.data[p + 2] as f32 / 255.,
+            self.data[p + 3] as f32 / 255.,
+        ]
+    }
+
+    /// Bilinear sampling with clamp-to-edge, the same as the GL textures.
+    fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.; 4];
+        }
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+        let c00 = self.texel(x0, y0);
+        let c10 = self.texel(x0 + 1, y0);
+        let c01 = self.texel(x0, y0 + 1);
+        let c11 = self.texel(x0 + 1, y0 + 1);
+        let mut ret = [0.; 4];
+        for i in 0..4 {
+            let top = c00[i] + (c10[i] - c00[i]) * fx;
+            let bottom = c01[i] + (c11[i] - c01[i]) * fx;
+            ret[i] = top + (bottom - top) * fy;
+        }
+        ret
+    }
+
+    /// Blend a premultiplied color with `ONE, ONE_MINUS_SRC_ALPHA`.
+    #[inline]
+    fn blend(&mut self, x: i32, y: i32, src: [f32; 4]) {
+        let p = ((x + y * self.width) * 4) as usize;
+        let inv = 1. - src[3];
+        for i in 0..4 {
+            let dest = self.data[p + i] as f32 / 255.;
+            self.data[p + i] = to_u8(src[i] + dest * inv);
+        }
+    }
+}
+
+#[inline]
+fn to_u8(v: f32) -> u8 {
+    let v = v * 255.;
+    if v <= 0. { 0 } else if v >= 255. { 255 } else { v.round() as u8 }
+}
+
+/// Whether a point is in a convex quad, in either winding order.
+fn in_quad(x: f32, y: f32, quad: &[(f32, f32); 4]) -> bool {
+    let mut has_pos = false;
+    let mut has_neg = false;
+    for i in 0..4 {
+        let a = quad[i];
+        let b = quad[(i + 1) % 4];
+        let cross = (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0);
+        if cross > 0. { has_pos = true }
+        if cross < 0. { has_neg = true }
+    }
+    !(has_pos && has_neg)
+}
+
+/// The position of a point in a quad, normalized to (0, 0) at the first corner and (1, 1) at the third corner.
+/// The quad is the perspective projection of the unit square, which is the same as the GLES shader does.
+fn quad_local(x: f32, y: f32, quad: &[(f32, f32); 4]) -> (f32, f32) {
+    // the projective mapping from the unit square, in which (u, v) is along the fourth and the second corner
+    let (p0, p1, p2, p3) = (quad[0], quad[3], quad[2], quad[1]);
+    let (sx, sy) = (p0.0 - p1.0 + p2.0 - p3.0, p0.1 - p1.1 + p2.1 - p3.1);
+    let (dx1, dy1, dx2, dy2) = (p1.0 - p2.0, p1.1 - p2.1, p3.0 - p2.0, p3.1 - p2.1);
+    let den = dx1 * dy2 - dx2 * dy1;
+    let (g, h) = if den == 0. { (0., 0.) } else { ((sx * dy2 - dx2 * sy) / den, (dx1 * sy - sx * dy1) / den) };
+    let (a, b, c) = (p1.0 - p0.0 + g * p1.0, p3.0 - p0.0 + h * p3.0, p0.0);
+    let (d, e, f) = (p1.1 - p0.1 + g * p1.1, p3.1 - p0.1 + h * p3.1, p0.1);
+    // the inverse by the adjugate
+    let u = (e - f * h) * x + (c * h - b) * y + (b * f - c * e);
+    let v = (f * g - d) * x + (a - c * g) * y + (c * d - a * f);
+    let w = (d * h - e * g) * x + (b * g - a * h) * y + (a * e - b * d);
+    if w == 0. { return (-1., -1.) }
+    (u / w, v / w)
+}
+
+/// The signed distance to a rounded rect from (0, 0) to `size`, negative inside.
+fn rounded_rect_distance(x: f32, y: f32, size: (f32, f32), radii: [f32; 4]) -> f32 {
+    let (cx, cy) = (size.0 / 2., size.1 / 2.);
+    let (qx, qy) = (x - cx, y - cy);
+    let r = if qx < 0. {
+        if qy < 0. { radii[0] } else { radii[3] }
+    } else {
+        if qy < 0. { radii[1] } else { radii[2] }
+    };
+    let (dx, dy) = (qx.abs() - cx + r, qy.abs() - cy + r);
+    dx.max(dy).min(0.) + (dx.max(0.).powi(2) + dy.max(0.).powi(2)).sqrt() - r
+}
+
+/// The same as the shape in `img.f.glsl`.
+#[derive(Clone, Copy)]
+struct Shape {
+    size: (f32, f32),
+    radii: [f32; 4],
+    border: [f32; 4], // top, right, bottom, left
+    pixel_scale: f32,
+}
+
+impl Shape {
+    /// The covered ratio of the pixel at a position in the shape.
+    fn coverage(&self, x: f32, y: f32) -> f32 {
+        let s = self.pixel_scale;
+        let outer = (0.5 - rounded_rect_distance(x, y, self.size, self.radii) * s).max(0.).min(1.);
+        let (b, r) = (self.border, self.radii);
+        if b.iter().all(|x| *x <= 0.) {
+            return outer;
+        }
+        let inner_size = (self.size.0 - b[1] - b[3], self.size.1 - b[0] - b[2]);
+        if inner_size.0 <= 0. || inner_size.1 <= 0. {
+            return outer;
+        }
+        let inner_radii = [
+            (r[0] - b[3].max(b[0])).max(0.),
+            (r[1] - b[1].max(b[0])).max(0.),
+            (r[2] - b[1].max(b[2])).max(0.),
+            (r[3] - b[3].max(b[2])).max(0.),
+        ];
+        let inner = (0.5 - rounded_rect_distance(x - b[3], y - b[0], inner_size, inner_radii) * s).max(0.).min(1.);
+        outer * (1. - inner)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Clip {
+    rect: (f32, f32, f32, f32), // left, top, right, bottom, intersected with the outer clips
+    quad: Option<[(f32, f32); 4]>,
+    shape: Option<Shape>, // stretched to the quad
+}
+
+impl Clip {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        let quad = match self.quad {
+            None => return true,
+            Some(ref x) => x,
+        };
+        if !in_quad(x, y, quad) { return false }
+        match self.shape {
+            None => true,
+            Some(ref shape) => {
+                let (u, v) = quad_local(x, y, quad);
+                shape.coverage(u * shape.size.0, v * shape.size.1) >= 0.5
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct DrawRect {
+    tex_shader_index: i32,
+    tex_pos: (f32, f32, f32, f32),
+    quad: [(f32, f32); 4], // left-top, left-bottom, right-bottom, right-top of the tex
+}
+
+pub struct TexManager {
+    width: i32,
+    height: i32,
+    pixel_ratio: f64,
+    clear_color: (f32, f32, f32, f32),
+    color: [f32; 4],
+    alpha: f32,
+    shape: Shape,
+    blur: (f32, f32, f32),
+    framebuffer: PixelBuffer,
+    temp_tex: PixelBuffer,
+    tex_map: HashMap<i32, PixelBuffer>,
+    active_textures: [i32; TEXTURE_MAX as usize],
+    draw_rects: Vec<DrawRect>,
+    binded_rendering_target_stack: Vec<(i32, i32, i32)>,
+    clip_stack: Vec<Clip>,
+    // the clips of the outer rendering targets, which do not apply to the bound one
+    saved_clip_stacks: Vec<Vec<Clip>>,
+}
+
+impl TexManager {
+    pub fn new(width: i32, height: i32, pixel_ratio: f64) -> Self {
+        let mut ret = Self {
+            width: 1,
+            height: 1,
+            pixel_ratio: 1.,
+            clear_color: (0., 0., 0., 0.),
+            color: [0., 0., 0., 1.],
+            alpha: 1.,
+            shape: Shape { size: (0., 0.), radii: [0.; 4], border: [0.; 4], pixel_scale: 1. },
+            blur: (0., 0., 0.),
+            framebuffer: PixelBuffer::new(1, 1),
+            temp_tex: PixelBuffer::new(256, 256),
+            tex_map: HashMap::new(),
+            active_textures: [-1; TEXTURE_MAX as usize],
+            draw_rects: vec![Default::default(); GL_DRAW_RECT_MAX as usize],
+            binded_rendering_target_stack: vec![],
+            clip_stack: vec![],
+            saved_clip_stacks: vec![],
+        };
+        ret.set_tex_draw_size(width, height, pixel_ratio);
+        ret
+    }
+
+    pub fn set_tex_draw_size(&mut self, w: i32, h: i32, pixel_ratio: f64) {
+        self.width = w;
+        self.height = h;
+        self.pixel_ratio = pixel_ratio;
+        self.framebuffer = PixelBuffer::new((w as f64 * pixel_ratio).round() as i32, (h as f64 * pixel_ratio).round() as i32);
+    }
+
+    #[inline]
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+    #[inline]
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+    #[inline]
+    pub fn pixel_ratio(&self) -> f64 {
+        self.pixel_ratio
+    }
+
+    /// The main framebuffer, in device pixels, rows from top to bottom.
+    #[inline]
+    pub fn framebuffer(&self) -> &PixelBuffer {
+        &self.framebuffer
+    }
+
+    pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.clear_color = (r, g, b, a);
+    }
+
+    pub fn clear(&mut self) {
+        let c = self.clear_color;
+        let scale = self.pixel_ratio as f32;
+        let clip = self.clip_pixels(scale, self.framebuffer.width, self.framebuffer.height);
+        self.framebuffer.fill_rect(c, clip);
+    }
+
+    /// The pixel range of the current clip in a target, as `(left, top, right, bottom)`.
+    fn clip_pixels(&self, scale: f32, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        match self.clip_stack.last() {
+            None => (0, 0, width, height),
+            Some(&Clip { rect: (left, top, right, bottom), .. }) => {
+                let left = ((left * scale).floor() as i32).max(0);
+                let top = ((top * scale).floor() as i32).max(0);
+                let right = ((right * scale).ceil() as i32).min(width);
+                let bottom = ((bottom * scale).ceil() as i32).min(height);
+                (left, top, right.max(left), bottom.max(top))
+            }
+        }
+    }
+
+    fn push_clip(&mut self, (mut l, mut t, mut r, mut b): (f32, f32, f32, f32), quad: Option<[(f32, f32); 4]>, shape: Option<Shape>) {
+        match self.clip_stack.last() {
+            None => { },
+            Some(&Clip { rect: (cl, ct, cr, cb), .. }) => {
+                l = l.max(cl);
+                t = t.max(ct);
+                r = r.min(cr);
+                b = b.min(cb);
+            }
+        }
+        self.clip_stack.push(Clip {
+            rect: (l, t, r.max(l), b.max(t)),
+            quad,
+            shape,
+        });
+    }
+
+    fn push_clip_rect(&mut self, left: f32, top: f32, width: f32, height: f32) {
+        self.push_clip((left.min(left + width), top.min(top + height), left.max(left + width), top.max(top + height)), None, None);
+    }
+
+    fn push_clip_quad(&mut self, quad: [(f32, f32); 4], shape: Option<Shape>) {
+        let mut rect = (quad[0].0, quad[0].1, quad[0].0, quad[0].1);
+        for &(x, y) in quad.iter() {
+            rect = (rect.0.min(x), rect.1.min(y), rect.2.max(x), rect.3.max(y));
+        }
+        self.push_clip(rect, Some(quad), shape);
+    }
+
+    fn pop_clip(&mut self) {
+        self.clip_stack.pop();
+    }
+
+    #[inline]
+    fn tex_mut(&mut self, tex_id: i32) -> &mut PixelBuffer {
+        if tex_id < 0 { &mut self.temp_tex } else { self.tex_map.get_mut(&tex_id).unwrap() }
+    }
+
+    fn take_rendering_target(&mut self) -> (i32, PixelBuffer, f32) {
+        match self.binded_rendering_target_stack.last() {
+            None => {
+                let fb = mem::replace(&mut self.framebuffer, PixelBuffer::new(0, 0));
+                (-2, fb, self.pixel_ratio as f32)
+            },
+            Some(&(tex_id, _, _)) => {
+                let tex = mem::replace(self.tex_mut(tex_id), PixelBuffer::new(0, 0));
+                (tex_id, tex, 1.)
+            },
+        }
+    }
+
+    fn restore_rendering_target(&mut self, tex_id: i32, target: PixelBuffer) {
+        if tex_id < -1 {
+            self.framebuffer = target;
+        } else {
+            *self.tex_mut(tex_id) = target;
+        }
+    }
+
+    fn rasterize(&self, target: &mut PixelBuffer, scale: f32, clip: (i32, i32, i32, i32), clip_quads: &[Clip], rect: &DrawRect) {
+        let quad = rect.quad;
+        let (tx, ty, tw, th) = rect.tex_pos;
+        let (mut left, mut top, mut right, mut bottom) = (quad[0].0, quad[0].1, quad[0].0, quad[0].1);
+        for p in quad.iter() {
+            left = left.min(p.0);
+            top = top.min(p.1);
+            right = right.max(p.0);
+            bottom = bottom.max(p.1);
+        }
+        if left == right || top == bottom {
+            return;
+        }
+        // an axis-aligned quad (not rotated) covers its whole bounding box
+        let axis_aligned = quad[0].0 == quad[1].0 && quad[2].0 == quad[3].0 && quad[0].1 == quad[3].1 && quad[1].1 == quad[2].1;
+        let (left, right, top, bottom) = (left * scale, right * scale, top * scale, bottom * scale);
+        // pixels whose centers are inside the quad
+        let px_start = ((left - 0.5).ceil() as i32).max(clip.0);
+        let px_end = ((right - 0.5).ceil() as i32).min(clip.2);
+        let py_start = ((top - 0.5).ceil() as i32).max(clip.1);
+        let py_end = ((bottom - 0.5).ceil() as i32).min(clip.3);
+        let mut tex_shader_index = rect.tex_shader_index;
+        let use_color_rgb = tex_shader_index < 256;
+        if !use_color_rgb {
+            tex_shader_index -= 256;
+        }
+        let tex = if tex_shader_index < 0 || tex_shader_index >= TEXTURE_MAX {
+            None
+        } else {
+            let tex_id = self.active_textures[tex_shader_index as usize];
+            if tex_id < 0 { Some(&self.temp_tex) } else { self.tex_map.get(&tex_id) }
+        };
+        let color = self.color;
+        let alpha = self.alpha;
+        let blur_weights = self.blur_weights();
+        for py in py_start..py_end {
+            for px in px_start..px_end {
+                let (cx, cy) = ((px as f32 + 0.5) / scale, (py as f32 + 0.5) / scale);
+                if !axis_aligned && !in_quad(cx, cy, &quad) {
+                    continue;
+                }
+                if clip_quads.len() > 0 {
+                    if !clip_quads.iter().all(|c| c.contains(cx, cy)) {
+                        continue;
+                    }
+                }
+                let (s, t) = if axis_aligned {
+                    ((cx - quad[0].0) / (quad[3].0 - quad[0].0), (cy - quad[0].1) / (quad[1].1 - quad[0].1))
+                } else {
+                    quad_local(cx, cy, &quad)
+                };
+                let (u, v) = (tx + s * tw, ty + t * th);
+                let src = if rect.tex_shader_index == -3 || rect.tex_shader_index == -4 {
+                    // the tex position is the position in the shape
+                    let mut a = self.shape.coverage(u, v);
+                    if rect.tex_shader_index == -4 {
+                        a = if a < 0.5 { 0. } else { 1. };
+                    }
+                    let a = a * alpha;
+                    [color[0] * a, color[1] * a, color[2] * a, color[3] * a]
+                } else if rect.tex_shader_index < -1 {
+                    [color[0] * alpha, color[1] * alpha, color[2] * alpha, color[3] * alpha]
+                } else {
+                    let c = match tex {
+                        None => [0.; 4],
+                        Some(tex) => {
+                            if blur_weights.len() == 0 {
+                                tex.sample(u, v)
+                            } else {
+                                let radius = (blur_weights.len() / 2) as i32;
+                                let mut c = [0.; 4];
+                                for (i, weight) in blur_weights.iter().enumerate() {
+                                    let d = (i as i32 - radius) as f32;
+                                    let s = tex.sample(u + self.blur.0 * d, v + self.blur.1 * d);
+                                    for j in 0..4 {
+                                        c[j] += s[j] * weight;
+                                    }
+                                }
+                                c
+                            }
+                        },
+                    };
+                    if use_color_rgb {
+                        let a = c[3] * alpha;
+                        [color[0] * a, color[1] * a, color[2] * a, color[3] * a]
+                    } else {
+                        [c[0] * alpha, c[1] * alpha, c[2] * alpha, c[3] * alpha]
+                    }
+                };
+                target.blend(px, py, src);
+            }
+        }
+    }
+
+    // the normalized gaussian weights of the samples from -radius to radius, or empty if not blurring
+    fn blur_weights(&self) -> Vec<f32> {
+        let sigma = self.blur.2;
+        if sigma <= 0. {
+            return vec![];
+        }
+        let radius = ((sigma * 3.).ceil() as i32).min(BLUR_MAX_STEPS);
+        let mut weights: Vec<f32> = (-radius..=radius).map(|i| (-((i * i) as f32) / (2. * sigma * sigma)).exp()).collect();
+        let sum: f32 = weights.iter().sum();
+        for w in weights.iter_mut() {
+            *w /= sum;
+        }
+        weights
+    }
+
+    fn draw_end(&mut self, draw_count: i32) {
+        let (tex_id, mut target, scale) = self.take_rendering_target();
+        let clip = self.clip_pixels(scale, target.width, target.height);
+        let clip_quads: Vec<Clip> = self.clip_stack.iter().filter(|c| c.quad.is_some()).cloned().collect();
+        for i in 0..(draw_count as usize) {
+            let rect = self.draw_rects[i];
+            self.rasterize(&mut target, scale, clip, &clip_quads, &rect);
+        }
+        self.restore_rendering_target(tex_id, target);
+    }
+
+    fn bind_rendering_target(&mut self, tex_id: i32, width: i32, height: i32) {
+        self.binded_rendering_target_stack.push((tex_id, width, height));
+        self.saved_clip_stacks.push(mem::replace(&mut self.clip_stack, vec![]));
+        *self.tex_mut(tex_id) = PixelBuffer::new(width, height);
+    }
+
+    fn unbind_rendering_target(&mut self) {
+        self.binded_rendering_target_stack.pop();
+        self.clip_stack = self.saved_clip_stacks.pop().unwrap();
+    }
+}
+
+pub fn tex_create(canvas_index: i32, width: i32, height: i32, buf: Vec<u8>, tex_id: i32) {
+    paint!(canvas_index, |tex_manager| {
+        let tex = PixelBuffer {
+            width,
+            height,
+            data: buf,
+        };
+        if tex_id < 0 {
+            tex_manager.temp_tex = tex;
+        } else {
+            tex_manager.tex_map.insert(tex_id, tex);
+        }
+    });
+}
+pub fn tex_create_from_pixels(canvas_index: i32, tex_id: i32, width: i32, height: i32, buf: *const u8) {
+    let buf = unsafe { slice::from_raw_parts(buf, (width * height * 4) as usize) }.to_vec();
+    tex_create(canvas_index, width, height, buf, tex_id);
+}
+pub fn tex_rewrite(canvas_index: i32, buf: Vec<u8>, tex_id: i32, left: i32, top: i32, width: i32, height: i32) {
+    paint!(canvas_index, |tex_manager| {
+        let tex = tex_manager.tex_mut(tex_id);
+        for y in 0..height {
+            let dy = top + y;
+            if dy < 0 || dy >= tex.height { continue }
+            for x in 0..width {
+                let dx = left + x;
+                if dx < 0 || dx >= tex.width { continue }
+                let src = ((x + y * width) * 4) as usize;
+                let dest = ((dx + dy * tex.width) * 4) as usize;
+                tex.data[dest..(dest + 4)].copy_from_slice(&buf[src..(src + 4)]);
+            }
+        }
+    });
+}
+pub fn tex_copy(canvas_index: i32, dest_tex_id: i32, dest_left: i32, dest_top: i32, src_left: i32, src_top: i32, width: i32, height: i32) {
+    paint!(canvas_index, |tex_manager| {
+        let (tex_id, src, _) = tex_manager.take_rendering_target();
+        {
+            let dest = tex_manager.tex_mut(dest_tex_id);
+            for y in 0..height {
+                let (sy, dy) = (src_top + y, dest_top + y);
+                if sy < 0 || sy >= src.height || dy < 0 || dy >= dest.height { continue }
+                for x in 0..width {
+                    let (sx, dx) = (src_left + x, dest_left + x);
+                    if sx < 0 || sx >= src.width || dx < 0 || dx >= dest.width { continue }
+                    let s = ((sx + sy * src.width) * 4) as usize;
+                    let d = ((dx + dy * dest.width) * 4) as usize;
+                    dest.data[d..(d + 4)].copy_from_slice(&src.data[s..(s + 4)]);
+                }
+            }
+        }
+        tex_manager.restore_rendering_target(tex_id, src);
+    });
+}
+pub fn tex_bind_rendering_target(canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.bind_rendering_target(tex_id, width, height);
+    });
+}
+pub fn tex_unbind_rendering_target(canvas_index: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.unbind_rendering_target();
+    });
+}
+pub fn tex_create_empty(canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.tex_map.insert(tex_id, PixelBuffer::new(width, height));
+    });
+}
+pub fn tex_delete(canvas_index: i32, tex_id: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.tex_map.remove(&tex_id).unwrap();
+    });
+}
+pub fn tex_draw(canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.draw_rects[draw_index as usize] = DrawRect {
+            tex_shader_index,
+            tex_pos: (normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h),
+            quad: [(x1, y1), (x2, y2), (x3, y3), (x4, y4)],
+        };
+    });
+}
+pub fn tex_set_active_texture(canvas_index: i32, tex_shader_index: i32, tex_id: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.active_textures[tex_shader_index as usize] = tex_id;
+    });
+}
+pub fn tex_draw_end(canvas_index: i32, draw_count: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.draw_end(draw_count);
+    });
+}
+pub fn tex_set_draw_state(canvas_index: i32, color_r: f32, color_g: f32, color_b: f32, color_a: f32, alpha: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.color = [color_r, color_g, color_b, color_a];
+        tex_manager.alpha = alpha;
+    });
+}
+pub fn tex_set_draw_shape(canvas_index: i32, width: f32, height: f32, radius_lt: f32, radius_rt: f32, radius_rb: f32, radius_lb: f32, border_top: f32, border_right: f32, border_bottom: f32, border_left: f32, pixel_scale: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.shape = Shape {
+            size: (width, height),
+            radii: [radius_lt, radius_rt, radius_rb, radius_lb],
+            border: [border_top, border_right, border_bottom, border_left],
+            pixel_scale,
+        };
+    });
+}
+pub fn tex_set_draw_blur(canvas_index: i32, step_x: f32, step_y: f32, sigma: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.blur = (step_x, step_y, sigma);
+    });
+}
+pub fn tex_push_clip_rect(canvas_index: i32, left: f32, top: f32, width: f32, height: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.push_clip_rect(left, top, width, height);
+    });
+}
+pub fn tex_push_clip_quad(canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.push_clip_quad([(x1, y1), (x2, y2), (x3, y3), (x4, y4)], None);
+    });
+}
+pub fn tex_push_clip_shape(canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, |tex_manager| {
+        let shape = tex_manager.shape;
+        tex_manager.push_clip_quad([(x1, y1), (x2, y2), (x3, y3), (x4, y4)], Some(shape));
+    });
+}
+pub fn tex_pop_clip(canvas_index: i32) {
+    paint!(canvas_index, |tex_manager| {
+        tex_manager.pop_clip();
+    });
+}
@@ -7,13 +7,17 @@ use super::utils;
 
 #[cfg(any(target_arch = "asmjs", target_arch = "wasm32"))]
 mod asmjs;
-#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+#[cfg(all(not(any(target_arch = "asmjs", target_arch = "wasm32")), not(feature = "headless")))]
 mod native;
+#[cfg(all(not(any(target_arch = "asmjs", target_arch = "wasm32")), feature = "headless"))]
+mod headless;
 
 #[cfg(any(target_arch = "asmjs", target_arch = "wasm32"))]
 pub use self::asmjs::*;
-#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+#[cfg(all(not(any(target_arch = "asmjs", target_arch = "wasm32")), not(feature = "headless")))]
 pub use self::native::*;
+#[cfg(all(not(any(target_arch = "asmjs", target_arch = "wasm32")), feature = "headless"))]
+pub use self::headless::*;
 
 lazy_static! {
     static ref SWAP_BUFFER_SIZE: utils::PretendSend<Cell<usize>> = utils::PretendSend::new(Cell::new(65536));
@@ -52,6 +56,13 @@ macro_rules! lib_callback {
     }
 }
 
+mod backend;
+#[cfg(test)]
+mod recording_backend;
+pub use self::backend::{Backend, DefaultBackend, IMAGE_LOAD_ERROR, IMAGE_LOAD_ERROR_READ, IMAGE_LOAD_ERROR_DECODE};
+#[cfg(test)]
+pub(crate) use self::recording_backend::{RecordingBackend, Call, TestCanvas, run_canvas_test};
+
 pub trait Callback {
     fn callback(&mut self, ret_0: i32, ret_1: i32, ret_2: i32, ret_3: i32) -> bool;
 }
@@ -16,6 +16,7 @@ lazy_static! {
 	static ref FONT_FAMILIE_NAMES: Arc<Mutex<HashMap<i32, String>>> = Arc::new(Mutex::new(HashMap::new()));
 	static ref FONT_INFO: Arc<Mutex<HashMap<FontInfoKey, Vec<SingleFontFamily>>>> = Arc::new(Mutex::new(HashMap::new()));
 	static ref CURRENT_FONT: Arc<Mutex<FontSettings>> = Arc::new(Mutex::new(FontSettings::new()));
+	static ref FONT_FILE: Arc<Mutex<Option<Arc<Vec<u8>>>>> = Arc::new(Mutex::new(None));
 }
 
 #[derive(PartialEq, Eq, Hash, Clone)]
@@ -59,6 +60,17 @@ fn select_font(fonts_info: &Vec<SingleFontFamily>, glyph: char) -> (&SingleFontF
 }
 
 fn load_font_family(names: &String, properties: &Properties) -> Vec<SingleFontFamily> {
+	match *FONT_FILE.lock().unwrap() {
+		None => { },
+		Some(ref data) => {
+			let font = Font::from_bytes(data.clone(), 0).unwrap();
+			let metrics = font.metrics();
+			return vec![SingleFontFamily {
+				font,
+				metrics,
+			}];
+		}
+	};
 	let mut ret = vec![];
 	names.split(',').for_each(|s| {
 		let name = String::from(s.trim());
@@ -112,19 +124,30 @@ fn load_font_family(names: &String, properties: &Properties) -> Vec<SingleFontFa
 }
 
 fn init_default_font_family() -> i32 {
+	load_default_font_family();
+	-1
+}
+
+fn load_default_font_family() {
 	let key = FontInfoKey {
 		font_family_id: -1,
 		italic: 0,
 		bold: 0,
 	};
 	FONT_INFO.lock().unwrap().insert(key, load_font_family(&String::from("sans-serif"), &Properties::new()));
-	-1
 }
 
 pub fn init() {
 	assert!(*DEFAULT_FONT_FAMILY_ID == -1);
 }
 
+/// Use the font file for all font families instead of the system fonts, so that the text looks the same on every machine.
+pub fn text_use_font_file(data: Vec<u8>) {
+	*FONT_FILE.lock().unwrap() = Some(Arc::new(data));
+	FONT_INFO.lock().unwrap().clear();
+	load_default_font_family();
+}
+
 struct FontSettings {
 	font_size: i32,
 	line_height: i32,
@@ -2,44 +2,151 @@ use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::os::raw::c_char;
 use std::ffi::CStr;
-use std::path::Path;
+use std::path::PathBuf;
 use std::thread;
+use std::fs;
+use std::slice;
 use std::time::Instant;
 use image;
 use super::layout_thread;
-use super::super::Callback;
+use super::super::{Callback, IMAGE_LOAD_ERROR_READ, IMAGE_LOAD_ERROR_DECODE};
 use super::super::super::utils::PretendSend;
+use super::svg::SvgDocument;
 
 lazy_static! {
-    static ref IMAGES: Arc<Mutex<HashMap<i32, (i32, i32, Box<[u8]>)>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref IMAGES: Arc<Mutex<HashMap<i32, ImageData>>> = Arc::new(Mutex::new(HashMap::new()));
+    static ref IMAGE_ERRORS: Arc<Mutex<HashMap<i32, String>>> = Arc::new(Mutex::new(HashMap::new()));
+}
+
+enum ImageData {
+    Raster(i32, i32, Box<[u8]>),
+    // the vector images are rendered when creating the textures
+    Vector(SvgDocument),
+}
+
+impl ImageData {
+    fn size(&self) -> (i32, i32) {
+        match self {
+            ImageData::Raster(w, h, _) => (*w, *h),
+            ImageData::Vector(doc) => {
+                let (w, h) = doc.size();
+                (w.ceil() as i32, h.ceil() as i32)
+            },
+        }
+    }
+}
+
+fn decode_raster(result: image::ImageResult<image::DynamicImage>) -> Result<ImageData, (i32, String)> {
+    match result {
+        Ok(x) => {
+            let rgba_image = x.to_rgba();
+            Ok(ImageData::Raster(rgba_image.width() as i32, rgba_image.height() as i32, rgba_image.into_raw().into_boxed_slice()))
+        },
+        Err(image::ImageError::IoError(e)) => Err((IMAGE_LOAD_ERROR_READ, e.to_string())),
+        Err(e) => Err((IMAGE_LOAD_ERROR_DECODE, e.to_string())),
+    }
+}
+fn decode_svg(text: &str) -> Result<ImageData, (i32, String)> {
+    SvgDocument::parse(text).map(|x| ImageData::Vector(x)).map_err(|e| (IMAGE_LOAD_ERROR_DECODE, e))
+}
+// store the image and call the callback in the layout thread
+fn finish_loading(id: i32, image_info: Result<ImageData, (i32, String)>, cb_ptr: PretendSend<*mut Box<Callback>>, src: &str) {
+    let ret_code = match image_info {
+        Ok(image_info) => {
+            IMAGES.lock().unwrap().insert(id, image_info);
+            IMAGE_ERRORS.lock().unwrap().remove(&id);
+            0
+        },
+        Err((code, e)) => {
+            warn!("Failed to load image {}: {}", src, e);
+            IMAGE_ERRORS.lock().unwrap().insert(id, e);
+            code
+        },
+    };
+    layout_thread::push_event(Instant::now(), layout_thread::EventDetail::ImageLoadEvent, move |_time, _detail| {
+        super::super::callback(*cb_ptr, ret_code, 0, 0, 0);
+    })
 }
 
 pub fn image_load_url(id: i32, url: *mut c_char, cb_ptr: *mut Box<Callback>) {
     let url = unsafe { CStr::from_ptr(url) };
-    let url = Path::new(url.to_str().unwrap());
+    let cb_ptr = PretendSend::new(cb_ptr);
+    let url = match url.to_str() {
+        Ok(x) => PathBuf::from(x),
+        Err(e) => {
+            finish_loading(id, Err((IMAGE_LOAD_ERROR_READ, e.to_string())), cb_ptr, &url.to_string_lossy());
+            return;
+        },
+    };
+    thread::spawn(move || {
+        let is_svg = match url.extension() {
+            None => false,
+            Some(x) => x.to_string_lossy().eq_ignore_ascii_case("svg"),
+        };
+        let image_info = if is_svg {
+            fs::read_to_string(&url).map_err(|e| (IMAGE_LOAD_ERROR_READ, e.to_string())).and_then(|x| decode_svg(&x))
+        } else {
+            decode_raster(image::open(&url))
+        };
+        finish_loading(id, image_info, cb_ptr, &format!("{:?}", url));
+    });
+}
+pub fn image_load_bytes(id: i32, buf: *const u8, len: i32, cb_ptr: *mut Box<Callback>) {
+    let buf = unsafe { slice::from_raw_parts(buf, len as usize) }.to_vec();
     let cb_ptr = PretendSend::new(cb_ptr);
     thread::spawn(move || {
-        let rgba_image = image::open(url).unwrap().to_rgba();
-        let image_info = (rgba_image.width() as i32, rgba_image.height() as i32, rgba_image.into_raw().into_boxed_slice());
-        IMAGES.lock().unwrap().insert(id, image_info);
-        layout_thread::push_event(Instant::now(), layout_thread::EventDetail::ImageLoadEvent, move |_time, _detail| {
-            super::super::callback(*cb_ptr, 0, 0, 0, 0);
-        })
+        // the bytes are treated as SVG if not in any raster format
+        let image_info = match image::guess_format(&buf) {
+            Ok(_) => decode_raster(image::load_from_memory(&buf)),
+            Err(_) => String::from_utf8(buf).map_err(|e| (IMAGE_LOAD_ERROR_DECODE, e.to_string())).and_then(|x| decode_svg(&x)),
+        };
+        finish_loading(id, image_info, cb_ptr, "from bytes");
     });
 }
 pub fn image_unload(id: i32) {
     IMAGES.lock().unwrap().remove(&id);
+    IMAGE_ERRORS.lock().unwrap().remove(&id);
+}
+// copy at most `len` bytes of the error message to `buf`, and return the length of the whole message
+pub fn image_get_error(id: i32, buf: *mut u8, len: i32) -> i32 {
+    let errors = IMAGE_ERRORS.lock().unwrap();
+    let message = match errors.get(&id) {
+        None => return 0,
+        Some(x) => x.as_bytes(),
+    };
+    let copied = message.len().min(len.max(0) as usize);
+    if copied > 0 {
+        unsafe { slice::from_raw_parts_mut(buf, copied) }.copy_from_slice(&message[..copied]);
+    }
+    message.len() as i32
 }
 pub fn image_get_natural_width(id: i32) -> i32 {
-    IMAGES.lock().unwrap().get(&id).unwrap().0 as i32
+    IMAGES.lock().unwrap().get(&id).unwrap().size().0
 }
 pub fn image_get_natural_height(id: i32) -> i32 {
-    IMAGES.lock().unwrap().get(&id).unwrap().1 as i32
+    IMAGES.lock().unwrap().get(&id).unwrap().size().1
+}
+pub fn image_is_vector(id: i32) -> i32 {
+    match IMAGES.lock().unwrap().get(&id).unwrap() {
+        ImageData::Raster(..) => 0,
+        ImageData::Vector(_) => 1,
+    }
 }
 pub fn tex_from_image(canvas_index: i32, tex_id: i32, img_id: i32) {
+    let (width, height) = IMAGES.lock().unwrap()[&img_id].size();
+    tex_from_image_with_size(canvas_index, tex_id, img_id, width, height);
+}
+pub fn tex_from_image_with_size(canvas_index: i32, tex_id: i32, img_id: i32, width: i32, height: i32) {
     let images = &IMAGES.lock().unwrap();
-    let image = &images[&img_id];
-    let original = &*image.2;
+    let (width, height, original) = match &images[&img_id] {
+        ImageData::Raster(w, h, buf) => (*w, *h, &**buf),
+        ImageData::Vector(doc) => {
+            // the rendered pixels are premultiplied already
+            let (width, height) = (width.max(1), height.max(1));
+            super::tex_manager::tex_create(canvas_index, width, height, doc.render((width, height)), tex_id);
+            return;
+        },
+    };
     let mut premultiplied: Vec<u8> = Vec::with_capacity(original.len());
 	for i in 0..(original.len() / 4) {
         let p = i * 4;
@@ -51,5 +158,25 @@ pub fn tex_from_image(canvas_index: i32, tex_id: i32, img_id: i32) {
             original[p + 3]
         ]);
 	}
-    super::tex_manager::tex_create(canvas_index, image.0, image.1, premultiplied, tex_id);
+    super::tex_manager::tex_create(canvas_index, width, height, premultiplied, tex_id);
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr;
+    use super::{IMAGE_ERRORS, image_get_error, image_unload};
+    use super::super::super::{Backend, DefaultBackend};
+
+    #[test]
+    fn error_message() {
+        let message = "Format error: unexpected EOF";
+        IMAGE_ERRORS.lock().unwrap().insert(-100, String::from(message));
+        let mut buf = [0u8; 6];
+        assert_eq!(image_get_error(-100, buf.as_mut_ptr(), 6), message.len() as i32);
+        assert_eq!(&buf, b"Format");
+        assert_eq!(DefaultBackend::new().image_get_error(-100), message);
+        image_unload(-100);
+        assert_eq!(image_get_error(-100, ptr::null_mut(), 0), 0);
+        assert_eq!(DefaultBackend::new().image_get_error(-100), "");
+    }
 }
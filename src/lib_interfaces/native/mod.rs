@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 
 use std::collections::HashMap;
-use std::sync::{Arc, Mutex, RwLock, Barrier};
+use std::sync::{Arc, Mutex, RwLock, Barrier, mpsc};
+use std::ptr;
 use std::cell::RefCell;
 use std::time::{Instant, Duration};
 use glutin;
@@ -14,6 +15,7 @@ mod layout_thread;
 mod painting_thread;
 mod tex_manager;
 mod image_manager;
+mod svg;
 mod font_manager;
 
 use self::gl::Gles2 as Gl;
@@ -22,6 +24,7 @@ use self::painting_thread::PaintingCommand;
 const DEFAULT_WINDOW_SIZE: (i32, i32) = (1280, 720);
 const GL_DRAW_RECT_MAX: i32 = 65536 / 8;
 const TEXTURE_MAX: i32 = 16;
+const WHEEL_LINE_HEIGHT: f64 = 40.;
 
 lazy_static! {
     static ref MAIN_LOOP: PretendSend<RefCell<MainLoop>> = PretendSend::new(RefCell::new(MainLoop::new()));
@@ -48,6 +51,7 @@ struct MainLoopWindow {
     redraw_needed: bool,
     keyboard_event_handler: PretendSend<Option<*mut Box<Callback>>>,
     touch_event_handler: PretendSend<Option<*mut Box<Callback>>>,
+    wheel_event_handler: PretendSend<Option<*mut Box<Callback>>>,
     window_size_listener: PretendSend<Option<*mut Box<Callback>>>,
     touching: bool,
     mouse_location: (i32, i32),
@@ -65,12 +69,13 @@ pub fn trigger_painting() {
         let canvas_index = window.canvas_index;
         let painting_thread = &mut window.painting_thread;
         let barrier = barrier_self.clone();
-        painting_thread.append_command(PaintingCommand::CustomCommand(Box::new(move |_ctx, _tex_manager| {
+        painting_thread.append_command(PaintingCommand::CustomCommand(Box::new(move |ctx, tex_manager| {
             let w = MAIN_LOOP_WINDOWS.read().unwrap();
             let w = w.get(&canvas_index).unwrap();
             let mut w = w.lock().unwrap();
             if w.redraw_needed {
                 w.redraw_needed = false;
+                tex_manager.present(ctx);
                 w.gl_window.swap_buffers().unwrap();
             }
             barrier.wait();
@@ -181,6 +186,24 @@ pub fn main_loop(f: fn() -> ()) {
                                                         }, None);
                                                     }
                                                 },
+                                                WindowEvent::MouseWheel { device_id: _, delta, phase: _, modifiers: _ } => {
+                                                    let (cb, mouse_location) = {
+                                                        let cm = MAIN_LOOP_WINDOWS.read().unwrap();
+                                                        let window = cm[&canvas_index].lock().unwrap();
+                                                        ((*window.wheel_event_handler).clone(), window.mouse_location)
+                                                    };
+                                                    // positive deltas of glutin scroll the content up and left
+                                                    let (dx, dy) = match delta {
+                                                        glutin::MouseScrollDelta::LineDelta(x, y) => (-x as f64 * WHEEL_LINE_HEIGHT, -y as f64 * WHEEL_LINE_HEIGHT),
+                                                        glutin::MouseScrollDelta::PixelDelta(pos) => (-pos.x, -pos.y),
+                                                    };
+                                                    match cb {
+                                                        None => { },
+                                                        Some(cb) => {
+                                                            super::callback(cb, mouse_location.0, mouse_location.1, dx.round() as i32, dy.round() as i32);
+                                                        }
+                                                    }
+                                                },
                                                 WindowEvent::Touch(touch) => {
                                                     generate_touch_event(touch.phase, Some((touch.location.x as i32, touch.location.y as i32)));
                                                 },
@@ -299,6 +322,7 @@ pub fn bind_canvas(canvas_index: i32) {
             redraw_needed: false,
             keyboard_event_handler: PretendSend::new(None),
             touch_event_handler: PretendSend::new(None),
+            wheel_event_handler: PretendSend::new(None),
             window_size_listener: PretendSend::new(None),
             touching: false,
             mouse_location: (0, 0),
@@ -383,6 +407,11 @@ pub fn bind_touch_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
     let mut c = cm.get(&canvas_index).unwrap().lock().unwrap();
     c.touch_event_handler = PretendSend::new(Some(cb_ptr));
 }
+pub fn bind_wheel_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+    let cm = MAIN_LOOP_WINDOWS.read().unwrap();
+    let mut c = cm.get(&canvas_index).unwrap().lock().unwrap();
+    c.wheel_event_handler = PretendSend::new(Some(cb_ptr));
+}
 pub fn bind_keyboard_events(canvas_index: i32, cb_ptr: *mut Box<Callback>) {
     let cm = MAIN_LOOP_WINDOWS.read().unwrap();
     let mut c = cm.get(&canvas_index).unwrap().lock().unwrap();
@@ -409,7 +438,21 @@ pub fn tex_get_count(canvas_index: i32) -> i32 {
 pub fn tex_get_max_draws() -> i32 {
     GL_DRAW_RECT_MAX
 }
+pub fn tex_get_pixels(canvas_index: i32, left: i32, top: i32, width: i32, height: i32, buf: *mut u8) {
+    let (sender, receiver) = mpsc::channel();
+    {
+        let w = MAIN_LOOP_WINDOWS.read().unwrap();
+        let w = w.get(&canvas_index).unwrap();
+        let mut w = w.lock().unwrap();
+        w.painting_thread.append_command(PaintingCommand::CustomCommand(Box::new(move |ctx, tex_manager| {
+            sender.send(tex_manager.get_pixels(ctx, left, top, width, height)).unwrap();
+        })));
+        w.painting_thread.redraw();
+    }
+    let pixels = receiver.recv().unwrap();
+    unsafe { ptr::copy_nonoverlapping(pixels.as_ptr(), buf, pixels.len()) };
+}
 
-pub use self::tex_manager::{tex_create_empty, tex_copy, tex_bind_rendering_target, tex_unbind_rendering_target, tex_delete, tex_draw, tex_set_active_texture, tex_draw_end, tex_set_draw_state};
-pub use self::image_manager::{image_load_url, image_unload, image_get_natural_width, image_get_natural_height, tex_from_image};
-pub use self::font_manager::{text_bind_font_family, text_unbind_font_family, text_set_font, text_get_width, text_to_tex};
+pub use self::tex_manager::{tex_create_empty, tex_create_from_pixels, tex_copy, tex_bind_rendering_target, tex_unbind_rendering_target, tex_delete, tex_draw, tex_set_active_texture, tex_draw_end, tex_set_draw_state, tex_set_draw_shape, tex_set_draw_blur, tex_push_clip_rect, tex_push_clip_quad, tex_push_clip_shape, tex_pop_clip};
+pub use self::image_manager::{image_load_url, image_load_bytes, image_unload, image_get_error, image_get_natural_width, image_get_natural_height, image_is_vector, tex_from_image, tex_from_image_with_size};
+pub use self::font_manager::{text_bind_font_family, text_unbind_font_family, text_set_font, text_get_width, text_to_tex, text_use_font_file};
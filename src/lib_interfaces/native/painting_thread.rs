@@ -110,11 +110,16 @@ impl PaintingThread {
     }
 
     pub fn redraw(&mut self) {
-        // FIXME consider swap but not create new vec
+        // the commands cannot be dropped since the canvas content is preserved between frames
         let pending = self.cmd_buffer_pending.replace(vec![]);
-        let dropped = self.cmd_buffer.lock().unwrap().replace(pending);
-        if dropped.len() > 0 {
-            debug!("Draw call dropped!");
+        {
+            let cmd_buffer = self.cmd_buffer.lock().unwrap();
+            let mut buf = cmd_buffer.replace(vec![]);
+            if buf.len() > 0 {
+                debug!("Draw call delayed!");
+            }
+            buf.extend(pending);
+            cmd_buffer.set(buf);
         }
         self.sender.send(PaintingJob::Queue).unwrap();
     }
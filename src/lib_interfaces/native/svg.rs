@@ -0,0 +1,469 @@
+use xml::reader::{EventReader, XmlEvent};
+use xml::attribute::OwnedAttribute;
+use cssparser::{Parser, ParserInput, Color};
+use super::super::super::canvas::path::{PathSegment, PathStyle, parse_path_data, build_path_shape, render_path};
+use super::super::super::canvas::element::{FillRule, LineJoin, LineCap};
+
+const DEFAULT_WIDTH: f64 = 300.;
+const DEFAULT_HEIGHT: f64 = 150.;
+const PIXEL_TOLERANCE: f64 = 0.25;
+
+// the 2D affine matrix (a, b, c, d, e, f) the same as SVG, which maps (x, y) to (ax + cy + e, bx + dy + f)
+type Matrix = [f64; 6];
+const IDENTITY: Matrix = [1., 0., 0., 1., 0., 0.];
+
+fn multiply(m: &Matrix, n: &Matrix) -> Matrix {
+    [
+        m[0] * n[0] + m[2] * n[1],
+        m[1] * n[0] + m[3] * n[1],
+        m[0] * n[2] + m[2] * n[3],
+        m[1] * n[2] + m[3] * n[3],
+        m[0] * n[4] + m[2] * n[5] + m[4],
+        m[1] * n[4] + m[3] * n[5] + m[5],
+    ]
+}
+
+/// A parsed SVG document, which can be rendered at any size.
+/// Only the basic shapes, the paths and the groups with the fill and stroke properties are supported.
+pub struct SvgDocument {
+    width: f64,
+    height: f64,
+    view_box: Option<(f64, f64, f64, f64)>,
+    // the alignment ratio of the view box, and whether it is sliced instead of fitted
+    align: Option<(f64, f64, bool)>,
+    shapes: Vec<(Vec<PathSegment>, PathStyle, Matrix)>,
+}
+
+// the inherited properties
+#[derive(Clone)]
+struct SvgState {
+    matrix: Matrix,
+    fill: Option<(f32, f32, f32)>,
+    fill_opacity: f32,
+    fill_rule: FillRule,
+    stroke: Option<(f32, f32, f32)>,
+    stroke_opacity: f32,
+    stroke_width: f64,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    miter_limit: f64,
+    opacity: f32,
+    visible: bool,
+}
+
+impl SvgDocument {
+    /// Parse the SVG source.
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut doc = SvgDocument {
+            width: DEFAULT_WIDTH,
+            height: DEFAULT_HEIGHT,
+            view_box: None,
+            align: Some((0.5, 0.5, false)),
+            shapes: vec![],
+        };
+        let mut stack: Vec<SvgState> = vec![];
+        let mut skip_depth = 0;
+        let mut has_root = false;
+        for e in EventReader::from_str(source) {
+            match e.map_err(|e| e.to_string())? {
+                XmlEvent::StartElement { name, attributes, .. } => {
+                    if skip_depth > 0 {
+                        skip_depth += 1;
+                        continue;
+                    }
+                    let name = name.local_name;
+                    let mut state = match stack.last() {
+                        Some(x) => x.clone(),
+                        None => {
+                            if name != "svg" { return Err(format!("The root element is <{}> instead of <svg>", name)) }
+                            has_root = true;
+                            doc.read_root_attributes(&attributes);
+                            SvgState {
+                                matrix: IDENTITY,
+                                fill: Some((0., 0., 0.)),
+                                fill_opacity: 1.,
+                                fill_rule: FillRule::NonZero,
+                                stroke: None,
+                                stroke_opacity: 1.,
+                                stroke_width: 1.,
+                                line_join: LineJoin::Miter,
+                                line_cap: LineCap::Butt,
+                                miter_limit: 4.,
+                                opacity: 1.,
+                                visible: true,
+                            }
+                        },
+                    };
+                    let displayed = state.apply_attributes(&attributes);
+                    let segments = match &*name {
+                        "svg" | "g" | "a" => None,
+                        "path" | "rect" | "circle" | "ellipse" | "line" | "polyline" | "polygon" => Some(shape_segments(&name, &attributes)),
+                        // the definitions, the texts and the unknown elements are not drawn
+                        _ => {
+                            skip_depth = 1;
+                            continue;
+                        },
+                    };
+                    if !displayed {
+                        skip_depth = 1;
+                        continue;
+                    }
+                    match segments {
+                        Some(segments) => {
+                            if state.visible && !segments.is_empty() {
+                                doc.shapes.push((segments, state.path_style(), state.matrix));
+                            }
+                        },
+                        None => { },
+                    }
+                    stack.push(state);
+                },
+                XmlEvent::EndElement { .. } => {
+                    if skip_depth > 0 {
+                        skip_depth -= 1;
+                    } else {
+                        stack.pop();
+                    }
+                },
+                _ => { },
+            }
+        }
+        if !has_root { return Err(String::from("No <svg> element")) }
+        Ok(doc)
+    }
+    fn read_root_attributes(&mut self, attributes: &[OwnedAttribute]) {
+        let attr = |name: &str| attributes.iter().find(|x| x.name.local_name == name).map(|x| x.value.as_str());
+        self.view_box = attr("viewBox").and_then(|x| {
+            let v = parse_numbers(x);
+            if v.len() == 4 && v[2] > 0. && v[3] > 0. { Some((v[0], v[1], v[2], v[3])) } else { None }
+        });
+        self.align = match attr("preserveAspectRatio") {
+            None => Some((0.5, 0.5, false)),
+            Some(x) => parse_preserve_aspect_ratio(x),
+        };
+        let width = attr("width").and_then(parse_length);
+        let height = attr("height").and_then(parse_length);
+        // the missing size follows the aspect ratio of the view box
+        let (width, height) = match (width, height, self.view_box) {
+            (Some(w), Some(h), _) => (w, h),
+            (Some(w), None, Some(vb)) => (w, w / vb.2 * vb.3),
+            (None, Some(h), Some(vb)) => (h / vb.3 * vb.2, h),
+            (None, None, Some(vb)) => (vb.2, vb.3),
+            (w, h, None) => (w.unwrap_or(DEFAULT_WIDTH), h.unwrap_or(DEFAULT_HEIGHT)),
+        };
+        self.width = width.max(0.);
+        self.height = height.max(0.);
+    }
+    /// The natural size in pixels.
+    #[inline]
+    pub fn size(&self) -> (f64, f64) {
+        (self.width, self.height)
+    }
+    /// Render the document stretched to premultiplied RGBA pixels of `tex_size`, rows from top to bottom.
+    pub fn render(&self, tex_size: (i32, i32)) -> Vec<u8> {
+        let (tex_w, tex_h) = tex_size;
+        let mut ret = vec![0u8; (tex_w * tex_h * 4) as usize];
+        if self.width <= 0. || self.height <= 0. { return ret }
+        // the document size to the pixels, then the view box to the document size
+        let mut base = [tex_w as f64 / self.width, 0., 0., tex_h as f64 / self.height, 0., 0.];
+        match self.view_box {
+            None => { },
+            Some(vb) => {
+                let (mut sx, mut sy) = (self.width / vb.2, self.height / vb.3);
+                let (mut tx, mut ty) = (0., 0.);
+                match self.align {
+                    None => { },
+                    Some((ax, ay, slice)) => {
+                        let s = if slice { sx.max(sy) } else { sx.min(sy) };
+                        sx = s;
+                        sy = s;
+                        tx = (self.width - vb.2 * s) * ax;
+                        ty = (self.height - vb.3 * s) * ay;
+                    },
+                }
+                base = multiply(&base, &[sx, 0., 0., sy, tx - vb.0 * sx, ty - vb.1 * sy]);
+            },
+        }
+        for (segments, style, matrix) in self.shapes.iter() {
+            let m = multiply(&base, matrix);
+            let segments: Vec<PathSegment> = segments.iter().map(|x| transform_segment(&m, x)).collect();
+            let mut style = *style;
+            style.stroke_width *= (m[0] * m[3] - m[1] * m[2]).abs().sqrt();
+            let shape = build_path_shape(&segments, &style, PIXEL_TOLERANCE);
+            let pixels = render_path(&shape, &style, (0., 0., tex_w as f64, tex_h as f64), tex_size);
+            // paint over the previous shapes
+            for i in 0..(tex_w * tex_h) as usize {
+                let p = i * 4;
+                let a = pixels[p + 3] as u32;
+                if a == 0 { continue }
+                for j in 0..4 {
+                    ret[p + j] = (pixels[p + j] as u32 + (ret[p + j] as u32 * (255 - a) + 127) / 255).min(255) as u8;
+                }
+            }
+        }
+        ret
+    }
+}
+
+impl SvgState {
+    // apply the presentation attributes and the style attribute, returns false if not displayed
+    fn apply_attributes(&mut self, attributes: &[OwnedAttribute]) -> bool {
+        let mut displayed = true;
+        let mut props: Vec<(String, String)> = attributes.iter().map(|x| (x.name.local_name.clone(), x.value.clone())).collect();
+        let style = attributes.iter().find(|x| x.name.local_name == "style").map(|x| x.value.clone());
+        match style {
+            None => { },
+            Some(style) => {
+                for decl in style.split(';') {
+                    let mut kv = decl.splitn(2, ':');
+                    match (kv.next(), kv.next()) {
+                        (Some(k), Some(v)) => props.push((k.trim().to_string(), v.trim().to_string())),
+                        _ => { },
+                    }
+                }
+            },
+        }
+        for (k, v) in props.iter() {
+            let v = v.trim();
+            match k.as_str() {
+                "transform" => self.matrix = multiply(&self.matrix, &parse_transform(v)),
+                "fill" => match parse_paint(v) { Some(x) => self.fill = x, None => { } },
+                "fill-opacity" => match parse_number(v) { Some(x) => self.fill_opacity = x as f32, None => { } },
+                "fill-rule" => self.fill_rule = if v == "evenodd" { FillRule::EvenOdd } else { FillRule::NonZero },
+                "stroke" => match parse_paint(v) { Some(x) => self.stroke = x, None => { } },
+                "stroke-opacity" => match parse_number(v) { Some(x) => self.stroke_opacity = x as f32, None => { } },
+                "stroke-width" => match parse_length(v) { Some(x) => self.stroke_width = x, None => { } },
+                "stroke-linejoin" => match v {
+                    "round" => self.line_join = LineJoin::Round,
+                    "bevel" => self.line_join = LineJoin::Bevel,
+                    "miter" => self.line_join = LineJoin::Miter,
+                    _ => { },
+                },
+                "stroke-linecap" => match v {
+                    "round" => self.line_cap = LineCap::Round,
+                    "square" => self.line_cap = LineCap::Square,
+                    "butt" => self.line_cap = LineCap::Butt,
+                    _ => { },
+                },
+                "stroke-miterlimit" => match parse_number(v) { Some(x) => self.miter_limit = x, None => { } },
+                // the group opacity is applied to the shapes separately
+                "opacity" => match parse_number(v) { Some(x) => self.opacity *= x as f32, None => { } },
+                "visibility" => self.visible = v == "visible",
+                "display" => displayed = v != "none",
+                _ => { },
+            }
+        }
+        displayed
+    }
+    fn path_style(&self) -> PathStyle {
+        let color = |c: Option<(f32, f32, f32)>, opacity: f32| match c {
+            None => (0., 0., 0., 0.),
+            Some(c) => (c.0, c.1, c.2, (opacity * self.opacity).max(0.).min(1.)),
+        };
+        PathStyle {
+            fill_color: color(self.fill, self.fill_opacity),
+            fill_rule: self.fill_rule,
+            stroke_color: color(self.stroke, self.stroke_opacity),
+            stroke_width: self.stroke_width,
+            line_join: self.line_join,
+            line_cap: self.line_cap,
+            miter_limit: self.miter_limit,
+        }
+    }
+}
+
+// the segments of a shape element, converted through the path data
+fn shape_segments(name: &str, attributes: &[OwnedAttribute]) -> Vec<PathSegment> {
+    let attr = |name: &str| attributes.iter().find(|x| x.name.local_name == name).map(|x| x.value.as_str());
+    let len = |name: &str| attr(name).and_then(parse_length).unwrap_or(0.);
+    let data = match name {
+        "path" => attr("d").unwrap_or("").to_string(),
+        "rect" => {
+            let (x, y, w, h) = (len("x"), len("y"), len("width"), len("height"));
+            if w <= 0. || h <= 0. { return vec![] }
+            let (rx, ry) = match (attr("rx").and_then(parse_length), attr("ry").and_then(parse_length)) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0., 0.),
+            };
+            let (rx, ry) = (rx.max(0.).min(w / 2.), ry.max(0.).min(h / 2.));
+            if rx == 0. || ry == 0. {
+                format!("M{} {}h{}v{}h{}z", x, y, w, h, -w)
+            } else {
+                format!(
+                    "M{} {}h{}a{} {} 0 0 1 {} {}v{}a{} {} 0 0 1 {} {}h{}a{} {} 0 0 1 {} {}v{}a{} {} 0 0 1 {} {}z",
+                    x + rx, y, w - 2. * rx, rx, ry, rx, ry, h - 2. * ry, rx, ry, -rx, ry,
+                    -(w - 2. * rx), rx, ry, -rx, -ry, -(h - 2. * ry), rx, ry, rx, -ry,
+                )
+            }
+        },
+        "circle" | "ellipse" => {
+            let (cx, cy) = (len("cx"), len("cy"));
+            let (rx, ry) = if name == "circle" { (len("r"), len("r")) } else { (len("rx"), len("ry")) };
+            if rx <= 0. || ry <= 0. { return vec![] }
+            format!("M{} {}a{} {} 0 1 0 {} 0a{} {} 0 1 0 {} 0z", cx - rx, cy, rx, ry, 2. * rx, rx, ry, -2. * rx)
+        },
+        "line" => format!("M{} {}L{} {}", len("x1"), len("y1"), len("x2"), len("y2")),
+        _ => {
+            let points = attr("points").unwrap_or("");
+            if points.trim().is_empty() { return vec![] }
+            format!("M{}{}", points, if name == "polygon" { "z" } else { "" })
+        },
+    };
+    parse_path_data(&data).0
+}
+
+fn transform_segment(m: &Matrix, seg: &PathSegment) -> PathSegment {
+    let p = |x: f64, y: f64| (m[0] * x + m[2] * y + m[4], m[1] * x + m[3] * y + m[5]);
+    match *seg {
+        PathSegment::MoveTo(x, y) => { let a = p(x, y); PathSegment::MoveTo(a.0, a.1) },
+        PathSegment::LineTo(x, y) => { let a = p(x, y); PathSegment::LineTo(a.0, a.1) },
+        PathSegment::CubicTo(x1, y1, x2, y2, x, y) => {
+            let (a, b, c) = (p(x1, y1), p(x2, y2), p(x, y));
+            PathSegment::CubicTo(a.0, a.1, b.0, b.1, c.0, c.1)
+        },
+        PathSegment::Close => PathSegment::Close,
+    }
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    s.trim().parse().ok()
+}
+
+// a length in px, the percentages are not supported
+fn parse_length(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let units = [("px", 1.), ("pt", 4. / 3.), ("pc", 16.), ("mm", 96. / 25.4), ("cm", 96. / 2.54), ("in", 96.), ("em", 16.)];
+    for (unit, ratio) in units.iter() {
+        if s.ends_with(unit) {
+            return parse_number(&s[..(s.len() - unit.len())]).map(|x| x * ratio);
+        }
+    }
+    parse_number(s)
+}
+
+fn parse_numbers(s: &str) -> Vec<f64> {
+    s.split(|c: char| c == ',' || c.is_whitespace()).filter(|x| !x.is_empty()).filter_map(parse_number).collect()
+}
+
+// `Some(None)` for `none`, and `None` if it is not supported
+fn parse_paint(s: &str) -> Option<Option<(f32, f32, f32)>> {
+    if s == "none" { return Some(None) }
+    let mut input = ParserInput::new(s);
+    let mut parser = Parser::new(&mut input);
+    match Color::parse(&mut parser) {
+        Ok(Color::RGBA(c)) => Some(Some((c.red_f32(), c.green_f32(), c.blue_f32()))),
+        Ok(Color::CurrentColor) => Some(Some((0., 0., 0.))),
+        Err(_) => None,
+    }
+}
+
+fn parse_preserve_aspect_ratio(s: &str) -> Option<(f64, f64, bool)> {
+    let mut words = s.split_whitespace();
+    let align = words.next().unwrap_or("xMidYMid");
+    if align == "none" { return None }
+    let (ax, ay) = match align {
+        "xMinYMin" => (0., 0.),
+        "xMidYMin" => (0.5, 0.),
+        "xMaxYMin" => (1., 0.),
+        "xMinYMid" => (0., 0.5),
+        "xMaxYMid" => (1., 0.5),
+        "xMinYMax" => (0., 1.),
+        "xMidYMax" => (0.5, 1.),
+        "xMaxYMax" => (1., 1.),
+        _ => (0.5, 0.5),
+    };
+    Some((ax, ay, words.next() == Some("slice")))
+}
+
+fn parse_transform(s: &str) -> Matrix {
+    let mut ret = IDENTITY;
+    for item in s.split(')') {
+        let mut parts = item.splitn(2, '(');
+        let (name, args) = match (parts.next(), parts.next()) {
+            (Some(n), Some(a)) => (n.trim_matches(|c: char| c == ',' || c.is_whitespace()), parse_numbers(a)),
+            _ => continue,
+        };
+        let arg = |i: usize, default: f64| *args.get(i).unwrap_or(&default);
+        let m = match name {
+            "matrix" if args.len() == 6 => [args[0], args[1], args[2], args[3], args[4], args[5]],
+            "translate" => [1., 0., 0., 1., arg(0, 0.), arg(1, 0.)],
+            "scale" => [arg(0, 1.), 0., 0., arg(1, arg(0, 1.)), 0., 0.],
+            "rotate" => {
+                let (sin, cos) = arg(0, 0.).to_radians().sin_cos();
+                let (cx, cy) = (arg(1, 0.), arg(2, 0.));
+                [cos, sin, -sin, cos, cx - cos * cx + sin * cy, cy - sin * cx - cos * cy]
+            },
+            "skewX" => [1., 0., arg(0, 0.).to_radians().tan(), 1., 0., 0.],
+            "skewY" => [1., arg(0, 0.).to_radians().tan(), 0., 1., 0., 0.],
+            _ => continue,
+        };
+        ret = multiply(&ret, &m);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SvgDocument, parse_preserve_aspect_ratio, parse_transform};
+
+    fn pixel(pixels: &[u8], width: i32, x: i32, y: i32) -> [u8; 4] {
+        let p = ((y * width + x) * 4) as usize;
+        [pixels[p], pixels[p + 1], pixels[p + 2], pixels[p + 3]]
+    }
+
+    #[test]
+    fn parse() {
+        assert!(SvgDocument::parse("<div/>").is_err());
+        assert!(SvgDocument::parse("<svg").is_err());
+        let doc = SvgDocument::parse(r#"<svg xmlns="http://www.w3.org/2000/svg" width="40">
+            <defs><rect width="1" height="1"/></defs>
+            <g fill="red"><rect width="10" height="10"/><circle r="0"/></g>
+            <path d="M0 0L10 10" display="none"/>
+        </svg>"#).unwrap();
+        assert_eq!(doc.size(), (40., 150.));
+        assert_eq!(doc.shapes.len(), 1);
+        assert_eq!(doc.shapes[0].1.fill_color, (1., 0., 0., 1.));
+    }
+
+    #[test]
+    fn view_box() {
+        let doc = SvgDocument::parse(r#"<svg viewBox="0 0 20 10" height="5"/>"#).unwrap();
+        assert_eq!(doc.size(), (10., 5.));
+        assert_eq!(doc.view_box, Some((0., 0., 20., 10.)));
+        assert_eq!(parse_preserve_aspect_ratio("xMinYMax slice"), Some((0., 1., true)));
+        assert_eq!(parse_preserve_aspect_ratio("xMaxYMid"), Some((1., 0.5, false)));
+        assert_eq!(parse_preserve_aspect_ratio("none"), None);
+        assert_eq!(parse_preserve_aspect_ratio(""), Some((0.5, 0.5, false)));
+        // not panic inside a character
+        assert_eq!(parse_preserve_aspect_ratio("\u{e9}inYMid"), Some((0.5, 0.5, false)));
+        assert_eq!(parse_preserve_aspect_ratio("xMinY\u{e9}i"), Some((0.5, 0.5, false)));
+    }
+
+    #[test]
+    fn transform() {
+        assert_eq!(parse_transform("translate(10 20) scale(2)"), [2., 0., 0., 2., 10., 20.]);
+        assert_eq!(parse_transform("scale(2, 3),translate(1)"), [2., 0., 0., 3., 2., 0.]);
+        assert_eq!(parse_transform("matrix(1 2 3)"), [1., 0., 0., 1., 0., 0.]);
+        let m = parse_transform("rotate(90 10 0)");
+        let expected = [0., 1., -1., 0., 10., -10.];
+        for i in 0..6 {
+            assert!((m[i] - expected[i]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn render() {
+        // the view box is fitted to the left of the wider document
+        let doc = SvgDocument::parse(r#"<svg width="20" height="10" viewBox="0 0 10 10" preserveAspectRatio="xMinYMid">
+            <rect width="10" height="10" fill="blue" fill-opacity="0.5"/>
+        </svg>"#).unwrap();
+        let pixels = doc.render((20, 10));
+        assert_eq!(pixel(&pixels, 20, 5, 5), [0, 0, 128, 128]);
+        assert_eq!(pixel(&pixels, 20, 15, 5), [0, 0, 0, 0]);
+        // stretched to the texture size
+        let pixels = doc.render((40, 20));
+        assert_eq!(pixel(&pixels, 40, 19, 19), [0, 0, 128, 128]);
+        assert_eq!(pixel(&pixels, 40, 20, 0), [0, 0, 0, 0]);
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 use std::ptr;
+use std::slice;
 use std::mem;
 use std::ffi::{CStr, CString};
 use std::os::raw::{c_void, c_char};
@@ -82,6 +83,21 @@ fn generate_index_buf_content(buf: &mut [u16; GL_DRAW_RECT_MAX as usize * 6]) {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
+struct Shape {
+    size: (f32, f32),
+    radii: (f32, f32, f32, f32),
+    border: (f32, f32, f32, f32),
+    pixel_scale: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Clip {
+    rect: (f32, f32, f32, f32), // left, top, right, bottom, intersected with the outer clips
+    quad: Option<[(f32, f32); 4]>, // drawn to the stencil buffer
+    shape: Option<Shape>, // stretched to the quad
+}
+
 pub struct TexManager {
     width: i32,
     height: i32,
@@ -89,19 +105,36 @@ pub struct TexManager {
     u_area_size: i32,
     u_color: i32,
     u_alpha: i32,
+    u_shape_size: i32,
+    u_shape_radii: i32,
+    u_shape_border: i32,
+    u_shape_scale: i32,
+    u_blur: i32,
     tex_size: i32,
     tex_count: i32,
     img_shader_program: u32,
     tex_pos_gl_buf: u32,
-    tex_pos_buf: Box<[f32; GL_DRAW_RECT_MAX as usize * 8]>,
+    tex_pos_buf: Box<[f32; GL_DRAW_RECT_MAX as usize * 12]>, // the tex positions multiplied by the projective weights, with the weights
     draw_pos_gl_buf: u32,
     draw_pos_buf: Box<[f32; GL_DRAW_RECT_MAX as usize * 8]>,
     tex_index_gl_buf: u32,
     tex_index_buf: Box<[f32; GL_DRAW_RECT_MAX as usize * 4]>,
     temp_framebuffer: u32,
     temp_tex: u32,
+    temp_stencil: u32,
+    temp_stencil_size: (i32, i32),
+    frame_framebuffer: u32,
+    frame_tex: u32,
+    frame_stencil: u32,
+    color: (f32, f32, f32, f32),
+    alpha: f32,
+    shape: Shape,
+    blur: (f32, f32, f32),
     tex_map: HashMap<i32, u32>,
     binded_rendering_target_stack: Vec<(i32, i32, i32)>,
+    clip_stack: Vec<Clip>,
+    // the clips of the outer rendering targets, which do not apply to the bound one
+    saved_clip_stacks: Vec<Vec<Clip>>,
 }
 impl TexManager {
     pub fn new(ctx: &mut Gl, tex_size: i32, tex_count: i32) -> Self {
@@ -115,12 +148,12 @@ impl TexManager {
 
             // the texture position buffer
             let tex_pos_gl_buf = gl_bufs[0];
-            let tex_pos_buf = Box::new([0. as f32; GL_DRAW_RECT_MAX as usize * 8]);
+            let tex_pos_buf = Box::new([0. as f32; GL_DRAW_RECT_MAX as usize * 12]);
             ctx.BindBuffer(gl::ARRAY_BUFFER, tex_pos_gl_buf);
-            ctx.BufferData(gl::ARRAY_BUFFER, GL_DRAW_RECT_MAX as isize * 8 * mem::size_of::<f32>() as isize, tex_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
+            ctx.BufferData(gl::ARRAY_BUFFER, GL_DRAW_RECT_MAX as isize * 12 * mem::size_of::<f32>() as isize, tex_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
             let a_tex_pos = ctx.GetAttribLocation(img_shader_program, buffer_from_str("aTexPos")) as u32;
             ctx.EnableVertexAttribArray(a_tex_pos);
-            ctx.VertexAttribPointer(a_tex_pos, 2, gl::FLOAT, gl::FALSE, 0, 0 as *const c_void);
+            ctx.VertexAttribPointer(a_tex_pos, 3, gl::FLOAT, gl::FALSE, 0, 0 as *const c_void);
 
             // the draw position buffer
             let draw_pos_gl_buf = gl_bufs[1];
@@ -148,11 +181,11 @@ impl TexManager {
             ctx.BufferData(gl::ELEMENT_ARRAY_BUFFER, GL_DRAW_RECT_MAX as isize * 6 * mem::size_of::<u16>() as isize, index_buf.as_ptr() as *const c_void, gl::STATIC_DRAW);
 
             // the temp framebuffer and texture
-            let mut gl_framebuffers = [0 as u32; 1];
-            ctx.GenFramebuffers(1, gl_framebuffers.as_mut_ptr());
+            let mut gl_framebuffers = [0 as u32; 2];
+            ctx.GenFramebuffers(2, gl_framebuffers.as_mut_ptr());
             let temp_framebuffer = gl_framebuffers[0];
-            let mut gl_textures = [0 as u32; 1];
-            ctx.GenTextures(1, gl_textures.as_mut_ptr());
+            let mut gl_textures = [0 as u32; 2];
+            ctx.GenTextures(2, gl_textures.as_mut_ptr());
             let temp_tex = gl_textures[0];
             ctx.BindTexture(gl::TEXTURE_2D, temp_tex);
             ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
@@ -160,12 +193,47 @@ impl TexManager {
             ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
             ctx.TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, 256, 256, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
 
+            // the stencil buffers for clipping, resized with the rendering targets
+            let mut gl_renderbuffers = [0 as u32; 2];
+            ctx.GenRenderbuffers(2, gl_renderbuffers.as_mut_ptr());
+            let temp_stencil = gl_renderbuffers[0];
+            ctx.BindRenderbuffer(gl::RENDERBUFFER, temp_stencil);
+            ctx.RenderbufferStorage(gl::RENDERBUFFER, gl::STENCIL_INDEX8, 256, 256);
+            ctx.BindFramebuffer(gl::FRAMEBUFFER, temp_framebuffer);
+            ctx.FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::STENCIL_ATTACHMENT, gl::RENDERBUFFER, temp_stencil);
+            let frame_stencil = gl_renderbuffers[1];
+            ctx.BindRenderbuffer(gl::RENDERBUFFER, frame_stencil);
+            ctx.RenderbufferStorage(gl::RENDERBUFFER, gl::STENCIL_INDEX8, super::DEFAULT_WINDOW_SIZE.0, super::DEFAULT_WINDOW_SIZE.1);
+            ctx.BindRenderbuffer(gl::RENDERBUFFER, 0);
+
+            // the frame framebuffer and texture
+            // the canvas is drawn into it and presented before swapping buffers,
+            // so that the content is preserved between frames (the back buffer is undefined after swapping)
+            let frame_framebuffer = gl_framebuffers[1];
+            let frame_tex = gl_textures[1];
+            ctx.BindTexture(gl::TEXTURE_2D, frame_tex);
+            ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+            ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            ctx.TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            ctx.TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, super::DEFAULT_WINDOW_SIZE.0, super::DEFAULT_WINDOW_SIZE.1, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            ctx.BindFramebuffer(gl::FRAMEBUFFER, frame_framebuffer);
+            ctx.FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, frame_tex, 0);
+            ctx.FramebufferRenderbuffer(gl::FRAMEBUFFER, gl::STENCIL_ATTACHMENT, gl::RENDERBUFFER, frame_stencil);
+
             // get other vars
             let u_area_size = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uAreaSize"));
             let u_color = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uColor"));
             let u_alpha = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uAlpha"));
+            let u_shape_size = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uShapeSize"));
+            let u_shape_radii = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uShapeRadii"));
+            let u_shape_border = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uShapeBorder"));
+            let u_shape_scale = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uShapeScale"));
+            let u_blur = ctx.GetUniformLocation(img_shader_program, buffer_from_str("uBlur"));
             ctx.Uniform4f(u_color, 0., 0., 0., 1.);
             ctx.Uniform1f(u_alpha, 1.);
+            ctx.Uniform1f(u_shape_scale, 1.);
+            ctx.Uniform3f(u_blur, 0., 0., 0.);
 
             // bind default tex
             for i in 0..TEXTURE_MAX {
@@ -182,6 +250,11 @@ impl TexManager {
                 u_area_size,
                 u_color,
                 u_alpha,
+                u_shape_size,
+                u_shape_radii,
+                u_shape_border,
+                u_shape_scale,
+                u_blur,
                 tex_size,
                 tex_count,
                 img_shader_program,
@@ -193,8 +266,19 @@ impl TexManager {
                 tex_index_buf,
                 temp_framebuffer,
                 temp_tex,
+                temp_stencil,
+                temp_stencil_size: (256, 256),
+                frame_framebuffer,
+                frame_tex,
+                frame_stencil,
+                color: (0., 0., 0., 1.),
+                alpha: 1.,
+                shape: Shape { size: (0., 0.), radii: (0., 0., 0., 0.), border: (0., 0., 0., 0.), pixel_scale: 1. },
+                blur: (0., 0., 0.),
                 tex_map: HashMap::new(),
                 binded_rendering_target_stack: vec![],
+                clip_stack: vec![],
+                saved_clip_stacks: vec![],
             }
         }
     }
@@ -205,10 +289,217 @@ impl TexManager {
         self.width = w;
         self.height = h;
         self.pixel_ratio = pixel_ratio;
+        let (device_w, device_h) = self.device_size();
         unsafe {
-            ctx.Viewport(0, 0, (w as f64 * pixel_ratio).round() as i32, (h as f64 * pixel_ratio).round() as i32);
+            ctx.BindTexture(gl::TEXTURE_2D, self.frame_tex);
+            ctx.TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32, device_w, device_h, 0, gl::RGBA, gl::UNSIGNED_BYTE, ptr::null());
+            ctx.BindTexture(gl::TEXTURE_2D, 0);
+            ctx.BindRenderbuffer(gl::RENDERBUFFER, self.frame_stencil);
+            ctx.RenderbufferStorage(gl::RENDERBUFFER, gl::STENCIL_INDEX8, device_w, device_h);
+            ctx.BindRenderbuffer(gl::RENDERBUFFER, 0);
+            ctx.Viewport(0, 0, device_w, device_h);
             ctx.Uniform3f(self.u_area_size, w as f32, h as f32, 1.);
         }
+        self.restamp_clip(ctx);
+    }
+
+    #[inline]
+    fn device_size(&self) -> (i32, i32) {
+        ((self.width as f64 * self.pixel_ratio).round() as i32, (self.height as f64 * self.pixel_ratio).round() as i32)
+    }
+
+    #[inline]
+    fn clip_stencil_level(&self) -> i32 {
+        self.clip_stack.iter().filter(|c| c.quad.is_some()).count() as i32
+    }
+
+    /// Update the scissor and stencil test according to the clip stack and the current rendering target.
+    fn apply_clip(&self, ctx: &mut Gl) {
+        let level = self.clip_stencil_level();
+        unsafe {
+            if level == 0 {
+                ctx.Disable(gl::STENCIL_TEST);
+            } else {
+                ctx.Enable(gl::STENCIL_TEST);
+                ctx.StencilFunc(gl::EQUAL, level, 0xff);
+                ctx.StencilOp(gl::KEEP, gl::KEEP, gl::KEEP);
+            }
+        }
+        match self.clip_stack.last() {
+            None => {
+                unsafe { ctx.Disable(gl::SCISSOR_TEST) };
+            },
+            Some(&Clip { rect: (left, top, right, bottom), .. }) => {
+                let (scale, flip_height) = match self.binded_rendering_target_stack.last() {
+                    None => (self.pixel_ratio as f32, Some(self.device_size().1)),
+                    Some(_) => (1., None),
+                };
+                let left = (left * scale).floor() as i32;
+                let top = (top * scale).floor() as i32;
+                let right = ((right * scale).ceil() as i32).max(left);
+                let bottom = ((bottom * scale).ceil() as i32).max(top);
+                // textures are drawn upside down, see uAreaSize
+                let y = match flip_height {
+                    None => top,
+                    Some(h) => h - bottom,
+                };
+                unsafe {
+                    ctx.Enable(gl::SCISSOR_TEST);
+                    ctx.Scissor(left, y, right - left, bottom - top);
+                }
+            }
+        }
+    }
+
+    fn apply_shape(&self, ctx: &mut Gl, shape: &Shape) {
+        unsafe {
+            ctx.Uniform2f(self.u_shape_size, shape.size.0, shape.size.1);
+            ctx.Uniform4f(self.u_shape_radii, shape.radii.0, shape.radii.1, shape.radii.2, shape.radii.3);
+            ctx.Uniform4f(self.u_shape_border, shape.border.0, shape.border.1, shape.border.2, shape.border.3);
+            ctx.Uniform1f(self.u_shape_scale, shape.pixel_scale);
+        }
+    }
+
+    /// Increase the stencil value in the quad (or the shape stretched to the quad) where it equals `level - 1`.
+    fn stamp_clip_quad(&mut self, ctx: &mut Gl, quad: [(f32, f32); 4], shape: Option<Shape>, level: i32) {
+        unsafe {
+            ctx.Disable(gl::SCISSOR_TEST);
+            ctx.Enable(gl::STENCIL_TEST);
+            ctx.StencilFunc(gl::EQUAL, level - 1, 0xff);
+            ctx.StencilOp(gl::KEEP, gl::KEEP, gl::INCR);
+            ctx.ColorMask(gl::FALSE, gl::FALSE, gl::FALSE, gl::FALSE);
+        }
+        match shape {
+            None => {
+                set_draw_quad(self, 0, -2, 0., 0., 1., 1., quad);
+                draw_rects(ctx, self, 1);
+            },
+            Some(shape) => {
+                self.apply_shape(ctx, &shape);
+                set_draw_quad(self, 0, -4, 0., 0., shape.size.0, shape.size.1, quad);
+                draw_rects(ctx, self, 1);
+                let current = self.shape;
+                self.apply_shape(ctx, &current);
+            },
+        }
+        unsafe {
+            ctx.ColorMask(gl::TRUE, gl::TRUE, gl::TRUE, gl::TRUE);
+        }
+    }
+
+    /// Rebuild the stencil buffer of the current rendering target from the clip stack.
+    fn restamp_clip(&mut self, ctx: &mut Gl) {
+        if self.clip_stencil_level() > 0 {
+            unsafe {
+                ctx.Disable(gl::SCISSOR_TEST);
+                ctx.ClearStencil(0);
+                ctx.Clear(gl::STENCIL_BUFFER_BIT);
+            }
+            let quads: Vec<([(f32, f32); 4], Option<Shape>)> = self.clip_stack.iter().filter_map(|c| c.quad.map(|q| (q, c.shape))).collect();
+            for (i, (quad, shape)) in quads.into_iter().enumerate() {
+                self.stamp_clip_quad(ctx, quad, shape, i as i32 + 1);
+            }
+        }
+        self.apply_clip(ctx);
+    }
+
+    fn push_clip(&mut self, (mut l, mut t, mut r, mut b): (f32, f32, f32, f32), quad: Option<[(f32, f32); 4]>, shape: Option<Shape>) {
+        match self.clip_stack.last() {
+            None => { },
+            Some(&Clip { rect: (cl, ct, cr, cb), .. }) => {
+                l = l.max(cl);
+                t = t.max(ct);
+                r = r.min(cr);
+                b = b.min(cb);
+            }
+        }
+        self.clip_stack.push(Clip {
+            rect: (l, t, r.max(l), b.max(t)),
+            quad,
+            shape,
+        });
+    }
+
+    fn push_clip_rect(&mut self, ctx: &mut Gl, left: f32, top: f32, width: f32, height: f32) {
+        self.push_clip((left.min(left + width), top.min(top + height), left.max(left + width), top.max(top + height)), None, None);
+        self.apply_clip(ctx);
+    }
+
+    fn push_clip_quad(&mut self, ctx: &mut Gl, quad: [(f32, f32); 4], shape: Option<Shape>) {
+        let mut rect = (quad[0].0, quad[0].1, quad[0].0, quad[0].1);
+        for &(x, y) in quad.iter() {
+            rect = (rect.0.min(x), rect.1.min(y), rect.2.max(x), rect.3.max(y));
+        }
+        self.push_clip(rect, Some(quad), shape);
+        let level = self.clip_stencil_level();
+        if level == 1 {
+            unsafe {
+                ctx.Disable(gl::SCISSOR_TEST);
+                ctx.ClearStencil(0);
+                ctx.Clear(gl::STENCIL_BUFFER_BIT);
+            }
+        }
+        self.stamp_clip_quad(ctx, quad, shape, level);
+        self.apply_clip(ctx);
+    }
+
+    fn pop_clip(&mut self, ctx: &mut Gl) {
+        match self.clip_stack.pop() {
+            Some(Clip { quad: Some(_), .. }) => self.restamp_clip(ctx),
+            _ => self.apply_clip(ctx),
+        }
+    }
+
+    /// Copy the frame framebuffer to the default framebuffer, should be called before swapping buffers.
+    pub fn present(&mut self, ctx: &mut Gl) {
+        let (device_w, device_h) = self.device_size();
+        unsafe {
+            ctx.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            ctx.Disable(gl::SCISSOR_TEST);
+            ctx.Disable(gl::STENCIL_TEST);
+            ctx.Viewport(0, 0, device_w, device_h);
+            ctx.ClearColor(0., 0., 0., 0.);
+            ctx.Clear(gl::COLOR_BUFFER_BIT);
+            ctx.Uniform3f(self.u_area_size, self.width as f32, self.height as f32, 1.);
+            ctx.Uniform4f(self.u_color, 0., 0., 0., 1.);
+            ctx.Uniform1f(self.u_alpha, 1.);
+            ctx.Uniform3f(self.u_blur, 0., 0., 0.);
+            ctx.ActiveTexture(gl::TEXTURE0);
+            ctx.BindTexture(gl::TEXTURE_2D, self.frame_tex);
+        }
+        // the frame texture is upside down
+        set_draw_rect(self, 0, 256, 0., 1., 1., -1., 0., 0., self.width as f32, self.height as f32);
+        draw_rects(ctx, self, 1);
+        unsafe {
+            ctx.Uniform4f(self.u_color, self.color.0, self.color.1, self.color.2, self.color.3);
+            ctx.Uniform1f(self.u_alpha, self.alpha);
+            ctx.Uniform3f(self.u_blur, self.blur.0, self.blur.1, self.blur.2);
+        }
+        let x = match self.binded_rendering_target_stack.last() {
+            None => (-2, 0, 0),
+            Some(x) => *x
+        };
+        tex_set_rendering_target(ctx, self, x.0, x.1, x.2, false);
+    }
+
+    /// Read pixels of the canvas in device pixels, rows from top to bottom.
+    pub fn get_pixels(&mut self, ctx: &mut Gl, left: i32, top: i32, width: i32, height: i32) -> Vec<u8> {
+        let fb_height = (self.height as f64 * self.pixel_ratio).round() as i32;
+        let mut buf: Vec<u8> = vec![0; (width * height * 4) as usize];
+        unsafe {
+            ctx.BindFramebuffer(gl::FRAMEBUFFER, self.frame_framebuffer);
+            ctx.PixelStorei(gl::PACK_ALIGNMENT, 1);
+            ctx.ReadPixels(left, fb_height - top - height, width, height, gl::RGBA, gl::UNSIGNED_BYTE, buf.as_mut_ptr() as *mut c_void);
+            if self.binded_rendering_target_stack.len() > 0 {
+                ctx.BindFramebuffer(gl::FRAMEBUFFER, self.temp_framebuffer);
+            }
+        }
+        let row_size = (width * 4) as usize;
+        let mut ret: Vec<u8> = Vec::with_capacity(buf.len());
+        for row in buf.chunks(row_size).rev() {
+            ret.extend_from_slice(row);
+        }
+        ret
     }
 
     fn tex_create(&mut self, ctx: &mut Gl, w: i32, h: i32, buf: &Vec<u8>, tex_id: i32) {
@@ -237,6 +528,10 @@ pub fn tex_create(canvas_index: i32, width: i32, height: i32, buf: Vec<u8>, tex_
         tex_manager.tex_create(ctx, width, height, &buf, tex_id);
     });
 }
+pub fn tex_create_from_pixels(canvas_index: i32, tex_id: i32, width: i32, height: i32, buf: *const u8) {
+    let buf = unsafe { slice::from_raw_parts(buf, (width * height * 4) as usize) }.to_vec();
+    tex_create(canvas_index, width, height, buf, tex_id);
+}
 pub fn tex_rewrite(canvas_index: i32, buf: Vec<u8>, tex_id: i32, left: i32, top: i32, width: i32, height: i32) {
     paint!(canvas_index, move |ctx, tex_manager| {
         // println!("tex_rewrite {:?}", (canvas_index, tex_id, left, top, width, height));
@@ -260,10 +555,11 @@ pub fn tex_copy(canvas_index: i32, dest_tex_id: i32, dest_left: i32, dest_top: i
 
 fn tex_set_rendering_target(ctx: &mut Gl, tex_manager: &mut TexManager, tex_id: i32, width: i32, height: i32, need_clear: bool) {
     if tex_id < -1 {
+        let (device_w, device_h) = tex_manager.device_size();
         unsafe {
-            ctx.BindFramebuffer(gl::FRAMEBUFFER, 0);
+            ctx.BindFramebuffer(gl::FRAMEBUFFER, tex_manager.frame_framebuffer);
             ctx.UseProgram(tex_manager.img_shader_program);
-            ctx.Viewport(0, 0, (tex_manager.width as f64 * tex_manager.pixel_ratio) as i32, (tex_manager.height as f64 * tex_manager.pixel_ratio) as i32);
+            ctx.Viewport(0, 0, device_w, device_h);
             ctx.Uniform3f(tex_manager.u_area_size, tex_manager.width as f32, tex_manager.height as f32, 1.);
         }
     } else {
@@ -271,16 +567,29 @@ fn tex_set_rendering_target(ctx: &mut Gl, tex_manager: &mut TexManager, tex_id:
             let tex = if tex_id < 0 { tex_manager.temp_tex } else { tex_manager.tex_map[&tex_id] };
             ctx.BindFramebuffer(gl::FRAMEBUFFER, tex_manager.temp_framebuffer);
             ctx.FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, tex, 0);
+            if tex_manager.temp_stencil_size != (width, height) && width > 0 && height > 0 {
+                tex_manager.temp_stencil_size = (width, height);
+                ctx.BindRenderbuffer(gl::RENDERBUFFER, tex_manager.temp_stencil);
+                ctx.RenderbufferStorage(gl::RENDERBUFFER, gl::STENCIL_INDEX8, width, height);
+                ctx.BindRenderbuffer(gl::RENDERBUFFER, 0);
+            }
             ctx.UseProgram(tex_manager.img_shader_program);
             ctx.Viewport(0, 0, width, height);
             ctx.Uniform3f(tex_manager.u_area_size, width as f32, height as f32, -1.);
             ctx.ClearColor(0., 0., 0., 0.);
-            if need_clear { ctx.Clear(gl::COLOR_BUFFER_BIT); }
+            if need_clear {
+                ctx.Disable(gl::SCISSOR_TEST);
+                ctx.Clear(gl::COLOR_BUFFER_BIT);
+            }
         }
     }
+    // the stencil buffer is shared by the texture targets, so it is rebuilt when switching
+    tex_manager.restamp_clip(ctx);
 }
 fn tex_bind_rendering_target_self(ctx: &mut Gl, tex_manager: &mut TexManager, tex_id: i32, width: i32, height: i32) {
     tex_manager.binded_rendering_target_stack.push((tex_id, width, height));
+    let clip_stack = mem::replace(&mut tex_manager.clip_stack, vec![]);
+    tex_manager.saved_clip_stacks.push(clip_stack);
     let tex = if tex_id < 0 { tex_manager.temp_tex } else { tex_manager.tex_map[&tex_id] };
     unsafe {
         ctx.BindTexture(gl::TEXTURE_2D, tex);
@@ -297,6 +606,7 @@ pub fn tex_bind_rendering_target(canvas_index: i32, tex_id: i32, width: i32, hei
 }
 fn tex_unbind_rendering_target_self(ctx: &mut Gl, tex_manager: &mut TexManager) {
     tex_manager.binded_rendering_target_stack.pop();
+    tex_manager.clip_stack = tex_manager.saved_clip_stacks.pop().unwrap();
     let x = match tex_manager.binded_rendering_target_stack.last().clone() {
         None => (-2, 0, 0),
         Some(x) => *x
@@ -341,34 +651,70 @@ pub fn tex_delete(canvas_index: i32, tex_id: i32) {
         }
     });
 }
-pub fn tex_draw(canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x: f32, y: f32, w: f32, h: f32) {
-    paint!(canvas_index, move |_ctx, tex_manager| {
-        // println!("tex_draw {:?}", (canvas_index, draw_index, tex_shader_index, normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h, x, y, w, h));
+fn set_draw_rect(tex_manager: &mut TexManager, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x: f32, y: f32, w: f32, h: f32) {
+    set_draw_quad(tex_manager, draw_index, tex_shader_index, normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h, [(x, y), (x, y + h), (x + w, y + h), (x + w, y)]);
+}
+/// The weights of the corners for the perspective-correct mapping of a rect to the quad.
+/// The tex positions are multiplied by them and divided by the interpolated weight in the shader.
+fn quad_projective_weights(quad: &[(f32, f32); 4]) -> [f32; 4] {
+    // the intersection of the diagonals
+    let (d1x, d1y) = (quad[2].0 - quad[0].0, quad[2].1 - quad[0].1);
+    let (d2x, d2y) = (quad[3].0 - quad[1].0, quad[3].1 - quad[1].1);
+    let det = d1x * d2y - d1y * d2x;
+    if det == 0. { return [1.; 4] }
+    let (ox, oy) = (quad[1].0 - quad[0].0, quad[1].1 - quad[0].1);
+    let s = (ox * d2y - oy * d2x) / det;
+    let t = (ox * d1y - oy * d1x) / det;
+    if s <= 0. || s >= 1. || t <= 0. || t >= 1. { return [1.; 4] }
+    // the ratio of the diagonal to the distance from the intersection to the opposite corner
+    [1. / (1. - s), 1. / (1. - t), 1. / s, 1. / t]
+}
+fn set_draw_quad(tex_manager: &mut TexManager, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, quad: [(f32, f32); 4]) {
+    let tex_pos_buf = &mut *tex_manager.tex_pos_buf;
+    let draw_pos_buf = &mut *tex_manager.draw_pos_buf;
+    let tex_index_buf = &mut *tex_manager.tex_index_buf;
+    let draw_index_12 = draw_index as usize * 12;
+    let draw_index_8 = draw_index as usize * 8;
+    let draw_index_4 = draw_index as usize * 4;
+    let tex_pos = [
+        (normalized_tex_x, normalized_tex_y),
+        (normalized_tex_x, normalized_tex_y + normalized_tex_h),
+        (normalized_tex_x + normalized_tex_w, normalized_tex_y + normalized_tex_h),
+        (normalized_tex_x + normalized_tex_w, normalized_tex_y),
+    ];
+    let q = quad_projective_weights(&quad);
+    for i in 0..4 {
+        tex_pos_buf[draw_index_12 + i * 3] = tex_pos[i].0 * q[i];
+        tex_pos_buf[draw_index_12 + i * 3 + 1] = tex_pos[i].1 * q[i];
+        tex_pos_buf[draw_index_12 + i * 3 + 2] = q[i];
+    }
+    for i in 0..4 {
+        draw_pos_buf[draw_index_8 + i * 2] = quad[i].0;
+        draw_pos_buf[draw_index_8 + i * 2 + 1] = quad[i].1;
+    }
+    tex_index_buf[draw_index_4 + 0] = tex_shader_index as f32;
+    tex_index_buf[draw_index_4 + 1] = tex_shader_index as f32;
+    tex_index_buf[draw_index_4 + 2] = tex_shader_index as f32;
+    tex_index_buf[draw_index_4 + 3] = tex_shader_index as f32;
+}
+fn draw_rects(ctx: &mut Gl, tex_manager: &mut TexManager, draw_count: i32) {
+    unsafe {
         let tex_pos_buf = &mut *tex_manager.tex_pos_buf;
         let draw_pos_buf = &mut *tex_manager.draw_pos_buf;
         let tex_index_buf = &mut *tex_manager.tex_index_buf;
-        let draw_index_8 = draw_index as usize * 8;
-        let draw_index_4 = draw_index as usize * 4;
-        tex_pos_buf[draw_index_8 + 0] = normalized_tex_x;
-        tex_pos_buf[draw_index_8 + 1] = normalized_tex_y;
-        tex_pos_buf[draw_index_8 + 2] = normalized_tex_x;
-        tex_pos_buf[draw_index_8 + 3] = normalized_tex_y + normalized_tex_h;
-        tex_pos_buf[draw_index_8 + 4] = normalized_tex_x + normalized_tex_w;
-        tex_pos_buf[draw_index_8 + 5] = normalized_tex_y + normalized_tex_h;
-        tex_pos_buf[draw_index_8 + 6] = normalized_tex_x + normalized_tex_w;
-        tex_pos_buf[draw_index_8 + 7] = normalized_tex_y;
-        draw_pos_buf[draw_index_8 + 0] = x;
-        draw_pos_buf[draw_index_8 + 1] = y;
-        draw_pos_buf[draw_index_8 + 2] = x;
-        draw_pos_buf[draw_index_8 + 3] = y + h;
-        draw_pos_buf[draw_index_8 + 4] = x + w;
-        draw_pos_buf[draw_index_8 + 5] = y + h;
-        draw_pos_buf[draw_index_8 + 6] = x + w;
-        draw_pos_buf[draw_index_8 + 7] = y;
-        tex_index_buf[draw_index_4 + 0] = tex_shader_index as f32;
-        tex_index_buf[draw_index_4 + 1] = tex_shader_index as f32;
-        tex_index_buf[draw_index_4 + 2] = tex_shader_index as f32;
-        tex_index_buf[draw_index_4 + 3] = tex_shader_index as f32;
+        ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.tex_pos_gl_buf);
+        ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 12 * mem::size_of::<f32>() as isize, tex_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.draw_pos_gl_buf);
+        ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 8 * mem::size_of::<f32>() as isize, draw_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.tex_index_gl_buf);
+        ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 4 * mem::size_of::<f32>() as isize, tex_index_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
+        ctx.DrawElements(gl::TRIANGLES, draw_count * 6, gl::UNSIGNED_SHORT, 0 as *const c_void);
+    }
+}
+pub fn tex_draw(canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, move |_ctx, tex_manager| {
+        // println!("tex_draw {:?}", (canvas_index, draw_index, tex_shader_index, normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h, x1, y1, x2, y2, x3, y3, x4, y4));
+        set_draw_quad(tex_manager, draw_index, tex_shader_index, normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h, [(x1, y1), (x2, y2), (x3, y3), (x4, y4)]);
     });
 }
 pub fn tex_set_active_texture(canvas_index: i32, tex_shader_index: i32, tex_id: i32) {
@@ -383,26 +729,64 @@ pub fn tex_set_active_texture(canvas_index: i32, tex_shader_index: i32, tex_id:
 pub fn tex_draw_end(canvas_index: i32, draw_count: i32) {
     paint!(canvas_index, move |ctx, tex_manager| {
         // println!("tex_draw_end {:?}", (canvas_index, draw_count));
-        unsafe {
-            let tex_pos_buf = &mut *tex_manager.tex_pos_buf;
-            let draw_pos_buf = &mut *tex_manager.draw_pos_buf;
-            let tex_index_buf = &mut *tex_manager.tex_index_buf;
-            ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.tex_pos_gl_buf);
-            ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 8 * mem::size_of::<f32>() as isize, tex_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
-            ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.draw_pos_gl_buf);
-            ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 8 * mem::size_of::<f32>() as isize, draw_pos_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
-            ctx.BindBuffer(gl::ARRAY_BUFFER, tex_manager.tex_index_gl_buf);
-            ctx.BufferData(gl::ARRAY_BUFFER, draw_count as isize * 4 * mem::size_of::<f32>() as isize, tex_index_buf.as_ptr() as *const c_void, gl::STREAM_DRAW);
-            ctx.DrawElements(gl::TRIANGLES, draw_count * 6, gl::UNSIGNED_SHORT, 0 as *const c_void);
-        }
+        draw_rects(ctx, tex_manager, draw_count);
     });
 }
 pub fn tex_set_draw_state(canvas_index: i32, color_r: f32, color_g: f32, color_b: f32, color_a: f32, alpha: f32) {
     paint!(canvas_index, move |ctx, tex_manager| {
         // println!("tex_set_draw_state {:?}", (canvas_index, color_r, color_g, color_b, color_a, alpha));
+        tex_manager.color = (color_r, color_g, color_b, color_a);
+        tex_manager.alpha = alpha;
         unsafe {
             ctx.Uniform4f(tex_manager.u_color, color_r, color_g, color_b, color_a);
             ctx.Uniform1f(tex_manager.u_alpha, alpha);
         }
     });
 }
+pub fn tex_set_draw_shape(canvas_index: i32, width: f32, height: f32, radius_lt: f32, radius_rt: f32, radius_rb: f32, radius_lb: f32, border_top: f32, border_right: f32, border_bottom: f32, border_left: f32, pixel_scale: f32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_set_draw_shape {:?}", (canvas_index, width, height, radius_lt, radius_rt, radius_rb, radius_lb, border_top, border_right, border_bottom, border_left, pixel_scale));
+        let shape = Shape {
+            size: (width, height),
+            radii: (radius_lt, radius_rt, radius_rb, radius_lb),
+            border: (border_top, border_right, border_bottom, border_left),
+            pixel_scale,
+        };
+        tex_manager.shape = shape;
+        tex_manager.apply_shape(ctx, &shape);
+    });
+}
+pub fn tex_set_draw_blur(canvas_index: i32, step_x: f32, step_y: f32, sigma: f32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_set_draw_blur {:?}", (canvas_index, step_x, step_y, sigma));
+        tex_manager.blur = (step_x, step_y, sigma);
+        unsafe {
+            ctx.Uniform3f(tex_manager.u_blur, step_x, step_y, sigma);
+        }
+    });
+}
+pub fn tex_push_clip_rect(canvas_index: i32, left: f32, top: f32, width: f32, height: f32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_push_clip_rect {:?}", (canvas_index, left, top, width, height));
+        tex_manager.push_clip_rect(ctx, left, top, width, height);
+    });
+}
+pub fn tex_push_clip_quad(canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_push_clip_quad {:?}", (canvas_index, x1, y1, x2, y2, x3, y3, x4, y4));
+        tex_manager.push_clip_quad(ctx, [(x1, y1), (x2, y2), (x3, y3), (x4, y4)], None);
+    });
+}
+pub fn tex_push_clip_shape(canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_push_clip_shape {:?}", (canvas_index, x1, y1, x2, y2, x3, y3, x4, y4));
+        let shape = tex_manager.shape;
+        tex_manager.push_clip_quad(ctx, [(x1, y1), (x2, y2), (x3, y3), (x4, y4)], Some(shape));
+    });
+}
+pub fn tex_pop_clip(canvas_index: i32) {
+    paint!(canvas_index, move |ctx, tex_manager| {
+        // println!("tex_pop_clip {:?}", (canvas_index));
+        tex_manager.pop_clip(ctx);
+    });
+}
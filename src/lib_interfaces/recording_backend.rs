@@ -0,0 +1,419 @@
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::sync::{Mutex, mpsc};
+use std::{thread, panic};
+use std::any::Any;
+use super::{Callback, Backend, IMAGE_LOAD_ERROR};
+
+/// A call recorded by `RecordingBackend`.
+/// The canvas indexes and the callbacks are not recorded, and the queries returning values are not recorded either.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Call {
+    Timeout { ms: i32 },
+    EnableAnimationFrame,
+    DisableAnimationFrame,
+    BindCanvas,
+    UnbindCanvas,
+    SetCanvasSize { size: (i32, i32), pixel_ratio: f64, update_logical_size: bool },
+    SetClearColor { color: (f32, f32, f32, f32) },
+    Clear,
+    BindTouchEvents,
+    BindWheelEvents,
+    BindKeyboardEvents,
+    BindCanvasSizeChange,
+    TexGetPixels { pos: (i32, i32), size: (i32, i32) },
+    TexCreateEmpty { tex_id: i32, size: (i32, i32) },
+    TexCreateFromPixels { tex_id: i32, size: (i32, i32) },
+    TexCopy { dest_tex_id: i32, dest: (i32, i32), src: (i32, i32), size: (i32, i32) },
+    TexBindRenderingTarget { tex_id: i32, size: (i32, i32) },
+    TexUnbindRenderingTarget,
+    TexDelete { tex_id: i32 },
+    TexDraw { draw_index: i32, tex_shader_index: i32, tex_pos: (f32, f32, f32, f32), quad: [(f32, f32); 4] },
+    TexSetActiveTexture { tex_shader_index: i32, tex_id: i32 },
+    TexDrawEnd { draw_count: i32 },
+    TexSetDrawState { color: (f32, f32, f32, f32), alpha: f32 },
+    TexSetDrawShape { size: (f32, f32), radii: (f32, f32, f32, f32), border: (f32, f32, f32, f32), pixel_scale: f32 },
+    TexSetDrawBlur { step: (f32, f32), sigma: f32 },
+    TexPushClipRect { rect: (f32, f32, f32, f32) },
+    TexPushClipQuad { points: [(f32, f32); 4] },
+    TexPushClipShape { points: [(f32, f32); 4] },
+    TexPopClip,
+    ImageLoadUrl { id: i32, url: String },
+    ImageLoadBytes { id: i32, len: usize },
+    ImageUnload { id: i32 },
+    TexFromImage { tex_id: i32, img_id: i32 },
+    TexFromImageWithSize { tex_id: i32, img_id: i32, size: (i32, i32) },
+    TextBindFontFamily { id: i32, font_family: String },
+    TextUnbindFontFamily { id: i32 },
+    TextSetFont { font_size: i32, line_height: i32, font_family_id: i32, italic: bool, bold: bool },
+    TextToTex { tex_id: i32, pos: (i32, i32), text: String, size: (i32, i32), line_height: i32 },
+}
+
+/// A backend which draws nothing but records the calls, so that the canvas can be tested without any window.
+/// The timeouts and the image loads are queued until `run_callbacks`, and the images fail to load unless `set_image` is called.
+/// The frames are not generated automatically, call `frame::generate` instead.
+pub(crate) struct RecordingBackend {
+    calls: RefCell<Vec<Call>>,
+    callbacks: RefCell<Vec<(*mut Box<Callback>, i32)>>,
+    animation_frame_enabled: Cell<bool>,
+    canvas_size: Cell<(i32, i32, f64)>,
+    font_size: Cell<i32>,
+    image: Cell<Option<(i32, i32, bool)>>,
+    touch_callback: Cell<Option<*mut Box<Callback>>>,
+}
+
+impl RecordingBackend {
+    pub fn new() -> Self {
+        Self {
+            calls: RefCell::new(vec![]),
+            callbacks: RefCell::new(vec![]),
+            animation_frame_enabled: Cell::new(false),
+            canvas_size: Cell::new((1280, 720, 1.)),
+            font_size: Cell::new(16),
+            image: Cell::new(None),
+            touch_callback: Cell::new(None),
+        }
+    }
+    #[inline]
+    fn record(&self, call: Call) {
+        self.calls.borrow_mut().push(call);
+    }
+    #[inline]
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.borrow().clone()
+    }
+    #[inline]
+    pub fn clear_calls(&self) {
+        self.calls.borrow_mut().clear();
+    }
+    /// The count of the calls recorded which match `f`.
+    pub fn count_calls<F: Fn(&Call) -> bool>(&self, f: F) -> usize {
+        self.calls.borrow().iter().filter(|x| f(x)).count()
+    }
+    #[inline]
+    pub fn is_animation_frame_enabled(&self) -> bool {
+        self.animation_frame_enabled.get()
+    }
+    /// Make the following image loads succeed with the natural size and whether it is a vector image,
+    /// or fail if `None`.
+    #[inline]
+    pub fn set_image(&self, image: Option<(i32, i32, bool)>) {
+        self.image.set(image);
+    }
+    /// Call the queued callbacks of the timeouts and the image loads in order, returns the count.
+    /// The callbacks queued during this call are kept for the next call.
+    pub fn run_callbacks(&self) -> usize {
+        let callbacks: Vec<_> = self.callbacks.borrow_mut().drain(..).collect();
+        let count = callbacks.len();
+        for (cb_ptr, ret_code) in callbacks {
+            super::callback(cb_ptr, ret_code, 0, 0, 0);
+        }
+        count
+    }
+    /// Dispatch a touch event of `touch_type` (1 for start, 2 for move, 3 for end and 4 for cancel) to the canvas.
+    pub fn touch(&self, touch_type: i32, x: i32, y: i32) {
+        let cb_ptr = self.touch_callback.get().expect("touch events are not bound");
+        super::callback(cb_ptr, touch_type, x, y, 0);
+    }
+    #[inline]
+    fn image_load_ret_code(&self) -> i32 {
+        if self.image.get().is_some() { 0 } else { IMAGE_LOAD_ERROR }
+    }
+}
+
+impl Backend for RecordingBackend {
+    fn timeout(&self, ms: i32, cb_ptr: *mut Box<Callback>) {
+        self.record(Call::Timeout { ms });
+        self.callbacks.borrow_mut().push((cb_ptr, 0));
+    }
+    fn enable_animation_frame(&self) {
+        self.record(Call::EnableAnimationFrame);
+        self.animation_frame_enabled.set(true);
+    }
+    fn disable_animation_frame(&self) {
+        self.record(Call::DisableAnimationFrame);
+        self.animation_frame_enabled.set(false);
+    }
+
+    fn bind_canvas(&self, _canvas_index: i32) {
+        self.record(Call::BindCanvas);
+    }
+    fn unbind_canvas(&self, _canvas_index: i32) {
+        self.record(Call::UnbindCanvas);
+    }
+    fn set_canvas_size(&self, _canvas_index: i32, w: i32, h: i32, pixel_ratio: f64, update_logical_size: bool) {
+        self.record(Call::SetCanvasSize { size: (w, h), pixel_ratio, update_logical_size });
+        self.canvas_size.set((w, h, pixel_ratio));
+    }
+    fn get_canvas_width(&self, _canvas_index: i32) -> i32 {
+        self.canvas_size.get().0
+    }
+    fn get_canvas_height(&self, _canvas_index: i32) -> i32 {
+        self.canvas_size.get().1
+    }
+    fn get_device_pixel_ratio(&self, _canvas_index: i32) -> f64 {
+        self.canvas_size.get().2
+    }
+    fn set_clear_color(&self, _canvas_index: i32, r: f32, g: f32, b: f32, a: f32) {
+        self.record(Call::SetClearColor { color: (r, g, b, a) });
+    }
+    fn clear(&self, _canvas_index: i32) {
+        self.record(Call::Clear);
+    }
+    fn bind_touch_events(&self, _canvas_index: i32, cb_ptr: *mut Box<Callback>) {
+        self.record(Call::BindTouchEvents);
+        self.touch_callback.set(Some(cb_ptr));
+    }
+    fn bind_wheel_events(&self, _canvas_index: i32, _cb_ptr: *mut Box<Callback>) {
+        self.record(Call::BindWheelEvents);
+    }
+    fn bind_keyboard_events(&self, _canvas_index: i32, _cb_ptr: *mut Box<Callback>) {
+        self.record(Call::BindKeyboardEvents);
+    }
+    fn bind_canvas_size_change(&self, _canvas_index: i32, _cb_ptr: *mut Box<Callback>) {
+        self.record(Call::BindCanvasSizeChange);
+    }
+
+    fn tex_get_size(&self, _canvas_index: i32) -> i32 {
+        4096
+    }
+    fn tex_get_count(&self, _canvas_index: i32) -> i32 {
+        16
+    }
+    fn tex_get_max_draws(&self) -> i32 {
+        8192
+    }
+    fn tex_get_pixels(&self, _canvas_index: i32, left: i32, top: i32, width: i32, height: i32, buf: &mut [u8]) {
+        self.record(Call::TexGetPixels { pos: (left, top), size: (width, height) });
+        for x in buf.iter_mut() {
+            *x = 0;
+        }
+    }
+    fn tex_create_empty(&self, _canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+        self.record(Call::TexCreateEmpty { tex_id, size: (width, height) });
+    }
+    fn tex_create_from_pixels(&self, _canvas_index: i32, tex_id: i32, width: i32, height: i32, _buf: &[u8]) {
+        self.record(Call::TexCreateFromPixels { tex_id, size: (width, height) });
+    }
+    fn tex_copy(&self, _canvas_index: i32, dest_tex_id: i32, dest_left: i32, dest_top: i32, src_left: i32, src_top: i32, width: i32, height: i32) {
+        self.record(Call::TexCopy { dest_tex_id, dest: (dest_left, dest_top), src: (src_left, src_top), size: (width, height) });
+    }
+    fn tex_bind_rendering_target(&self, _canvas_index: i32, tex_id: i32, width: i32, height: i32) {
+        self.record(Call::TexBindRenderingTarget { tex_id, size: (width, height) });
+    }
+    fn tex_unbind_rendering_target(&self, _canvas_index: i32) {
+        self.record(Call::TexUnbindRenderingTarget);
+    }
+    fn tex_delete(&self, _canvas_index: i32, tex_id: i32) {
+        self.record(Call::TexDelete { tex_id });
+    }
+    fn tex_draw(&self, _canvas_index: i32, draw_index: i32, tex_shader_index: i32, normalized_tex_x: f32, normalized_tex_y: f32, normalized_tex_w: f32, normalized_tex_h: f32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        self.record(Call::TexDraw {
+            draw_index,
+            tex_shader_index,
+            tex_pos: (normalized_tex_x, normalized_tex_y, normalized_tex_w, normalized_tex_h),
+            quad: [(x1, y1), (x2, y2), (x3, y3), (x4, y4)],
+        });
+    }
+    fn tex_set_active_texture(&self, _canvas_index: i32, tex_shader_index: i32, tex_id: i32) {
+        self.record(Call::TexSetActiveTexture { tex_shader_index, tex_id });
+    }
+    fn tex_draw_end(&self, _canvas_index: i32, draw_count: i32) {
+        self.record(Call::TexDrawEnd { draw_count });
+    }
+    fn tex_set_draw_state(&self, _canvas_index: i32, color_r: f32, color_g: f32, color_b: f32, color_a: f32, alpha: f32) {
+        self.record(Call::TexSetDrawState { color: (color_r, color_g, color_b, color_a), alpha });
+    }
+    fn tex_set_draw_shape(&self, _canvas_index: i32, width: f32, height: f32, radius_lt: f32, radius_rt: f32, radius_rb: f32, radius_lb: f32, border_top: f32, border_right: f32, border_bottom: f32, border_left: f32, pixel_scale: f32) {
+        self.record(Call::TexSetDrawShape {
+            size: (width, height),
+            radii: (radius_lt, radius_rt, radius_rb, radius_lb),
+            border: (border_top, border_right, border_bottom, border_left),
+            pixel_scale,
+        });
+    }
+    fn tex_set_draw_blur(&self, _canvas_index: i32, step_x: f32, step_y: f32, sigma: f32) {
+        self.record(Call::TexSetDrawBlur { step: (step_x, step_y), sigma });
+    }
+    fn tex_push_clip_rect(&self, _canvas_index: i32, left: f32, top: f32, width: f32, height: f32) {
+        self.record(Call::TexPushClipRect { rect: (left, top, width, height) });
+    }
+    fn tex_push_clip_quad(&self, _canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        self.record(Call::TexPushClipQuad { points: [(x1, y1), (x2, y2), (x3, y3), (x4, y4)] });
+    }
+    fn tex_push_clip_shape(&self, _canvas_index: i32, x1: f32, y1: f32, x2: f32, y2: f32, x3: f32, y3: f32, x4: f32, y4: f32) {
+        self.record(Call::TexPushClipShape { points: [(x1, y1), (x2, y2), (x3, y3), (x4, y4)] });
+    }
+    fn tex_pop_clip(&self, _canvas_index: i32) {
+        self.record(Call::TexPopClip);
+    }
+
+    fn image_load_url(&self, id: i32, url: &str, cb_ptr: *mut Box<Callback>) {
+        self.record(Call::ImageLoadUrl { id, url: String::from(url) });
+        self.callbacks.borrow_mut().push((cb_ptr, self.image_load_ret_code()));
+    }
+    fn image_load_bytes(&self, id: i32, buf: &[u8], cb_ptr: *mut Box<Callback>) {
+        self.record(Call::ImageLoadBytes { id, len: buf.len() });
+        self.callbacks.borrow_mut().push((cb_ptr, self.image_load_ret_code()));
+    }
+    fn image_unload(&self, id: i32) {
+        self.record(Call::ImageUnload { id });
+    }
+    fn image_get_error(&self, _id: i32) -> String {
+        String::from("Images are not loaded by RecordingBackend")
+    }
+    fn image_get_natural_width(&self, _id: i32) -> i32 {
+        self.image.get().map_or(0, |x| x.0)
+    }
+    fn image_get_natural_height(&self, _id: i32) -> i32 {
+        self.image.get().map_or(0, |x| x.1)
+    }
+    fn image_is_vector(&self, _id: i32) -> bool {
+        self.image.get().map_or(false, |x| x.2)
+    }
+    fn tex_from_image(&self, _canvas_index: i32, tex_id: i32, img_id: i32) {
+        self.record(Call::TexFromImage { tex_id, img_id });
+    }
+    fn tex_from_image_with_size(&self, _canvas_index: i32, tex_id: i32, img_id: i32, width: i32, height: i32) {
+        self.record(Call::TexFromImageWithSize { tex_id, img_id, size: (width, height) });
+    }
+
+    fn text_bind_font_family(&self, id: i32, font_family: &str) {
+        self.record(Call::TextBindFontFamily { id, font_family: String::from(font_family) });
+    }
+    fn text_unbind_font_family(&self, id: i32) {
+        self.record(Call::TextUnbindFontFamily { id });
+    }
+    fn text_set_font(&self, font_size: i32, line_height: i32, font_family_id: i32, italic: bool, bold: bool) {
+        self.record(Call::TextSetFont { font_size, line_height, font_family_id, italic, bold });
+        self.font_size.set(font_size);
+    }
+    fn text_get_width(&self, text: &str) -> f64 {
+        // every character is half of the font size wide
+        (text.chars().count() as i32 * self.font_size.get()) as f64 / 2.
+    }
+    fn text_to_tex(&self, _canvas_index: i32, tex_id: i32, tex_left: i32, tex_top: i32, text: &str, width: i32, height: i32, line_height: i32) {
+        self.record(Call::TextToTex { tex_id, pos: (tex_left, tex_top), text: String::from(text), size: (width, height), line_height });
+    }
+}
+
+lazy_static! {
+    static ref CANVAS_TEST_THREAD: Mutex<mpsc::Sender<Box<FnOnce() + Send>>> = {
+        let (tx, rx) = mpsc::channel::<Box<FnOnce() + Send>>();
+        thread::spawn(move || {
+            for f in rx.iter() {
+                f();
+            }
+        });
+        Mutex::new(tx)
+    };
+}
+
+/// Run a test in the thread shared by all the tests using canvases, since the canvases and the frames are global states bound to one thread.
+/// The panics are passed to the calling thread.
+pub(crate) fn run_canvas_test<F: FnOnce() + Send + 'static>(f: F) {
+    let (tx, rx) = mpsc::channel::<Result<(), Box<Any + Send>>>();
+    CANVAS_TEST_THREAD.lock().unwrap_or_else(|e| e.into_inner()).send(Box::new(move || {
+        tx.send(panic::catch_unwind(panic::AssertUnwindSafe(f))).unwrap();
+    })).unwrap();
+    match rx.recv().unwrap() {
+        Ok(_) => { },
+        Err(e) => panic::resume_unwind(e),
+    }
+}
+
+/// A canvas drawn and scheduled with a `RecordingBackend`.
+/// It should be used in `run_canvas_test`.
+pub(crate) struct TestCanvas {
+    pub backend: Rc<RecordingBackend>,
+    pub canvas: super::super::canvas::Canvas,
+}
+
+impl TestCanvas {
+    pub fn new(width: i32, height: i32) -> Self {
+        let backend = Rc::new(RecordingBackend::new());
+        let canvas = super::super::canvas::Canvas::new_with_backend(0, backend.clone());
+        canvas.context().borrow_mut().set_canvas_size(width, height, 1.);
+        Self {
+            backend,
+            canvas,
+        }
+    }
+}
+
+impl Drop for TestCanvas {
+    fn drop(&mut self) {
+        self.canvas.destroy();
+    }
+}
+
+mod test {
+    use std::rc::Rc;
+    use std::cell::Cell;
+    use std::time::{Instant, Duration};
+    use super::{Call, RecordingBackend, TestCanvas, run_canvas_test};
+    use super::super::super::canvas::element::{Element, Empty};
+    use super::super::super::canvas::element::style::DisplayType;
+    use super::super::super::canvas::Canvas;
+    use super::super::super::frame;
+
+    #[test]
+    fn drive_frames_and_timeouts() {
+        run_canvas_test(|| {
+            let mut t = TestCanvas::new(100, 100);
+            let backend = t.backend.clone();
+            assert!(backend.is_animation_frame_enabled());
+            {
+                let ctx = t.canvas.context();
+                let mut ctx = ctx.borrow_mut();
+                let cfg = ctx.canvas_config();
+                let root = ctx.root();
+                let mut root = root.borrow_mut();
+                let child = root.create_another(Element::new(&cfg, Box::new(Empty::new(&cfg))));
+                {
+                    let child = child.deref_mut_with(&mut root);
+                    child.style_mut().display(DisplayType::Block);
+                    child.style_mut().width(10.);
+                    child.style_mut().height(10.);
+                    child.style_mut().background_color((1., 0., 0., 1.));
+                }
+                root.append(child);
+            }
+            backend.clear_calls();
+            frame::generate(Instant::now());
+            let calls = backend.calls();
+            assert_eq!(backend.count_calls(|x| match x { Call::TexDrawEnd { .. } => true, _ => false }), 1);
+            assert!(calls.contains(&Call::TexSetDrawState { color: (1., 0., 0., 1.), alpha: 1. }));
+            assert!(calls.contains(&Call::TexDraw { draw_index: 0, tex_shader_index: -2, tex_pos: (0., 0., 1., 1.), quad: [(0., 0.), (0., 10.), (10., 10.), (10., 0.)] }));
+            let fired = Rc::new(Cell::new(0));
+            let f = fired.clone();
+            t.canvas.context().borrow().set_timeout(move || f.set(f.get() + 1), Duration::from_millis(100));
+            assert_eq!(fired.get(), 0);
+            assert_eq!(backend.run_callbacks(), 1);
+            assert_eq!(fired.get(), 1);
+            assert_eq!(backend.run_callbacks(), 0);
+            t.canvas.destroy();
+            assert!(!backend.is_animation_frame_enabled());
+        });
+    }
+
+    #[test]
+    fn schedule_with_own_backend() {
+        run_canvas_test(|| {
+            let t = TestCanvas::new(100, 100);
+            let other_backend = Rc::new(RecordingBackend::new());
+            let mut other = Canvas::new_with_backend(1, other_backend.clone());
+            assert!(t.backend.is_animation_frame_enabled());
+            assert!(other_backend.is_animation_frame_enabled());
+            let fired = Rc::new(Cell::new(0));
+            let f = fired.clone();
+            other.context().borrow().set_timeout(move || f.set(f.get() + 1), Duration::from_millis(0));
+            assert_eq!(t.backend.run_callbacks(), 0);
+            assert_eq!(other_backend.run_callbacks(), 1);
+            assert_eq!(fired.get(), 1);
+            other.destroy();
+            assert!(!other_backend.is_animation_frame_enabled());
+            assert!(t.backend.is_animation_frame_enabled());
+        });
+    }
+}
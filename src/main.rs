@@ -44,6 +44,15 @@ fn auto_run() {
 
 fn main() {
     glayout::init();
+    #[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+    {
+        if test::regression::requested() {
+            glayout::set_log_level_num(1);
+            set_test_log_level_num(0);
+            glayout::main_loop(test::regression::run);
+            return;
+        }
+    }
     glayout::set_log_level_num(-1);
     glayout::main_loop(auto_run);
 }
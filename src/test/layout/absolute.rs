@@ -1,4 +1,3 @@
-use std::time;
 use glayout::canvas::element::{Element, Empty, Image, Text, Size};
 use glayout::canvas::element::style::{DisplayType, PositionType};
 
@@ -58,9 +57,10 @@ pub fn init() {
         let mut root_elem = context.root().borrow_mut();
         root_elem.append(elem);
 
-        let init_time = time::Instant::now();
+        // moved by frames instead of the time, so that the regression runner captures the same position every time
+        let mut frame_count = 0;
         let rc_context = rc_context.clone();
-        frame!(move |time| {
+        frame!(move |_time| {
             let mut context = rc_context.borrow_mut();
             let mut root = context.root().borrow_mut();
 
@@ -76,9 +76,8 @@ pub fn init() {
             }
 
             let f = root.node_by_id("f").unwrap();
-            let time = time.duration_since(init_time);
-            let ts = time.as_secs() as f64 * 1000. + time.subsec_nanos() as f64 / 1_000_000.;
-            f.deref_mut_with(&mut root).style_mut().transform_mut().reset().offset(Size::new(ts / 1000. % 4. * 400., 0.));
+            frame_count += 1;
+            f.deref_mut_with(&mut root).style_mut().transform_mut().reset().offset(Size::new(frame_count as f64 / 60. % 4. * 400., 0.));
             context.redraw();
 
             return true;
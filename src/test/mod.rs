@@ -53,13 +53,20 @@ mod mouse_event;
 mod painting;
 mod layout;
 
-pub fn init() {
+#[cfg(not(any(target_arch = "asmjs", target_arch = "wasm32")))]
+pub mod regression;
+
+fn load() {
     animation::init();
     canvas::init();
     element::init();
     mouse_event::init();
     painting::init();
     layout::init();
+}
+
+pub fn init() {
+    load();
 
     let canvas = Canvas::new(0);
     *MAIN_CANVAS_CONTEXT.borrow_mut() = Some(canvas.context());
@@ -0,0 +1,244 @@
+//! Non-interactive runner which renders each registered test case and compares it with reference images.
+//!
+//! Run with `cargo run --features headless -- --regression [OPTIONS] [CASE_NAME_PREFIX...]`:
+//! * `--frames N` the number of frames to run before capturing (default 10)
+//! * `--tolerance T` the max difference allowed in each color channel, 0 to 255 (default 2)
+//! * `--golden-dir DIR` where the reference images are stored (default `resources/golden`)
+//! * `--output-dir DIR` where the actual images and diff images are written on failure (default `target/golden`)
+//! * `--font FILE` the font used for all text instead of the system fonts (default `resources/fonts/Inconsolata-Regular.ttf`)
+//! * `--update` write the captured images as the new reference images
+
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use image;
+use glayout;
+use glayout::canvas::{Canvas, CaptureSize, FrameCapture};
+use super::{TEST_CASE_MAP, MAIN_CANVAS_CONTEXT};
+
+#[derive(Clone, Debug)]
+pub struct RegressionOptions {
+    pub frames: u32,
+    pub tolerance: u8,
+    pub golden_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub font: PathBuf,
+    pub update: bool,
+    pub filters: Vec<String>,
+}
+
+impl RegressionOptions {
+    fn from_args() -> Self {
+        let mut ret = Self {
+            frames: 10,
+            tolerance: 2,
+            golden_dir: PathBuf::from("resources/golden"),
+            output_dir: PathBuf::from("target/golden"),
+            font: PathBuf::from("resources/fonts/Inconsolata-Regular.ttf"),
+            update: false,
+            filters: vec![],
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--regression" => { },
+                "--frames" => {
+                    ret.frames = args.next().and_then(|x| x.parse().ok()).expect("--frames requires a number");
+                },
+                "--tolerance" => {
+                    ret.tolerance = args.next().and_then(|x| x.parse().ok()).expect("--tolerance requires a number between 0 and 255");
+                },
+                "--golden-dir" => {
+                    ret.golden_dir = PathBuf::from(args.next().expect("--golden-dir requires a path"));
+                },
+                "--output-dir" => {
+                    ret.output_dir = PathBuf::from(args.next().expect("--output-dir requires a path"));
+                },
+                "--font" => {
+                    ret.font = PathBuf::from(args.next().expect("--font requires a path"));
+                },
+                "--update" => {
+                    ret.update = true;
+                },
+                _ => {
+                    ret.filters.push(arg);
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Whether the regression runner is requested in command line.
+pub fn requested() -> bool {
+    env::args().any(|x| x == "--regression")
+}
+
+struct Runner {
+    options: RegressionOptions,
+    names: Vec<String>,
+    current: usize,
+    canvases: Vec<Canvas>,
+    failed: Vec<String>,
+}
+
+/// Run all test cases which match the command line options, then exit the process.
+pub fn run() {
+    let options = RegressionOptions::from_args();
+    if !options.update && !options.golden_dir.is_dir() {
+        error!("Reference image directory {} not found, run with --update to create the reference images first", options.golden_dir.display());
+        process::exit(1);
+    }
+    // the reference images are rendered with the bundled font, so they do not depend on the fonts installed
+    match fs::read(&options.font) {
+        Ok(data) => glayout::lib_interfaces::text_use_font_file(data),
+        Err(e) => {
+            error!("Cannot read font file {} ({})", options.font.display(), e);
+            process::exit(1);
+        }
+    }
+    super::load();
+    let names: Vec<String> = TEST_CASE_MAP.borrow().keys().filter(|name| {
+        options.filters.len() == 0 || options.filters.iter().any(|f| name.starts_with(f.as_str()))
+    }).cloned().collect();
+    log!("Running {} regression test cases", names.len());
+    let runner = Rc::new(RefCell::new(Runner {
+        options,
+        names,
+        current: 0,
+        canvases: vec![],
+        failed: vec![],
+    }));
+    run_next(runner);
+}
+
+fn run_next(runner: Rc<RefCell<Runner>>) {
+    let (name, frames) = {
+        let mut r = runner.borrow_mut();
+        if r.current >= r.names.len() {
+            finish(&r);
+            return;
+        }
+        let name = r.names[r.current].clone();
+        // every test case gets a new canvas, so that timers of previous test cases do not affect it
+        let canvas = Canvas::new(r.current as i32 + 1);
+        *MAIN_CANVAS_CONTEXT.borrow_mut() = Some(canvas.context());
+        r.canvases.push(canvas);
+        (name, r.options.frames)
+    };
+    info!("Regression test case: {}", name);
+    run_test_case!(name);
+    let mut frame_count = 0;
+    frame!(move |_time| {
+        frame_count += 1;
+        if frame_count < frames {
+            return true;
+        }
+        let capture = {
+            let r = runner.borrow();
+            let canvas = r.canvases.last().unwrap();
+            let ctx = canvas.context();
+            let mut ctx = ctx.borrow_mut();
+            ctx.capture_frame(CaptureSize::Logical)
+        };
+        {
+            let mut r = runner.borrow_mut();
+            match check_capture(&r.options, &name, &capture) {
+                Ok(_) => { },
+                Err(reason) => {
+                    error!("Regression test case {} failed: {}", name, reason);
+                    r.failed.push(name.clone());
+                }
+            }
+            r.canvases.last_mut().unwrap().destroy();
+            r.current += 1;
+        }
+        let runner = runner.clone();
+        glayout::set_timeout(move || {
+            run_next(runner.clone());
+        }, Duration::new(0, 0));
+        false
+    });
+}
+
+fn finish(r: &Runner) {
+    if r.failed.len() > 0 {
+        error!("{} of {} regression test cases failed: {}", r.failed.len(), r.names.len(), r.failed.join(", "));
+        process::exit(1);
+    }
+    log!("All {} regression test cases passed", r.names.len());
+    process::exit(0);
+}
+
+fn file_name(name: &str, suffix: &str) -> String {
+    name.replace("::", ".") + suffix
+}
+
+fn check_capture(options: &RegressionOptions, name: &str, capture: &FrameCapture) -> Result<(), String> {
+    let golden_path = options.golden_dir.join(file_name(name, ".png"));
+    if options.update {
+        fs::create_dir_all(&options.golden_dir).map_err(|e| e.to_string())?;
+        capture.save_png(&golden_path).map_err(|e| e.to_string())?;
+        log!("Reference image updated: {}", golden_path.display());
+        return Ok(());
+    }
+    if !golden_path.is_file() {
+        write_failure(options, name, capture, None)?;
+        return Err(format!("reference image {} not found, run with --update to create it", golden_path.display()));
+    }
+    let golden = match image::open(&golden_path) {
+        Ok(x) => x.to_rgba(),
+        Err(e) => {
+            write_failure(options, name, capture, None)?;
+            return Err(format!("cannot read reference image {} ({})", golden_path.display(), e));
+        }
+    };
+    if golden.width() as i32 != capture.width() || golden.height() as i32 != capture.height() {
+        write_failure(options, name, capture, None)?;
+        return Err(format!("size mismatched, expected {}x{} but got {}x{}", golden.width(), golden.height(), capture.width(), capture.height()));
+    }
+    let (mismatched, diff) = compare(capture, &golden, options.tolerance);
+    if mismatched > 0 {
+        write_failure(options, name, capture, Some(diff))?;
+        return Err(format!("{} pixels differ by more than {}", mismatched, options.tolerance));
+    }
+    Ok(())
+}
+
+/// Compare pixels, returns the mismatched pixel count and the diff image.
+/// In the diff image, mismatched pixels are red and others are the faded actual pixels.
+fn compare(capture: &FrameCapture, golden: &image::RgbaImage, tolerance: u8) -> (usize, Vec<u8>) {
+    let actual = capture.data();
+    let expected: &[u8] = &golden;
+    let mut mismatched = 0;
+    let mut diff: Vec<u8> = Vec::with_capacity(actual.len());
+    for (a, e) in actual.chunks(4).zip(expected.chunks(4)) {
+        let max_diff = (0..4).map(|i| (a[i] as i32 - e[i] as i32).abs()).max().unwrap();
+        if max_diff > tolerance as i32 {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let gray = ((a[0] as u32 + a[1] as u32 + a[2] as u32) / 3 / 4 + 191) as u8;
+            diff.extend_from_slice(&[gray, gray, gray, 255]);
+        }
+    }
+    (mismatched, diff)
+}
+
+fn write_failure(options: &RegressionOptions, name: &str, capture: &FrameCapture, diff: Option<Vec<u8>>) -> Result<(), String> {
+    fs::create_dir_all(&options.output_dir).map_err(|e| e.to_string())?;
+    let actual_path = options.output_dir.join(file_name(name, ".actual.png"));
+    capture.save_png(&actual_path).map_err(|e| e.to_string())?;
+    match diff {
+        None => { },
+        Some(diff) => {
+            let diff_path = options.output_dir.join(file_name(name, ".diff.png"));
+            image::save_buffer(&diff_path, &diff, capture.width() as u32, capture.height() as u32, image::RGBA(8)).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}